@@ -1,34 +1,119 @@
 use aead::{
     generic_array::GenericArray,
     stream::{DecryptorBE32, EncryptorBE32},
-    Payload,
+    Aead, Payload,
 };
 use argon2::{Algorithm, Argon2, Params, Version};
+use base64::Engine;
 use chacha20poly1305::{KeyInit, XChaCha20Poly1305};
-use flate2::read::GzDecoder;
+use flate2::read::MultiGzDecoder;
 use flate2::write::GzEncoder;
 use flate2::Compression;
-use std::collections::HashSet;
+use sha2::{Digest, Sha256};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::ffi::{OsStr, OsString};
 use std::fs::{self, File, OpenOptions};
-use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::time::{SystemTime, UNIX_EPOCH};
-use tauri::{command, Emitter, Window};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use sysinfo::{Disks, System};
+use tauri::{command, Emitter, State, Window};
+use unicode_normalization::UnicodeNormalization;
 
 const MAGIC_HEADER: &[u8; 9] = b"KRATE_PKG";
-const FORMAT_MARKER: &[u8; 4] = b"V002";
+const SEVEN_ZIP_SIGNATURE: &[u8; 6] = b"7z\xbc\xaf\x27\x1c";
+const ZIP_SIGNATURES: [&[u8; 4]; 2] = [b"PK\x03\x04", b"PK\x05\x06"];
+const GZIP_SIGNATURE: &[u8; 2] = b"\x1f\x8b";
+const ZSTD_SIGNATURE: &[u8; 4] = b"\x28\xb5\x2f\xfd";
+const XZ_SIGNATURE: &[u8; 6] = b"\xfd7zXZ\x00";
+const TAR_USTAR_OFFSET: usize = 257;
+const TAR_USTAR_MAGIC: &[u8; 5] = b"ustar";
+const FORMAT_MARKER: &[u8; 4] = b"V003";
+// V002 归档没有元数据块，仍可读取与解压。
+const LEGACY_FORMAT_MARKER: &[u8; 4] = b"V002";
+// 打包时作为最后一个 tar 条目写入；解压时默认不输出到磁盘。
+const MANIFEST_ENTRY_NAME: &str = "KRATE_MANIFEST.json";
+// 顶层名称按 Windows 文件名规则校验，保证归档在各平台上都能解出。
+const INVALID_ROOT_NAME_CHARS: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+// Windows 保留的设备名，带扩展名（如 `con.txt`）同样不可用。
+const WINDOWS_RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+// 内容去重产生的硬链接条目带有该 PAX 扩展，解压时据此与真实硬链接区分。
+const DEDUPE_PAX_KEY: &str = "KRATE.dedupe";
 
 const FLAG_ENCRYPTED: u8 = 0b0000_0001;
+// 头部附带密码校验块；旧归档没有该标记，解压时退回到首块认证失败的判断方式。
+const FLAG_KEY_CHECK: u8 = 0b0000_0010;
+// 头部末尾附带长度前缀的 JSON 元数据块（明文，但作为 AAD 的一部分受认证保护）。
+const FLAG_METADATA: u8 = 0b0000_0100;
+const MAX_METADATA_LEN: usize = 64 * 1024;
+// 分卷归档：头部记录分卷大小，除最后一卷外每卷都恰好为该大小。
+const FLAG_VOLUMES: u8 = 0b0000_1000;
+// 密钥文件参与密钥派生；同时设置 `FLAG_KEYFILE_PASSWORD` 表示密码与密钥文件拼接后派生，
+// `FLAG_RAW_KEY` 表示 32 字节密钥文件直接作为密钥。
+const FLAG_KEYFILE: u8 = 0b0001_0000;
+const FLAG_KEYFILE_PASSWORD: u8 = 0b0010_0000;
+const FLAG_RAW_KEY: u8 = 0b0100_0000;
+// 头部末尾带有明文 tar 字节数与文件数（各 8 字节），用于按解压后字节计算进度。
+const FLAG_CONTENT_SIZE: u8 = 0b1000_0000;
+const CONTENT_SIZE_LEN: usize = 16;
+// 头部必须完整落在第一卷内，读取分卷信息时只打开 `.001`。
+const MIN_VOLUME_SIZE: u64 = 1024 * 1024;
+// 头部的压缩字节自 V002 起就存在，新增算法只需分配新值，旧归档照常按 gzip 读取。
+const COMPRESSION_NONE: u8 = 0;
 const COMPRESSION_GZIP: u8 = 1;
+const COMPRESSION_ZSTD: u8 = 2;
+const COMPRESSION_XZ: u8 = 3;
+// 压缩字节的最高位：隐藏文件列表，元数据改为放在加密流开头。flags 字节已用满，
+// 因此借用压缩字节，旧版本会把它当成未知压缩算法直接拒绝，而不是误解析后续字段。
+const COMPRESSION_HIDDEN_NAMES: u8 = 0b1000_0000;
+// 压缩字节的次高位：文件末尾附带覆盖魔数之后全部字节的 32 字节校验尾，无需密码即可校验。
+// 旧版本同样会拒绝读取，因此只在创建时显式开启，追加与改密码沿用原归档的设置。
+const COMPRESSION_CHECKSUM_TRAILER: u8 = 0b0100_0000;
+const COMPRESSION_FLAG_BITS: u8 = COMPRESSION_HIDDEN_NAMES | COMPRESSION_CHECKSUM_TRAILER;
+const CHECKSUM_TRAILER_LEN: usize = 32;
+const HIDDEN_NAMES_ERROR: &str = "已加密，需要密码查看文件列表";
 
 const DEFAULT_GZIP_LEVEL: u32 = 6;
+const DEFAULT_XZ_PRESET: u32 = 6;
+const MAX_COMPRESSION_LEVEL: u32 = 9;
+const DEFAULT_ZSTD_LEVEL: i32 = 3;
+const PARALLEL_GZIP_BLOCK_SIZE: usize = 1024 * 1024;
+const MAX_COMPRESSION_THREADS: usize = 32;
+// 并行压缩时源文件由读取线程按块预读；小文件不值得单开线程。
+const READ_AHEAD_CHUNK_SIZE: usize = 256 * 1024;
+const READ_AHEAD_CHUNKS: usize = 4;
+const READ_AHEAD_MIN_FILE_SIZE: u64 = PARALLEL_GZIP_BLOCK_SIZE as u64;
+// 体积估算的采样上限：单块大小、默认/最大采样总量与采样耗时上限。
+const ESTIMATE_CHUNK_SIZE: u64 = 256 * 1024;
+const DEFAULT_ESTIMATE_SAMPLE_BYTES: u64 = 8 * 1024 * 1024;
+const MAX_ESTIMATE_SAMPLE_BYTES: u64 = 64 * 1024 * 1024;
+const ESTIMATE_TIME_BUDGET: Duration = Duration::from_secs(3);
+// tar 每个文件的头部与对齐填充按 1 KiB 估算。
+const ESTIMATE_TAR_OVERHEAD_PER_FILE: u64 = 1024;
+const MAX_PASSWORD_SCORE: u8 = 4;
+const MAX_BENCHMARK_SAMPLE_BYTES: u64 = 64 * 1024 * 1024;
+const BENCHMARK_CHUNK_SIZE: usize = 1024 * 1024;
+const DEFAULT_PREVIEW_BYTES: u64 = 1024 * 1024;
+const MAX_PREVIEW_BYTES: u64 = 16 * 1024 * 1024;
+const DEFAULT_SEARCH_LIMIT: usize = 1000;
+const SEARCH_PASSWORD_REQUIRED: &str = "需要密码";
+const PREVIEW_SUGGESTION_LIMIT: usize = 5;
 const DEFAULT_ARGON2_MEMORY_KIB: u32 = 64 * 1024;
 const DEFAULT_ARGON2_ITERATIONS: u32 = 2;
 const MAX_ARGON2_LANES: u32 = 4;
-const MAX_ARCHIVE_ARGON2_MEMORY_KIB: u32 = 256 * 1024;
-const MAX_ARCHIVE_ARGON2_ITERATIONS: u32 = 6;
+// 自定义参数的上下限：打包时超出直接报错，解压时超出视为不受支持的归档。
+const MIN_ARCHIVE_ARGON2_MEMORY_KIB: u32 = 8 * 1024;
+const MAX_ARCHIVE_ARGON2_MEMORY_KIB: u32 = 2 * 1024 * 1024;
+const MAX_ARCHIVE_ARGON2_ITERATIONS: u32 = 16;
+const MAX_ARCHIVE_ARGON2_LANES: u32 = 16;
 const TEMP_OUTPUT_ATTEMPTS: usize = 16;
 
 const KEY_LEN: usize = 32;
@@ -39,8 +124,19 @@ const CHUNK_LENGTH_BYTES: usize = 4;
 const AEAD_TAG_LEN: usize = 16;
 const CHUNK_FLAG_NEXT: u8 = 0;
 const CHUNK_FLAG_LAST: u8 = 1;
+const KEY_CHECK_LEN: usize = AEAD_TAG_LEN;
+const KEY_CHECK_CONTEXT: &[u8] = b"KRATE_KEY_CHECK";
+const WRONG_PASSWORD_ERROR: &str = "归档解密失败：密码错误";
+const WRONG_KEY_FILE_ERROR: &str = "归档解密失败：密钥文件不正确";
+const WRONG_CREDENTIALS_ERROR: &str = "归档解密失败：密码或密钥文件不正确";
+const WRONG_ZIP_PASSWORD_ERROR: &str = "ZIP 解压失败：密码错误";
 
 const ARCHIVE_PROGRESS_EVENT: &str = "archive://progress";
+// 进度事件最小间隔（每秒最多约 10 次），速度按最近几秒的滑动窗口计算。
+const PROGRESS_EMIT_INTERVAL: Duration = Duration::from_millis(100);
+const PROGRESS_SPEED_WINDOW: Duration = Duration::from_secs(3);
+// 用户取消时返回的固定错误，前端据此区分“取消”和“失败”。
+const ARCHIVE_CANCELLED_ERROR: &str = "已取消";
 
 type ArchiveCipher = XChaCha20Poly1305;
 type ArchiveEncryptor = EncryptorBE32<ArchiveCipher>;
@@ -49,11 +145,39 @@ type ArchiveDecryptor = DecryptorBE32<ArchiveCipher>;
 #[derive(Clone, Debug, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 struct ArchiveProgressPayload {
+    // 任务 ID，同时进行多个任务时前端据此区分进度条。
+    operation_id: String,
     operation: String,
     stage: String,
     message: String,
     progress: f64,
     current_path: Option<String>,
+    processed_bytes: u64,
+    total_bytes: u64,
+    bytes_per_second: Option<f64>,
+    eta_seconds: Option<u64>,
+}
+
+// 归档压缩算法；解压时从头部自动识别。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiveCompression {
+    #[default]
+    Gzip,
+    Zstd,
+    Xz,
+    None,
+}
+
+// 打包时按大小与修改时间筛选文件；目录与符号链接不受影响。
+#[derive(Clone, Copy, Debug, Default)]
+struct EntryFilter {
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    // Unix 秒；修改时间不早于该值的文件才会打包。
+    modified_after: Option<u64>,
+    // Unix 秒；修改时间早于该值的文件才会打包。
+    modified_before: Option<u64>,
 }
 
 #[derive(Clone, Copy, Debug, Default)]
@@ -67,8 +191,533 @@ struct ArchiveProgressTracker {
     stage: &'static str,
     total_bytes: u64,
     processed_bytes: u64,
-    last_emitted_progress: i16,
+    last_emitted_at: Option<Instant>,
     current_path: Option<String>,
+    samples: VecDeque<(Instant, u64)>,
+    cancel: Arc<OperationControl>,
+}
+
+// 正在运行的归档任务（按任务 ID 保存取消标记）。
+#[derive(Default)]
+pub struct ArchiveState {
+    operations: Mutex<HashMap<String, Arc<OperationControl>>>,
+}
+
+// 单个归档任务的取消与暂停控制；暂停的任务在读取下一块数据前阻塞，取消会立即唤醒它。
+#[derive(Default)]
+struct OperationControl {
+    id: String,
+    // 最近一次上报的进度，供 `list_archive_operations` 查询。
+    progress: Mutex<Option<ArchiveProgressPayload>>,
+    cancelled: AtomicBool,
+    paused: AtomicBool,
+    gate: Mutex<()>,
+    resumed: Condvar,
+}
+
+// 任务结束时从 `ArchiveState` 中注销。
+struct ArchiveOperationGuard<'a> {
+    state: &'a ArchiveState,
+    operation_id: String,
+    cancel: Arc<OperationControl>,
+}
+
+// 正在运行的归档任务；`progress` 为最近一次进度事件，任务尚未上报时为空。
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveOperationInfo {
+    operation_id: String,
+    paused: bool,
+    cancelled: bool,
+    progress: Option<ArchiveProgressPayload>,
+}
+
+// 解压结果：输出目录与因路径不安全被跳过的条目。
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtractReport {
+    output_dir: String,
+    skipped_entries: Vec<String>,
+    // 开启 `verify_hashes` 时，与文件清单不一致或缺失的路径。
+    hash_mismatches: Vec<String>,
+    // 未提供密码时跳过的加密 ZIP 条目。
+    password_required_entries: Vec<String>,
+    // 开启 `salvage` 时的恢复结果。
+    salvage: Option<SalvageReport>,
+    // 为适配当前平台而改名的条目。
+    renamed_entries: Vec<RenamedEntry>,
+    // 归档未记录解压后大小，解压前没有检查磁盘空间。
+    space_check_skipped: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenamedEntry {
+    original: String,
+    extracted: String,
+}
+
+// 从截断或损坏的归档中尽量恢复完整条目的结果。
+#[derive(Clone, Debug, Default, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SalvageReport {
+    recovered_entries: Vec<String>,
+    // 读取到一半时出错、以 `.partial` 后缀保存的条目。
+    partial_entries: Vec<String>,
+    // 文件清单中有记录但未完整恢复的条目；清单位于归档末尾，读取在清单之前中断时为空。
+    missing_entries: Vec<String>,
+    // 加密归档中首个未通过认证的数据块在归档内的偏移；未加密归档无法定位，为空。
+    corruption_offset: Option<u64>,
+    // 中止读取的错误；归档完好时为空。
+    error: Option<String>,
+}
+
+// 打包时对符号链接的处理方式。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymlinkMode {
+    // 保存为符号链接条目。
+    #[default]
+    Preserve,
+    // 打包链接指向的内容。
+    Follow,
+    // 忽略链接，并在结果中列出。
+    Skip,
+}
+
+// 打包选项。
+#[derive(Clone, Debug, Default)]
+struct CreateOptions {
+    // gzip/xz 压缩等级。
+    level: Option<u32>,
+    compression: ArchiveCompression,
+    symlinks: SymlinkMode,
+    // 写入元数据块的备注。
+    comment: Option<String>,
+    // 分卷大小（字节），输出为 `name.krate.001`、`.002`……
+    volume_size: Option<u64>,
+    // 密钥文件恰好 32 字节时直接作为密钥，不经过 Argon2。
+    raw_key: bool,
+    kdf: KdfOptions,
+    // gzip 压缩线程数，默认取物理核心数；大于 1 时输出多成员 gzip，并由独立线程预读源文件。
+    threads: Option<usize>,
+    // 设置后只打包相对基准快照有变化的文件，并在归档旁写出新的快照清单。
+    incremental: Option<IncrementalOptions>,
+    // 单个条目读取失败时记为警告并继续，而不是中止整个打包。
+    skip_errors: bool,
+    // 把元数据放进加密流，没有密码时无法查看文件列表与备注；仅适用于加密归档。
+    hide_file_names: bool,
+    // 内容相同的文件只保存一份，其余写为带去重标记的硬链接条目。
+    dedupe: bool,
+    filter: EntryFilter,
+    // 调用方为每个输入指定的顶层名称；为空时按文件名自动命名。
+    top_level_names: Option<Vec<String>>,
+    // `create_archive_from_entries` 中的文件夹展开到给定路径下，而不是报错。
+    expand_directories: bool,
+    // 设置密码时要求的最低强度评分（0–4）；强制创建时为空。
+    min_password_score: Option<u8>,
+    // 在文件末尾写入校验尾；旧版本无法读取带校验尾的归档。
+    checksum_trailer: bool,
+}
+
+// `create_archive_from_entries` 的单个条目：源路径与其在归档内的路径。
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntrySpec {
+    src_path: String,
+    archive_path: String,
+}
+
+// `create_archive` 的参数；除输入与输出路径外均可省略。
+// `gzip_level` 为 gzip/xz 的压缩等级（0–9），沿用原参数名以兼容前端。
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateArchiveRequest {
+    inputs: Vec<String>,
+    output_path: String,
+    password: Option<String>,
+    key_file_path: Option<String>,
+    raw_key: Option<bool>,
+    kdf: Option<KdfOptions>,
+    min_password_score: Option<u8>,
+    // 为 true 时跳过 `min_password_score` 检查。
+    force: Option<bool>,
+    gzip_level: Option<u32>,
+    compression: Option<ArchiveCompression>,
+    threads: Option<usize>,
+    symlinks: Option<SymlinkMode>,
+    // `symlinks: "follow"` 的简写，显式指定 `symlinks` 时以其为准。
+    follow_symlinks: Option<bool>,
+    comment: Option<String>,
+    volume_size_bytes: Option<u64>,
+    skip_errors: Option<bool>,
+    hide_file_names: Option<bool>,
+    dedupe: Option<bool>,
+    min_size_bytes: Option<u64>,
+    max_size_bytes: Option<u64>,
+    modified_after: Option<u64>,
+    modified_before: Option<u64>,
+    top_level_names: Option<Vec<String>>,
+    checksum_trailer: Option<bool>,
+}
+
+// `extract_archive` 的参数；选项省略时取 `ExtractOptions` 的默认值。
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtractArchiveRequest {
+    archive_path: String,
+    output_dir: String,
+    password: Option<String>,
+    key_file_path: Option<String>,
+    strict_security: Option<bool>,
+    preserve_metadata: Option<bool>,
+    preserve_ownership: Option<bool>,
+    allow_external_symlinks: Option<bool>,
+    verify_hashes: Option<bool>,
+    include_manifest: Option<bool>,
+    salvage: Option<bool>,
+    dedupe_restore: Option<DedupeRestore>,
+    strip_components: Option<u32>,
+    ignore_space_check: Option<bool>,
+}
+
+// `search_archive` 与 `search_archives` 共用的搜索条件。
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveSearchQuery {
+    pattern: String,
+    password: Option<String>,
+    key_file_path: Option<String>,
+    case_sensitive: Option<bool>,
+    limit: Option<usize>,
+}
+
+// `create_archive_incremental` 的参数。
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IncrementalArchiveRequest {
+    inputs: Vec<String>,
+    output_path: String,
+    base_manifest_path: Option<String>,
+    compare_hashes: Option<bool>,
+    password: Option<String>,
+    key_file_path: Option<String>,
+    gzip_level: Option<u32>,
+    compression: Option<ArchiveCompression>,
+}
+
+// `create_archive_from_entries` 的参数。
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveEntriesRequest {
+    entries: Vec<EntrySpec>,
+    output_path: String,
+    password: Option<String>,
+    key_file_path: Option<String>,
+    compression: Option<ArchiveCompression>,
+    expand_directories: Option<bool>,
+}
+
+// `convert_archive` 的参数。
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConvertArchiveRequest {
+    input_path: String,
+    output_path: String,
+    password: Option<String>,
+    key_file_path: Option<String>,
+    ignore_space_check: Option<bool>,
+}
+
+// 增量打包选项。
+#[derive(Clone, Debug, Default)]
+struct IncrementalOptions {
+    // 上一次打包写出的快照清单；为空时打包全部文件，作为增量链的起点。
+    base_manifest: Option<PathBuf>,
+    // 大小相同时比较 SHA-256，而不是只比较大小与修改时间。
+    compare_hashes: bool,
+}
+
+// 自定义 Argon2 参数，未指定的字段使用默认值；参数写入归档头，解压时按头部还原。
+#[derive(Clone, Copy, Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KdfOptions {
+    memory_kib: Option<u32>,
+    iterations: Option<u32>,
+    parallelism: Option<u32>,
+}
+
+// 打包前的体积估算：压缩率由采样数据外推，`estimated_low_bytes`/`estimated_high_bytes`
+// 为约 95% 的置信区间；全部数据都被采样时区间退化为单点。
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EstimateResult {
+    total_files: u64,
+    total_bytes: u64,
+    sampled_bytes: u64,
+    estimated_bytes: u64,
+    estimated_low_bytes: u64,
+    estimated_high_bytes: u64,
+    sampling_ms: u64,
+}
+
+// 密码强度评估：`entropy_bits` 按字符集大小估算，重复与连续字符（如 `aaa`、`123`）只计少量熵；
+// `score` 为 0–4 的粗略评分，供前端显示强度条。
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PasswordReport {
+    length: usize,
+    entropy_bits: f64,
+    has_lowercase: bool,
+    has_uppercase: bool,
+    has_digits: bool,
+    has_symbols: bool,
+    has_other: bool,
+    score: u8,
+}
+
+// 单个压缩等级的测试结果；`ratio` 为压缩后与原始大小之比，越小越好。
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BenchmarkResult {
+    level: u32,
+    sample_bytes: u64,
+    compressed_bytes: u64,
+    ratio: f64,
+    megabytes_per_second: f64,
+    elapsed_ms: u64,
+}
+
+// 归档内单个文件的预览；超过 `max_bytes` 时只返回开头部分。
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryPreview {
+    path: String,
+    mime_type: &'static str,
+    // 条目的完整大小。
+    size: u64,
+    truncated: bool,
+    // base64 编码的内容。
+    data: String,
+}
+
+// 搜索命中的归档条目；`archive_path` 标明条目来自哪个归档。
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveEntryInfo {
+    archive_path: String,
+    path: String,
+    // `file`、`directory`、`symlink`、`hardlink` 或 `other`。
+    kind: &'static str,
+    size: u64,
+    mtime: Option<u64>,
+}
+
+// 批量搜索的结果；无法搜索的归档记录原因后跳过，不影响其余归档。
+#[derive(Clone, Debug, Default, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveSearchReport {
+    matches: Vec<ArchiveEntryInfo>,
+    skipped_archives: Vec<SkippedArchive>,
+    // 命中数达到上限后提前停止。
+    truncated: bool,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedArchive {
+    archive_path: String,
+    reason: String,
+}
+
+// 条目搜索条件：含 `*` 或 `?` 时按通配符匹配，否则按子串匹配。
+// 通配符不含 `/` 时只匹配文件名（`*.xlsx`），含 `/` 时匹配完整路径。
+struct EntryMatcher {
+    pattern: String,
+    glob: bool,
+    full_path: bool,
+    case_sensitive: bool,
+}
+
+// 加密凭据：密码、密钥文件，或两者拼接后共同作为 Argon2 输入。
+struct ArchiveSecret {
+    password: Option<String>,
+    key_file: Option<Vec<u8>>,
+    raw_key: bool,
+}
+
+// 打包结果：被跳过的符号链接（`skip` 模式、悬空链接或循环链接）。
+#[derive(Clone, Debug, Default, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateReport {
+    skipped_entries: Vec<String>,
+    // 增量打包时与基准快照一致而未打包的文件数。
+    unchanged_files: u64,
+    // 增量打包时基准快照中已不存在的路径。
+    deleted_entries: Vec<String>,
+    // `follow` 模式下经由符号链接到达的条目数（含链接目录下的全部条目）。
+    linked_entries: u64,
+    // 开启 `skip_errors` 时因读取失败而未打包的条目。
+    warnings: Vec<PackWarning>,
+    // 开启 `dedupe` 时因内容与已打包文件相同而只写入链接的文件数与省去的字节数。
+    deduped_files: u64,
+    deduped_bytes: u64,
+    // 未通过大小或修改时间筛选的文件数。
+    filtered_files: u64,
+    // 每个输入在归档内的顶层名称，顺序与输入一致。
+    input_mapping: Vec<InputMapping>,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputMapping {
+    input: String,
+    archive_name: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackWarning {
+    path: String,
+    error: String,
+}
+
+// 打包遍历状态：符号链接处理方式、被跳过的条目与逐文件的哈希清单。
+struct PackWalker {
+    mode: SymlinkMode,
+    skipped_entries: Vec<String>,
+    ancestors: Vec<PathBuf>,
+    // 当前路径上已跟随的符号链接层数。
+    link_depth: usize,
+    linked_entries: u64,
+    // 已写入归档的多链接文件，按文件标识记录首次出现的归档内路径。
+    hardlinks: HashMap<(u64, u64), PathBuf>,
+    manifest: ArchiveManifest,
+    incremental: Option<IncrementalState>,
+    skip_errors: bool,
+    warnings: Vec<PackWarning>,
+    dedupe: Option<DedupePlan>,
+    filter: EntryFilter,
+    filtered_files: u64,
+    // 开启后较大的源文件交给 `ReadAheadReader` 预读，读取与 tar 组装、压缩同时进行。
+    read_ahead: bool,
+}
+
+// 内容去重计划：打包前按大小预筛、再按 SHA-256 找出的重复文件。
+#[derive(Default)]
+struct DedupePlan {
+    // 存在重复内容的源文件路径及其哈希。
+    digests: HashMap<PathBuf, String>,
+    // 已写入归档的内容，按哈希记录首次写入的归档内路径。
+    stored: HashMap<String, PathBuf>,
+    deduped_files: u64,
+    deduped_bytes: u64,
+}
+
+// 增量打包状态：基准快照与本次遍历得到的完整快照。
+struct IncrementalState {
+    base: BTreeMap<String, ManifestEntry>,
+    base_dirs: BTreeSet<String>,
+    compare_hashes: bool,
+    snapshot: ArchiveManifest,
+    unchanged_files: u64,
+}
+
+// 写在 tar 末尾的 `KRATE_MANIFEST.json`，按归档内路径记录 SHA-256 与大小；
+// 增量归档额外记录相对基准已删除的路径。增量打包写在归档旁的快照清单也使用该结构。
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+struct ArchiveManifest {
+    files: BTreeMap<String, ManifestEntry>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    deleted: Vec<String>,
+    // 目录路径，只在快照清单中记录，用于发现两次打包之间删除的目录。
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    dirs: BTreeSet<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+struct ManifestEntry {
+    sha256: String,
+    size: u64,
+    // 修改时间（Unix 秒），只在快照清单中记录。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mtime: Option<u64>,
+    // 修改时间的纳秒部分，同一秒内的修改也能被发现。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mtime_nanos: Option<u32>,
+}
+
+// 在读取流上旁路计算 SHA-256；未调用 `start` 时只做透传。
+#[derive(Clone, Default)]
+struct HashTap(Rc<RefCell<Option<(Sha256, u64)>>>);
+
+struct HashingReader<R: Read> {
+    inner: R,
+    tap: HashTap,
+}
+
+// 按分卷大小切分输出；未设置分卷大小时直接写入单个文件。
+struct VolumeWriter {
+    base_path: PathBuf,
+    volume_size: Option<u64>,
+    index: u32,
+    current: BufWriter<File>,
+    written: u64,
+    // 写完头部后开始计算的负载摘要，用于生成校验尾。
+    checksum: Option<Sha256>,
+}
+
+// 依次读取各分卷；单文件归档视作只有一卷。
+struct VolumeReader {
+    parts: Vec<PathBuf>,
+    index: usize,
+    current: BufReader<File>,
+    // 剩余可读字节数；读到校验尾之前即返回 EOF。
+    remaining: u64,
+}
+
+// `verify_archive` 的结果。
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyReport {
+    checked_files: u64,
+    hash_mismatches: Vec<String>,
+}
+
+// 解压选项。
+#[derive(Clone, Copy, Debug)]
+struct ExtractOptions {
+    // 遇到越界条目直接失败，而不是跳过。
+    strict_security: bool,
+    // 还原权限位与修改时间（Windows 上仅只读属性有效）。
+    preserve_metadata: bool,
+    // 还原属主，需要相应权限。
+    preserve_ownership: bool,
+    // 允许解出指向输出目录之外的符号链接。
+    allow_external_symlinks: bool,
+    // 解压时重新计算 SHA-256 并与文件清单比对。
+    verify_hashes: bool,
+    // 同时输出 `KRATE_MANIFEST.json`。
+    include_manifest: bool,
+    // 归档损坏时保留已解出的完整条目，而不是整体失败。
+    salvage: bool,
+    // 去重条目的还原方式。
+    dedupe_restore: DedupeRestore,
+    // 去掉条目路径开头的若干层（类似 `tar --strip-components`），层数不足的条目不解出。
+    strip_components: u32,
+    // 跳过解压前的磁盘空间检查。
+    ignore_space_check: bool,
+}
+
+// 打包时去重的文件在解压时的还原方式；真实的硬链接不受影响，始终还原为硬链接。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DedupeRestore {
+    // 复制为独立文件，与打包前一致。
+    #[default]
+    Copy,
+    // 还原为硬链接，节省磁盘空间，但修改其中一个会影响所有副本。
+    Hardlink,
 }
 
 #[derive(Clone, Debug)]
@@ -82,6 +731,8 @@ struct ProgressReader<'a, R: Read> {
     tracker: &'a mut ArchiveProgressTracker,
     window: Option<&'a Window>,
     message: &'static str,
+    // 设置后按解压侧计数推进进度，而不是按本层读取的字节数。
+    plain_bytes: Option<ByteCounter>,
 }
 
 #[derive(Clone, Debug)]
@@ -91,1545 +742,9828 @@ struct EncryptionMetadata {
     lanes: u32,
     salt: [u8; SALT_LEN],
     stream_nonce: [u8; STREAM_NONCE_LEN],
+    key_check: Option<[u8; KEY_CHECK_LEN]>,
 }
 
 #[derive(Clone, Debug)]
 struct ArchiveHeader {
+    version: [u8; 4],
     flags: u8,
     compression: u8,
     encryption: Option<EncryptionMetadata>,
+    // 元数据 JSON 原始字节，按读取时的内容参与 AAD。
+    metadata: Option<Vec<u8>>,
+    volume_size: Option<u64>,
+    // 打包完成后才回填，因此不参与 AAD。
+    content_size: Option<ContentSize>,
 }
 
-impl ArchiveProgressTracker {
-    fn new(operation: &'static str, stage: &'static str, total_bytes: u64) -> Self {
+// 归档内明文 tar 流的字节数与文件数。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct ContentSize {
+    tar_bytes: u64,
+    files: u64,
+}
+
+// 统计写入 tar 流的明文字节数。
+struct CountingWriter<W: Write> {
+    inner: W,
+    written: u64,
+}
+
+// 解压侧与 `ProgressReader` 共享的明文字节计数。
+#[derive(Clone, Default)]
+struct ByteCounter(Rc<Cell<u64>>);
+
+struct CountingReader<R: Read> {
+    inner: R,
+    counter: ByteCounter,
+}
+
+// 解压时与负载读取层共享的计数。
+#[derive(Clone, Default)]
+struct PayloadCounters {
+    // 解压后的明文字节，用于计算进度。
+    plain: ByteCounter,
+    // 加密归档中已通过认证的数据块结束处在归档内的偏移。
+    intact: ByteCounter,
+}
+
+// 归档元数据，打包时写入头部，解压前即可查看。
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveMetadata {
+    comment: Option<String>,
+    created_at: u64,
+    app_version: String,
+    total_bytes: u64,
+    total_files: u64,
+}
+
+// `get_archive_info` 的返回值；V002 归档的 `metadata` 为空。
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveInfo {
+    version: String,
+    flags: u8,
+    compression: ArchiveCompression,
+    encrypted: bool,
+    metadata: Option<ArchiveMetadata>,
+    file_size: u64,
+    // 分卷大小；非分卷归档为空。
+    volume_size: Option<u64>,
+    // 明文 tar 流字节数；旧归档与隐藏文件列表的归档为空。
+    content_bytes: Option<u64>,
+    content_files: Option<u64>,
+    // 元数据位于加密流内，需要密码才能读取。
+    hidden_names: bool,
+    // 文件末尾是否带有校验尾，以及重新计算后是否一致；没有校验尾时为空。
+    has_checksum: bool,
+    checksum_matches: Option<bool>,
+}
+
+impl Default for ExtractOptions {
+    fn default() -> Self {
         Self {
-            operation,
-            stage,
-            total_bytes,
-            processed_bytes: 0,
-            last_emitted_progress: -1,
-            current_path: None,
+            strict_security: false,
+            preserve_metadata: true,
+            preserve_ownership: false,
+            allow_external_symlinks: false,
+            verify_hashes: false,
+            include_manifest: false,
+            salvage: false,
+            dedupe_restore: DedupeRestore::Copy,
+            strip_components: 0,
+            ignore_space_check: false,
         }
     }
+}
 
-    fn set_stage(&mut self, window: Option<&Window>, stage: &'static str, message: &'static str) {
-        self.stage = stage;
-        self.emit(window, message, true);
+impl PackWalker {
+    fn new(mode: SymlinkMode) -> Self {
+        Self {
+            mode,
+            skipped_entries: Vec::new(),
+            ancestors: Vec::new(),
+            link_depth: 0,
+            linked_entries: 0,
+            hardlinks: HashMap::new(),
+            manifest: ArchiveManifest::default(),
+            incremental: None,
+            skip_errors: false,
+            warnings: Vec::new(),
+            dedupe: None,
+            filter: EntryFilter::default(),
+            filtered_files: 0,
+            read_ahead: false,
+        }
     }
 
-    fn set_current_path(
+    fn skip(&mut self, path: &Path) {
+        self.skipped_entries.push(path.display().to_string());
+    }
+
+    // 开启 `skip_errors` 时把单个条目的读取错误记为警告并继续，否则原样返回错误。
+    fn tolerate(
         &mut self,
+        tracker: &mut ArchiveProgressTracker,
         window: Option<&Window>,
-        current_path: Option<String>,
-        message: &'static str,
-    ) {
-        self.current_path = current_path;
-        self.emit(window, message, true);
+        path: &Path,
+        error: String,
+    ) -> Result<(), String> {
+        if !self.skip_errors {
+            return Err(error);
+        }
+        tracker.set_current_path(
+            window,
+            Some(path.display().to_string()),
+            "已跳过无法读取的文件",
+        );
+        self.warnings.push(PackWarning {
+            path: path.display().to_string(),
+            error,
+        });
+        Ok(())
     }
 
-    fn advance_bytes(&mut self, window: Option<&Window>, bytes: u64, message: &'static str) {
-        self.processed_bytes = self.processed_bytes.saturating_add(bytes);
-        if self.total_bytes > 0 {
-            self.processed_bytes = self.processed_bytes.min(self.total_bytes);
+    fn finish(self) -> (CreateReport, Option<ArchiveManifest>) {
+        let (deduped_files, deduped_bytes) = self.dedupe.as_ref().map_or((0, 0), |dedupe| {
+            (dedupe.deduped_files, dedupe.deduped_bytes)
+        });
+        let Some(incremental) = self.incremental else {
+            return (
+                CreateReport {
+                    skipped_entries: self.skipped_entries,
+                    linked_entries: self.linked_entries,
+                    warnings: self.warnings,
+                    deduped_files,
+                    deduped_bytes,
+                    filtered_files: self.filtered_files,
+                    ..Default::default()
+                },
+                None,
+            );
+        };
+        (
+            CreateReport {
+                skipped_entries: self.skipped_entries,
+                unchanged_files: incremental.unchanged_files,
+                deleted_entries: self.manifest.deleted,
+                linked_entries: self.linked_entries,
+                warnings: self.warnings,
+                deduped_files,
+                deduped_bytes,
+                filtered_files: self.filtered_files,
+                ..Default::default()
+            },
+            Some(incremental.snapshot),
+        )
+    }
+}
+
+impl EntryFilter {
+    fn is_active(&self) -> bool {
+        self.min_size.is_some()
+            || self.max_size.is_some()
+            || self.modified_after.is_some()
+            || self.modified_before.is_some()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if let (Some(min), Some(max)) = (self.min_size, self.max_size) {
+            if min > max {
+                return Err("最小文件大小不能大于最大文件大小".to_string());
+            }
         }
-        self.emit(window, message, false);
+        if let (Some(after), Some(before)) = (self.modified_after, self.modified_before) {
+            if after >= before {
+                return Err("修改时间范围无效：起始时间需早于结束时间".to_string());
+            }
+        }
+        Ok(())
     }
 
-    fn finish(&mut self, window: Option<&Window>, stage: &'static str, message: &'static str) {
-        self.stage = stage;
-        self.processed_bytes = self.total_bytes;
-        self.current_path = None;
-        self.emit(window, message, true);
+    fn matches(&self, metadata: &fs::Metadata) -> bool {
+        let size = metadata.len();
+        if self.min_size.is_some_and(|min| size < min)
+            || self.max_size.is_some_and(|max| size > max)
+        {
+            return false;
+        }
+        if self.modified_after.is_none() && self.modified_before.is_none() {
+            return true;
+        }
+        // 设置了时间条件时，无法读取修改时间的文件一律排除。
+        let Some(mtime) = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs())
+        else {
+            return false;
+        };
+        !(self.modified_after.is_some_and(|after| mtime < after)
+            || self.modified_before.is_some_and(|before| mtime >= before))
     }
+}
 
-    fn emit(&mut self, window: Option<&Window>, message: &'static str, force: bool) {
-        let progress = if self.total_bytes == 0 {
-            if force {
-                100.0
-            } else {
-                0.0
+impl IncrementalState {
+    fn load(options: &IncrementalOptions) -> Result<Self, String> {
+        let (base, base_dirs) = match options.base_manifest.as_ref() {
+            Some(path) => {
+                let mut file =
+                    File::open(path).map_err(|err| format!("读取基准清单失败: {}", err))?;
+                let manifest = parse_manifest(&mut file)?;
+                (manifest.files, manifest.dirs)
             }
-        } else {
-            (self.processed_bytes as f64 / self.total_bytes as f64 * 100.0).clamp(0.0, 100.0)
+            None => (BTreeMap::new(), BTreeSet::new()),
         };
-        let rounded = progress.floor() as i16;
+        Ok(Self {
+            base,
+            base_dirs,
+            compare_hashes: options.compare_hashes,
+            snapshot: ArchiveManifest::default(),
+            unchanged_files: 0,
+        })
+    }
 
-        if !force && rounded == self.last_emitted_progress {
-            return;
+    // 文件与基准快照一致时返回基准条目；默认只比较大小与修改时间（精确到纳秒）。
+    fn unchanged_entry(
+        &self,
+        key: &str,
+        source_path: &Path,
+        size: u64,
+        modified: Option<Duration>,
+    ) -> Result<Option<ManifestEntry>, String> {
+        let Some(base) = self.base.get(key) else {
+            return Ok(None);
+        };
+        if base.size != size {
+            return Ok(None);
+        }
+        if !self.compare_hashes {
+            // 旧快照没有纳秒部分，这类文件按已修改处理，重新打包一次。
+            let same_mtime = modified.is_some_and(|modified| {
+                base.mtime == Some(modified.as_secs())
+                    && base.mtime_nanos == Some(modified.subsec_nanos())
+            });
+            return Ok(same_mtime.then(|| base.clone()));
         }
 
-        self.last_emitted_progress = rounded;
-        emit_archive_progress(
-            window,
-            ArchiveProgressPayload {
-                operation: self.operation.to_string(),
-                stage: self.stage.to_string(),
-                message: message.to_string(),
-                progress,
-                current_path: self.current_path.clone(),
-            },
-        );
+        Ok((file_sha256(source_path)? == base.sha256).then(|| base.clone()))
+    }
+
+    fn deleted_paths(&self) -> Vec<String> {
+        let files = self
+            .base
+            .keys()
+            .filter(|path| !self.snapshot.files.contains_key(*path));
+        let dirs = self
+            .base_dirs
+            .iter()
+            .filter(|path| !self.snapshot.dirs.contains(*path));
+        files.chain(dirs).cloned().collect()
     }
 }
 
-impl<'a, R: Read> ProgressReader<'a, R> {
-    fn new(
-        inner: R,
-        tracker: &'a mut ArchiveProgressTracker,
-        window: Option<&'a Window>,
-        message: &'static str,
-    ) -> Self {
+impl ManifestEntry {
+    fn with_modified(self, modified: Option<Duration>) -> Self {
         Self {
-            inner,
-            tracker,
-            window,
-            message,
+            mtime: modified.map(|modified| modified.as_secs()),
+            mtime_nanos: modified.map(|modified| modified.subsec_nanos()),
+            ..self
         }
     }
 }
 
-impl<R: Read> Read for ProgressReader<'_, R> {
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let read = self.inner.read(buf)?;
-        if read > 0 {
-            self.tracker
-                .advance_bytes(self.window, read as u64, self.message);
-        }
-        Ok(read)
+impl HashTap {
+    fn start(&self) {
+        *self.0.borrow_mut() = Some((Sha256::new(), 0));
+    }
+
+    fn finish(&self) -> Option<ManifestEntry> {
+        self.0
+            .borrow_mut()
+            .take()
+            .map(|(hasher, size)| ManifestEntry {
+                sha256: hex_digest(&hasher.finalize()),
+                size,
+                mtime: None,
+                mtime_nanos: None,
+            })
     }
 }
 
-impl ArchiveHeader {
-    fn new_plain() -> Self {
-        Self {
-            flags: 0,
-            compression: COMPRESSION_GZIP,
-            encryption: None,
+impl<R: Read> HashingReader<R> {
+    fn new(inner: R, tap: HashTap) -> Self {
+        Self { inner, tap }
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        if let Some((hasher, size)) = self.tap.0.borrow_mut().as_mut() {
+            hasher.update(&buf[..read]);
+            *size += read as u64;
         }
+        Ok(read)
     }
+}
 
-    fn new_encrypted() -> Result<Self, String> {
+impl VolumeWriter {
+    fn create(base_path: &Path, volume_size: Option<u64>) -> io::Result<Self> {
+        let current = create_part(&part_path(base_path, volume_size, 1))?;
         Ok(Self {
-            flags: FLAG_ENCRYPTED,
-            compression: COMPRESSION_GZIP,
-            encryption: Some(EncryptionMetadata {
-                memory_kib: DEFAULT_ARGON2_MEMORY_KIB,
-                iterations: DEFAULT_ARGON2_ITERATIONS,
-                lanes: default_argon2_lanes(),
-                salt: random_bytes()?,
-                stream_nonce: random_bytes()?,
-            }),
+            base_path: base_path.to_path_buf(),
+            volume_size,
+            index: 1,
+            current,
+            written: 0,
+            checksum: None,
         })
     }
 
-    // 头部既负责描述归档格式，也作为 AEAD 的 AAD，
-    // 这样一旦有人篡改加密参数或压缩标记，认证阶段会直接失败。
-    fn encoded_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::with_capacity(
-            FORMAT_MARKER.len()
-                + 2
-                + self
-                    .encryption
-                    .as_ref()
-                    .map(|_| 12 + SALT_LEN + STREAM_NONCE_LEN)
-                    .unwrap_or(0),
-        );
-        bytes.extend_from_slice(FORMAT_MARKER);
-        bytes.push(self.flags);
-        bytes.push(self.compression);
-
-        if let Some(meta) = &self.encryption {
-            bytes.extend_from_slice(&meta.memory_kib.to_le_bytes());
-            bytes.extend_from_slice(&meta.iterations.to_le_bytes());
-            bytes.extend_from_slice(&meta.lanes.to_le_bytes());
-            bytes.extend_from_slice(&meta.salt);
-            bytes.extend_from_slice(&meta.stream_nonce);
+    // 写入魔数与头部；头部带校验尾标记时，从这里开始为后续负载计算摘要。
+    fn write_header(&mut self, header: &ArchiveHeader) -> Result<Vec<u8>, String> {
+        let aad = write_archive_header(self, header)?;
+        if header.has_checksum_trailer() {
+            self.checksum = Some(Sha256::new());
         }
+        Ok(aad)
+    }
 
-        bytes
+    fn roll(&mut self) -> io::Result<()> {
+        self.sync_current()?;
+        self.index += 1;
+        self.current = create_part(&volume_path(&self.base_path, self.index))?;
+        self.written = 0;
+        Ok(())
     }
 
-    fn aad_bytes(&self) -> Vec<u8> {
-        let mut aad = Vec::with_capacity(MAGIC_HEADER.len() + self.encoded_bytes().len());
-        aad.extend_from_slice(MAGIC_HEADER);
-        aad.extend_from_slice(&self.encoded_bytes());
-        aad
+    // 最后一卷必须小于分卷大小，读取时才能据此判断后面没有更多分卷；
+    // 恰好写满时补一个空的末卷。`header` 须已记录最终的内容大小，校验尾按它计算。
+    fn finish(mut self, header: &ArchiveHeader) -> io::Result<()> {
+        if let Some(hasher) = self.checksum.take() {
+            self.write_all(&archive_checksum(header, &hasher.finalize()))?;
+        }
+        if self.volume_size == Some(self.written) {
+            self.roll()?;
+        }
+        self.sync_current()
+    }
+
+    // 落盘后才会把临时文件改名为最终输出，避免崩溃后留下看似完整、实际缺数据的归档。
+    fn sync_current(&mut self) -> io::Result<()> {
+        self.current.flush()?;
+        self.current.get_ref().sync_all()
     }
 }
 
-// STREAM AEAD 基于流式分块工作：gzip 连续明文流写入这里后，
-// 会被切成固定大小的块并逐块认证加密，避免整包驻留内存。
-struct EncryptedPayloadWriter<W: Write> {
-    inner: W,
-    encryptor: Option<ArchiveEncryptor>,
-    aad: Vec<u8>,
-    buffer: Vec<u8>,
-    finished: bool,
+impl Write for VolumeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = match self.volume_size {
+            None => self.current.write(buf)?,
+            Some(volume_size) => {
+                if self.written == volume_size {
+                    self.roll()?;
+                }
+                let len = buf.len().min((volume_size - self.written) as usize);
+                let written = self.current.write(&buf[..len])?;
+                self.written += written as u64;
+                written
+            }
+        };
+        if let Some(hasher) = self.checksum.as_mut() {
+            hasher.update(&buf[..written]);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.current.flush()
+    }
 }
 
-impl<W: Write> EncryptedPayloadWriter<W> {
-    fn new(
-        inner: W,
-        key_bytes: [u8; KEY_LEN],
-        nonce_bytes: [u8; STREAM_NONCE_LEN],
-        aad: Vec<u8>,
-    ) -> Self {
-        let key = GenericArray::clone_from_slice(&key_bytes);
-        let nonce = GenericArray::clone_from_slice(&nonce_bytes);
-        let cipher = ArchiveCipher::new(&key);
+impl VolumeReader {
+    fn open(parts: Vec<PathBuf>, readable_bytes: u64) -> Result<Self, String> {
+        let first = parts.first().ok_or("归档分卷列表为空".to_string())?;
+        let current = BufReader::new(File::open(first).map_err(|err| err.to_string())?);
+        Ok(Self {
+            parts,
+            index: 0,
+            current,
+            remaining: readable_bytes,
+        })
+    }
 
-        Self {
-            inner,
-            encryptor: Some(ArchiveEncryptor::from_aead(cipher, &nonce)),
-            aad,
-            buffer: Vec::with_capacity(CHUNK_PLAINTEXT_SIZE),
-            finished: false,
+    fn read_parts(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let read = self.current.read(buf)?;
+            if read > 0 || buf.is_empty() || self.index + 1 >= self.parts.len() {
+                return Ok(read);
+            }
+            self.index += 1;
+            let part = &self.parts[self.index];
+            let file = File::open(part).map_err(|err| {
+                io::Error::new(
+                    err.kind(),
+                    format!("无法打开分卷 {}: {}", part.display(), err),
+                )
+            })?;
+            self.current = BufReader::new(file);
         }
     }
+}
 
-    fn write_chunk(&mut self, is_last: bool, plaintext: &[u8]) -> io::Result<()> {
-        let payload = Payload {
-            msg: plaintext,
-            aad: &self.aad,
-        };
+impl Read for VolumeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = (buf.len() as u64).min(self.remaining) as usize;
+        let read = self.read_parts(&mut buf[..len])?;
+        self.remaining -= read as u64;
+        Ok(read)
+    }
+}
 
-        let ciphertext = if is_last {
-            self.encryptor
-                .take()
-                .ok_or_else(|| {
-                    io::Error::new(
-                        io::ErrorKind::BrokenPipe,
-                        "archive writer already finalized",
-                    )
-                })?
-                .encrypt_last(payload)
-        } else {
-            self.encryptor
-                .as_mut()
-                .ok_or_else(|| {
-                    io::Error::new(
-                        io::ErrorKind::BrokenPipe,
-                        "archive writer already finalized",
-                    )
-                })?
-                .encrypt_next(payload)
+impl ArchiveState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 前端未传入 ID 时生成一个，随第一条进度事件告知前端，之后同样可以取消与暂停。
+    fn register(&self, operation_id: Option<String>) -> Result<ArchiveOperationGuard<'_>, String> {
+        let operation_id = match operation_id {
+            Some(operation_id) => operation_id,
+            None => format!("krate-{}", hex_digest(&random_bytes::<8>()?)),
+        };
+        let cancel = Arc::new(OperationControl {
+            id: operation_id.clone(),
+            ..Default::default()
+        });
+        let mut operations = self
+            .operations
+            .lock()
+            .map_err(|_| "归档状态锁异常".to_string())?;
+        if operations.contains_key(&operation_id) {
+            return Err(format!("归档任务 ID 已存在: {}", operation_id));
         }
-        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "archive encryption failed"))?;
+        operations.insert(operation_id.clone(), cancel.clone());
 
-        let chunk_len = u32::try_from(ciphertext.len()).map_err(|_| {
-            io::Error::new(
-                io::ErrorKind::InvalidData,
-                "encrypted chunk is too large to fit in the archive format",
-            )
-        })?;
+        Ok(ArchiveOperationGuard {
+            state: self,
+            operation_id,
+            cancel,
+        })
+    }
 
-        self.inner.write_all(&[if is_last {
-            CHUNK_FLAG_LAST
-        } else {
-            CHUNK_FLAG_NEXT
-        }])?;
-        self.inner.write_all(&chunk_len.to_le_bytes())?;
-        self.inner.write_all(&ciphertext)?;
+    fn control(&self, operation_id: &str) -> Result<Arc<OperationControl>, String> {
+        let operations = self
+            .operations
+            .lock()
+            .map_err(|_| "归档状态锁异常".to_string())?;
+        operations
+            .get(operation_id)
+            .cloned()
+            .ok_or_else(|| format!("归档任务不存在或已结束: {}", operation_id))
+    }
+
+    fn cancel(&self, operation_id: &str) -> Result<(), String> {
+        self.control(operation_id)?.cancel();
         Ok(())
     }
 
-    fn take_buffer(&mut self) -> Vec<u8> {
-        std::mem::replace(&mut self.buffer, Vec::with_capacity(CHUNK_PLAINTEXT_SIZE))
+    fn set_paused(&self, operation_id: &str, paused: bool) -> Result<(), String> {
+        self.control(operation_id)?.set_paused(paused);
+        Ok(())
     }
 
-    fn finish(mut self) -> io::Result<W> {
-        if !self.finished {
-            self.finished = true;
-            let final_chunk = self.take_buffer();
-            self.write_chunk(true, &final_chunk)?;
-            self.inner.flush()?;
-        }
-        Ok(self.inner)
+    fn list(&self) -> Result<Vec<ArchiveOperationInfo>, String> {
+        let operations = self
+            .operations
+            .lock()
+            .map_err(|_| "归档状态锁异常".to_string())?;
+        let mut infos = operations
+            .iter()
+            .map(|(operation_id, control)| ArchiveOperationInfo {
+                operation_id: operation_id.clone(),
+                paused: control.is_paused(),
+                cancelled: control.is_cancelled(),
+                progress: control
+                    .progress
+                    .lock()
+                    .ok()
+                    .and_then(|progress| progress.clone()),
+            })
+            .collect::<Vec<_>>();
+        infos.sort_by(|left, right| left.operation_id.cmp(&right.operation_id));
+        Ok(infos)
     }
 }
 
-impl<W: Write> Write for EncryptedPayloadWriter<W> {
-    fn write(&mut self, mut buf: &[u8]) -> io::Result<usize> {
-        if self.finished {
-            return Err(io::Error::new(
-                io::ErrorKind::BrokenPipe,
-                "cannot write to a finalized archive writer",
-            ));
-        }
-
-        let original_len = buf.len();
-
-        if !self.buffer.is_empty() {
-            let needed = CHUNK_PLAINTEXT_SIZE - self.buffer.len();
-            let take = needed.min(buf.len());
-            self.buffer.extend_from_slice(&buf[..take]);
-            buf = &buf[take..];
-
-            if self.buffer.len() == CHUNK_PLAINTEXT_SIZE {
-                let chunk = self.take_buffer();
-                self.write_chunk(false, &chunk)?;
-            }
-        }
+impl OperationControl {
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
 
-        while buf.len() >= CHUNK_PLAINTEXT_SIZE {
-            let (chunk, rest) = buf.split_at(CHUNK_PLAINTEXT_SIZE);
-            self.write_chunk(false, chunk)?;
-            buf = rest;
-        }
+    fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
 
-        if !buf.is_empty() {
-            self.buffer.extend_from_slice(buf);
-        }
+    // 先改标记再持锁通知，等待方在持锁检查标记，因此不会错过唤醒。
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+        let _gate = self.gate.lock();
+        self.resumed.notify_all();
+    }
 
-        Ok(original_len)
+    fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+        let _gate = self.gate.lock();
+        self.resumed.notify_all();
     }
 
-    fn flush(&mut self) -> io::Result<()> {
-        self.inner.flush()
+    fn wait_while_paused(&self) {
+        let Ok(mut gate) = self.gate.lock() else {
+            return;
+        };
+        while self.is_paused() && !self.is_cancelled() {
+            gate = match self.resumed.wait(gate) {
+                Ok(gate) => gate,
+                Err(_) => return,
+            };
+        }
     }
 }
 
-struct EncryptedPayloadReader<R: Read> {
-    inner: R,
-    decryptor: Option<ArchiveDecryptor>,
-    aad: Vec<u8>,
-    buffer: Vec<u8>,
-    offset: usize,
-    finished: bool,
+impl Drop for ArchiveOperationGuard<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut operations) = self.state.operations.lock() {
+            operations.remove(&self.operation_id);
+        }
+    }
 }
 
-impl<R: Read> EncryptedPayloadReader<R> {
+impl ArchiveProgressTracker {
     fn new(
-        inner: R,
-        key_bytes: [u8; KEY_LEN],
-        nonce_bytes: [u8; STREAM_NONCE_LEN],
-        aad: Vec<u8>,
+        operation: &'static str,
+        stage: &'static str,
+        total_bytes: u64,
+        cancel: Arc<OperationControl>,
     ) -> Self {
-        let key = GenericArray::clone_from_slice(&key_bytes);
-        let nonce = GenericArray::clone_from_slice(&nonce_bytes);
-        let cipher = ArchiveCipher::new(&key);
-
         Self {
-            inner,
-            decryptor: Some(ArchiveDecryptor::from_aead(cipher, &nonce)),
-            aad,
-            buffer: Vec::new(),
-            offset: 0,
-            finished: false,
+            operation,
+            stage,
+            total_bytes,
+            processed_bytes: 0,
+            last_emitted_at: None,
+            current_path: None,
+            samples: VecDeque::new(),
+            cancel,
         }
     }
 
-    fn load_next_chunk(&mut self) -> io::Result<()> {
-        let mut flag = [0u8; 1];
-        self.inner.read_exact(&mut flag).map_err(|err| {
-            if err.kind() == io::ErrorKind::UnexpectedEof {
-                io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    "encrypted archive ended unexpectedly",
-                )
-            } else {
-                err
-            }
-        })?;
-
-        let mut len_bytes = [0u8; CHUNK_LENGTH_BYTES];
-        self.inner.read_exact(&mut len_bytes)?;
-        let chunk_len = u32::from_le_bytes(len_bytes) as usize;
+    fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
 
-        if !(AEAD_TAG_LEN..=CHUNK_PLAINTEXT_SIZE + AEAD_TAG_LEN).contains(&chunk_len) {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "encrypted archive chunk length is invalid",
-            ));
+    // 暂停期间阻塞当前线程，输出文件句柄保持打开；恢复后还原暂停前的阶段。
+    fn wait_while_paused(&mut self, window: Option<&Window>, message: &'static str) {
+        if !self.cancel.is_paused() {
+            return;
         }
+        let stage = self.stage;
+        self.set_stage(window, "paused", "已暂停");
+        self.cancel.wait_while_paused();
+        self.set_stage(window, stage, message);
+    }
 
-        let mut ciphertext = vec![0u8; chunk_len];
-        self.inner.read_exact(&mut ciphertext)?;
+    fn check_cancelled(&self) -> Result<(), String> {
+        if self.is_cancelled() {
+            return Err(ARCHIVE_CANCELLED_ERROR.to_string());
+        }
+        Ok(())
+    }
 
-        let payload = Payload {
-            msg: ciphertext.as_slice(),
-            aad: &self.aad,
-        };
+    fn set_stage(&mut self, window: Option<&Window>, stage: &'static str, message: &'static str) {
+        self.stage = stage;
+        self.emit(window, message, true);
+    }
 
-        self.buffer = match flag[0] {
-            CHUNK_FLAG_NEXT => self
-                .decryptor
-                .as_mut()
-                .ok_or_else(|| {
-                    io::Error::new(
-                        io::ErrorKind::BrokenPipe,
-                        "archive reader already finalized",
-                    )
-                })?
-                .decrypt_next(payload),
-            CHUNK_FLAG_LAST => {
-                self.finished = true;
-                self.decryptor
-                    .take()
-                    .ok_or_else(|| {
-                        io::Error::new(
-                            io::ErrorKind::BrokenPipe,
-                            "archive reader already finalized",
-                        )
-                    })?
-                    .decrypt_last(payload)
-            }
-            _ => {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    "encrypted archive chunk flag is invalid",
-                ))
-            }
+    fn set_current_path(
+        &mut self,
+        window: Option<&Window>,
+        current_path: Option<String>,
+        message: &'static str,
+    ) {
+        self.current_path = current_path;
+        self.emit(window, message, true);
+    }
+
+    fn reset_total(&mut self, total_bytes: u64) {
+        self.total_bytes = total_bytes;
+        self.processed_bytes = 0;
+        self.samples.clear();
+    }
+
+    fn advance_bytes(&mut self, window: Option<&Window>, bytes: u64, message: &'static str) {
+        self.processed_bytes = self.processed_bytes.saturating_add(bytes);
+        if self.total_bytes > 0 {
+            self.processed_bytes = self.processed_bytes.min(self.total_bytes);
         }
-        .map_err(|_| {
-            io::Error::new(
-                io::ErrorKind::InvalidData,
-                "归档解密失败，密码错误或文件已损坏",
-            )
-        })?;
+        self.record_sample(Instant::now());
+        self.emit(window, message, false);
+    }
 
-        self.offset = 0;
-        Ok(())
+    fn record_sample(&mut self, now: Instant) {
+        self.samples.push_back((now, self.processed_bytes));
+        while self.samples.len() > 2 {
+            match self.samples.front() {
+                Some((at, _)) if now.duration_since(*at) > PROGRESS_SPEED_WINDOW => {
+                    self.samples.pop_front();
+                }
+                _ => break,
+            }
+        }
     }
 
-    fn prime(&mut self) -> io::Result<()> {
-        if self.buffer.is_empty() && !self.finished {
-            self.load_next_chunk()?;
+    fn bytes_per_second(&self) -> Option<f64> {
+        let (first_at, first_bytes) = self.samples.front()?;
+        let (last_at, last_bytes) = self.samples.back()?;
+        let elapsed = last_at.duration_since(*first_at).as_secs_f64();
+        if elapsed <= 0.0 {
+            return None;
         }
-        Ok(())
+        Some(last_bytes.saturating_sub(*first_bytes) as f64 / elapsed)
     }
-}
 
-impl<R: Read> Read for EncryptedPayloadReader<R> {
-    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
-        if out.is_empty() {
-            return Ok(0);
+    fn eta_seconds(&self, bytes_per_second: Option<f64>) -> Option<u64> {
+        let speed = bytes_per_second.filter(|speed| *speed > 0.0)?;
+        if self.total_bytes == 0 {
+            return None;
         }
+        let remaining = self.total_bytes.saturating_sub(self.processed_bytes);
+        Some((remaining as f64 / speed).ceil() as u64)
+    }
 
-        if self.offset >= self.buffer.len() {
-            if self.finished {
-                return Ok(0);
-            }
+    fn finish(&mut self, window: Option<&Window>, stage: &'static str, message: &'static str) {
+        self.stage = stage;
+        self.processed_bytes = self.total_bytes;
+        self.current_path = None;
+        self.emit(window, message, true);
+    }
 
-            self.load_next_chunk()?;
-            if self.offset >= self.buffer.len() && self.finished {
-                return Ok(0);
+    fn emit(&mut self, window: Option<&Window>, message: &'static str, force: bool) {
+        let progress = if self.total_bytes == 0 {
+            if force {
+                100.0
+            } else {
+                0.0
             }
+        } else {
+            (self.processed_bytes as f64 / self.total_bytes as f64 * 100.0).clamp(0.0, 100.0)
+        };
+        let now = Instant::now();
+        if !force
+            && self
+                .last_emitted_at
+                .is_some_and(|at| now.duration_since(at) < PROGRESS_EMIT_INTERVAL)
+        {
+            return;
         }
 
-        let remaining = &self.buffer[self.offset..];
-        let count = remaining.len().min(out.len());
-        out[..count].copy_from_slice(&remaining[..count]);
-        self.offset += count;
-        Ok(count)
+        self.last_emitted_at = Some(now);
+        let bytes_per_second = self.bytes_per_second();
+        let payload = ArchiveProgressPayload {
+            operation_id: self.cancel.id.clone(),
+            operation: self.operation.to_string(),
+            stage: self.stage.to_string(),
+            message: message.to_string(),
+            progress,
+            current_path: self.current_path.clone(),
+            processed_bytes: self.processed_bytes,
+            total_bytes: self.total_bytes,
+            bytes_per_second,
+            eta_seconds: self.eta_seconds(bytes_per_second),
+        };
+        if let Ok(mut latest) = self.cancel.progress.lock() {
+            *latest = Some(payload.clone());
+        }
+        emit_archive_progress(window, payload);
     }
 }
 
-fn emit_archive_progress(window: Option<&Window>, payload: ArchiveProgressPayload) {
-    if let Some(window) = window {
-        let _ = window.emit(ARCHIVE_PROGRESS_EVENT, payload);
+impl<'a, R: Read> ProgressReader<'a, R> {
+    fn new(
+        inner: R,
+        tracker: &'a mut ArchiveProgressTracker,
+        window: Option<&'a Window>,
+        message: &'static str,
+    ) -> Self {
+        Self {
+            inner,
+            tracker,
+            window,
+            message,
+            plain_bytes: None,
+        }
     }
 }
 
-fn normalized_password(password: Option<String>) -> Option<String> {
-    password.and_then(|value| if value.is_empty() { None } else { Some(value) })
-}
-
-fn default_argon2_lanes() -> u32 {
-    std::thread::available_parallelism()
-        .map(|parallelism| parallelism.get() as u32)
-        .unwrap_or(1)
-        .clamp(1, MAX_ARGON2_LANES)
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // 源文件与归档文件都经由这里读取，按块检查暂停与取消标记即可及时响应大文件。
+        self.tracker.wait_while_paused(self.window, self.message);
+        if self.tracker.is_cancelled() {
+            return Err(io::Error::other(ARCHIVE_CANCELLED_ERROR));
+        }
+        let read = self.inner.read(buf)?;
+        let advanced = match self.plain_bytes.as_ref() {
+            Some(counter) => counter.0.replace(0),
+            None => read as u64,
+        };
+        if advanced > 0 {
+            self.tracker
+                .advance_bytes(self.window, advanced, self.message);
+        }
+        Ok(read)
+    }
 }
 
-fn random_bytes<const N: usize>() -> Result<[u8; N], String> {
-    let mut bytes = [0u8; N];
-    getrandom::fill(&mut bytes).map_err(|err| format!("生成归档随机参数失败: {}", err))?;
-    Ok(bytes)
+impl<W: Write> CountingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, written: 0 }
+    }
 }
 
-fn validate_encryption_metadata(metadata: &EncryptionMetadata) -> Result<(), String> {
-    if metadata.memory_kib > MAX_ARCHIVE_ARGON2_MEMORY_KIB {
-        return Err("归档加密参数超出当前版本支持范围".to_string());
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
     }
 
-    if metadata.iterations > MAX_ARCHIVE_ARGON2_ITERATIONS {
-        return Err("归档加密参数超出当前版本支持范围".to_string());
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
     }
+}
 
-    if metadata.lanes == 0 || metadata.lanes > MAX_ARGON2_LANES {
-        return Err("归档加密参数超出当前版本支持范围".to_string());
+impl<R: Read> CountingReader<R> {
+    fn new(inner: R, counter: ByteCounter) -> Self {
+        Self { inner, counter }
     }
-
-    Ok(())
 }
 
-fn derive_archive_key(
-    password: &str,
-    metadata: &EncryptionMetadata,
-) -> Result<[u8; KEY_LEN], String> {
-    validate_encryption_metadata(metadata)?;
-
-    let params = Params::new(
-        metadata.memory_kib,
-        metadata.iterations,
-        metadata.lanes,
-        Some(KEY_LEN),
-    )
-    .map_err(|err| format!("归档加密参数无效: {}", err))?;
-
-    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
-    let mut key = [0u8; KEY_LEN];
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        let cell = &self.counter.0;
+        cell.set(cell.get() + read as u64);
+        Ok(read)
+    }
+}
 
-    argon2
-        .hash_password_into(password.as_bytes(), &metadata.salt, &mut key)
-        .map_err(|err| format!("归档密钥派生失败: {}", err))?;
+impl ArchiveCompression {
+    fn code(self) -> u8 {
+        match self {
+            Self::Gzip => COMPRESSION_GZIP,
+            Self::Zstd => COMPRESSION_ZSTD,
+            Self::Xz => COMPRESSION_XZ,
+            Self::None => COMPRESSION_NONE,
+        }
+    }
 
-    Ok(key)
-}
+    fn from_code(code: u8) -> Option<Self> {
+        match code {
+            COMPRESSION_GZIP => Some(Self::Gzip),
+            COMPRESSION_ZSTD => Some(Self::Zstd),
+            COMPRESSION_XZ => Some(Self::Xz),
+            COMPRESSION_NONE => Some(Self::None),
+            _ => None,
+        }
+    }
 
-fn absolute_path(path: &Path) -> Result<PathBuf, String> {
-    if path.is_absolute() {
-        Ok(path.to_path_buf())
-    } else {
-        std::env::current_dir()
-            .map_err(|err| err.to_string())
-            .map(|cwd| cwd.join(path))
+    fn progress_message(self, encrypted: bool) -> &'static str {
+        match (self, encrypted) {
+            (Self::None, true) => "正在打包并加密",
+            (Self::None, false) => "正在打包",
+            (_, true) => "正在压缩并加密",
+            (_, false) => "正在压缩打包",
+        }
     }
 }
 
-fn normalize_path_for_comparison(path: &Path) -> Result<PathBuf, String> {
-    let absolute = absolute_path(path)?;
+// tar 流与加密层之间的压缩层，按头部记录的算法选择具体实现。
+enum CompressedWriter<W: Write> {
+    Gzip(GzEncoder<W>),
+    ParallelGzip(ParallelGzipWriter<W>),
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+    Xz(xz2::write::XzEncoder<W>),
+    Stored(W),
+}
 
-    if absolute.file_name().is_none() {
-        return absolute.canonicalize().map_err(|err| err.to_string());
+impl<W: Write> CompressedWriter<W> {
+    // gzip 与 xz 共用 0–9 的等级，超出范围的值按 9 处理；zstd 使用固定等级。
+    // `threads` 大于 1 时 gzip 改为分块并行压缩。
+    fn new(
+        inner: W,
+        compression: ArchiveCompression,
+        level: Option<u32>,
+        threads: usize,
+    ) -> Result<Self, String> {
+        Ok(match compression {
+            ArchiveCompression::Gzip if threads > 1 => Self::ParallelGzip(ParallelGzipWriter::new(
+                inner,
+                Compression::new(compression_level(level, DEFAULT_GZIP_LEVEL)),
+                threads,
+            )),
+            ArchiveCompression::Gzip => Self::Gzip(GzEncoder::new(
+                inner,
+                Compression::new(compression_level(level, DEFAULT_GZIP_LEVEL)),
+            )),
+            ArchiveCompression::Xz => Self::Xz(xz2::write::XzEncoder::new(
+                inner,
+                compression_level(level, DEFAULT_XZ_PRESET),
+            )),
+            ArchiveCompression::Zstd => Self::Zstd(
+                zstd::stream::write::Encoder::new(inner, DEFAULT_ZSTD_LEVEL)
+                    .map_err(|err| format!("初始化 zstd 压缩失败: {}", err))?,
+            ),
+            ArchiveCompression::None => Self::Stored(inner),
+        })
     }
 
-    let parent = absolute
-        .parent()
-        .ok_or_else(|| format!("无效的路径: {}", absolute.display()))?;
-    let normalized_parent = parent.canonicalize().map_err(|err| err.to_string())?;
-    Ok(normalized_parent.join(
-        absolute
-            .file_name()
-            .ok_or_else(|| format!("无效的路径: {}", absolute.display()))?,
-    ))
+    fn finish(self) -> io::Result<W> {
+        match self {
+            Self::Gzip(encoder) => encoder.finish(),
+            Self::ParallelGzip(encoder) => encoder.finish(),
+            Self::Zstd(encoder) => encoder.finish(),
+            Self::Xz(encoder) => encoder.finish(),
+            Self::Stored(inner) => Ok(inner),
+        }
+    }
 }
 
-fn suffixed_name(original_name: &OsStr, suffix: usize) -> OsString {
-    let original_path = Path::new(original_name);
-
-    if let (Some(stem), Some(extension)) = (original_path.file_stem(), original_path.extension()) {
-        let mut candidate = OsString::from(stem);
-        candidate.push(format!(" ({suffix})"));
-        candidate.push(".");
-        candidate.push(extension);
-        return candidate;
+impl<W: Write> Write for CompressedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Gzip(encoder) => encoder.write(buf),
+            Self::ParallelGzip(encoder) => encoder.write(buf),
+            Self::Zstd(encoder) => encoder.write(buf),
+            Self::Xz(encoder) => encoder.write(buf),
+            Self::Stored(inner) => inner.write(buf),
+        }
     }
 
-    let mut candidate = OsString::from(original_name);
-    candidate.push(format!(" ({suffix})"));
-    candidate
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Gzip(encoder) => encoder.flush(),
+            Self::ParallelGzip(encoder) => encoder.flush(),
+            Self::Zstd(encoder) => encoder.flush(),
+            Self::Xz(encoder) => encoder.flush(),
+            Self::Stored(inner) => inner.flush(),
+        }
+    }
 }
 
-fn build_archive_inputs(inputs: &[String]) -> Result<Vec<ArchiveInput>, String> {
-    let mut archive_inputs = Vec::with_capacity(inputs.len());
-    let mut used_roots = HashSet::new();
+// pigz 风格的并行 gzip：输入按块交给工作线程压缩成独立的 gzip 成员，再按块序号写给下游。
+struct ParallelGzipWriter<W: Write> {
+    inner: W,
+    buffer: Vec<u8>,
+    jobs: Option<mpsc::SyncSender<(u64, Vec<u8>)>>,
+    results: mpsc::Receiver<(u64, io::Result<Vec<u8>>)>,
+    pending: BTreeMap<u64, Vec<u8>>,
+    submitted: u64,
+    written: u64,
+    max_in_flight: u64,
+}
 
-    for path_str in inputs {
-        let source_path = PathBuf::from(path_str);
-        let file_name = source_path
-            .file_name()
-            .ok_or_else(|| format!("无效的归档输入路径: {}", source_path.display()))?;
-
-        let mut archive_name = file_name.to_os_string();
-        if used_roots.contains(&archive_name) {
-            let mut suffix = 2usize;
-            loop {
-                archive_name = suffixed_name(file_name, suffix);
-                if !used_roots.contains(&archive_name) {
-                    break;
+impl<W: Write> ParallelGzipWriter<W> {
+    fn new(inner: W, level: Compression, threads: usize) -> Self {
+        let (job_sender, job_receiver) = mpsc::sync_channel::<(u64, Vec<u8>)>(threads);
+        let (result_sender, results) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        // 写端被丢弃（完成或出错）后任务通道关闭，工作线程随之退出。
+        for _ in 0..threads {
+            let job_receiver = job_receiver.clone();
+            let result_sender = result_sender.clone();
+            std::thread::spawn(move || loop {
+                let job = match job_receiver.lock() {
+                    Ok(receiver) => receiver.recv(),
+                    Err(_) => return,
+                };
+                let Ok((index, block)) = job else {
+                    return;
+                };
+                let mut encoder = GzEncoder::new(Vec::with_capacity(block.len() / 2), level);
+                let compressed = encoder.write_all(&block).and_then(|_| encoder.finish());
+                if result_sender.send((index, compressed)).is_err() {
+                    return;
                 }
-                suffix += 1;
-            }
+            });
         }
-        used_roots.insert(archive_name.clone());
 
-        archive_inputs.push(ArchiveInput {
-            source_path,
-            archive_root: PathBuf::from(archive_name),
-        });
+        Self {
+            inner,
+            buffer: Vec::with_capacity(PARALLEL_GZIP_BLOCK_SIZE),
+            jobs: Some(job_sender),
+            results,
+            pending: BTreeMap::new(),
+            submitted: 0,
+            written: 0,
+            max_in_flight: threads as u64 * 2,
+        }
     }
 
-    Ok(archive_inputs)
-}
+    // 未写出的块数有上限，读取端不会无限领先于压缩与写出。
+    fn submit_block(&mut self) -> io::Result<()> {
+        while self.submitted - self.written >= self.max_in_flight {
+            self.receive_one()?;
+        }
+        let block = std::mem::replace(
+            &mut self.buffer,
+            Vec::with_capacity(PARALLEL_GZIP_BLOCK_SIZE),
+        );
+        self.jobs
+            .as_ref()
+            .ok_or_else(|| io::Error::other("压缩线程已关闭"))?
+            .send((self.submitted, block))
+            .map_err(|_| io::Error::other("压缩线程异常退出"))?;
+        self.submitted += 1;
+        Ok(())
+    }
 
-fn ensure_output_path_is_safe(inputs: &[ArchiveInput], output_path: &Path) -> Result<(), String> {
-    if output_path.exists() && output_path.is_dir() {
-        return Err("输出路径不能是文件夹".to_string());
+    // 先完成的块暂存，等前面的块都写出后再按顺序写。
+    fn receive_one(&mut self) -> io::Result<()> {
+        let (index, compressed) = self
+            .results
+            .recv()
+            .map_err(|_| io::Error::other("压缩线程异常退出"))?;
+        self.pending.insert(index, compressed?);
+        while let Some(compressed) = self.pending.remove(&self.written) {
+            self.inner.write_all(&compressed)?;
+            self.written += 1;
+        }
+        Ok(())
     }
 
-    let normalized_output = normalize_path_for_comparison(output_path)?;
+    fn finish(mut self) -> io::Result<W> {
+        if !self.buffer.is_empty() || self.submitted == 0 {
+            self.submit_block()?;
+        }
+        self.jobs = None;
+        while self.written < self.submitted {
+            self.receive_one()?;
+        }
+        Ok(self.inner)
+    }
+}
 
-    for input in inputs {
-        let metadata = fs::symlink_metadata(&input.source_path).map_err(|err| err.to_string())?;
-        let normalized_input = normalize_path_for_comparison(&input.source_path)?;
+impl<W: Write> Write for ParallelGzipWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(PARALLEL_GZIP_BLOCK_SIZE - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+        if self.buffer.len() == PARALLEL_GZIP_BLOCK_SIZE {
+            self.submit_block()?;
+        }
+        Ok(len)
+    }
 
-        if normalized_input == normalized_output {
-            return Err(format!(
-                "输出文件不能与输入路径相同: {}",
-                input.source_path.display()
-            ));
-        }
-
-        if metadata.is_dir() && normalized_output.starts_with(&normalized_input) {
-            return Err(format!(
-                "输出文件不能位于待归档目录内: {}",
-                input.source_path.display()
-            ));
-        }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
     }
-
-    Ok(())
 }
 
-fn unique_temp_output_path(output_path: &Path) -> Result<PathBuf, String> {
-    let parent = output_path
-        .parent()
-        .ok_or_else(|| format!("无效的输出路径: {}", output_path.display()))?;
-    let file_name = output_path
-        .file_name()
-        .ok_or_else(|| format!("无效的输出路径: {}", output_path.display()))?;
+// 由独立线程按块读取源文件，经有界通道交给 tar 组装线程；读取端最多领先 `READ_AHEAD_CHUNKS` 块。
+struct ReadAheadReader {
+    chunks: mpsc::Receiver<io::Result<Vec<u8>>>,
+    current: Vec<u8>,
+    position: usize,
+}
 
-    for attempt in 0..TEMP_OUTPUT_ATTEMPTS {
-        let nanos = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .map_err(|err| err.to_string())?
-            .as_nanos();
-        let mut temp_name = OsString::from(".");
-        temp_name.push(file_name);
-        temp_name.push(format!(".tmp-{}-{nanos}-{attempt}", std::process::id()));
-        let temp_path = parent.join(temp_name);
-        if !temp_path.exists() {
-            return Ok(temp_path);
+impl ReadAheadReader {
+    fn spawn(mut file: File) -> Self {
+        let (sender, chunks) = mpsc::sync_channel(READ_AHEAD_CHUNKS);
+        // 读到文件末尾或出错后关闭通道；读取端提前丢弃时发送失败，线程随之退出。
+        std::thread::spawn(move || loop {
+            let mut chunk = vec![0; READ_AHEAD_CHUNK_SIZE];
+            let chunk = match file.read(&mut chunk) {
+                Ok(0) => return,
+                Ok(read) => {
+                    chunk.truncate(read);
+                    Ok(chunk)
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => Err(err),
+            };
+            let failed = chunk.is_err();
+            if sender.send(chunk).is_err() || failed {
+                return;
+            }
+        });
+        Self {
+            chunks,
+            current: Vec::new(),
+            position: 0,
         }
     }
-
-    Err("无法为归档创建临时输出文件".to_string())
 }
 
-fn persist_temp_output(temp_path: &Path, output_path: &Path) -> Result<(), String> {
-    #[cfg(target_os = "windows")]
-    if output_path.exists() {
-        fs::remove_file(output_path).map_err(|err| err.to_string())?;
+impl Read for ReadAheadReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position == self.current.len() {
+            match self.chunks.recv() {
+                Ok(chunk) => {
+                    self.current = chunk?;
+                    self.position = 0;
+                }
+                Err(_) => return Ok(0),
+            }
+        }
+        let len = buf.len().min(self.current.len() - self.position);
+        buf[..len].copy_from_slice(&self.current[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
     }
-
-    fs::rename(temp_path, output_path).map_err(|err| err.to_string())
 }
 
-fn extract_root_base_name(archive_path: &Path) -> OsString {
-    match archive_path.file_stem() {
-        Some(stem) if !stem.is_empty() => stem.to_os_string(),
-        _ => OsString::from("krate-archive"),
-    }
+enum DecompressedReader<R: Read> {
+    // 并行压缩会输出多个首尾相接的 gzip 成员，需要按多成员流读取。
+    Gzip(MultiGzDecoder<R>),
+    Zstd(zstd::stream::read::Decoder<'static, BufReader<R>>),
+    Xz(xz2::read::XzDecoder<R>),
+    Stored(R),
 }
 
-fn unique_output_dir(parent: &Path, base_name: &OsStr) -> PathBuf {
-    let first_candidate = parent.join(base_name);
-    if !first_candidate.exists() {
-        return first_candidate;
+impl<R: Read> DecompressedReader<R> {
+    fn new(inner: R, compression: ArchiveCompression) -> Result<Self, String> {
+        Ok(match compression {
+            ArchiveCompression::Gzip => Self::Gzip(MultiGzDecoder::new(inner)),
+            ArchiveCompression::Zstd => Self::Zstd(
+                zstd::stream::read::Decoder::new(inner)
+                    .map_err(|err| format!("初始化 zstd 解压失败: {}", err))?,
+            ),
+            ArchiveCompression::Xz => Self::Xz(xz2::read::XzDecoder::new(inner)),
+            ArchiveCompression::None => Self::Stored(inner),
+        })
     }
+}
 
-    let mut suffix = 2usize;
-    loop {
-        let candidate = parent.join(suffixed_name(base_name, suffix));
-        if !candidate.exists() {
-            return candidate;
+impl<R: Read> Read for DecompressedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Gzip(decoder) => decoder.read(buf),
+            Self::Zstd(decoder) => decoder.read(buf),
+            Self::Xz(decoder) => decoder.read(buf),
+            Self::Stored(inner) => inner.read(buf),
         }
-        suffix += 1;
     }
 }
 
-fn prepare_extract_output_dir(
-    archive_path: &Path,
-    output_parent: &Path,
-) -> Result<PathBuf, String> {
-    if output_parent.exists() {
-        if !output_parent.is_dir() {
-            return Err("输出目录不是文件夹".to_string());
+impl ArchiveHeader {
+    fn new_plain(compression: ArchiveCompression) -> Self {
+        Self {
+            version: *FORMAT_MARKER,
+            flags: 0,
+            compression: compression.code(),
+            encryption: None,
+            metadata: None,
+            volume_size: None,
+            content_size: None,
         }
-    } else {
-        fs::create_dir_all(output_parent).map_err(|err| err.to_string())?;
     }
 
-    Ok(unique_output_dir(
-        output_parent,
-        &extract_root_base_name(archive_path),
-    ))
-}
+    fn new_encrypted(compression: ArchiveCompression, kdf: KdfOptions) -> Result<Self, String> {
+        let memory_kib = kdf.memory_kib.unwrap_or(DEFAULT_ARGON2_MEMORY_KIB);
+        if memory_kib > MAX_ARCHIVE_ARGON2_MEMORY_KIB {
+            return Err("Argon2 内存开销不能超过 2 GiB".to_string());
+        }
+        if memory_kib < MIN_ARCHIVE_ARGON2_MEMORY_KIB {
+            return Err("Argon2 内存开销不能小于 8 MiB".to_string());
+        }
+        let iterations = kdf.iterations.unwrap_or(DEFAULT_ARGON2_ITERATIONS);
+        if iterations == 0 || iterations > MAX_ARCHIVE_ARGON2_ITERATIONS {
+            return Err(format!(
+                "Argon2 迭代次数需在 1 到 {} 之间",
+                MAX_ARCHIVE_ARGON2_ITERATIONS
+            ));
+        }
+        let lanes = kdf.parallelism.unwrap_or_else(default_argon2_lanes);
+        if lanes == 0 || lanes > MAX_ARCHIVE_ARGON2_LANES {
+            return Err(format!(
+                "Argon2 并行度需在 1 到 {} 之间",
+                MAX_ARCHIVE_ARGON2_LANES
+            ));
+        }
 
-fn collect_input_stats(inputs: &[ArchiveInput]) -> Result<InputStats, String> {
-    let mut stats = InputStats::default();
+        Ok(Self {
+            version: *FORMAT_MARKER,
+            flags: FLAG_ENCRYPTED | FLAG_KEY_CHECK,
+            compression: compression.code(),
+            encryption: Some(EncryptionMetadata {
+                memory_kib,
+                iterations,
+                lanes,
+                salt: random_bytes()?,
+                stream_nonce: random_bytes()?,
+                key_check: None,
+            }),
+            metadata: None,
+            volume_size: None,
+            content_size: None,
+        })
+    }
 
-    for input in inputs {
-        collect_path_stats(&input.source_path, &mut stats)?;
+    fn set_metadata(&mut self, metadata: &ArchiveMetadata) -> Result<(), String> {
+        self.flags |= FLAG_METADATA;
+        self.metadata = Some(encode_metadata(metadata)?);
+        Ok(())
     }
 
-    Ok(stats)
-}
+    fn set_hidden_names(&mut self, hidden: bool) {
+        if hidden {
+            self.compression |= COMPRESSION_HIDDEN_NAMES;
+        } else {
+            self.compression &= !COMPRESSION_HIDDEN_NAMES;
+        }
+    }
 
-fn collect_path_stats(path: &Path, stats: &mut InputStats) -> Result<(), String> {
-    let metadata = fs::symlink_metadata(path).map_err(|err| err.to_string())?;
+    fn hides_names(&self) -> bool {
+        self.compression & COMPRESSION_HIDDEN_NAMES != 0
+    }
 
-    if metadata.file_type().is_symlink() {
-        stats.total_files = stats.total_files.saturating_add(1);
-        return Ok(());
+    fn set_checksum_trailer(&mut self) {
+        self.compression |= COMPRESSION_CHECKSUM_TRAILER;
     }
 
-    if metadata.is_file() {
-        stats.total_bytes = stats.total_bytes.saturating_add(metadata.len());
-        stats.total_files = stats.total_files.saturating_add(1);
-        return Ok(());
+    fn has_checksum_trailer(&self) -> bool {
+        self.compression & COMPRESSION_CHECKSUM_TRAILER != 0
     }
 
-    if metadata.is_dir() {
-        for child in sorted_children(path)? {
-            collect_path_stats(&child, stats)?;
+    fn trailer_len(&self) -> u64 {
+        if self.has_checksum_trailer() {
+            CHECKSUM_TRAILER_LEN as u64
+        } else {
+            0
         }
-        return Ok(());
     }
 
-    Err(format!("不支持归档的路径类型: {}", path.display()))
-}
-
-fn sorted_children(path: &Path) -> Result<Vec<PathBuf>, String> {
-    let mut children = fs::read_dir(path)
-        .map_err(|err| err.to_string())?
-        .map(|entry| {
-            entry
-                .map(|child| child.path())
-                .map_err(|err| err.to_string())
-        })
-        .collect::<Result<Vec<_>, _>>()?;
-    children.sort();
-    Ok(children)
-}
-
-// 打包进度按已读取的源文件字节数推进，同时在切换文件时发出当前路径，
-// 这样前端能展示“正在处理哪个文件”，而不是只有百分比。
-fn append_inputs_to_tar<W: Write>(
-    tar: &mut tar::Builder<W>,
-    inputs: &[ArchiveInput],
-    tracker: &mut ArchiveProgressTracker,
-    window: Option<&Window>,
-    progress_message: &'static str,
-) -> Result<(), String> {
-    for input in inputs {
-        append_path_to_tar(
-            tar,
-            &input.source_path,
-            &input.archive_root,
-            tracker,
-            window,
-            progress_message,
-        )?;
+    // 打包结束后记录最终的内容大小；隐藏文件列表的归档不记录。
+    fn record_content_size(&mut self, content_size: ContentSize) {
+        if self.content_size.is_some() {
+            self.content_size = Some(content_size);
+        }
     }
 
-    Ok(())
-}
+    fn set_volume_size(&mut self, volume_size: Option<u64>) {
+        if volume_size.is_some() {
+            self.flags |= FLAG_VOLUMES;
+        }
+        self.volume_size = volume_size;
+    }
 
-fn append_path_to_tar<W: Write>(
-    tar: &mut tar::Builder<W>,
-    source_path: &Path,
-    archive_path: &Path,
-    tracker: &mut ArchiveProgressTracker,
-    window: Option<&Window>,
-    progress_message: &'static str,
-) -> Result<(), String> {
-    let metadata = fs::symlink_metadata(source_path).map_err(|err| err.to_string())?;
+    fn set_content_size(&mut self, content_size: Option<ContentSize>) {
+        if content_size.is_some() {
+            self.flags |= FLAG_CONTENT_SIZE;
+        } else {
+            self.flags &= !FLAG_CONTENT_SIZE;
+        }
+        self.content_size = content_size;
+    }
 
-    if metadata.file_type().is_symlink() {
-        tracker.set_current_path(
-            window,
-            Some(source_path.display().to_string()),
-            progress_message,
-        );
+    fn parsed_metadata(&self) -> Option<ArchiveMetadata> {
+        self.metadata
+            .as_ref()
+            .and_then(|bytes| serde_json::from_slice(bytes).ok())
+    }
 
-        let mut header = tar::Header::new_gnu();
-        header.set_metadata(&metadata);
-        let target = fs::read_link(source_path).map_err(|err| err.to_string())?;
-        tar.append_link(&mut header, archive_path, target)
-            .map_err(|err| err.to_string())?;
-        return Ok(());
+    // 头部既负责描述归档格式，也作为 AEAD 的 AAD，
+    // 这样一旦有人篡改加密参数或压缩标记，认证阶段会直接失败。
+    fn encoded_bytes(&self) -> Vec<u8> {
+        self.encode(true)
     }
 
-    if metadata.is_dir() {
-        tar.append_dir(archive_path, source_path)
-            .map_err(|err| err.to_string())?;
+    fn encode(&self, include_content_size: bool) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(
+            FORMAT_MARKER.len()
+                + 2
+                + self
+                    .encryption
+                    .as_ref()
+                    .map(|meta| {
+                        12 + SALT_LEN
+                            + STREAM_NONCE_LEN
+                            + meta.key_check.map(|_| KEY_CHECK_LEN).unwrap_or(0)
+                    })
+                    .unwrap_or(0)
+                + self
+                    .metadata
+                    .as_ref()
+                    .map(|metadata| 4 + metadata.len())
+                    .unwrap_or(0)
+                + self.volume_size.map(|_| 8).unwrap_or(0)
+                + self.content_size.map(|_| CONTENT_SIZE_LEN).unwrap_or(0),
+        );
+        bytes.extend_from_slice(&self.version);
+        bytes.push(self.flags);
+        bytes.push(self.compression);
 
-        for child in sorted_children(source_path)? {
-            let child_name = child
-                .file_name()
-                .map(PathBuf::from)
-                .ok_or_else(|| format!("无效的归档路径: {}", child.display()))?;
-            append_path_to_tar(
-                tar,
-                &child,
-                &archive_path.join(child_name),
-                tracker,
-                window,
-                progress_message,
-            )?;
+        if let Some(meta) = &self.encryption {
+            bytes.extend_from_slice(&meta.memory_kib.to_le_bytes());
+            bytes.extend_from_slice(&meta.iterations.to_le_bytes());
+            bytes.extend_from_slice(&meta.lanes.to_le_bytes());
+            bytes.extend_from_slice(&meta.salt);
+            bytes.extend_from_slice(&meta.stream_nonce);
+            if let Some(key_check) = &meta.key_check {
+                bytes.extend_from_slice(key_check);
+            }
         }
 
-        return Ok(());
-    }
+        if let Some(metadata) = &self.metadata {
+            bytes.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(metadata);
+        }
 
-    if metadata.is_file() {
-        tracker.set_current_path(
-            window,
-            Some(source_path.display().to_string()),
-            progress_message,
-        );
+        if let Some(volume_size) = self.volume_size {
+            bytes.extend_from_slice(&volume_size.to_le_bytes());
+        }
 
-        let mut header = tar::Header::new_gnu();
-        header.set_metadata(&metadata);
-        header.set_cksum();
+        if let Some(content_size) = self.content_size.filter(|_| include_content_size) {
+            bytes.extend_from_slice(&content_size.encoded());
+        }
 
-        let file = File::open(source_path).map_err(|err| err.to_string())?;
-        let mut reader =
-            ProgressReader::new(BufReader::new(file), tracker, window, progress_message);
-        tar.append_data(&mut header, archive_path, &mut reader)
-            .map_err(|err| err.to_string())?;
-        return Ok(());
+        bytes
     }
 
-    Err(format!("不支持归档的路径类型: {}", source_path.display()))
+    fn aad_bytes(&self) -> Vec<u8> {
+        let encoded = self.encode(false);
+        let mut aad = Vec::with_capacity(MAGIC_HEADER.len() + encoded.len());
+        aad.extend_from_slice(MAGIC_HEADER);
+        aad.extend_from_slice(&encoded);
+        aad
+    }
 }
 
-fn write_archive_header<W: Write>(
-    writer: &mut W,
-    header: &ArchiveHeader,
-) -> Result<Vec<u8>, String> {
-    writer
-        .write_all(MAGIC_HEADER)
-        .map_err(|err| err.to_string())?;
-    let encoded_header = header.encoded_bytes();
-    writer
-        .write_all(&encoded_header)
-        .map_err(|err| err.to_string())?;
-    Ok(header.aad_bytes())
+impl ContentSize {
+    fn encoded(self) -> [u8; CONTENT_SIZE_LEN] {
+        let mut bytes = [0u8; CONTENT_SIZE_LEN];
+        bytes[..8].copy_from_slice(&self.tar_bytes.to_le_bytes());
+        bytes[8..].copy_from_slice(&self.files.to_le_bytes());
+        bytes
+    }
 }
 
-fn read_archive_header<R: Read>(reader: &mut R) -> Result<ArchiveHeader, String> {
-    let mut flags = [0u8; 1];
-    reader
-        .read_exact(&mut flags)
-        .map_err(|err| err.to_string())?;
+// STREAM AEAD 基于流式分块工作：gzip 连续明文流写入这里后，
+// 会被切成固定大小的块并逐块认证加密，避免整包驻留内存。
+struct EncryptedPayloadWriter<W: Write> {
+    inner: W,
+    encryptor: Option<ArchiveEncryptor>,
+    aad: Vec<u8>,
+    buffer: Vec<u8>,
+    finished: bool,
+}
 
-    let mut compression = [0u8; 1];
-    reader
-        .read_exact(&mut compression)
-        .map_err(|err| err.to_string())?;
+impl<W: Write> EncryptedPayloadWriter<W> {
+    fn new(
+        inner: W,
+        key_bytes: [u8; KEY_LEN],
+        nonce_bytes: [u8; STREAM_NONCE_LEN],
+        aad: Vec<u8>,
+    ) -> Self {
+        let key = GenericArray::clone_from_slice(&key_bytes);
+        let nonce = GenericArray::clone_from_slice(&nonce_bytes);
+        let cipher = ArchiveCipher::new(&key);
 
-    if compression[0] != COMPRESSION_GZIP {
-        return Err("不支持的 .krate 压缩格式".to_string());
+        Self {
+            inner,
+            encryptor: Some(ArchiveEncryptor::from_aead(cipher, &nonce)),
+            aad,
+            buffer: Vec::with_capacity(CHUNK_PLAINTEXT_SIZE),
+            finished: false,
+        }
     }
 
-    let encryption = if flags[0] & FLAG_ENCRYPTED != 0 {
-        let mut memory_kib = [0u8; 4];
-        let mut iterations = [0u8; 4];
-        let mut lanes = [0u8; 4];
-        let mut salt = [0u8; SALT_LEN];
-        let mut stream_nonce = [0u8; STREAM_NONCE_LEN];
+    fn write_chunk(&mut self, is_last: bool, plaintext: &[u8]) -> io::Result<()> {
+        let payload = Payload {
+            msg: plaintext,
+            aad: &self.aad,
+        };
 
-        reader
-            .read_exact(&mut memory_kib)
-            .and_then(|_| reader.read_exact(&mut iterations))
-            .and_then(|_| reader.read_exact(&mut lanes))
-            .and_then(|_| reader.read_exact(&mut salt))
-            .and_then(|_| reader.read_exact(&mut stream_nonce))
-            .map_err(|err| err.to_string())?;
+        let ciphertext = if is_last {
+            self.encryptor
+                .take()
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::BrokenPipe,
+                        "archive writer already finalized",
+                    )
+                })?
+                .encrypt_last(payload)
+        } else {
+            self.encryptor
+                .as_mut()
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::BrokenPipe,
+                        "archive writer already finalized",
+                    )
+                })?
+                .encrypt_next(payload)
+        }
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "archive encryption failed"))?;
 
-        Some(EncryptionMetadata {
-            memory_kib: u32::from_le_bytes(memory_kib),
-            iterations: u32::from_le_bytes(iterations),
-            lanes: u32::from_le_bytes(lanes),
-            salt,
-            stream_nonce,
-        })
-    } else {
-        None
-    };
+        let chunk_len = u32::try_from(ciphertext.len()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "encrypted chunk is too large to fit in the archive format",
+            )
+        })?;
 
-    Ok(ArchiveHeader {
-        flags: flags[0],
-        compression: compression[0],
-        encryption,
-    })
+        self.inner.write_all(&[if is_last {
+            CHUNK_FLAG_LAST
+        } else {
+            CHUNK_FLAG_NEXT
+        }])?;
+        self.inner.write_all(&chunk_len.to_le_bytes())?;
+        self.inner.write_all(&ciphertext)?;
+        Ok(())
+    }
+
+    fn take_buffer(&mut self) -> Vec<u8> {
+        std::mem::replace(&mut self.buffer, Vec::with_capacity(CHUNK_PLAINTEXT_SIZE))
+    }
+
+    fn finish(mut self) -> io::Result<W> {
+        if !self.finished {
+            self.finished = true;
+            let final_chunk = self.take_buffer();
+            self.write_chunk(true, &final_chunk)?;
+            self.inner.flush()?;
+        }
+        Ok(self.inner)
+    }
 }
 
-fn extract_archive_contents<R: Read>(reader: R, output_dir: &Path) -> Result<(), String> {
-    fs::create_dir(output_dir).map_err(|err| err.to_string())?;
-    let decompressor = GzDecoder::new(reader);
-    let mut archive = tar::Archive::new(decompressor);
-    archive.set_preserve_permissions(true);
-    archive.set_unpack_xattrs(false);
-    archive.set_overwrite(false);
-    if let Err(err) = archive.unpack(output_dir) {
-        let _ = fs::remove_dir_all(output_dir);
-        return Err(err.to_string());
+impl<W: Write> Write for EncryptedPayloadWriter<W> {
+    fn write(&mut self, mut buf: &[u8]) -> io::Result<usize> {
+        if self.finished {
+            return Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "cannot write to a finalized archive writer",
+            ));
+        }
+
+        let original_len = buf.len();
+
+        if !self.buffer.is_empty() {
+            let needed = CHUNK_PLAINTEXT_SIZE - self.buffer.len();
+            let take = needed.min(buf.len());
+            self.buffer.extend_from_slice(&buf[..take]);
+            buf = &buf[take..];
+
+            if self.buffer.len() == CHUNK_PLAINTEXT_SIZE {
+                let chunk = self.take_buffer();
+                self.write_chunk(false, &chunk)?;
+            }
+        }
+
+        while buf.len() >= CHUNK_PLAINTEXT_SIZE {
+            let (chunk, rest) = buf.split_at(CHUNK_PLAINTEXT_SIZE);
+            self.write_chunk(false, chunk)?;
+            buf = rest;
+        }
+
+        if !buf.is_empty() {
+            self.buffer.extend_from_slice(buf);
+        }
+
+        Ok(original_len)
     }
-    Ok(())
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+struct EncryptedPayloadReader<R: Read> {
+    inner: R,
+    decryptor: Option<ArchiveDecryptor>,
+    aad: Vec<u8>,
+    buffer: Vec<u8>,
+    offset: usize,
+    finished: bool,
+    intact: ByteCounter,
 }
 
-async fn create_archive_impl(
-    window: Option<&Window>,
-    inputs: Vec<String>,
-    output_path: String,
-    password: Option<String>,
-    gzip_level: Option<u32>,
-) -> Result<(), String> {
-    if inputs.is_empty() {
-        return Err("请至少选择一个文件或文件夹".to_string());
+impl<R: Read> EncryptedPayloadReader<R> {
+    fn new(
+        inner: R,
+        key_bytes: [u8; KEY_LEN],
+        nonce_bytes: [u8; STREAM_NONCE_LEN],
+        aad: Vec<u8>,
+    ) -> Self {
+        let key = GenericArray::clone_from_slice(&key_bytes);
+        let nonce = GenericArray::clone_from_slice(&nonce_bytes);
+        let cipher = ArchiveCipher::new(&key);
+
+        Self {
+            inner,
+            decryptor: Some(ArchiveDecryptor::from_aead(cipher, &nonce)),
+            aad,
+            buffer: Vec::new(),
+            offset: 0,
+            finished: false,
+            intact: ByteCounter::default(),
+        }
+    }
+
+    fn load_next_chunk(&mut self) -> io::Result<()> {
+        let mut flag = [0u8; 1];
+        self.inner.read_exact(&mut flag).map_err(|err| {
+            if err.kind() == io::ErrorKind::UnexpectedEof {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "encrypted archive ended unexpectedly",
+                )
+            } else {
+                err
+            }
+        })?;
+
+        let mut len_bytes = [0u8; CHUNK_LENGTH_BYTES];
+        self.inner.read_exact(&mut len_bytes)?;
+        let chunk_len = u32::from_le_bytes(len_bytes) as usize;
+
+        if !(AEAD_TAG_LEN..=CHUNK_PLAINTEXT_SIZE + AEAD_TAG_LEN).contains(&chunk_len) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "encrypted archive chunk length is invalid",
+            ));
+        }
+
+        let mut ciphertext = vec![0u8; chunk_len];
+        self.inner.read_exact(&mut ciphertext)?;
+
+        let payload = Payload {
+            msg: ciphertext.as_slice(),
+            aad: &self.aad,
+        };
+
+        self.buffer = match flag[0] {
+            CHUNK_FLAG_NEXT => self
+                .decryptor
+                .as_mut()
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::BrokenPipe,
+                        "archive reader already finalized",
+                    )
+                })?
+                .decrypt_next(payload),
+            CHUNK_FLAG_LAST => {
+                self.finished = true;
+                self.decryptor
+                    .take()
+                    .ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::BrokenPipe,
+                            "archive reader already finalized",
+                        )
+                    })?
+                    .decrypt_last(payload)
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "encrypted archive chunk flag is invalid",
+                ))
+            }
+        }
+        .map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "归档解密失败，密码错误或文件已损坏",
+            )
+        })?;
+
+        let cell = &self.intact.0;
+        cell.set(cell.get() + (1 + CHUNK_LENGTH_BYTES + chunk_len) as u64);
+        self.offset = 0;
+        Ok(())
+    }
+
+    fn prime(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() && !self.finished {
+            self.load_next_chunk()?;
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for EncryptedPayloadReader<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if out.is_empty() {
+            return Ok(0);
+        }
+
+        if self.offset >= self.buffer.len() {
+            if self.finished {
+                return Ok(0);
+            }
+
+            self.load_next_chunk()?;
+            if self.offset >= self.buffer.len() && self.finished {
+                return Ok(0);
+            }
+        }
+
+        let remaining = &self.buffer[self.offset..];
+        let count = remaining.len().min(out.len());
+        out[..count].copy_from_slice(&remaining[..count]);
+        self.offset += count;
+        Ok(count)
+    }
+}
+
+fn emit_archive_progress(window: Option<&Window>, payload: ArchiveProgressPayload) {
+    if let Some(window) = window {
+        let _ = window.emit(ARCHIVE_PROGRESS_EVENT, payload);
+    }
+}
+
+fn compression_level(level: Option<u32>, default: u32) -> u32 {
+    level.unwrap_or(default).min(MAX_COMPRESSION_LEVEL)
+}
+
+impl ArchiveSecret {
+    // 打包时使用；既没有密码也没有密钥文件时返回 `None`，即不加密。
+    fn for_create(
+        password: Option<String>,
+        key_file: Option<&Path>,
+        raw_key: bool,
+    ) -> Result<Option<Self>, String> {
+        let key_file = key_file.map(read_key_file).transpose()?;
+        if raw_key {
+            let bytes = key_file
+                .as_ref()
+                .ok_or("原始密钥模式需要选择密钥文件".to_string())?;
+            if bytes.len() != KEY_LEN {
+                return Err(format!("原始密钥文件必须恰好为 {} 字节", KEY_LEN));
+            }
+            if password.is_some() {
+                return Err("原始密钥模式不能与密码组合使用".to_string());
+            }
+        }
+
+        if password.is_none() && key_file.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            password,
+            key_file,
+            raw_key,
+        }))
+    }
+
+    // 解压时按头部标记决定需要哪些凭据，缺少时给出明确提示。
+    fn for_header(
+        password: Option<String>,
+        key_file: Option<&Path>,
+        flags: u8,
+    ) -> Result<Self, String> {
+        let uses_key_file = flags & FLAG_KEYFILE != 0;
+        let uses_password = !uses_key_file || flags & FLAG_KEYFILE_PASSWORD != 0;
+
+        let key_file = if uses_key_file {
+            let path =
+                key_file.ok_or("该归档使用密钥文件加密，请选择密钥文件后再解压".to_string())?;
+            Some(read_key_file(path)?)
+        } else {
+            None
+        };
+        let password = if uses_password {
+            Some(password.ok_or("该 .krate 归档已加密，请输入密码后再解压".to_string())?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            password,
+            key_file,
+            raw_key: flags & FLAG_RAW_KEY != 0,
+        })
+    }
+
+    fn flags(&self) -> u8 {
+        let mut flags = 0;
+        if self.key_file.is_some() {
+            flags |= FLAG_KEYFILE;
+            if self.password.is_some() {
+                flags |= FLAG_KEYFILE_PASSWORD;
+            }
+        }
+        if self.raw_key {
+            flags |= FLAG_RAW_KEY;
+        }
+        flags
+    }
+
+    fn derive_key(&self, metadata: &EncryptionMetadata) -> Result<[u8; KEY_LEN], String> {
+        if self.raw_key {
+            let bytes = self
+                .key_file
+                .as_deref()
+                .filter(|bytes| bytes.len() == KEY_LEN)
+                .ok_or(format!("原始密钥文件必须恰好为 {} 字节", KEY_LEN))?;
+            let mut key = [0u8; KEY_LEN];
+            key.copy_from_slice(bytes);
+            return Ok(key);
+        }
+
+        let mut input = self.password.clone().unwrap_or_default().into_bytes();
+        if let Some(key_file) = &self.key_file {
+            input.extend_from_slice(key_file);
+        }
+        derive_archive_key(&input, metadata)
+    }
+
+    // 派生密钥并用校验块确认，错误信息区分密码与密钥文件。
+    fn unlock(&self, metadata: &EncryptionMetadata) -> Result<[u8; KEY_LEN], String> {
+        let key = self.derive_key(metadata)?;
+        verify_key_check(&key, metadata).map_err(|err| {
+            if err != WRONG_PASSWORD_ERROR {
+                return err;
+            }
+            match (self.password.is_some(), self.key_file.is_some()) {
+                (_, false) => err,
+                (false, true) => WRONG_KEY_FILE_ERROR.to_string(),
+                (true, true) => WRONG_CREDENTIALS_ERROR.to_string(),
+            }
+        })?;
+        Ok(key)
+    }
+}
+
+fn read_key_file(path: &Path) -> Result<Vec<u8>, String> {
+    let bytes = fs::read(path).map_err(|err| {
+        if err.kind() == io::ErrorKind::NotFound {
+            format!("密钥文件不存在: {}", path.display())
+        } else {
+            format!("读取密钥文件失败: {}", err)
+        }
+    })?;
+    if bytes.is_empty() {
+        return Err("密钥文件为空".to_string());
+    }
+    Ok(bytes)
+}
+
+fn normalized_password(password: Option<String>) -> Option<String> {
+    password.and_then(|value| if value.is_empty() { None } else { Some(value) })
+}
+
+// 字符集大小取各类字符的并集；与前一个字符相同或相邻（`a`→`b`、`3`→`2`）的字符只计 1 bit。
+// 评分阈值参照常见的密码管理器：低于 28 bit 极弱，80 bit 以上很强。
+fn password_report(password: &str) -> PasswordReport {
+    let chars = password.chars().collect::<Vec<_>>();
+    let has_lowercase = chars.iter().any(char::is_ascii_lowercase);
+    let has_uppercase = chars.iter().any(char::is_ascii_uppercase);
+    let has_digits = chars.iter().any(char::is_ascii_digit);
+    let has_symbols = chars
+        .iter()
+        .any(|ch| ch.is_ascii_punctuation() || *ch == ' ');
+    let has_other = chars.iter().any(|ch| !ch.is_ascii());
+    let pool = [
+        (has_lowercase, 26),
+        (has_uppercase, 26),
+        (has_digits, 10),
+        (has_symbols, 33),
+        (has_other, 100),
+    ]
+    .iter()
+    .filter(|(present, _)| *present)
+    .map(|(_, size)| size)
+    .sum::<u32>();
+
+    let predictable = chars
+        .windows(2)
+        .filter(|pair| (pair[0] as i64 - pair[1] as i64).abs() <= 1)
+        .count();
+    let entropy_bits = if pool == 0 {
+        0.0
+    } else {
+        (chars.len() - predictable) as f64 * (pool as f64).log2() + predictable as f64
+    };
+    let score = match entropy_bits {
+        bits if bits < 28.0 => 0,
+        bits if bits < 36.0 => 1,
+        bits if bits < 60.0 => 2,
+        bits if bits < 80.0 => 3,
+        _ => MAX_PASSWORD_SCORE,
+    };
+
+    PasswordReport {
+        length: chars.len(),
+        entropy_bits,
+        has_lowercase,
+        has_uppercase,
+        has_digits,
+        has_symbols,
+        has_other,
+        score,
+    }
+}
+
+fn ensure_password_strength(password: Option<&str>, min_score: Option<u8>) -> Result<(), String> {
+    let (Some(password), Some(min_score)) = (password.filter(|value| !value.is_empty()), min_score)
+    else {
+        return Ok(());
+    };
+    if min_score > MAX_PASSWORD_SCORE {
+        return Err(format!("密码强度要求必须在 0–{} 之间", MAX_PASSWORD_SCORE));
+    }
+    let score = password_report(password).score;
+    if score < min_score {
+        return Err(format!(
+            "密码强度不足（评分 {}，要求至少 {}），如确认使用请选择强制创建",
+            score, min_score
+        ));
+    }
+    Ok(())
+}
+
+// 路径所在磁盘的可用空间；无法确定对应磁盘时返回 `None`。
+fn available_space(path: &Path) -> Option<u64> {
+    // Windows 上规范化会带 `\\?\` 前缀，与挂载点 `C:\` 无法比较，直接使用绝对路径。
+    let path = if cfg!(windows) {
+        path.to_path_buf()
+    } else {
+        path.ancestors()
+            .find_map(|ancestor| ancestor.canonicalize().ok())?
+    };
+    let disks = Disks::new_with_refreshed_list();
+    disks
+        .list()
+        .iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| disk.available_space())
+}
+
+fn ensure_free_space(path: &Path, required_bytes: u64) -> Result<(), String> {
+    let Some(available_bytes) = available_space(path) else {
+        return Ok(());
+    };
+    if required_bytes > available_bytes {
+        return Err(format!(
+            "磁盘空间不足：需要 {:.1} MiB，可用 {:.1} MiB",
+            required_bytes as f64 / (1024.0 * 1024.0),
+            available_bytes as f64 / (1024.0 * 1024.0)
+        ));
+    }
+    Ok(())
+}
+
+fn default_compression_threads() -> usize {
+    System::physical_core_count()
+        .unwrap_or(1)
+        .clamp(1, MAX_COMPRESSION_THREADS)
+}
+
+fn default_argon2_lanes() -> u32 {
+    std::thread::available_parallelism()
+        .map(|parallelism| parallelism.get() as u32)
+        .unwrap_or(1)
+        .clamp(1, MAX_ARGON2_LANES)
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N], String> {
+    let mut bytes = [0u8; N];
+    getrandom::fill(&mut bytes).map_err(|err| format!("生成归档随机参数失败: {}", err))?;
+    Ok(bytes)
+}
+
+fn validate_encryption_metadata(metadata: &EncryptionMetadata) -> Result<(), String> {
+    if metadata.memory_kib > MAX_ARCHIVE_ARGON2_MEMORY_KIB {
+        return Err("归档加密参数超出当前版本支持范围".to_string());
+    }
+
+    if metadata.iterations > MAX_ARCHIVE_ARGON2_ITERATIONS {
+        return Err("归档加密参数超出当前版本支持范围".to_string());
+    }
+
+    if metadata.lanes == 0 || metadata.lanes > MAX_ARCHIVE_ARGON2_LANES {
+        return Err("归档加密参数超出当前版本支持范围".to_string());
+    }
+
+    Ok(())
+}
+
+fn derive_archive_key(
+    password: &[u8],
+    metadata: &EncryptionMetadata,
+) -> Result<[u8; KEY_LEN], String> {
+    validate_encryption_metadata(metadata)?;
+
+    let params = Params::new(
+        metadata.memory_kib,
+        metadata.iterations,
+        metadata.lanes,
+        Some(KEY_LEN),
+    )
+    .map_err(|err| format!("归档加密参数无效: {}", err))?;
+
+    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+    let mut key = [0u8; KEY_LEN];
+
+    argon2
+        .hash_password_into(password, &metadata.salt, &mut key)
+        .map_err(|err| format!("归档密钥派生失败: {}", err))?;
+
+    Ok(key)
+}
+
+// 校验块是用派生密钥对固定上下文计算的 AEAD 标签。nonce 以流 nonce 为前缀、
+// 末字节为 0xFF，而 STREAM 分块的末字节只会是 0 或 1，两者不会重复。
+fn key_check_nonce(stream_nonce: &[u8; STREAM_NONCE_LEN]) -> [u8; 24] {
+    let mut nonce = [0xFFu8; 24];
+    nonce[..STREAM_NONCE_LEN].copy_from_slice(stream_nonce);
+    nonce
+}
+
+fn compute_key_check(
+    key: &[u8; KEY_LEN],
+    stream_nonce: &[u8; STREAM_NONCE_LEN],
+) -> Result<[u8; KEY_CHECK_LEN], String> {
+    let cipher = ArchiveCipher::new(GenericArray::from_slice(key));
+    let nonce = key_check_nonce(stream_nonce);
+    let tag = cipher
+        .encrypt(
+            GenericArray::from_slice(&nonce),
+            Payload {
+                msg: &[],
+                aad: KEY_CHECK_CONTEXT,
+            },
+        )
+        .map_err(|_| "生成密码校验块失败".to_string())?;
+
+    let mut key_check = [0u8; KEY_CHECK_LEN];
+    key_check.copy_from_slice(&tag);
+    Ok(key_check)
+}
+
+fn verify_key_check(key: &[u8; KEY_LEN], metadata: &EncryptionMetadata) -> Result<(), String> {
+    let Some(key_check) = metadata.key_check.as_ref() else {
+        return Ok(());
+    };
+    let cipher = ArchiveCipher::new(GenericArray::from_slice(key));
+    let nonce = key_check_nonce(&metadata.stream_nonce);
+    cipher
+        .decrypt(
+            GenericArray::from_slice(&nonce),
+            Payload {
+                msg: key_check,
+                aad: KEY_CHECK_CONTEXT,
+            },
+        )
+        .map(|_| ())
+        .map_err(|_| WRONG_PASSWORD_ERROR.to_string())
+}
+
+fn absolute_path(path: &Path) -> Result<PathBuf, String> {
+    if path.is_absolute() {
+        Ok(path.to_path_buf())
+    } else {
+        std::env::current_dir()
+            .map_err(|err| err.to_string())
+            .map(|cwd| cwd.join(path))
+    }
+}
+
+fn normalize_path_for_comparison(path: &Path) -> Result<PathBuf, String> {
+    let absolute = absolute_path(path)?;
+
+    if absolute.file_name().is_none() {
+        return absolute.canonicalize().map_err(|err| err.to_string());
+    }
+
+    let parent = absolute
+        .parent()
+        .ok_or_else(|| format!("无效的路径: {}", absolute.display()))?;
+    let normalized_parent = parent.canonicalize().map_err(|err| err.to_string())?;
+    Ok(normalized_parent.join(
+        absolute
+            .file_name()
+            .ok_or_else(|| format!("无效的路径: {}", absolute.display()))?,
+    ))
+}
+
+fn suffixed_name(original_name: &OsStr, suffix: usize) -> OsString {
+    let original_path = Path::new(original_name);
+
+    if let (Some(stem), Some(extension)) = (original_path.file_stem(), original_path.extension()) {
+        let mut candidate = OsString::from(stem);
+        candidate.push(format!(" ({suffix})"));
+        candidate.push(".");
+        candidate.push(extension);
+        return candidate;
+    }
+
+    let mut candidate = OsString::from(original_name);
+    candidate.push(format!(" ({suffix})"));
+    candidate
+}
+
+fn build_archive_inputs(
+    inputs: &[String],
+    names: Option<&[String]>,
+) -> Result<Vec<ArchiveInput>, String> {
+    // 子路径都由输入路径拼接而来，在根部转换一次即可覆盖整棵目录树。
+    let source_paths = inputs
+        .iter()
+        .map(|input| long_path(Path::new(input)))
+        .collect::<Vec<_>>();
+    let archive_names = match names {
+        Some(names) => explicit_root_names(names, source_paths.len())?,
+        None => disambiguated_root_names(&source_paths)?,
+    };
+
+    Ok(source_paths
+        .into_iter()
+        .zip(archive_names)
+        .map(|(source_path, archive_name)| ArchiveInput {
+            source_path,
+            archive_root: PathBuf::from(archive_name),
+        })
+        .collect())
+}
+
+// 顶层同名的输入（以及与清单同名的输入）全部加上序号，按源路径排序后依次编号，
+// 结果与输入顺序无关。
+fn disambiguated_root_names(source_paths: &[PathBuf]) -> Result<Vec<OsString>, String> {
+    let file_names = source_paths
+        .iter()
+        .map(|path| {
+            path.file_name()
+                .map(OsStr::to_os_string)
+                .ok_or_else(|| format!("无效的归档输入路径: {}", path.display()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut counts: HashMap<&OsStr, usize> = HashMap::new();
+    for name in &file_names {
+        *counts.entry(name.as_os_str()).or_default() += 1;
+    }
+    let collides = |name: &OsStr| counts[name] > 1 || name == MANIFEST_ENTRY_NAME;
+
+    let mut used_roots = file_names
+        .iter()
+        .filter(|name| !collides(name))
+        .cloned()
+        .collect::<HashSet<_>>();
+    used_roots.insert(OsString::from(MANIFEST_ENTRY_NAME));
+
+    let mut colliding = (0..file_names.len())
+        .filter(|&index| collides(&file_names[index]))
+        .collect::<Vec<_>>();
+    colliding.sort_by(|&left, &right| {
+        file_names[left]
+            .cmp(&file_names[right])
+            .then_with(|| source_paths[left].cmp(&source_paths[right]))
+    });
+
+    let mut names = file_names.clone();
+    let mut next_suffix: HashMap<&OsStr, usize> = HashMap::new();
+    for index in colliding {
+        let file_name = file_names[index].as_os_str();
+        let suffix = next_suffix.entry(file_name).or_insert(1);
+        loop {
+            let candidate = suffixed_name(file_name, *suffix);
+            *suffix += 1;
+            if used_roots.insert(candidate.clone()) {
+                names[index] = candidate;
+                break;
+            }
+        }
+    }
+    Ok(names)
+}
+
+// 归档路径按解压时的规则检查：拒绝越界、重复，以及一个条目位于另一个条目之下的情况。
+fn build_entry_inputs(
+    entries: &[EntrySpec],
+    expand_directories: bool,
+) -> Result<Vec<ArchiveInput>, String> {
+    let mut archive_paths = HashSet::from([PathBuf::from(MANIFEST_ENTRY_NAME)]);
+    let mut archive_inputs = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let archive_root = sanitized_entry_path(&entry.archive_path)
+            .ok_or_else(|| format!("无效的归档内路径: {}", entry.archive_path))?;
+        if !archive_paths.insert(archive_root.clone()) {
+            return Err(format!("归档内路径重复: {}", entry.archive_path));
+        }
+
+        let source_path = long_path(Path::new(&entry.src_path));
+        let metadata = fs::metadata(&source_path)
+            .map_err(|err| format!("无法读取源文件 {}: {}", entry.src_path, err))?;
+        if metadata.is_dir() && !expand_directories {
+            return Err(format!(
+                "不能直接添加文件夹，请展开后逐个添加: {}",
+                entry.src_path
+            ));
+        }
+        archive_inputs.push(ArchiveInput {
+            source_path,
+            archive_root,
+        });
+    }
+
+    for path in &archive_paths {
+        if let Some(parent) = path
+            .ancestors()
+            .skip(1)
+            .find(|parent| archive_paths.contains(*parent))
+        {
+            return Err(format!(
+                "归档内路径冲突: {} 位于 {} 之下",
+                manifest_key(path),
+                manifest_key(parent)
+            ));
+        }
+    }
+    Ok(archive_inputs)
+}
+
+fn explicit_root_names(names: &[String], input_count: usize) -> Result<Vec<OsString>, String> {
+    if names.len() != input_count {
+        return Err("顶层名称数量必须与输入数量一致".to_string());
+    }
+    let mut used_roots = HashSet::new();
+    for name in names {
+        if name.trim().is_empty()
+            || name.ends_with(['.', ' '])
+            || name
+                .chars()
+                .any(|ch| ch.is_control() || INVALID_ROOT_NAME_CHARS.contains(&ch))
+        {
+            return Err(format!("无效的顶层名称: {}", name));
+        }
+        if name == MANIFEST_ENTRY_NAME {
+            return Err(format!("顶层名称不能使用保留名称: {}", name));
+        }
+        if !used_roots.insert(name.as_str()) {
+            return Err(format!("顶层名称重复: {}", name));
+        }
+    }
+    Ok(names.iter().map(OsString::from).collect())
+}
+
+fn ensure_output_path_is_safe(inputs: &[ArchiveInput], output_path: &Path) -> Result<(), String> {
+    if output_path.exists() && output_path.is_dir() {
+        return Err("输出路径不能是文件夹".to_string());
+    }
+
+    let normalized_output = normalize_path_for_comparison(output_path)?;
+
+    for input in inputs {
+        let metadata = fs::symlink_metadata(&input.source_path).map_err(|err| err.to_string())?;
+        let normalized_input = normalize_path_for_comparison(&input.source_path)?;
+
+        if normalized_input == normalized_output {
+            return Err(format!(
+                "输出文件不能与输入路径相同: {}",
+                input.source_path.display()
+            ));
+        }
+
+        if metadata.is_dir() && normalized_output.starts_with(&normalized_input) {
+            return Err(format!(
+                "输出文件不能位于待归档目录内: {}",
+                input.source_path.display()
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn unique_temp_output_path(output_path: &Path) -> Result<PathBuf, String> {
+    let parent = output_path
+        .parent()
+        .ok_or_else(|| format!("无效的输出路径: {}", output_path.display()))?;
+    let file_name = output_path
+        .file_name()
+        .ok_or_else(|| format!("无效的输出路径: {}", output_path.display()))?;
+
+    for attempt in 0..TEMP_OUTPUT_ATTEMPTS {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|err| err.to_string())?
+            .as_nanos();
+        let mut temp_name = OsString::from(".");
+        temp_name.push(file_name);
+        temp_name.push(format!(".tmp-{}-{nanos}-{attempt}", std::process::id()));
+        let temp_path = parent.join(temp_name);
+        if !temp_path.exists() {
+            return Ok(temp_path);
+        }
+    }
+
+    Err("无法为归档创建临时输出文件".to_string())
+}
+
+fn persist_temp_output(temp_path: &Path, output_path: &Path) -> Result<(), String> {
+    #[cfg(target_os = "windows")]
+    if output_path.exists() {
+        fs::remove_file(output_path).map_err(|err| err.to_string())?;
+    }
+
+    fs::rename(temp_path, output_path).map_err(|err| err.to_string())
+}
+
+fn volume_path(base_path: &Path, index: u32) -> PathBuf {
+    let mut name = base_path.as_os_str().to_os_string();
+    name.push(format!(".{:03}", index));
+    PathBuf::from(name)
+}
+
+fn part_path(base_path: &Path, volume_size: Option<u64>, index: u32) -> PathBuf {
+    if volume_size.is_some() {
+        volume_path(base_path, index)
+    } else {
+        base_path.to_path_buf()
+    }
+}
+
+fn create_part(path: &Path) -> io::Result<BufWriter<File>> {
+    OpenOptions::new()
+        .create_new(true)
+        .write(true)
+        .open(path)
+        .map(BufWriter::new)
+}
+
+// `name.krate.001` 返回 `name.krate`，其余路径返回 `None`。
+fn volume_base_path(archive_path: &Path) -> Option<PathBuf> {
+    (archive_path.extension()? == "001").then(|| archive_path.with_extension(""))
+}
+
+// 分卷组只能从 `.001` 打开；依据头部记录的分卷大小逐卷确认，写满的分卷之后必须还有下一卷。
+fn archive_volumes(archive_path: &Path) -> Result<Vec<PathBuf>, String> {
+    let Some(base_path) = volume_base_path(archive_path) else {
+        return Ok(vec![archive_path.to_path_buf()]);
+    };
+    let file = File::open(archive_path).map_err(|err| err.to_string())?;
+    let (header, _) = read_archive_preamble(&mut BufReader::new(file))?;
+    let Some(volume_size) = header.volume_size else {
+        return Ok(vec![archive_path.to_path_buf()]);
+    };
+
+    let mut parts = Vec::new();
+    for index in 1.. {
+        let part = volume_path(&base_path, index);
+        let len = fs::metadata(&part)
+            .map_err(|_| format!("缺少分卷: {}", part.display()))?
+            .len();
+        if len > volume_size {
+            return Err(format!("分卷大小不正确: {}", part.display()));
+        }
+        parts.push(part);
+        if len < volume_size {
+            break;
+        }
+    }
+    Ok(parts)
+}
+
+// 打开归档（分卷归档从 `.001` 开始顺序读取），返回读取器与所有分卷的总字节数。
+fn open_archive_reader(archive_path: &Path) -> Result<(VolumeReader, u64), String> {
+    let parts = archive_volumes(archive_path)?;
+    let mut total_bytes = 0;
+    for part in &parts {
+        total_bytes += fs::metadata(part).map_err(|err| err.to_string())?.len();
+    }
+    // 校验尾不属于负载，读取在它之前结束，解压器与解密器都不会看到它。
+    let file = File::open(archive_path).map_err(|err| err.to_string())?;
+    let (header, _) = read_archive_preamble(&mut BufReader::new(file))?;
+    let readable_bytes = total_bytes.saturating_sub(header.trailer_len());
+    Ok((VolumeReader::open(parts, readable_bytes)?, total_bytes))
+}
+
+fn persist_output(
+    temp_path: &Path,
+    output_path: &Path,
+    volume_size: Option<u64>,
+) -> Result<(), String> {
+    if volume_size.is_none() {
+        return persist_temp_output(temp_path, output_path);
+    }
+
+    let mut index = 1;
+    while volume_path(temp_path, index).exists() {
+        persist_temp_output(
+            &volume_path(temp_path, index),
+            &volume_path(output_path, index),
+        )?;
+        index += 1;
+    }
+    // 清理上一次生成的多余分卷，避免与本次输出混在一起。
+    while fs::remove_file(volume_path(output_path, index)).is_ok() {
+        index += 1;
+    }
+    Ok(())
+}
+
+fn remove_temp_output(temp_path: &Path, volume_size: Option<u64>) {
+    if volume_size.is_none() {
+        let _ = fs::remove_file(temp_path);
+        return;
+    }
+
+    let mut index = 1;
+    while fs::remove_file(volume_path(temp_path, index)).is_ok() {
+        index += 1;
+    }
+}
+
+fn extract_root_base_name(archive_path: &Path) -> OsString {
+    match archive_path.file_stem() {
+        // `name.tar.gz` 去掉 `.gz` 后仍带 `.tar`，一并去掉。
+        Some(stem) if Path::new(stem).extension() == Some(OsStr::new("tar")) => {
+            Path::new(stem).file_stem().unwrap_or(stem).to_os_string()
+        }
+        Some(stem) if !stem.is_empty() => stem.to_os_string(),
+        _ => OsString::from("krate-archive"),
+    }
+}
+
+fn unique_output_dir(parent: &Path, base_name: &OsStr) -> PathBuf {
+    let first_candidate = parent.join(base_name);
+    if !first_candidate.exists() {
+        return first_candidate;
+    }
+
+    let mut suffix = 2usize;
+    loop {
+        let candidate = parent.join(suffixed_name(base_name, suffix));
+        if !candidate.exists() {
+            return candidate;
+        }
+        suffix += 1;
+    }
+}
+
+fn prepare_extract_output_dir(
+    archive_path: &Path,
+    output_parent: &Path,
+) -> Result<PathBuf, String> {
+    if output_parent.exists() {
+        if !output_parent.is_dir() {
+            return Err("输出目录不是文件夹".to_string());
+        }
+    } else {
+        fs::create_dir_all(output_parent).map_err(|err| err.to_string())?;
+    }
+
+    Ok(unique_output_dir(
+        output_parent,
+        &extract_root_base_name(archive_path),
+    ))
+}
+
+fn collect_input_stats(
+    inputs: &[ArchiveInput],
+    symlinks: SymlinkMode,
+) -> Result<InputStats, String> {
+    let (stats, _) = collect_input_files(inputs, symlinks, EntryFilter::default(), false)?;
+    Ok(stats)
+}
+
+// 与 `collect_input_stats` 相同的遍历；`keep_files` 为 true 时同时返回每个文件的路径与大小。
+fn collect_input_files(
+    inputs: &[ArchiveInput],
+    symlinks: SymlinkMode,
+    filter: EntryFilter,
+    keep_files: bool,
+) -> Result<(InputStats, Vec<(PathBuf, u64)>), String> {
+    let mut stats = InputStats::default();
+    let mut ancestors = Vec::new();
+    let mut files = Vec::new();
+    let mut on_file = |path: &Path, len: u64| {
+        if keep_files {
+            files.push((path.to_path_buf(), len));
+        }
+    };
+
+    for input in inputs {
+        collect_path_stats(
+            &input.source_path,
+            &mut stats,
+            symlinks,
+            filter,
+            &mut ancestors,
+            &mut on_file,
+        )?;
+    }
+
+    Ok((stats, files))
+}
+
+fn collect_path_stats(
+    path: &Path,
+    stats: &mut InputStats,
+    symlinks: SymlinkMode,
+    filter: EntryFilter,
+    ancestors: &mut Vec<PathBuf>,
+    on_file: &mut dyn FnMut(&Path, u64),
+) -> Result<(), String> {
+    // 读取失败的条目留到打包阶段处理（报错，或在 `skip_errors` 下记为警告），统计时直接略过。
+    let Ok(mut metadata) = fs::symlink_metadata(path) else {
+        return Ok(());
+    };
+
+    if metadata.file_type().is_symlink() {
+        match symlinks {
+            SymlinkMode::Preserve => {
+                stats.total_files = stats.total_files.saturating_add(1);
+                return Ok(());
+            }
+            SymlinkMode::Skip => return Ok(()),
+            // 悬空链接在打包时会被跳过，这里同样不计入。
+            SymlinkMode::Follow => match fs::metadata(path) {
+                Ok(target_metadata) => metadata = target_metadata,
+                Err(_) => return Ok(()),
+            },
+        }
+    }
+
+    if metadata.is_file() {
+        if !filter.matches(&metadata) {
+            return Ok(());
+        }
+        stats.total_bytes = stats.total_bytes.saturating_add(metadata.len());
+        stats.total_files = stats.total_files.saturating_add(1);
+        on_file(path, metadata.len());
+        return Ok(());
+    }
+
+    if metadata.is_dir() {
+        let canonical = if symlinks == SymlinkMode::Follow {
+            let canonical = path.canonicalize().map_err(|err| err.to_string())?;
+            if ancestors.contains(&canonical) {
+                return Ok(());
+            }
+            Some(canonical)
+        } else {
+            None
+        };
+        if let Some(canonical) = canonical.as_ref() {
+            ancestors.push(canonical.clone());
+        }
+        for child in sorted_children(path).unwrap_or_default() {
+            collect_path_stats(&child, stats, symlinks, filter, ancestors, on_file)?;
+        }
+        if canonical.is_some() {
+            ancestors.pop();
+        }
+        return Ok(());
+    }
+
+    Err(format!("不支持归档的路径类型: {}", path.display()))
+}
+
+fn sorted_children(path: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut children = fs::read_dir(path)
+        .map_err(|err| err.to_string())?
+        .map(|entry| {
+            entry
+                .map(|child| child.path())
+                .map_err(|err| err.to_string())
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    children.sort();
+    Ok(children)
+}
+
+// 打包进度按已读取的源文件字节数推进，同时在切换文件时发出当前路径，
+// 这样前端能展示“正在处理哪个文件”，而不是只有百分比。
+fn append_inputs_to_tar<W: Write>(
+    tar: &mut tar::Builder<W>,
+    inputs: &[ArchiveInput],
+    tracker: &mut ArchiveProgressTracker,
+    window: Option<&Window>,
+    progress_message: &'static str,
+    walker: &mut PackWalker,
+) -> Result<(), String> {
+    for input in inputs {
+        append_path_to_tar(
+            tar,
+            &input.source_path,
+            &input.archive_root,
+            tracker,
+            window,
+            progress_message,
+            walker,
+        )?;
+    }
+
+    if let Some(incremental) = walker.incremental.as_ref() {
+        walker.manifest.deleted = incremental.deleted_paths();
+    }
+    append_manifest(tar, &walker.manifest)
+}
+
+fn incremental_manifest_path(output_path: &Path) -> PathBuf {
+    let mut name = output_path.as_os_str().to_os_string();
+    name.push(".manifest.json");
+    PathBuf::from(name)
+}
+
+fn write_incremental_manifest(
+    output_path: &Path,
+    snapshot: &ArchiveManifest,
+) -> Result<(), String> {
+    let bytes =
+        serde_json::to_vec_pretty(snapshot).map_err(|err| format!("生成快照清单失败: {}", err))?;
+    fs::write(incremental_manifest_path(output_path), bytes)
+        .map_err(|err| format!("写入快照清单失败: {}", err))
+}
+
+fn append_manifest<W: Write>(
+    tar: &mut tar::Builder<W>,
+    manifest: &ArchiveManifest,
+) -> Result<(), String> {
+    let bytes =
+        serde_json::to_vec_pretty(manifest).map_err(|err| format!("生成文件清单失败: {}", err))?;
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::Regular);
+    header.set_size(bytes.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0),
+    );
+    tar.append_data(&mut header, MANIFEST_ENTRY_NAME, bytes.as_slice())
+        .map_err(|err| err.to_string())
+}
+
+// tar 内部统一使用 `/` 分隔，清单的键与解压时读到的条目名保持一致。
+fn manifest_key(archive_path: &Path) -> String {
+    let key = archive_path.to_string_lossy();
+    if std::path::MAIN_SEPARATOR == '\\' {
+        key.replace('\\', "/")
+    } else {
+        key.into_owned()
+    }
+}
+
+fn hex_digest(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn file_sha256(path: &Path) -> Result<String, String> {
+    let mut file = File::open(path).map_err(|err| err.to_string())?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).map_err(|err| err.to_string())?;
+    Ok(hex_digest(&hasher.finalize()))
+}
+
+// 大小相同的文件再计算 SHA-256，找出内容重复的文件；返回去重计划与预计省去的字节数。
+fn plan_dedupe(
+    files: &[(PathBuf, u64)],
+    tracker: &ArchiveProgressTracker,
+) -> Result<(DedupePlan, u64), String> {
+    let mut by_size: HashMap<u64, Vec<&Path>> = HashMap::new();
+    for (path, len) in files {
+        if *len > 0 {
+            by_size.entry(*len).or_default().push(path.as_path());
+        }
+    }
+
+    let mut plan = DedupePlan::default();
+    let mut duplicate_bytes = 0;
+    let mut seen_hardlinks = HashSet::new();
+    for (len, paths) in by_size {
+        if paths.len() < 2 {
+            continue;
+        }
+        let mut groups: HashMap<String, Vec<&Path>> = HashMap::new();
+        for path in paths {
+            tracker.check_cancelled()?;
+            // 同一文件的其他硬链接由硬链接条目处理；读取失败的文件留到打包阶段处理。
+            let Ok(metadata) = fs::metadata(path) else {
+                continue;
+            };
+            if hardlink_id(path, &metadata).is_some_and(|id| !seen_hardlinks.insert(id)) {
+                continue;
+            }
+            let Ok(digest) = file_sha256(path) else {
+                continue;
+            };
+            groups.entry(digest).or_default().push(path);
+        }
+        for (digest, paths) in groups {
+            if paths.len() < 2 {
+                continue;
+            }
+            duplicate_bytes += len * (paths.len() as u64 - 1);
+            plan.digests.extend(
+                paths
+                    .into_iter()
+                    .map(|path| (path.to_path_buf(), digest.clone())),
+            );
+        }
+    }
+    Ok((plan, duplicate_bytes))
+}
+
+fn parse_manifest<R: Read>(reader: &mut R) -> Result<ArchiveManifest, String> {
+    let mut bytes = Vec::new();
+    reader
+        .read_to_end(&mut bytes)
+        .map_err(|err| err.to_string())?;
+    serde_json::from_slice(&bytes).map_err(|err| format!("归档文件清单损坏: {}", err))
+}
+
+// 对照清单返回哈希不一致或未能解出的路径；清单之外的条目不做判断。
+fn manifest_mismatches(
+    manifest: Option<&ArchiveManifest>,
+    actual: &HashMap<String, ManifestEntry>,
+    skipped_entries: &[String],
+) -> Result<Vec<String>, String> {
+    let manifest = manifest.ok_or("归档不包含文件清单，无法校验哈希".to_string())?;
+    Ok(manifest
+        .files
+        .iter()
+        .filter(|(path, expected)| match actual.get(*path) {
+            Some(entry) => entry != *expected,
+            None => !skipped_entries.contains(path),
+        })
+        .map(|(path, _)| path.clone())
+        .collect())
+}
+
+fn append_path_to_tar<W: Write>(
+    tar: &mut tar::Builder<W>,
+    source_path: &Path,
+    archive_path: &Path,
+    tracker: &mut ArchiveProgressTracker,
+    window: Option<&Window>,
+    progress_message: &'static str,
+    walker: &mut PackWalker,
+) -> Result<(), String> {
+    tracker.wait_while_paused(window, progress_message);
+    tracker.check_cancelled()?;
+    let mut metadata = match fs::symlink_metadata(source_path) {
+        Ok(metadata) => metadata,
+        Err(err) => return walker.tolerate(tracker, window, source_path, err.to_string()),
+    };
+    let mut via_link = false;
+
+    if metadata.file_type().is_symlink() {
+        match walker.mode {
+            SymlinkMode::Preserve => {
+                tracker.set_current_path(
+                    window,
+                    Some(source_path.display().to_string()),
+                    progress_message,
+                );
+
+                let mut header = tar::Header::new_gnu();
+                header.set_metadata(&metadata);
+                let target = match fs::read_link(source_path) {
+                    Ok(target) => target,
+                    Err(err) => {
+                        return walker.tolerate(tracker, window, source_path, err.to_string())
+                    }
+                };
+                tar.append_link(&mut header, archive_path, target)
+                    .map_err(|err| err.to_string())?;
+                return Ok(());
+            }
+            SymlinkMode::Skip => {
+                walker.skip(source_path);
+                return Ok(());
+            }
+            SymlinkMode::Follow => match fs::metadata(source_path) {
+                Ok(target_metadata) => {
+                    metadata = target_metadata;
+                    via_link = true;
+                }
+                Err(_) => {
+                    walker.skip(source_path);
+                    return Ok(());
+                }
+            },
+        }
+    }
+    let through_link = via_link || walker.link_depth > 0;
+
+    if metadata.is_dir() {
+        // 跟随链接时记录当前路径上的目录，指回祖先目录的链接会造成无限递归，直接跳过。
+        let canonical = if walker.mode == SymlinkMode::Follow {
+            let canonical = match source_path.canonicalize() {
+                Ok(canonical) => canonical,
+                Err(err) => return walker.tolerate(tracker, window, source_path, err.to_string()),
+            };
+            if walker.ancestors.contains(&canonical) {
+                walker.skip(source_path);
+                return Ok(());
+            }
+            Some(canonical)
+        } else {
+            None
+        };
+
+        if via_link {
+            // `append_dir` 按链接本身生成头部，链接目录需要用目标目录的元数据写入。
+            let mut header = tar::Header::new_gnu();
+            header.set_metadata(&metadata);
+            tar.append_data(&mut header, archive_path, io::empty())
+                .map_err(|err| err.to_string())?;
+        } else {
+            tar.append_dir(archive_path, source_path)
+                .map_err(|err| err.to_string())?;
+        }
+        if through_link {
+            walker.linked_entries += 1;
+        }
+        if let Some(incremental) = walker.incremental.as_mut() {
+            incremental.snapshot.dirs.insert(manifest_key(archive_path));
+        }
+
+        if let Some(canonical) = canonical.as_ref() {
+            walker.ancestors.push(canonical.clone());
+        }
+        walker.link_depth += usize::from(via_link);
+        // 目录条目已写入；无法列出内容时只跳过其子条目。
+        let children = match sorted_children(source_path) {
+            Ok(children) => children,
+            Err(err) => {
+                walker.tolerate(tracker, window, source_path, err)?;
+                Vec::new()
+            }
+        };
+        for child in children {
+            let child_name = child
+                .file_name()
+                .map(PathBuf::from)
+                .ok_or_else(|| format!("无效的归档路径: {}", child.display()))?;
+            append_path_to_tar(
+                tar,
+                &child,
+                &archive_path.join(child_name),
+                tracker,
+                window,
+                progress_message,
+                walker,
+            )?;
+        }
+        if canonical.is_some() {
+            walker.ancestors.pop();
+        }
+        walker.link_depth -= usize::from(via_link);
+
+        return Ok(());
+    }
+
+    if metadata.is_file() {
+        let key = manifest_key(archive_path);
+        if !walker.filter.matches(&metadata) {
+            walker.filtered_files += 1;
+            // 增量打包时筛掉的文件沿用基准记录，避免被当成已删除。
+            if let Some(incremental) = walker.incremental.as_mut() {
+                if let Some(entry) = incremental.base.get(&key).cloned() {
+                    incremental.snapshot.files.insert(key, entry);
+                }
+            }
+            return Ok(());
+        }
+        if through_link {
+            walker.linked_entries += 1;
+        }
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok());
+        if let Some(incremental) = walker.incremental.as_mut() {
+            if let Some(entry) =
+                incremental.unchanged_entry(&key, source_path, metadata.len(), modified)?
+            {
+                incremental
+                    .snapshot
+                    .files
+                    .insert(key, entry.with_modified(modified));
+                incremental.unchanged_files += 1;
+                tracker.advance_bytes(window, metadata.len(), progress_message);
+                return Ok(());
+            }
+        }
+
+        // 同一文件的后续硬链接只写入指向首次出现路径的链接条目，不再重复保存内容。
+        let hardlink_id = hardlink_id(source_path, &metadata);
+        if let Some(first_path) = hardlink_id
+            .and_then(|id| walker.hardlinks.get(&id))
+            .cloned()
+        {
+            append_link_entry(tar, walker, &metadata, archive_path, &first_path, modified)?;
+            tracker.advance_bytes(window, metadata.len(), progress_message);
+            return Ok(());
+        }
+
+        // 内容与已写入的文件相同时同样只写链接条目，并加上去重标记；
+        // 这部分字节在打包前已从进度总量中扣除，不再推进进度。
+        let digest = walker
+            .dedupe
+            .as_ref()
+            .and_then(|dedupe| dedupe.digests.get(source_path))
+            .cloned();
+        let stored_path = digest.as_ref().and_then(|digest| {
+            walker
+                .dedupe
+                .as_ref()
+                .and_then(|dedupe| dedupe.stored.get(digest))
+                .cloned()
+        });
+        if let Some(first_path) = stored_path {
+            tar.append_pax_extensions([(DEDUPE_PAX_KEY, b"1".as_slice())])
+                .map_err(|err| err.to_string())?;
+            append_link_entry(tar, walker, &metadata, archive_path, &first_path, modified)?;
+            if let Some(dedupe) = walker.dedupe.as_mut() {
+                dedupe.deduped_files += 1;
+                dedupe.deduped_bytes += metadata.len();
+            }
+            return Ok(());
+        }
+
+        tracker.set_current_path(
+            window,
+            Some(source_path.display().to_string()),
+            progress_message,
+        );
+
+        let mut header = tar::Header::new_gnu();
+        header.set_metadata(&metadata);
+        header.set_cksum();
+
+        // 在写入条目头之前打开文件：无权限或已被删除的文件可以整体跳过，不会留下半个条目。
+        // 头部写入后的读取错误无法回退，仍然中止打包。
+        let file = match File::open(source_path) {
+            Ok(file) => file,
+            Err(err) => {
+                tracker.advance_bytes(window, metadata.len(), progress_message);
+                return walker.tolerate(tracker, window, source_path, err.to_string());
+            }
+        };
+        // 哈希在写入 tar 的同一次读取中完成，不额外读一遍源文件。
+        let tap = HashTap::default();
+        tap.start();
+        let source: Box<dyn Read> =
+            if walker.read_ahead && metadata.len() >= READ_AHEAD_MIN_FILE_SIZE {
+                Box::new(ReadAheadReader::spawn(file))
+            } else {
+                Box::new(BufReader::new(file))
+            };
+        let mut reader = HashingReader::new(
+            ProgressReader::new(source, tracker, window, progress_message),
+            tap.clone(),
+        );
+        tar.append_data(&mut header, archive_path, &mut reader)
+            .map_err(|err| err.to_string())?;
+        if let Some(entry) = tap.finish() {
+            // 预扫描之后内容被修改的文件不作为去重来源。
+            if let (Some(dedupe), Some(digest)) = (walker.dedupe.as_mut(), digest) {
+                if digest == entry.sha256 {
+                    dedupe.stored.insert(digest, archive_path.to_path_buf());
+                }
+            }
+            if let Some(incremental) = walker.incremental.as_mut() {
+                incremental
+                    .snapshot
+                    .files
+                    .insert(key.clone(), entry.clone().with_modified(modified));
+            }
+            walker.manifest.files.insert(key, entry);
+        }
+        if let Some(id) = hardlink_id {
+            walker.hardlinks.insert(id, archive_path.to_path_buf());
+        }
+        return Ok(());
+    }
+
+    let error = format!("不支持归档的路径类型: {}", source_path.display());
+    walker.tolerate(tracker, window, source_path, error)
+}
+
+// 写入指向已打包路径的硬链接条目，增量快照沿用首个路径的哈希记录。
+fn append_link_entry<W: Write>(
+    tar: &mut tar::Builder<W>,
+    walker: &mut PackWalker,
+    metadata: &fs::Metadata,
+    archive_path: &Path,
+    first_path: &Path,
+    modified: Option<Duration>,
+) -> Result<(), String> {
+    let mut header = tar::Header::new_gnu();
+    header.set_metadata(metadata);
+    header.set_entry_type(tar::EntryType::Link);
+    header.set_size(0);
+    tar.append_link(&mut header, archive_path, first_path)
+        .map_err(|err| err.to_string())?;
+    if let Some(incremental) = walker.incremental.as_mut() {
+        if let Some(entry) = incremental.snapshot.files.get(&manifest_key(first_path)) {
+            let entry = entry.clone().with_modified(modified);
+            incremental
+                .snapshot
+                .files
+                .insert(manifest_key(archive_path), entry);
+        }
+    }
+    Ok(())
+}
+
+fn write_archive_header<W: Write>(
+    writer: &mut W,
+    header: &ArchiveHeader,
+) -> Result<Vec<u8>, String> {
+    writer
+        .write_all(MAGIC_HEADER)
+        .map_err(|err| err.to_string())?;
+    let encoded_header = header.encoded_bytes();
+    writer
+        .write_all(&encoded_header)
+        .map_err(|err| err.to_string())?;
+    Ok(header.aad_bytes())
+}
+
+// 头部的内容大小要到打包结束才回填，负载摘要边写边算，最后再与最终头部一起计算校验尾。
+fn archive_checksum(header: &ArchiveHeader, payload_digest: &[u8]) -> [u8; CHECKSUM_TRAILER_LEN] {
+    let mut hasher = Sha256::new();
+    hasher.update(header.encoded_bytes());
+    hasher.update(payload_digest);
+    hasher.finalize().into()
+}
+
+// 重新计算校验尾并与文件末尾比对，只读取原始字节，不需要密码也不解压；没有校验尾时返回 `None`。
+fn archive_checksum_matches(archive_path: &Path) -> Result<Option<bool>, String> {
+    let (mut reader, _) = open_archive_reader(archive_path)?;
+    let (header, _) = read_archive_preamble(&mut reader)?;
+    if !header.has_checksum_trailer() {
+        return Ok(None);
+    }
+    let mut hasher = Sha256::new();
+    io::copy(&mut reader, &mut hasher).map_err(|err| err.to_string())?;
+    let expected = archive_checksum(&header, &hasher.finalize());
+
+    reader.remaining = CHECKSUM_TRAILER_LEN as u64;
+    let mut trailer = [0u8; CHECKSUM_TRAILER_LEN];
+    if reader.read_exact(&mut trailer).is_err() {
+        return Ok(Some(false));
+    }
+    Ok(Some(trailer == expected))
+}
+
+fn encode_metadata(metadata: &ArchiveMetadata) -> Result<Vec<u8>, String> {
+    let bytes =
+        serde_json::to_vec(metadata).map_err(|err| format!("序列化归档元数据失败: {}", err))?;
+    if bytes.len() > MAX_METADATA_LEN {
+        return Err("归档元数据过大，请缩短备注".to_string());
+    }
+    Ok(bytes)
+}
+
+// 隐藏文件列表时写在加密流开头的元数据块，格式与头部元数据块相同（4 字节长度 + JSON）。
+fn write_hidden_metadata<W: Write>(writer: &mut W, metadata: &[u8]) -> Result<(), String> {
+    writer
+        .write_all(&(metadata.len() as u32).to_le_bytes())
+        .and_then(|_| writer.write_all(metadata))
+        .map_err(|err| format!("写入归档元数据失败: {}", err))
+}
+
+fn read_hidden_metadata<R: Read>(reader: &mut R) -> Result<Vec<u8>, String> {
+    let mut len_bytes = [0u8; 4];
+    reader
+        .read_exact(&mut len_bytes)
+        .map_err(|err| err.to_string())?;
+    let len = u32::from_le_bytes(len_bytes) as usize;
+    if len > MAX_METADATA_LEN {
+        return Err("归档元数据长度无效".to_string());
+    }
+    let mut metadata = vec![0u8; len];
+    reader
+        .read_exact(&mut metadata)
+        .map_err(|err| err.to_string())?;
+    Ok(metadata)
+}
+
+// 内容大小要等 tar 写完才知道：头部先写入占位值，完成后回填到首个分卷的头部末尾。
+// 隐藏文件列表的归档不在明文头部记录内容大小，没有占位值可回填。
+fn patch_content_size(
+    first_part: &Path,
+    header: &ArchiveHeader,
+    content_size: ContentSize,
+) -> Result<(), String> {
+    if header.content_size.is_none() {
+        return Ok(());
+    }
+    let offset = MAGIC_HEADER.len() + header.encoded_bytes().len() - CONTENT_SIZE_LEN;
+    let mut file = OpenOptions::new()
+        .write(true)
+        .open(first_part)
+        .map_err(|err| err.to_string())?;
+    file.seek(SeekFrom::Start(offset as u64))
+        .and_then(|_| file.write_all(&content_size.encoded()))
+        .and_then(|_| file.sync_all())
+        .map_err(|err| format!("回填归档内容大小失败: {}", err))
+}
+
+// 读取魔数、版本标记与归档头，返回头部及其压缩算法。
+fn read_archive_preamble<R: Read>(
+    reader: &mut R,
+) -> Result<(ArchiveHeader, ArchiveCompression), String> {
+    let mut magic = [0u8; MAGIC_HEADER.len()];
+    if reader.read_exact(&mut magic).is_err() || magic != *MAGIC_HEADER {
+        return Err("文件损坏或格式不正确：无法识别的 Krate 包".to_string());
+    }
+
+    let mut marker = [0u8; FORMAT_MARKER.len()];
+    reader
+        .read_exact(&mut marker)
+        .map_err(|err| err.to_string())?;
+
+    if marker != *FORMAT_MARKER && marker != *LEGACY_FORMAT_MARKER {
+        return Err("不支持的 .krate 版本，请使用当前版本重新生成归档".to_string());
+    }
+
+    let header = read_archive_header(reader, marker)?;
+    let compression = ArchiveCompression::from_code(header.compression & !COMPRESSION_FLAG_BITS)
+        .ok_or("不支持的 .krate 压缩格式".to_string())?;
+    Ok((header, compression))
+}
+
+fn read_archive_header<R: Read>(reader: &mut R, version: [u8; 4]) -> Result<ArchiveHeader, String> {
+    let mut flags = [0u8; 1];
+    reader
+        .read_exact(&mut flags)
+        .map_err(|err| err.to_string())?;
+
+    let mut compression = [0u8; 1];
+    reader
+        .read_exact(&mut compression)
+        .map_err(|err| err.to_string())?;
+
+    if ArchiveCompression::from_code(compression[0] & !COMPRESSION_FLAG_BITS).is_none() {
+        return Err("不支持的 .krate 压缩格式".to_string());
+    }
+    if compression[0] & COMPRESSION_HIDDEN_NAMES != 0 && flags[0] & FLAG_ENCRYPTED == 0 {
+        return Err("文件损坏或格式不正确：未加密的归档不能隐藏文件列表".to_string());
+    }
+
+    let encryption = if flags[0] & FLAG_ENCRYPTED != 0 {
+        let mut memory_kib = [0u8; 4];
+        let mut iterations = [0u8; 4];
+        let mut lanes = [0u8; 4];
+        let mut salt = [0u8; SALT_LEN];
+        let mut stream_nonce = [0u8; STREAM_NONCE_LEN];
+
+        reader
+            .read_exact(&mut memory_kib)
+            .and_then(|_| reader.read_exact(&mut iterations))
+            .and_then(|_| reader.read_exact(&mut lanes))
+            .and_then(|_| reader.read_exact(&mut salt))
+            .and_then(|_| reader.read_exact(&mut stream_nonce))
+            .map_err(|err| err.to_string())?;
+
+        let key_check = if flags[0] & FLAG_KEY_CHECK != 0 {
+            let mut key_check = [0u8; KEY_CHECK_LEN];
+            reader
+                .read_exact(&mut key_check)
+                .map_err(|err| err.to_string())?;
+            Some(key_check)
+        } else {
+            None
+        };
+
+        Some(EncryptionMetadata {
+            memory_kib: u32::from_le_bytes(memory_kib),
+            iterations: u32::from_le_bytes(iterations),
+            lanes: u32::from_le_bytes(lanes),
+            salt,
+            stream_nonce,
+            key_check,
+        })
+    } else {
+        None
+    };
+
+    let metadata = if flags[0] & FLAG_METADATA != 0 {
+        let mut len_bytes = [0u8; 4];
+        reader
+            .read_exact(&mut len_bytes)
+            .map_err(|err| err.to_string())?;
+        let len = u32::from_le_bytes(len_bytes) as usize;
+        if len > MAX_METADATA_LEN {
+            return Err("归档元数据长度无效".to_string());
+        }
+        let mut metadata = vec![0u8; len];
+        reader
+            .read_exact(&mut metadata)
+            .map_err(|err| err.to_string())?;
+        Some(metadata)
+    } else {
+        None
+    };
+
+    let volume_size = if flags[0] & FLAG_VOLUMES != 0 {
+        let mut volume_size = [0u8; 8];
+        reader
+            .read_exact(&mut volume_size)
+            .map_err(|err| err.to_string())?;
+        Some(u64::from_le_bytes(volume_size))
+    } else {
+        None
+    };
+
+    let content_size = if flags[0] & FLAG_CONTENT_SIZE != 0 {
+        let mut tar_bytes = [0u8; 8];
+        let mut files = [0u8; 8];
+        reader
+            .read_exact(&mut tar_bytes)
+            .and_then(|_| reader.read_exact(&mut files))
+            .map_err(|err| err.to_string())?;
+        Some(ContentSize {
+            tar_bytes: u64::from_le_bytes(tar_bytes),
+            files: u64::from_le_bytes(files),
+        })
+    } else {
+        None
+    };
+
+    Ok(ArchiveHeader {
+        version,
+        flags: flags[0],
+        compression: compression[0],
+        encryption,
+        metadata,
+        volume_size,
+        content_size,
+    })
+}
+
+// tar 只为文件还原修改时间，目录需要单独处理；Windows 上无法直接打开目录句柄，保持默认时间。
+#[cfg(unix)]
+fn restore_dir_mtime(path: &Path, header: &tar::Header) -> Result<(), String> {
+    let Ok(mtime) = header.mtime() else {
+        return Ok(());
+    };
+    File::open(path)
+        .and_then(|dir| dir.set_modified(UNIX_EPOCH + Duration::from_secs(mtime)))
+        .map_err(|err| format!("还原目录修改时间失败 {}: {}", path.display(), err))
+}
+
+#[cfg(not(unix))]
+fn restore_dir_mtime(_path: &Path, _header: &tar::Header) -> Result<(), String> {
+    Ok(())
+}
+
+// 链接数大于 1 的文件返回 (设备号, inode)，用于识别同一文件的多个硬链接。
+#[cfg(unix)]
+fn hardlink_id(_path: &Path, metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    (metadata.nlink() > 1).then(|| (metadata.dev(), metadata.ino()))
+}
+
+// 标准库在稳定版中不提供文件索引号，需要打开文件后通过句柄查询 (卷序列号, 文件索引)。
+#[cfg(windows)]
+fn hardlink_id(path: &Path, _metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::windows::io::AsRawHandle;
+    use windows::Win32::Foundation::HANDLE;
+    use windows::Win32::Storage::FileSystem::{
+        GetFileInformationByHandle, BY_HANDLE_FILE_INFORMATION,
+    };
+
+    let file = File::open(path).ok()?;
+    let mut info = BY_HANDLE_FILE_INFORMATION::default();
+    unsafe { GetFileInformationByHandle(HANDLE(file.as_raw_handle()), &mut info) }.ok()?;
+    (info.nNumberOfLinks > 1).then(|| {
+        (
+            u64::from(info.dwVolumeSerialNumber),
+            (u64::from(info.nFileIndexHigh) << 32) | u64::from(info.nFileIndexLow),
+        )
+    })
+}
+
+#[cfg(not(any(unix, windows)))]
+fn hardlink_id(_path: &Path, _metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+fn is_7z_archive(path: &Path) -> Result<bool, String> {
+    let mut file = File::open(path).map_err(|err| err.to_string())?;
+    let mut signature = [0u8; SEVEN_ZIP_SIGNATURE.len()];
+    match file.read_exact(&mut signature) {
+        Ok(()) => Ok(signature == *SEVEN_ZIP_SIGNATURE),
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+fn is_zip_archive(path: &Path) -> Result<bool, String> {
+    let mut file = File::open(path).map_err(|err| err.to_string())?;
+    let mut signature = [0u8; 4];
+    match file.read_exact(&mut signature) {
+        Ok(()) => Ok(ZIP_SIGNATURES.contains(&&signature)),
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+// 识别非 KRATE 格式的标准 tar 包（.tar / .tar.gz / .tar.zst / .tar.xz），返回其压缩算法。
+fn detect_tarball(path: &Path) -> Result<Option<ArchiveCompression>, String> {
+    let file = File::open(path).map_err(|err| err.to_string())?;
+    let mut head = Vec::new();
+    file.take((TAR_USTAR_OFFSET + TAR_USTAR_MAGIC.len()) as u64)
+        .read_to_end(&mut head)
+        .map_err(|err| err.to_string())?;
+
+    Ok(if head.starts_with(GZIP_SIGNATURE) {
+        Some(ArchiveCompression::Gzip)
+    } else if head.starts_with(ZSTD_SIGNATURE) {
+        Some(ArchiveCompression::Zstd)
+    } else if head.starts_with(XZ_SIGNATURE) {
+        Some(ArchiveCompression::Xz)
+    } else if head.get(TAR_USTAR_OFFSET..) == Some(TAR_USTAR_MAGIC.as_slice()) {
+        Some(ArchiveCompression::None)
+    } else {
+        None
+    })
+}
+
+// 标准 tar 包没有 KRATE 头，直接按文件字节推进进度，解包走与 .krate 相同的安全检查。
+fn extract_tarball(
+    window: Option<&Window>,
+    cancel: Arc<OperationControl>,
+    archive_path: &Path,
+    compression: ArchiveCompression,
+    output_dir: &Path,
+    options: ExtractOptions,
+) -> Result<ExtractReport, String> {
+    let file = File::open(archive_path).map_err(|err| err.to_string())?;
+    let total_bytes = file.metadata().map_err(|err| err.to_string())?.len();
+    let mut tracker =
+        ArchiveProgressTracker::new("extract", "正在解压 tar 包", total_bytes, cancel);
+    tracker.set_stage(window, "正在解压 tar 包", "正在解压 tar 包");
+
+    let reader = ProgressReader::new(file, &mut tracker, window, "正在解压 tar 包");
+    let report = extract_archive_contents(
+        reader,
+        compression,
+        output_dir,
+        options,
+        PayloadCounters::default(),
+    )?;
+    tracker.finish(window, "解压完成", "解压完成");
+    Ok(report)
+}
+
+// 把归档条目名转换为相对路径；含 `..`、根目录或盘符的条目返回 `None`。
+fn sanitized_entry_path(name: &str) -> Option<PathBuf> {
+    let normalized = name.replace('\\', "/");
+    let mut relative = PathBuf::new();
+    for component in Path::new(&normalized).components() {
+        match component {
+            std::path::Component::Normal(part) => relative.push(part),
+            std::path::Component::CurDir => {}
+            _ => return None,
+        }
+    }
+
+    if relative.as_os_str().is_empty() {
+        None
+    } else {
+        Some(relative)
+    }
+}
+
+// 解压时统一条目名：先做 NFC 规范化（macOS 上打包的名称多为 NFD）；`replace_reserved`（Windows）时
+// 再把保留字符换成全角字符、控制字符换成 `_`，保留设备名与以点或空格结尾的名称追加 `_`。
+fn portable_entry_name(name: &str, replace_reserved: bool) -> String {
+    let normalized = name.nfc().collect::<String>();
+    if !replace_reserved {
+        return normalized;
+    }
+    normalized
+        .split('/')
+        .map(portable_path_component)
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn portable_path_component(component: &str) -> String {
+    // `.` 与 `..` 留给后续的路径安全检查处理。
+    if matches!(component, "" | "." | "..") {
+        return component.to_string();
+    }
+    let mut portable = component
+        .chars()
+        .map(|ch| match ch {
+            ':' => '：',
+            '?' => '？',
+            '*' => '＊',
+            '<' => '＜',
+            '>' => '＞',
+            '|' => '｜',
+            '"' => '＂',
+            ch if ch.is_control() => '_',
+            ch => ch,
+        })
+        .collect::<String>();
+    let stem = portable.split('.').next().unwrap_or_default();
+    if portable.ends_with(['.', ' '])
+        || WINDOWS_RESERVED_NAMES.contains(&stem.to_ascii_uppercase().as_str())
+    {
+        portable.push('_');
+    }
+    portable
+}
+
+// Windows 上把路径转换为 `\\?\` 扩展长度形式，绕过 260 字符的 MAX_PATH 限制；
+// 其他平台原样返回。
+#[cfg(windows)]
+fn long_path(path: &Path) -> PathBuf {
+    use std::path::{Component, Prefix};
+
+    let Ok(absolute) = std::path::absolute(path) else {
+        return path.to_path_buf();
+    };
+    let Some(Component::Prefix(prefix)) = absolute.components().next() else {
+        return absolute;
+    };
+    match prefix.kind() {
+        Prefix::Disk(_) => {
+            let mut long = OsString::from(r"\\?\");
+            long.push(absolute.as_os_str());
+            PathBuf::from(long)
+        }
+        Prefix::UNC(..) => match absolute.to_str().and_then(|path| path.strip_prefix(r"\\")) {
+            Some(rest) => PathBuf::from(format!(r"\\?\UNC\{}", rest)),
+            None => absolute,
+        },
+        // 已经是 `\\?\` 或设备路径。
+        _ => absolute,
+    }
+}
+
+#[cfg(not(windows))]
+fn long_path(path: &Path) -> PathBuf {
+    path.to_path_buf()
+}
+
+// 条目先做词法校验，再把父目录规范化后确认仍在输出目录内，
+// 这样前面条目创建的符号链接也无法把后续文件“写穿”到目录之外。
+fn resolve_entry_target(output_root: &Path, name: &str) -> Result<Option<PathBuf>, String> {
+    let Some(relative) = sanitized_entry_path(name) else {
+        return Ok(None);
+    };
+    let target = output_root.join(relative);
+    let parent = target.parent().unwrap_or(output_root);
+    if !parent.starts_with(output_root) {
+        return Ok(None);
+    }
+
+    let mut existing = parent;
+    while !existing.exists() {
+        existing = existing.parent().unwrap_or(output_root);
+    }
+    let canonical = existing.canonicalize().map_err(|err| err.to_string())?;
+    if !canonical.starts_with(output_root) {
+        return Ok(None);
+    }
+
+    fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+    let canonical_parent = parent.canonicalize().map_err(|err| err.to_string())?;
+    if !canonical_parent.starts_with(output_root) {
+        return Ok(None);
+    }
+    Ok(Some(target))
+}
+
+// 从链接所在目录的真实位置出发逐段解析链接目标，判断是否会越出输出目录；绝对路径一律视为越界。
+fn symlink_stays_inside(output_root: &Path, link_dir: &Path, link_target: &str) -> bool {
+    // 所在目录可能经由之前解出的链接到达（如 `up -> .` 之后的 `up/esc`），必须从规范化后的位置算起。
+    let Ok(mut current) = link_dir.canonicalize() else {
+        return false;
+    };
+    for component in Path::new(&link_target.replace('\\', "/")).components() {
+        match component {
+            std::path::Component::Normal(part) => {
+                current.push(part);
+                // 途经已解出的链接时按其真实位置继续。
+                if let Ok(real) = current.canonicalize() {
+                    current = real;
+                }
+            }
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                current.pop();
+            }
+            std::path::Component::RootDir | std::path::Component::Prefix(_) => return false,
+        }
+        if current.strip_prefix(output_root).is_err() {
+            return false;
+        }
+    }
+    true
+}
+
+// 严格模式下不安全条目直接报错，否则记录后跳过。
+fn reject_unsafe_entry(
+    strict_security: bool,
+    skipped_entries: &mut Vec<String>,
+    name: String,
+) -> Result<(), String> {
+    if strict_security {
+        return Err(format!("归档条目路径不安全: {}", name));
+    }
+    skipped_entries.push(name);
+    Ok(())
+}
+
+fn map_7z_error(err: sevenz_rust::Error, has_password: bool) -> String {
+    match err {
+        sevenz_rust::Error::PasswordRequired => "该 7z 归档已加密，请输入密码后再解压".to_string(),
+        sevenz_rust::Error::MaybeBadPassword(_)
+        | sevenz_rust::Error::ChecksumVerificationFailed
+            if has_password =>
+        {
+            "归档解密失败，密码错误或文件已损坏".to_string()
+        }
+        sevenz_rust::Error::MaybeBadPassword(_) => {
+            "该 7z 归档已加密，请输入密码后再解压".to_string()
+        }
+        other => format!("7z 解压失败: {}", other),
+    }
+}
+
+// 7z 只支持解压：按条目写出文件，进度按解压出的字节推进并在每个条目切换时更新当前路径。
+fn extract_7z_archive(
+    window: Option<&Window>,
+    cancel: Arc<OperationControl>,
+    archive_path: &Path,
+    output_dir: &Path,
+    password: Option<&str>,
+    strict_security: bool,
+) -> Result<Vec<String>, String> {
+    let file = File::open(archive_path).map_err(|err| err.to_string())?;
+    let len = file.metadata().map_err(|err| err.to_string())?.len();
+    let sevenz_password = password
+        .map(sevenz_rust::Password::from)
+        .unwrap_or_else(sevenz_rust::Password::empty);
+    let mut reader = sevenz_rust::SevenZReader::new(file, len, sevenz_password)
+        .map_err(|err| map_7z_error(err, password.is_some()))?;
+
+    let total_bytes = reader
+        .archive()
+        .files
+        .iter()
+        .map(|entry| entry.size())
+        .sum();
+    let mut tracker =
+        ArchiveProgressTracker::new("extract", "正在解压 7z 归档", total_bytes, cancel);
+    tracker.set_stage(window, "正在解压 7z 归档", "正在解压 7z 归档");
+
+    fs::create_dir(output_dir).map_err(|err| err.to_string())?;
+    let output_root = output_dir.canonicalize().map_err(|err| err.to_string())?;
+    let mut skipped_entries = Vec::new();
+    let result = reader.for_each_entries(|entry, data| {
+        let to_io_error = |err: String| io::Error::new(io::ErrorKind::InvalidData, err);
+        let Some(target) = resolve_entry_target(&output_root, entry.name()).map_err(to_io_error)?
+        else {
+            reject_unsafe_entry(
+                strict_security,
+                &mut skipped_entries,
+                entry.name().to_string(),
+            )
+            .map_err(to_io_error)?;
+            io::copy(data, &mut io::sink())?;
+            return Ok(true);
+        };
+        tracker.set_current_path(window, Some(entry.name().to_string()), "正在解压 7z 归档");
+
+        if entry.is_directory() {
+            fs::create_dir_all(&target)?;
+            return Ok(true);
+        }
+
+        let mut output = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&target)?;
+        let mut reader = ProgressReader::new(data, &mut tracker, window, "正在解压 7z 归档");
+        io::copy(&mut reader, &mut output)?;
+        Ok(true)
+    });
+
+    if let Err(err) = result {
+        let _ = fs::remove_dir_all(output_dir);
+        return Err(map_7z_error(err, password.is_some()));
+    }
+
+    tracker.finish(window, "解压完成", "解压完成");
+    Ok(skipped_entries)
+}
+
+fn map_zip_error(err: zip::result::ZipError) -> String {
+    match err {
+        zip::result::ZipError::InvalidPassword => WRONG_ZIP_PASSWORD_ERROR.to_string(),
+        other => format!("ZIP 解压失败: {}", other),
+    }
+}
+
+// 未设置 UTF-8 标志的旧式 ZIP 按创建者的本地编码保存文件名，中文 Windows 上多为 GBK；
+// 原始字节能按 GBK 解码就用 GBK，否则退回 zip 库按 CP437 解码的结果。
+fn decode_zip_entry_name(raw: &[u8], cp437_name: &str) -> String {
+    if let Ok(name) = std::str::from_utf8(raw) {
+        return name.to_string();
+    }
+    encoding_rs::GBK
+        .decode_without_bom_handling_and_without_replacement(raw)
+        .map(|name| name.into_owned())
+        .unwrap_or_else(|| cp437_name.to_string())
+}
+
+// ZIP 只支持解压：提供密码时按 ZipCrypto / WinZip AES 解密加密条目，
+// 未提供密码时先解出未加密的条目，加密条目记录到结果中。
+fn extract_zip_archive(
+    window: Option<&Window>,
+    cancel: Arc<OperationControl>,
+    archive_path: &Path,
+    output_dir: &Path,
+    password: Option<&str>,
+    strict_security: bool,
+) -> Result<ExtractReport, String> {
+    let file = File::open(archive_path).map_err(|err| err.to_string())?;
+    let mut archive = zip::ZipArchive::new(BufReader::new(file)).map_err(map_zip_error)?;
+
+    let mut total_bytes = 0;
+    let mut file_count = 0;
+    let mut encrypted_count = 0;
+    for index in 0..archive.len() {
+        let entry = archive.by_index_raw(index).map_err(map_zip_error)?;
+        if !entry.is_dir() {
+            total_bytes += entry.size();
+            file_count += 1;
+            encrypted_count += usize::from(entry.encrypted());
+        }
+    }
+    if password.is_none() && file_count > 0 && encrypted_count == file_count {
+        return Err("该 ZIP 归档已加密，请输入密码后再解压".to_string());
+    }
+
+    let mut tracker =
+        ArchiveProgressTracker::new("extract", "正在解压 ZIP 归档", total_bytes, cancel);
+    tracker.set_stage(window, "正在解压 ZIP 归档", "正在解压 ZIP 归档");
+
+    fs::create_dir(output_dir).map_err(|err| err.to_string())?;
+    let result = unpack_zip_entries(
+        &mut archive,
+        window,
+        &mut tracker,
+        output_dir,
+        password,
+        strict_security,
+    );
+    if result.is_err() {
+        let _ = fs::remove_dir_all(output_dir);
+    }
+    let (skipped_entries, password_required_entries) = result?;
+
+    tracker.finish(window, "解压完成", "解压完成");
+    Ok(ExtractReport {
+        output_dir: output_dir.to_string_lossy().to_string(),
+        skipped_entries,
+        hash_mismatches: Vec::new(),
+        password_required_entries,
+        salvage: None,
+        renamed_entries: Vec::new(),
+        space_check_skipped: false,
+    })
+}
+
+fn unpack_zip_entries<R: Read + Seek>(
+    archive: &mut zip::ZipArchive<R>,
+    window: Option<&Window>,
+    tracker: &mut ArchiveProgressTracker,
+    output_dir: &Path,
+    password: Option<&str>,
+    strict_security: bool,
+) -> Result<(Vec<String>, Vec<String>), String> {
+    let output_root = output_dir.canonicalize().map_err(|err| err.to_string())?;
+    let mut skipped_entries = Vec::new();
+    let mut password_required_entries = Vec::new();
+
+    for index in 0..archive.len() {
+        let (name, encrypted) = {
+            let entry = archive.by_index_raw(index).map_err(map_zip_error)?;
+            (
+                decode_zip_entry_name(entry.name_raw(), entry.name()),
+                entry.encrypted(),
+            )
+        };
+        let Some(target) = resolve_entry_target(&output_root, &name)? else {
+            reject_unsafe_entry(strict_security, &mut skipped_entries, name)?;
+            continue;
+        };
+
+        let mut entry = match (encrypted, password) {
+            (false, _) => archive.by_index(index),
+            (true, Some(password)) => archive.by_index_decrypt(index, password.as_bytes()),
+            (true, None) => {
+                password_required_entries.push(name);
+                continue;
+            }
+        }
+        .map_err(map_zip_error)?;
+        tracker.set_current_path(window, Some(name), "正在解压 ZIP 归档");
+
+        if entry.is_dir() {
+            fs::create_dir_all(&target).map_err(|err| err.to_string())?;
+            continue;
+        }
+
+        let mut output = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&target)
+            .map_err(|err| err.to_string())?;
+        let mut reader = ProgressReader::new(&mut entry, tracker, window, "正在解压 ZIP 归档");
+        // ZipCrypto 的校验字节只有 1 字节，错误密码可能通过检查，最终表现为 CRC 不符；
+        // AES 的 HMAC 也在读完条目时才校验，因此加密条目的读取错误都按密码错误处理。
+        io::copy(&mut reader, &mut output).map_err(|err| {
+            if encrypted {
+                WRONG_ZIP_PASSWORD_ERROR.to_string()
+            } else {
+                format!("ZIP 解压失败: {}", err)
+            }
+        })?;
+    }
+
+    Ok((skipped_entries, password_required_entries))
+}
+
+fn extract_archive_contents<R: Read>(
+    reader: R,
+    compression: ArchiveCompression,
+    output_dir: &Path,
+    options: ExtractOptions,
+    counters: PayloadCounters,
+) -> Result<ExtractReport, String> {
+    let tap = HashTap::default();
+    let decompressor = HashingReader::new(
+        CountingReader::new(
+            DecompressedReader::new(reader, compression)?,
+            counters.plain.clone(),
+        ),
+        tap.clone(),
+    );
+    fs::create_dir(output_dir).map_err(|err| err.to_string())?;
+    let mut archive = tar::Archive::new(decompressor);
+    archive.set_preserve_permissions(options.preserve_metadata);
+    archive.set_preserve_mtime(options.preserve_metadata);
+    archive.set_preserve_ownerships(options.preserve_ownership);
+    archive.set_unpack_xattrs(false);
+    archive.set_overwrite(false);
+
+    let mut result = unpack_tar_entries(&mut archive, output_dir, options, &tap);
+    match result.as_mut() {
+        Ok(report) => {
+            if let Some(salvage) = report
+                .salvage
+                .as_mut()
+                .filter(|salvage| salvage.error.is_some())
+            {
+                salvage.corruption_offset =
+                    Some(counters.intact.0.get()).filter(|offset| *offset > 0);
+            }
+        }
+        Err(_) => {
+            let _ = fs::remove_dir_all(output_dir);
+        }
+    }
+    result
+}
+
+// 不使用 `Archive::unpack`：逐条校验目标路径，硬链接的源路径也必须位于输出目录内。
+// 目录条目放到最后再写入，避免子条目的写入把已还原的目录修改时间刷新掉。
+fn unpack_tar_entries<R: Read>(
+    archive: &mut tar::Archive<R>,
+    output_dir: &Path,
+    options: ExtractOptions,
+    tap: &HashTap,
+) -> Result<ExtractReport, String> {
+    let strict_security = options.strict_security;
+    let output_root = output_dir.canonicalize().map_err(|err| err.to_string())?;
+    let mut skipped_entries = Vec::new();
+    let mut directories = Vec::new();
+    let mut manifest = None;
+    let mut hashes = HashMap::new();
+    let mut recovered_entries = Vec::new();
+    let mut renamed_entries = Vec::new();
+    // 去除路径层级后不再解出的条目，校验哈希时不算缺失。
+    let mut stripped_entries = Vec::new();
+    // 正在写出的文件；读取中途出错时据此标记为 `.partial`。
+    let mut unpacking = None;
+
+    // 条目迭代器在闭包外创建，闭包内收集的目录条目才能在闭包结束后继续使用。
+    let entries = archive.entries().map_err(|err| err.to_string())?;
+    let walked = (|| -> Result<(), String> {
+        for entry in entries {
+            let mut entry = entry.map_err(|err| err.to_string())?;
+            let name = String::from_utf8_lossy(&entry.path_bytes()).to_string();
+            // 文件清单位于归档根部，不参与去除层级。
+            let stripped_name = if name == MANIFEST_ENTRY_NAME {
+                Some(name.clone())
+            } else {
+                strip_entry_components(&name, options.strip_components)
+            };
+            let Some(stripped_name) = stripped_name else {
+                stripped_entries.push(name);
+                continue;
+            };
+            let portable_name = portable_entry_name(&stripped_name, cfg!(windows));
+            let Some(target) = resolve_entry_target(&output_root, &portable_name)? else {
+                reject_unsafe_entry(strict_security, &mut skipped_entries, name)?;
+                continue;
+            };
+            // 去除层级后不同条目可能落到同一路径；输出目录是新建的，已存在即为冲突，保留先解出的条目。
+            if options.strip_components > 0
+                && fs::symlink_metadata(&target).is_ok_and(|metadata| {
+                    !(metadata.is_dir() && entry.header().entry_type().is_dir())
+                })
+            {
+                skipped_entries.push(name);
+                continue;
+            }
+            if portable_name != stripped_name {
+                renamed_entries.push(RenamedEntry {
+                    original: name.clone(),
+                    extracted: portable_name,
+                });
+            }
+
+            if entry.header().entry_type().is_hard_link() {
+                let link_name = entry
+                    .link_name_bytes()
+                    .map(|bytes| String::from_utf8_lossy(&bytes).to_string())
+                    .unwrap_or_default();
+                // 链接源被去除层级时内容无从还原，与源未解出的情况一样记为跳过。
+                let Some(link_name) = strip_entry_components(&link_name, options.strip_components)
+                else {
+                    skipped_entries.push(name);
+                    continue;
+                };
+                let link_name = portable_entry_name(&link_name, cfg!(windows));
+                let Some(source) = resolve_entry_target(&output_root, &link_name)? else {
+                    reject_unsafe_entry(strict_security, &mut skipped_entries, name)?;
+                    continue;
+                };
+                // 链接目标未被解出（路径不安全被跳过等）时无法还原内容，记为跳过。
+                if !fs::symlink_metadata(&source).is_ok_and(|metadata| metadata.is_file()) {
+                    skipped_entries.push(name);
+                    continue;
+                }
+                let copy =
+                    is_dedupe_entry(&mut entry) && options.dedupe_restore == DedupeRestore::Copy;
+                if restore_hard_link(&source, &target, copy)? {
+                    recovered_entries.push(name);
+                } else {
+                    skipped_entries.push(name);
+                }
+                continue;
+            }
+
+            if entry.header().entry_type().is_symlink() && !options.allow_external_symlinks {
+                let link_name = entry
+                    .link_name_bytes()
+                    .map(|bytes| String::from_utf8_lossy(&bytes).to_string())
+                    .unwrap_or_default();
+                let link_dir = target.parent().unwrap_or(output_root.as_path());
+                if !symlink_stays_inside(&output_root, link_dir, &link_name) {
+                    reject_unsafe_entry(strict_security, &mut skipped_entries, name)?;
+                    continue;
+                }
+            }
+
+            if entry.header().entry_type().is_dir() {
+                fs::create_dir_all(&target).map_err(|err| err.to_string())?;
+                directories.push((entry, target));
+                recovered_entries.push(name);
+                continue;
+            }
+
+            if name == MANIFEST_ENTRY_NAME && entry.header().entry_type().is_file() {
+                let mut bytes = Vec::new();
+                entry
+                    .read_to_end(&mut bytes)
+                    .map_err(|err| err.to_string())?;
+                manifest = Some(parse_manifest(&mut bytes.as_slice())?);
+                if options.include_manifest {
+                    fs::write(&target, bytes).map_err(|err| err.to_string())?;
+                }
+                continue;
+            }
+
+            // 解压过程中顺带计算哈希：tap 只在读取条目数据时开启，不包含 tar 头与填充。
+            if options.verify_hashes && entry.header().entry_type().is_file() {
+                tap.start();
+            }
+            unpacking = Some((name.clone(), target.clone()));
+            entry.unpack(&target).map_err(|err| err.to_string())?;
+            unpacking = None;
+            if let Some(hash) = tap.finish() {
+                hashes.insert(name.clone(), hash);
+            }
+            recovered_entries.push(name);
+        }
+        Ok(())
+    })();
+
+    // 加密数据块逐块认证，出错之前解出的条目都是完整可信的；只有写到一半的文件需要标记。
+    let salvage = match walked {
+        Ok(()) => options.salvage.then(|| SalvageReport {
+            recovered_entries,
+            ..Default::default()
+        }),
+        Err(err) if options.salvage => {
+            let partial_entries = match unpacking {
+                Some((name, target)) => mark_partial_entry(&target)?
+                    .then_some(name)
+                    .into_iter()
+                    .collect(),
+                None => Vec::new(),
+            };
+            let recovered: HashSet<&String> = recovered_entries.iter().collect();
+            let missing_entries = manifest
+                .as_ref()
+                .map(|manifest| {
+                    manifest
+                        .files
+                        .keys()
+                        .filter(|path| !recovered.contains(path))
+                        .cloned()
+                        .collect()
+                })
+                .unwrap_or_default();
+            Some(SalvageReport {
+                recovered_entries,
+                partial_entries,
+                missing_entries,
+                corruption_offset: None,
+                error: Some(err),
+            })
+        }
+        Err(err) => return Err(err),
+    };
+
+    for (mut entry, target) in directories.into_iter().rev() {
+        entry.unpack(&target).map_err(|err| err.to_string())?;
+        if options.preserve_metadata {
+            restore_dir_mtime(&target, entry.header())?;
+        }
+    }
+
+    // 损坏的归档缺少末尾的清单，缺失条目已在恢复结果中列出。
+    let damaged = salvage
+        .as_ref()
+        .is_some_and(|salvage| salvage.error.is_some());
+    let hash_mismatches = if options.verify_hashes && !damaged {
+        stripped_entries.extend(skipped_entries.iter().cloned());
+        manifest_mismatches(manifest.as_ref(), &hashes, &stripped_entries)?
+    } else {
+        Vec::new()
+    };
+
+    Ok(ExtractReport {
+        output_dir: output_dir.to_string_lossy().to_string(),
+        skipped_entries,
+        hash_mismatches,
+        password_required_entries: Vec::new(),
+        salvage,
+        renamed_entries,
+        space_check_skipped: false,
+    })
+}
+
+// 去掉条目路径开头的 `count` 层；剩余路径为空时返回 `None`。越界检查留给后续的目标路径解析。
+fn strip_entry_components(name: &str, count: u32) -> Option<String> {
+    if count == 0 {
+        return Some(name.to_string());
+    }
+    let remaining = name
+        .split('/')
+        .filter(|component| !component.is_empty() && *component != ".")
+        .skip(count as usize)
+        .collect::<Vec<_>>();
+    (!remaining.is_empty()).then(|| remaining.join("/"))
+}
+
+fn is_dedupe_entry<R: Read>(entry: &mut tar::Entry<R>) -> bool {
+    entry
+        .pax_extensions()
+        .ok()
+        .flatten()
+        .is_some_and(|mut extensions| {
+            extensions.any(|extension| {
+                extension.is_ok_and(|extension| extension.key() == Ok(DEDUPE_PAX_KEY))
+            })
+        })
+}
+
+// 还原硬链接条目；目标位置已有文件或链接时不覆盖，返回 `false` 由调用方记为跳过。
+// 只有文件系统不支持硬链接或跨设备时才退回复制，复制同样只写入新文件，不会经由符号链接写到别处。
+fn restore_hard_link(source: &Path, target: &Path, copy: bool) -> Result<bool, String> {
+    if fs::symlink_metadata(target).is_ok() {
+        return Ok(false);
+    }
+    if !copy {
+        match fs::hard_link(source, target) {
+            Ok(()) => return Ok(true),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => return Ok(false),
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    io::ErrorKind::Unsupported | io::ErrorKind::CrossesDevices
+                ) => {}
+            Err(err) => return Err(err.to_string()),
+        }
+    }
+
+    let mut reader = File::open(source).map_err(|err| err.to_string())?;
+    let mut writer = match OpenOptions::new().write(true).create_new(true).open(target) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => return Ok(false),
+        Err(err) => return Err(err.to_string()),
+    };
+    io::copy(&mut reader, &mut writer).map_err(|err| err.to_string())?;
+    let permissions = reader
+        .metadata()
+        .map_err(|err| err.to_string())?
+        .permissions();
+    writer
+        .set_permissions(permissions)
+        .map_err(|err| err.to_string())?;
+    Ok(true)
+}
+
+// 把写到一半的文件改名为 `<name>.partial`；尚未写出任何内容时返回 `false`。
+fn mark_partial_entry(target: &Path) -> Result<bool, String> {
+    if !fs::symlink_metadata(target).is_ok_and(|metadata| metadata.is_file()) {
+        return Ok(false);
+    }
+    let mut partial = target.as_os_str().to_os_string();
+    partial.push(".partial");
+    fs::rename(target, &partial).map_err(|err| format!("标记不完整文件失败: {}", err))?;
+    Ok(true)
+}
+
+// 只读取不落盘：逐条计算哈希并与清单比对。
+fn verify_archive_contents<R: Read>(
+    reader: R,
+    compression: ArchiveCompression,
+) -> Result<VerifyReport, String> {
+    let mut archive = tar::Archive::new(DecompressedReader::new(reader, compression)?);
+    let mut manifest = None;
+    let mut hashes = HashMap::new();
+
+    for entry in archive.entries().map_err(|err| err.to_string())? {
+        let mut entry = entry.map_err(|err| err.to_string())?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = String::from_utf8_lossy(&entry.path_bytes()).to_string();
+        if name == MANIFEST_ENTRY_NAME {
+            manifest = Some(parse_manifest(&mut entry)?);
+            continue;
+        }
+
+        let mut hasher = Sha256::new();
+        let size = io::copy(&mut entry, &mut hasher).map_err(|err| err.to_string())?;
+        hashes.insert(
+            name,
+            ManifestEntry {
+                sha256: hex_digest(&hasher.finalize()),
+                size,
+                mtime: None,
+                mtime_nanos: None,
+            },
+        );
+    }
+
+    Ok(VerifyReport {
+        checked_files: hashes.len() as u64,
+        hash_mismatches: manifest_mismatches(manifest.as_ref(), &hashes, &[])?,
+    })
+}
+
+// 未加密归档解压后的 tar 流，读取范围不含末尾的校验块。
+type PlainTarArchive = tar::Archive<DecompressedReader<io::Take<BufReader<File>>>>;
+
+// 打开未加密的 .krate 归档，返回其头部、压缩算法与解压后的 tar 流。
+fn open_plain_archive(
+    archive_path: &Path,
+) -> Result<(ArchiveHeader, ArchiveCompression, PlainTarArchive), String> {
+    let file = File::open(archive_path).map_err(|err| err.to_string())?;
+    let file_size = file.metadata().map_err(|err| err.to_string())?.len();
+    let mut reader = BufReader::new(file);
+    let (header, compression) = read_archive_preamble(&mut reader)?;
+    if header.encryption.is_some() {
+        return Err("暂不支持向加密归档追加文件".to_string());
+    }
+    if header.volume_size.is_some() {
+        return Err("暂不支持向分卷归档追加文件".to_string());
+    }
+    let header_end = reader.stream_position().map_err(|err| err.to_string())?;
+    let payload_len = file_size.saturating_sub(header_end + header.trailer_len());
+    let decompressor = DecompressedReader::new(reader.take(payload_len), compression)?;
+    Ok((header, compression, tar::Archive::new(decompressor)))
+}
+
+// 追加前先列出已有条目，新输入的顶层名称与之重复时在写入任何数据前报错。
+fn find_append_collisions(
+    archive_path: &Path,
+    inputs: &[ArchiveInput],
+) -> Result<Vec<String>, String> {
+    let (_, _, mut archive) = open_plain_archive(archive_path)?;
+    let mut existing_roots = HashSet::new();
+    for entry in archive.entries().map_err(|err| err.to_string())? {
+        let entry = entry.map_err(|err| err.to_string())?;
+        let path = entry.path().map_err(|err| err.to_string())?;
+        if let Some(root) = path.components().next() {
+            existing_roots.insert(root.as_os_str().to_os_string());
+        }
+    }
+
+    Ok(inputs
+        .iter()
+        .filter(|input| existing_roots.contains(input.archive_root.as_os_str()))
+        .map(|input| input.archive_root.display().to_string())
+        .collect())
+}
+
+// 已有条目逐条转写：路径与链接目标重新写入，超长名称会按 GNU 扩展正确保留。
+fn copy_archive_entries<R: Read, W: Write>(
+    archive: &mut tar::Archive<R>,
+    tar: &mut tar::Builder<W>,
+    tracker: &ArchiveProgressTracker,
+) -> Result<ArchiveManifest, String> {
+    let mut manifest = ArchiveManifest::default();
+    for entry in archive.entries().map_err(|err| err.to_string())? {
+        tracker.check_cancelled()?;
+        let mut entry = entry.map_err(|err| err.to_string())?;
+        let path = entry.path().map_err(|err| err.to_string())?.into_owned();
+        let mut header = entry.header().clone();
+        let entry_type = header.entry_type();
+
+        // 旧清单不原样复制，追加完成后与新文件的哈希合并写在末尾。
+        if path == Path::new(MANIFEST_ENTRY_NAME) && entry_type.is_file() {
+            manifest = parse_manifest(&mut entry)?;
+            continue;
+        }
+
+        if entry_type.is_symlink() || entry_type.is_hard_link() {
+            let link_name = entry
+                .link_name()
+                .map_err(|err| err.to_string())?
+                .map(|link| link.into_owned())
+                .unwrap_or_default();
+            tar.append_link(&mut header, &path, link_name)
+                .map_err(|err| err.to_string())?;
+        } else {
+            tar.append_data(&mut header, &path, &mut entry)
+                .map_err(|err| err.to_string())?;
+        }
+    }
+    Ok(manifest)
+}
+
+async fn append_to_archive_impl(
+    window: Option<&Window>,
+    cancel: Arc<OperationControl>,
+    archive_path: String,
+    inputs: Vec<String>,
+) -> Result<(), String> {
+    if inputs.is_empty() {
+        return Err("请至少选择一个文件或文件夹".to_string());
+    }
+
+    let archive_inputs = build_archive_inputs(&inputs, None)?;
+    let archive_path = absolute_path(Path::new(&archive_path))?;
+    ensure_output_path_is_safe(&archive_inputs, &archive_path)?;
+
+    let collisions = find_append_collisions(&archive_path, &archive_inputs)?;
+    if !collisions.is_empty() {
+        return Err(format!("归档中已存在同名条目: {}", collisions.join(", ")));
+    }
+
+    let temp_output_path = unique_temp_output_path(&archive_path)?;
+    let result = (|| -> Result<(), String> {
+        let stats = collect_input_stats(&archive_inputs, SymlinkMode::Preserve)?;
+        let mut tracker = ArchiveProgressTracker::new(
+            "pack",
+            "正在复制已有条目",
+            stats.total_bytes,
+            cancel.clone(),
+        );
+        tracker.set_stage(window, "正在复制已有条目", "正在复制已有条目");
+
+        let (source_header, compression, mut archive) = open_plain_archive(&archive_path)?;
+        // 沿用原归档的元数据，只更新体积统计。
+        let mut header = ArchiveHeader::new_plain(compression);
+        if let Some(mut metadata) = source_header.parsed_metadata() {
+            metadata.total_bytes = metadata.total_bytes.saturating_add(stats.total_bytes);
+            metadata.total_files = metadata.total_files.saturating_add(stats.total_files);
+            header.set_metadata(&metadata)?;
+        }
+        header.set_content_size(Some(ContentSize::default()));
+        if source_header.has_checksum_trailer() {
+            header.set_checksum_trailer();
+        }
+        let mut writer =
+            VolumeWriter::create(&temp_output_path, None).map_err(|err| err.to_string())?;
+        writer.write_header(&header)?;
+
+        let threads = default_compression_threads();
+        let compressor = CompressedWriter::new(writer, compression, None, threads)?;
+        let mut tar = tar::Builder::new(CountingWriter::new(compressor));
+        tar.follow_symlinks(false);
+        let manifest = copy_archive_entries(&mut archive, &mut tar, &tracker)?;
+
+        tracker.set_stage(window, "正在追加文件", "正在追加文件");
+        let mut walker = PackWalker::new(SymlinkMode::Preserve);
+        walker.manifest = manifest;
+        walker.read_ahead = threads > 1;
+        append_inputs_to_tar(
+            &mut tar,
+            &archive_inputs,
+            &mut tracker,
+            window,
+            "正在追加文件",
+            &mut walker,
+        )?;
+
+        let counting = tar
+            .into_inner()
+            .map_err(|err| format!("Tar finish failed: {}", err))?;
+        let content_size = ContentSize {
+            tar_bytes: counting.written,
+            files: walker.manifest.files.len() as u64,
+        };
+        let writer = counting
+            .inner
+            .finish()
+            .map_err(|err| format!("Compression finish failed: {}", err))?;
+        header.record_content_size(content_size);
+        writer.finish(&header).map_err(|err| err.to_string())?;
+        patch_content_size(&temp_output_path, &header, content_size)?;
+
+        tracker.finish(window, "追加完成", "追加完成");
+        Ok(())
+    })();
+
+    if let Err(err) = result {
+        let _ = fs::remove_file(&temp_output_path);
+        return Err(cancelled_or(&cancel, err));
+    }
+
+    if let Err(err) = persist_temp_output(&temp_output_path, &archive_path) {
+        let _ = fs::remove_file(&temp_output_path);
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+// 只替换加密层：旧密钥解密出的仍是压缩后的负载，原样交给新密钥重新加密，不做解压与重新压缩。
+async fn change_archive_password_impl(
+    window: Option<&Window>,
+    cancel: Arc<OperationControl>,
+    archive_path: String,
+    old_password: String,
+    new_password: String,
+) -> Result<(), String> {
+    let old_password = normalized_password(Some(old_password)).ok_or("请输入原密码".to_string())?;
+    let new_password =
+        normalized_password(Some(new_password)).ok_or("新密码不能为空".to_string())?;
+    let archive_path = absolute_path(Path::new(&archive_path))?;
+    let output_path = volume_base_path(&archive_path).unwrap_or_else(|| archive_path.clone());
+
+    let (volume_reader, total_bytes) = open_archive_reader(&archive_path)?;
+    let mut tracker =
+        ArchiveProgressTracker::new("password", "校验原密码", total_bytes, cancel.clone());
+    tracker.set_stage(window, "校验原密码", "正在校验原密码");
+    let mut progress_reader =
+        ProgressReader::new(volume_reader, &mut tracker, window, "正在更换密码");
+
+    let (source_header, compression) = read_archive_preamble(&mut progress_reader)?;
+    let source_metadata = source_header
+        .encryption
+        .as_ref()
+        .ok_or("该归档未加密，无法修改密码".to_string())?;
+    if source_header.flags & FLAG_KEYFILE != 0 {
+        return Err("使用密钥文件加密的归档暂不支持修改密码".to_string());
+    }
+    let old_key = derive_archive_key(old_password.as_bytes(), source_metadata)?;
+    verify_key_check(&old_key, source_metadata)?;
+    let mut payload_reader = EncryptedPayloadReader::new(
+        progress_reader,
+        old_key,
+        source_metadata.stream_nonce,
+        source_header.aad_bytes(),
+    );
+    // 没有密码校验块的旧归档在这里通过首块认证确认原密码。
+    payload_reader.prime().map_err(|err| err.to_string())?;
+
+    // 新头部使用新的盐值与 nonce，保留 Argon2 参数、元数据与分卷设置。
+    let mut header = ArchiveHeader::new_encrypted(
+        compression,
+        KdfOptions {
+            memory_kib: Some(source_metadata.memory_kib),
+            iterations: Some(source_metadata.iterations),
+            parallelism: Some(source_metadata.lanes),
+        },
+    )?;
+    if let Some(metadata) = source_header.metadata.clone() {
+        header.flags |= FLAG_METADATA;
+        header.metadata = Some(metadata);
+    }
+    header.set_volume_size(source_header.volume_size);
+    header.set_content_size(source_header.content_size);
+    // 加密流开头的隐藏元数据块随负载原样复制。
+    header.set_hidden_names(source_header.hides_names());
+    if source_header.has_checksum_trailer() {
+        header.set_checksum_trailer();
+    }
+    let new_key = {
+        let metadata = header
+            .encryption
+            .as_mut()
+            .ok_or("归档加密参数缺失".to_string())?;
+        let key = derive_archive_key(new_password.as_bytes(), metadata)?;
+        metadata.key_check = Some(compute_key_check(&key, &metadata.stream_nonce)?);
+        key
+    };
+    let stream_nonce = header
+        .encryption
+        .as_ref()
+        .map(|metadata| metadata.stream_nonce)
+        .ok_or("归档加密参数缺失".to_string())?;
+
+    let temp_output_path = unique_temp_output_path(&output_path)?;
+    let result = (|| -> Result<(), String> {
+        let mut writer = VolumeWriter::create(&temp_output_path, header.volume_size)
+            .map_err(|err| err.to_string())?;
+        let aad = writer.write_header(&header)?;
+        let mut payload_writer = EncryptedPayloadWriter::new(writer, new_key, stream_nonce, aad);
+        payload_reader
+            .inner
+            .tracker
+            .set_stage(window, "正在更换密码", "正在更换密码");
+        io::copy(&mut payload_reader, &mut payload_writer).map_err(|err| err.to_string())?;
+        let writer = payload_writer.finish().map_err(|err| err.to_string())?;
+        writer.finish(&header).map_err(|err| err.to_string())
+    })();
+
+    if let Err(err) = result {
+        remove_temp_output(&temp_output_path, header.volume_size);
+        return Err(cancelled_or(&cancel, err));
+    }
+
+    if let Err(err) = persist_output(&temp_output_path, &output_path, header.volume_size) {
+        remove_temp_output(&temp_output_path, header.volume_size);
+        return Err(err);
+    }
+
+    tracker.finish(window, "密码已更换", "密码已更换");
+    Ok(())
+}
+
+// 流式解密后直接写出 gzip：负载本身就是 gzip 时原样拷贝，其他算法先解压再重新压缩。
+async fn convert_archive_impl(
+    window: Option<&Window>,
+    cancel: Arc<OperationControl>,
+    input_path: String,
+    output_path: String,
+    password: Option<String>,
+    key_file: Option<String>,
+    ignore_space_check: bool,
+) -> Result<(), String> {
+    let normalized_password = normalized_password(password);
+    let key_file = key_file
+        .map(|path| absolute_path(Path::new(&path)))
+        .transpose()?;
+    let input_path = absolute_path(Path::new(&input_path))?;
+    let output_path = absolute_path(Path::new(&output_path))?;
+    if is_7z_archive(&input_path)? {
+        return Err("7z 归档暂不支持转换".to_string());
+    }
+    if output_path == input_path
+        || volume_base_path(&input_path).as_deref() == Some(output_path.as_path())
+    {
+        return Err("输出路径不能与源归档相同".to_string());
+    }
+
+    let (volume_reader, total_bytes) = open_archive_reader(&input_path)?;
+    // 输出的 gzip 大小与源归档相近，按源归档大小估算所需空间。
+    if !ignore_space_check {
+        ensure_free_space(output_path.parent().unwrap_or(&output_path), total_bytes)?;
+    }
+    let mut tracker =
+        ArchiveProgressTracker::new("convert", "读取归档头", total_bytes, cancel.clone());
+    tracker.set_stage(window, "读取归档头", "正在读取归档头");
+    let progress_reader = ProgressReader::new(volume_reader, &mut tracker, window, "正在转换归档");
+
+    let temp_output_path = unique_temp_output_path(&output_path)?;
+    let result = with_archive_payload(
+        progress_reader,
+        normalized_password,
+        key_file.as_deref(),
+        "正在解密并转换归档",
+        "正在转换归档",
+        None,
+        |payload, compression| {
+            let output = File::create(&temp_output_path).map_err(|err| err.to_string())?;
+            let mut writer = BufWriter::new(output);
+            if compression == ArchiveCompression::Gzip {
+                io::copy(payload, &mut writer).map_err(|err| err.to_string())?;
+            } else {
+                let mut decoder = DecompressedReader::new(payload, compression)?;
+                let mut encoder = GzEncoder::new(writer, Compression::default());
+                io::copy(&mut decoder, &mut encoder).map_err(|err| err.to_string())?;
+                writer = encoder.finish().map_err(|err| err.to_string())?;
+            }
+            writer
+                .into_inner()
+                .map_err(|err| err.to_string())?
+                .sync_all()
+                .map_err(|err| err.to_string())
+        },
+    )
+    .and_then(|()| persist_temp_output(&temp_output_path, &output_path));
+
+    if let Err(err) = result {
+        let _ = fs::remove_file(&temp_output_path);
+        return Err(cancelled_or(&cancel, err));
+    }
+
+    tracker.finish(window, "转换完成", "转换完成");
+    Ok(())
+}
+
+// 数据量不超过采样预算时逐块全部采样；否则先取最大几个文件的开头，再按字节均匀随机抽块。
+// 每块单独压缩，按块压缩率的离散程度给出区间。采样总量与耗时都有上限，超大目录也能很快返回。
+fn estimate_archive_impl(
+    inputs: Vec<String>,
+    compression: ArchiveCompression,
+    level: Option<u32>,
+    sample_bytes: Option<u64>,
+) -> Result<EstimateResult, String> {
+    if inputs.is_empty() {
+        return Err("请至少选择一个文件或文件夹".to_string());
+    }
+
+    let started_at = Instant::now();
+    let archive_inputs = build_archive_inputs(&inputs, None)?;
+    let (stats, mut files) = collect_input_files(
+        &archive_inputs,
+        SymlinkMode::Preserve,
+        EntryFilter::default(),
+        true,
+    )?;
+    let budget = sample_bytes
+        .unwrap_or(DEFAULT_ESTIMATE_SAMPLE_BYTES)
+        .clamp(ESTIMATE_CHUNK_SIZE, MAX_ESTIMATE_SAMPLE_BYTES);
+    files.retain(|(_, len)| *len > 0);
+    files.sort_by_key(|(_, len)| std::cmp::Reverse(*len));
+
+    // 每个块以（文件序号，块序号）表示。
+    let file_ends = files
+        .iter()
+        .scan(0u64, |end, (_, len)| {
+            *end += len;
+            Some(*end)
+        })
+        .collect::<Vec<_>>();
+    let data_bytes = file_ends.last().copied().unwrap_or(0);
+    let mut chunks = if data_bytes <= budget {
+        files
+            .iter()
+            .enumerate()
+            .flat_map(|(index, (_, len))| {
+                (0..len.div_ceil(ESTIMATE_CHUNK_SIZE)).map(move |chunk| (index, chunk))
+            })
+            .collect::<Vec<_>>()
+    } else {
+        let max_chunks = (budget / ESTIMATE_CHUNK_SIZE) as usize;
+        let mut picked = (0..files.len().min(max_chunks.div_ceil(2)))
+            .map(|index| (index, 0))
+            .collect::<HashSet<_>>();
+        let mut seed = u64::from_le_bytes(random_bytes()?) | 1;
+        for _ in 0..max_chunks * 4 {
+            if picked.len() >= max_chunks {
+                break;
+            }
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            let position = seed % data_bytes;
+            let index = file_ends.partition_point(|end| *end <= position);
+            let start = index
+                .checked_sub(1)
+                .map(|previous| file_ends[previous])
+                .unwrap_or(0);
+            picked.insert((index, (position - start) / ESTIMATE_CHUNK_SIZE));
+        }
+        picked.into_iter().collect()
+    };
+    chunks.sort_unstable();
+
+    let mut samples = Vec::new();
+    for (index, chunk) in chunks {
+        if started_at.elapsed() >= ESTIMATE_TIME_BUDGET {
+            break;
+        }
+        let (path, len) = &files[index];
+        let offset = chunk * ESTIMATE_CHUNK_SIZE;
+        let chunk_len = (len - offset).min(ESTIMATE_CHUNK_SIZE);
+        if let Some(sample) = sample_compressed_size(path, offset, chunk_len, compression, level)? {
+            samples.push(sample);
+        }
+    }
+
+    let sampled_bytes = samples.iter().map(|(raw, _)| raw).sum::<u64>();
+    let compressed_bytes = samples
+        .iter()
+        .map(|(_, compressed)| compressed)
+        .sum::<u64>();
+    let ratio = if sampled_bytes > 0 {
+        compressed_bytes as f64 / sampled_bytes as f64
+    } else {
+        1.0
+    };
+    let margin = if samples.len() > 1 && sampled_bytes < data_bytes {
+        let variance = samples
+            .iter()
+            .map(|(raw, compressed)| {
+                *raw as f64 * (*compressed as f64 / *raw as f64 - ratio).powi(2)
+            })
+            .sum::<f64>()
+            / sampled_bytes as f64;
+        let unsampled = 1.0 - sampled_bytes as f64 / data_bytes as f64;
+        2.0 * (variance / samples.len() as f64).sqrt() * unsampled
+    } else {
+        0.0
+    };
+
+    let tar_bytes = stats.total_bytes as f64
+        + stats
+            .total_files
+            .saturating_mul(ESTIMATE_TAR_OVERHEAD_PER_FILE) as f64;
+    Ok(EstimateResult {
+        total_files: stats.total_files,
+        total_bytes: stats.total_bytes,
+        sampled_bytes,
+        estimated_bytes: (tar_bytes * ratio).round() as u64,
+        estimated_low_bytes: (tar_bytes * (ratio - margin).max(0.0)).round() as u64,
+        estimated_high_bytes: (tar_bytes * (ratio + margin)).round() as u64,
+        sampling_ms: started_at.elapsed().as_millis() as u64,
+    })
+}
+
+// 读取文件中的一块并单独压缩，返回（原始字节数，压缩后字节数）。
+fn sample_compressed_size(
+    path: &Path,
+    offset: u64,
+    len: u64,
+    compression: ArchiveCompression,
+    level: Option<u32>,
+) -> Result<Option<(u64, u64)>, String> {
+    let mut file = File::open(path).map_err(|err| err.to_string())?;
+    file.seek(SeekFrom::Start(offset))
+        .map_err(|err| err.to_string())?;
+    let mut chunk = Vec::with_capacity(len as usize);
+    file.take(len)
+        .read_to_end(&mut chunk)
+        .map_err(|err| err.to_string())?;
+    if chunk.is_empty() {
+        return Ok(None);
+    }
+
+    let mut compressor = CompressedWriter::new(Vec::new(), compression, level, 1)?;
+    compressor
+        .write_all(&chunk)
+        .map_err(|err| err.to_string())?;
+    let compressed = compressor.finish().map_err(|err| err.to_string())?;
+    Ok(Some((chunk.len() as u64, compressed.len() as u64)))
+}
+
+// 样本只读取一次并在各等级间复用，测得的差异只来自压缩本身，而不是磁盘缓存。
+fn benchmark_compression_impl(
+    window: Option<&Window>,
+    cancel: Arc<OperationControl>,
+    sample_path: String,
+    compression: ArchiveCompression,
+    levels: Vec<u32>,
+) -> Result<Vec<BenchmarkResult>, String> {
+    if levels.is_empty() {
+        return Err("请至少选择一个压缩等级".to_string());
+    }
+    if let Some(level) = levels.iter().find(|level| **level > MAX_COMPRESSION_LEVEL) {
+        return Err(format!("压缩等级必须在 0–9 之间: {}", level));
+    }
+    match compression {
+        ArchiveCompression::Gzip | ArchiveCompression::Xz => {}
+        ArchiveCompression::Zstd => return Err("zstd 暂不支持自定义压缩等级".to_string()),
+        ArchiveCompression::None => return Err("不压缩时无需测试压缩等级".to_string()),
+    }
+
+    let mut tracker = ArchiveProgressTracker::new("benchmark", "读取样本", 0, cancel);
+    tracker.set_stage(window, "读取样本", "正在读取样本数据");
+    let sample = read_benchmark_sample(&sample_path, &mut tracker)?;
+    if sample.is_empty() {
+        return Err("样本数据为空".to_string());
+    }
+
+    tracker.reset_total(sample.len() as u64 * levels.len() as u64);
+    tracker.set_stage(window, "压缩测试", "正在测试压缩等级");
+    let mut results = Vec::with_capacity(levels.len());
+    for level in levels {
+        let started_at = Instant::now();
+        let mut compressor = CompressedWriter::new(Vec::new(), compression, Some(level), 1)?;
+        for chunk in sample.chunks(BENCHMARK_CHUNK_SIZE) {
+            tracker.wait_while_paused(window, "正在测试压缩等级");
+            tracker.check_cancelled()?;
+            compressor.write_all(chunk).map_err(|err| err.to_string())?;
+            tracker.advance_bytes(window, chunk.len() as u64, "正在测试压缩等级");
+        }
+        let compressed = compressor.finish().map_err(|err| err.to_string())?;
+        let elapsed = started_at.elapsed().as_secs_f64().max(f64::EPSILON);
+        results.push(BenchmarkResult {
+            level,
+            sample_bytes: sample.len() as u64,
+            compressed_bytes: compressed.len() as u64,
+            ratio: compressed.len() as f64 / sample.len() as f64,
+            megabytes_per_second: sample.len() as f64 / (1024.0 * 1024.0) / elapsed,
+            elapsed_ms: (elapsed * 1000.0).round() as u64,
+        });
+    }
+
+    tracker.finish(window, "测试完成", "压缩测试完成");
+    Ok(results)
+}
+
+// 按打包时的遍历顺序依次读取文件，直到样本达到上限。
+fn read_benchmark_sample(
+    sample_path: &str,
+    tracker: &mut ArchiveProgressTracker,
+) -> Result<Vec<u8>, String> {
+    let archive_inputs = build_archive_inputs(&[sample_path.to_string()], None)?;
+    let (_, files) = collect_input_files(
+        &archive_inputs,
+        SymlinkMode::Preserve,
+        EntryFilter::default(),
+        true,
+    )?;
+
+    let mut sample = Vec::new();
+    for (path, _) in files {
+        let remaining = MAX_BENCHMARK_SAMPLE_BYTES - sample.len() as u64;
+        if remaining == 0 {
+            break;
+        }
+        tracker.check_cancelled()?;
+        File::open(&path)
+            .and_then(|file| file.take(remaining).read_to_end(&mut sample))
+            .map_err(|err| format!("读取样本失败 {}: {}", path.display(), err))?;
+    }
+    Ok(sample)
+}
+
+fn read_archive_entry_impl(
+    archive_path: String,
+    entry_path: String,
+    password: Option<String>,
+    key_file: Option<String>,
+    max_bytes: Option<u64>,
+) -> Result<EntryPreview, String> {
+    let max_bytes = max_bytes
+        .unwrap_or(DEFAULT_PREVIEW_BYTES)
+        .min(MAX_PREVIEW_BYTES);
+    let key_file = key_file
+        .map(|path| absolute_path(Path::new(&path)))
+        .transpose()?;
+    let archive_path = absolute_path(Path::new(&archive_path))?;
+    if is_7z_archive(&archive_path)? {
+        return Err("暂不支持预览 7z 归档中的文件".to_string());
+    }
+    if let Some(compression) = detect_tarball(&archive_path)? {
+        let file = File::open(&archive_path).map_err(|err| err.to_string())?;
+        return preview_entry(file, compression, &entry_path, max_bytes);
+    }
+
+    let (volume_reader, total_bytes) = open_archive_reader(&archive_path)?;
+    let mut tracker =
+        ArchiveProgressTracker::new("preview", "读取归档头", total_bytes, Arc::default());
+    let progress_reader = ProgressReader::new(volume_reader, &mut tracker, None, "正在读取归档");
+    with_archive_payload(
+        progress_reader,
+        normalized_password(password),
+        key_file.as_deref(),
+        "正在读取归档",
+        "正在读取归档",
+        None,
+        |payload, compression| preview_entry(payload, compression, &entry_path, max_bytes),
+    )
+}
+
+fn normalized_entry_name(name: &str) -> String {
+    name.replace('\\', "/")
+        .trim_start_matches("./")
+        .trim_matches('/')
+        .to_string()
+}
+
+// 找到目标条目后立即返回，只解密、解压到该条目为止。
+fn preview_entry<R: Read>(
+    reader: R,
+    compression: ArchiveCompression,
+    entry_path: &str,
+    max_bytes: u64,
+) -> Result<EntryPreview, String> {
+    let wanted = normalized_entry_name(entry_path);
+    let mut archive = tar::Archive::new(DecompressedReader::new(reader, compression)?);
+    let mut names = Vec::new();
+
+    for entry in archive.entries().map_err(|err| err.to_string())? {
+        let entry = entry.map_err(|err| err.to_string())?;
+        let name = normalized_entry_name(&String::from_utf8_lossy(&entry.path_bytes()));
+        if name != wanted {
+            names.push(name);
+            continue;
+        }
+
+        if !entry.header().entry_type().is_file() {
+            return Err(format!("该条目不是普通文件，无法预览: {}", name));
+        }
+        let size = entry.header().size().map_err(|err| err.to_string())?;
+        let mut data = Vec::with_capacity(size.min(max_bytes) as usize);
+        entry
+            .take(max_bytes)
+            .read_to_end(&mut data)
+            .map_err(|err| err.to_string())?;
+
+        return Ok(EntryPreview {
+            mime_type: detect_mime_type(&name, &data),
+            path: name,
+            size,
+            truncated: size > data.len() as u64,
+            data: base64::engine::general_purpose::STANDARD.encode(&data),
+        });
+    }
+
+    let suggestions = similar_entry_names(&wanted, &names);
+    if suggestions.is_empty() {
+        Err(format!("归档中不存在条目: {}", wanted))
+    } else {
+        Err(format!(
+            "归档中不存在条目: {}，相近的条目: {}",
+            wanted,
+            suggestions.join(", ")
+        ))
+    }
+}
+
+impl EntryMatcher {
+    fn new(pattern: &str, case_sensitive: bool) -> Result<Self, String> {
+        let pattern = pattern.trim();
+        if pattern.is_empty() {
+            return Err("请输入搜索内容".to_string());
+        }
+        let pattern = if case_sensitive {
+            pattern.to_string()
+        } else {
+            pattern.to_lowercase()
+        };
+        Ok(Self {
+            glob: pattern.contains(['*', '?']),
+            full_path: pattern.contains('/'),
+            pattern,
+            case_sensitive,
+        })
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        let path = if self.case_sensitive {
+            path.to_string()
+        } else {
+            path.to_lowercase()
+        };
+        if !self.glob {
+            return path.contains(&self.pattern);
+        }
+        let subject = if self.full_path {
+            path.as_str()
+        } else {
+            path.rsplit('/').next().unwrap_or(&path)
+        };
+        glob_matches(
+            &self.pattern.chars().collect::<Vec<_>>(),
+            &subject.chars().collect::<Vec<_>>(),
+        )
+    }
+}
+
+// `*` 匹配任意多个字符（含 `/`），`?` 匹配单个字符；遇到不匹配时回退到最近一个 `*` 多吞一个字符。
+fn glob_matches(pattern: &[char], text: &[char]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p + 1, t));
+                p += 1;
+            }
+            Some(ch) if *ch == '?' || *ch == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_t)) => {
+                    p = star_p;
+                    t = star_t + 1;
+                    backtrack = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|ch| *ch == '*')
+}
+
+// 依次搜索各归档，命中总数达到 `limit` 后停止；单个归档失败只记入跳过列表，取消则整体结束。
+fn search_archives_impl(
+    window: Option<&Window>,
+    cancel: Arc<OperationControl>,
+    paths: Vec<String>,
+    matcher: &EntryMatcher,
+    password: Option<String>,
+    key_file: Option<String>,
+    limit: usize,
+) -> Result<ArchiveSearchReport, String> {
+    if paths.is_empty() {
+        return Err("请至少选择一个归档".to_string());
+    }
+    let key_file = key_file
+        .map(|path| absolute_path(Path::new(&path)))
+        .transpose()?;
+    let archive_paths = paths
+        .iter()
+        .map(|path| absolute_path(Path::new(path)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let sizes = archive_paths
+        .iter()
+        .map(|path| {
+            archive_volumes(path)
+                .map(|parts| volumes_size(&parts))
+                .unwrap_or(0)
+        })
+        .collect::<Vec<_>>();
+    let mut tracker =
+        ArchiveProgressTracker::new("search", "搜索归档", sizes.iter().sum(), cancel.clone());
+    tracker.set_stage(window, "搜索归档", "正在搜索归档");
+
+    let mut report = ArchiveSearchReport::default();
+    for (archive_path, size) in archive_paths.iter().zip(sizes) {
+        if report.matches.len() >= limit {
+            report.truncated = true;
+            break;
+        }
+        let display_path = archive_path.to_string_lossy().to_string();
+        tracker.set_current_path(window, Some(display_path.clone()), "正在搜索归档");
+        let processed_before = tracker.processed_bytes;
+        let result = search_one_archive(
+            archive_path,
+            matcher,
+            normalized_password(password.clone()),
+            key_file.as_deref(),
+            &mut tracker,
+            window,
+            limit - report.matches.len(),
+        );
+        tracker.check_cancelled()?;
+        match result {
+            Ok(matches) => report.matches.extend(matches),
+            Err(reason) => report.skipped_archives.push(SkippedArchive {
+                archive_path: display_path,
+                reason,
+            }),
+        }
+        // 提前停止时补齐本归档剩余的字节，进度按归档推进。
+        let read = tracker.processed_bytes - processed_before;
+        tracker.advance_bytes(window, size.saturating_sub(read), "正在搜索归档");
+    }
+    report.truncated |= report.matches.len() >= limit;
+
+    tracker.finish(window, "搜索完成", "搜索完成");
+    Ok(report)
+}
+
+fn volumes_size(parts: &[PathBuf]) -> u64 {
+    parts
+        .iter()
+        .filter_map(|part| fs::metadata(part).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+fn search_one_archive(
+    archive_path: &Path,
+    matcher: &EntryMatcher,
+    password: Option<String>,
+    key_file: Option<&Path>,
+    tracker: &mut ArchiveProgressTracker,
+    window: Option<&Window>,
+    limit: usize,
+) -> Result<Vec<ArchiveEntryInfo>, String> {
+    if is_7z_archive(archive_path)? || is_zip_archive(archive_path)? {
+        return Err("暂不支持搜索 ZIP 与 7z 归档".to_string());
+    }
+    if let Some(compression) = detect_tarball(archive_path)? {
+        let file = File::open(archive_path).map_err(|err| err.to_string())?;
+        let progress_reader = ProgressReader::new(file, tracker, window, "正在搜索归档");
+        return search_entries(progress_reader, compression, archive_path, matcher, limit);
+    }
+
+    let file = File::open(archive_path).map_err(|err| err.to_string())?;
+    let (header, _) = read_archive_preamble(&mut BufReader::new(file))?;
+    if header.encryption.is_some() && password.is_none() && key_file.is_none() {
+        return Err(SEARCH_PASSWORD_REQUIRED.to_string());
+    }
+    let (volume_reader, _) = open_archive_reader(archive_path)?;
+    let progress_reader = ProgressReader::new(volume_reader, tracker, window, "正在搜索归档");
+    with_archive_payload(
+        progress_reader,
+        password,
+        key_file,
+        "正在搜索归档",
+        "正在搜索归档",
+        None,
+        |payload, compression| search_entries(payload, compression, archive_path, matcher, limit),
+    )
+}
+
+// 只读取 tar 头部，条目数据由 tar 跳过；命中数达到上限即停止读取。
+fn search_entries<R: Read>(
+    reader: R,
+    compression: ArchiveCompression,
+    archive_path: &Path,
+    matcher: &EntryMatcher,
+    limit: usize,
+) -> Result<Vec<ArchiveEntryInfo>, String> {
+    let mut archive = tar::Archive::new(DecompressedReader::new(reader, compression)?);
+    let mut matches = Vec::new();
+    for entry in archive.entries().map_err(|err| err.to_string())? {
+        if matches.len() >= limit {
+            break;
+        }
+        let entry = entry.map_err(|err| err.to_string())?;
+        let path = normalized_entry_name(&String::from_utf8_lossy(&entry.path_bytes()));
+        if path == MANIFEST_ENTRY_NAME || !matcher.matches(&path) {
+            continue;
+        }
+        let header = entry.header();
+        let entry_type = header.entry_type();
+        let kind = if entry_type.is_file() {
+            "file"
+        } else if entry_type.is_dir() {
+            "directory"
+        } else if entry_type.is_symlink() {
+            "symlink"
+        } else if entry_type.is_hard_link() {
+            "hardlink"
+        } else {
+            "other"
+        };
+        matches.push(ArchiveEntryInfo {
+            archive_path: archive_path.to_string_lossy().to_string(),
+            path,
+            kind,
+            size: header.size().unwrap_or(0),
+            mtime: header.mtime().ok(),
+        });
+    }
+    Ok(matches)
+}
+
+// 编辑距离足够小，或文件名相同（目录写错）的条目视为相近。
+fn similar_entry_names(wanted: &str, names: &[String]) -> Vec<String> {
+    let wanted_file_name = wanted.rsplit('/').next().unwrap_or(wanted);
+    let threshold = (wanted.chars().count() / 3).max(2);
+    let mut candidates = names
+        .iter()
+        .filter_map(|name| {
+            let distance = edit_distance(wanted, name);
+            let same_file_name = name.rsplit('/').next() == Some(wanted_file_name);
+            (distance <= threshold || same_file_name).then_some((distance, name))
+        })
+        .collect::<Vec<_>>();
+    candidates.sort();
+    candidates
+        .into_iter()
+        .take(PREVIEW_SUGGESTION_LIMIT)
+        .map(|(_, name)| name.clone())
+        .collect()
+}
+
+fn edit_distance(left: &str, right: &str) -> usize {
+    let right = right.chars().collect::<Vec<_>>();
+    let mut previous = (0..=right.len()).collect::<Vec<_>>();
+    for (i, left_char) in left.chars().enumerate() {
+        let mut current = vec![i + 1; right.len() + 1];
+        for (j, right_char) in right.iter().enumerate() {
+            let substitution = previous[j] + usize::from(left_char != *right_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[right.len()]
+}
+
+// 优先按文件头识别常见格式，其余按扩展名判断；无法识别的有效 UTF-8 视为纯文本。
+fn detect_mime_type(name: &str, data: &[u8]) -> &'static str {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+    ];
+    if let Some((_, mime)) = SIGNATURES
+        .iter()
+        .find(|(signature, _)| data.starts_with(signature))
+    {
+        return mime;
+    }
+    if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        return "image/webp";
+    }
+
+    let extension = Path::new(name)
+        .extension()
+        .and_then(OsStr::to_str)
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("json") => "application/json",
+        Some("svg") => "image/svg+xml",
+        Some("html" | "htm") => "text/html",
+        Some("md") => "text/markdown",
+        Some("csv") => "text/csv",
+        // 截断可能切在多字节字符中间，只要求前缀是合法 UTF-8。
+        _ if std::str::from_utf8(data)
+            .map(|_| true)
+            .unwrap_or_else(|err| err.error_len().is_none()) =>
+        {
+            "text/plain"
+        }
+        _ => "application/octet-stream",
+    }
+}
+
+fn archive_info(
+    archive_path: &Path,
+    password: Option<String>,
+    key_file: Option<&Path>,
+) -> Result<ArchiveInfo, String> {
+    let file = File::open(archive_path).map_err(|err| err.to_string())?;
+    let file_size = file.metadata().map_err(|err| err.to_string())?.len();
+    let mut reader = BufReader::new(file);
+    let (mut header, compression) = read_archive_preamble(&mut reader)?;
+
+    if header.hides_names() {
+        header.metadata = Some(read_archive_hidden_metadata(
+            archive_path,
+            password,
+            key_file,
+        )?);
+    }
+
+    Ok(ArchiveInfo {
+        version: String::from_utf8_lossy(&header.version).to_string(),
+        flags: header.flags,
+        compression,
+        encrypted: header.encryption.is_some(),
+        metadata: header.parsed_metadata(),
+        file_size,
+        volume_size: header.volume_size,
+        content_bytes: header.content_size.map(|content| content.tar_bytes),
+        content_files: header.content_size.map(|content| content.files),
+        hidden_names: header.hides_names(),
+        has_checksum: header.has_checksum_trailer(),
+        checksum_matches: archive_checksum_matches(archive_path)?,
+    })
+}
+
+// 解密加密流开头的元数据块；只需读取第一个加密块，不解压负载。
+fn read_archive_hidden_metadata(
+    archive_path: &Path,
+    password: Option<String>,
+    key_file: Option<&Path>,
+) -> Result<Vec<u8>, String> {
+    let password = normalized_password(password);
+    if password.is_none() && key_file.is_none() {
+        return Err(HIDDEN_NAMES_ERROR.to_string());
+    }
+
+    let (mut reader, _) = open_archive_reader(archive_path)?;
+    let (header, _) = read_archive_preamble(&mut reader)?;
+    let metadata = header
+        .encryption
+        .as_ref()
+        .ok_or("归档加密参数缺失".to_string())?;
+    let key = ArchiveSecret::for_header(password, key_file, header.flags)?.unlock(metadata)?;
+    let mut payload_reader =
+        EncryptedPayloadReader::new(reader, key, metadata.stream_nonce, header.aad_bytes());
+    read_hidden_metadata(&mut payload_reader)
+}
+
+// 取消后底层错误信息各异（tar/gzip 会包装 IO 错误），统一替换为取消错误。
+fn cancelled_or(cancel: &OperationControl, err: String) -> String {
+    if cancel.is_cancelled() {
+        ARCHIVE_CANCELLED_ERROR.to_string()
+    } else {
+        err
+    }
+}
+
+async fn create_archive_impl(
+    window: Option<&Window>,
+    cancel: Arc<OperationControl>,
+    inputs: Vec<String>,
+    output_path: String,
+    password: Option<String>,
+    key_file: Option<String>,
+    options: CreateOptions,
+) -> Result<CreateReport, String> {
+    if inputs.is_empty() {
+        return Err("请至少选择一个文件或文件夹".to_string());
+    }
+
+    let archive_inputs = build_archive_inputs(&inputs, options.top_level_names.as_deref())?;
+    let mut report = pack_archive_inputs(
+        window,
+        cancel,
+        &archive_inputs,
+        output_path,
+        password,
+        key_file,
+        options,
+    )
+    .await?;
+    report.input_mapping = inputs
+        .iter()
+        .zip(&archive_inputs)
+        .map(|(input, archive_input)| InputMapping {
+            input: input.clone(),
+            archive_name: archive_input.archive_root.display().to_string(),
+        })
+        .collect();
+    Ok(report)
+}
+
+// 按调用方给定的归档内路径打包，不经过顶层名称的自动命名。
+async fn create_archive_from_entries_impl(
+    window: Option<&Window>,
+    cancel: Arc<OperationControl>,
+    entries: Vec<EntrySpec>,
+    output_path: String,
+    password: Option<String>,
+    key_file: Option<String>,
+    options: CreateOptions,
+) -> Result<CreateReport, String> {
+    if entries.is_empty() {
+        return Err("请至少选择一个文件".to_string());
+    }
+
+    let archive_inputs = build_entry_inputs(&entries, options.expand_directories)?;
+    let mut report = pack_archive_inputs(
+        window,
+        cancel,
+        &archive_inputs,
+        output_path,
+        password,
+        key_file,
+        options,
+    )
+    .await?;
+    report.input_mapping = entries
+        .into_iter()
+        .zip(&archive_inputs)
+        .map(|(entry, archive_input)| InputMapping {
+            input: entry.src_path,
+            archive_name: manifest_key(&archive_input.archive_root),
+        })
+        .collect();
+    Ok(report)
+}
+
+async fn pack_archive_inputs(
+    window: Option<&Window>,
+    cancel: Arc<OperationControl>,
+    archive_inputs: &[ArchiveInput],
+    output_path: String,
+    password: Option<String>,
+    key_file: Option<String>,
+    options: CreateOptions,
+) -> Result<CreateReport, String> {
+    let volume_size = options.volume_size;
+    if volume_size.is_some_and(|size| size < MIN_VOLUME_SIZE) {
+        return Err("分卷大小不能小于 1 MiB".to_string());
+    }
+    ensure_password_strength(password.as_deref(), options.min_password_score)?;
+
+    let output_path = absolute_path(Path::new(&output_path))?;
+    ensure_output_path_is_safe(archive_inputs, &output_path)?;
+    let temp_output_path = unique_temp_output_path(&output_path)?;
+
+    let result = (|| -> Result<(CreateReport, Option<ArchiveManifest>), String> {
+        let CreateOptions {
+            level,
+            compression,
+            symlinks,
+            comment,
+            volume_size,
+            raw_key,
+            kdf,
+            threads,
+            incremental,
+            skip_errors,
+            hide_file_names,
+            dedupe,
+            filter,
+            top_level_names: _,
+            expand_directories: _,
+            min_password_score: _,
+            checksum_trailer,
+        } = options;
+        let threads = threads
+            .unwrap_or_else(default_compression_threads)
+            .clamp(1, MAX_COMPRESSION_THREADS);
+        let mut walker = PackWalker::new(symlinks);
+        walker.read_ahead = threads > 1;
+        walker.incremental = incremental
+            .as_ref()
+            .map(IncrementalState::load)
+            .transpose()?;
+        walker.skip_errors = skip_errors;
+        filter.validate()?;
+        walker.filter = filter;
+        let (stats, files) = collect_input_files(archive_inputs, symlinks, filter, dedupe)?;
+        if filter.is_active() && stats.total_files == 0 {
+            return Err("没有可打包的文件".to_string());
+        }
+        let mut tracker =
+            ArchiveProgressTracker::new("pack", "准备归档", stats.total_bytes, cancel.clone());
+        tracker.set_stage(window, "准备归档", "正在准备归档");
+        if dedupe {
+            tracker.set_stage(window, "查找重复文件", "正在查找重复文件");
+            let (plan, duplicate_bytes) = plan_dedupe(&files, &tracker)?;
+            tracker.reset_total(stats.total_bytes - duplicate_bytes);
+            walker.dedupe = Some(plan);
+        }
+
+        let key_file = key_file
+            .map(|path| absolute_path(Path::new(&path)))
+            .transpose()?;
+        let secret =
+            ArchiveSecret::for_create(normalized_password(password), key_file.as_deref(), raw_key)?;
+        if hide_file_names && secret.is_none() {
+            return Err("隐藏文件列表需要设置密码或密钥文件".to_string());
+        }
+        let mut header = if let Some(secret) = secret.as_ref() {
+            let mut header = ArchiveHeader::new_encrypted(compression, kdf)?;
+            header.flags |= secret.flags();
+            header
+        } else {
+            ArchiveHeader::new_plain(compression)
+        };
+        let metadata = ArchiveMetadata {
+            comment: comment.filter(|comment| !comment.is_empty()),
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or(0),
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            total_bytes: stats.total_bytes,
+            total_files: stats.total_files,
+        };
+        let hidden_metadata = if hide_file_names {
+            header.set_hidden_names(true);
+            Some(encode_metadata(&metadata)?)
+        } else {
+            header.set_metadata(&metadata)?;
+            // 明文内容大小会暴露文件数，隐藏文件列表时不记录。
+            header.set_content_size(Some(ContentSize::default()));
+            None
+        };
+        header.set_volume_size(volume_size);
+        if checksum_trailer {
+            header.set_checksum_trailer();
+        }
+        // 先派生密钥，以便把密码校验块写进头部。
+        let key = match (secret.as_ref(), header.encryption.as_mut()) {
+            (Some(secret), Some(metadata)) => {
+                let key = secret.derive_key(metadata)?;
+                metadata.key_check = Some(compute_key_check(&key, &metadata.stream_nonce)?);
+                Some(key)
+            }
+            _ => None,
+        };
+        let progress_message = compression.progress_message(secret.is_some());
+
+        let mut writer =
+            VolumeWriter::create(&temp_output_path, volume_size).map_err(|err| err.to_string())?;
+        let aad = writer.write_header(&header)?;
+
+        tracker.set_stage(window, progress_message, progress_message);
+
+        if let (Some(key), Some(metadata)) = (key, header.encryption.as_ref()) {
+            let mut payload_writer =
+                EncryptedPayloadWriter::new(writer, key, metadata.stream_nonce, aad);
+            if let Some(hidden_metadata) = hidden_metadata.as_ref() {
+                write_hidden_metadata(&mut payload_writer, hidden_metadata)?;
+            }
+            let compressor = CompressedWriter::new(payload_writer, compression, level, threads)?;
+            let mut tar = tar::Builder::new(CountingWriter::new(compressor));
+            tar.follow_symlinks(false);
+
+            append_inputs_to_tar(
+                &mut tar,
+                archive_inputs,
+                &mut tracker,
+                window,
+                progress_message,
+                &mut walker,
+            )?;
+
+            let counting = tar
+                .into_inner()
+                .map_err(|err| format!("Tar finish failed: {}", err))?;
+            let content_size = ContentSize {
+                tar_bytes: counting.written,
+                files: walker.manifest.files.len() as u64,
+            };
+            let payload_writer = counting
+                .inner
+                .finish()
+                .map_err(|err| format!("Compression finish failed: {}", err))?;
+            let writer = payload_writer.finish().map_err(|err| err.to_string())?;
+            header.record_content_size(content_size);
+            writer.finish(&header).map_err(|err| err.to_string())?;
+            patch_content_size(
+                &part_path(&temp_output_path, volume_size, 1),
+                &header,
+                content_size,
+            )?;
+            tracker.finish(window, "归档完成", "归档完成");
+            return Ok(walker.finish());
+        }
+
+        let compressor = CompressedWriter::new(writer, compression, level, threads)?;
+        let mut tar = tar::Builder::new(CountingWriter::new(compressor));
+        tar.follow_symlinks(false);
+
+        append_inputs_to_tar(
+            &mut tar,
+            archive_inputs,
+            &mut tracker,
+            window,
+            progress_message,
+            &mut walker,
+        )?;
+
+        let counting = tar
+            .into_inner()
+            .map_err(|err| format!("Tar finish failed: {}", err))?;
+        let content_size = ContentSize {
+            tar_bytes: counting.written,
+            files: walker.manifest.files.len() as u64,
+        };
+        let writer = counting
+            .inner
+            .finish()
+            .map_err(|err| format!("Compression finish failed: {}", err))?;
+        header.record_content_size(content_size);
+        writer.finish(&header).map_err(|err| err.to_string())?;
+        patch_content_size(
+            &part_path(&temp_output_path, volume_size, 1),
+            &header,
+            content_size,
+        )?;
+
+        tracker.finish(window, "归档完成", "归档完成");
+        Ok(walker.finish())
+    })();
+
+    let (report, snapshot) = match result {
+        Ok(result) => result,
+        Err(err) => {
+            remove_temp_output(&temp_output_path, volume_size);
+            return Err(cancelled_or(&cancel, err));
+        }
+    };
+
+    if let Err(err) = persist_output(&temp_output_path, &output_path, volume_size) {
+        remove_temp_output(&temp_output_path, volume_size);
+        return Err(err);
+    }
+    if let Some(snapshot) = snapshot {
+        write_incremental_manifest(&output_path, &snapshot)?;
+    }
+
+    Ok(report)
+}
+
+async fn extract_archive_impl(
+    window: Option<&Window>,
+    cancel: Arc<OperationControl>,
+    archive_path: String,
+    output_dir: String,
+    password: Option<String>,
+    key_file: Option<String>,
+    options: ExtractOptions,
+) -> Result<ExtractReport, String> {
+    extract_archive_inner(
+        window,
+        cancel.clone(),
+        archive_path,
+        output_dir,
+        password,
+        key_file,
+        options,
+    )
+    .await
+    .map_err(|err| cancelled_or(&cancel, err))
+}
+
+async fn extract_archive_inner(
+    window: Option<&Window>,
+    cancel: Arc<OperationControl>,
+    archive_path: String,
+    output_dir: String,
+    password: Option<String>,
+    key_file: Option<String>,
+    options: ExtractOptions,
+) -> Result<ExtractReport, String> {
+    let normalized_password = normalized_password(password);
+    let key_file = key_file
+        .map(|path| absolute_path(Path::new(&path)))
+        .transpose()?;
+    let archive_path = absolute_path(Path::new(&archive_path))?;
+    let output_parent = absolute_path(Path::new(&output_dir))?;
+    let root_name_path = volume_base_path(&archive_path).unwrap_or_else(|| archive_path.clone());
+    let extract_root = prepare_extract_output_dir(&root_name_path, &output_parent)?;
+
+    let foreign_archive = is_7z_archive(&archive_path)? || is_zip_archive(&archive_path)?;
+    if foreign_archive && options.strip_components > 0 {
+        return Err("ZIP 与 7z 归档暂不支持去除路径层级".to_string());
+    }
+    if is_7z_archive(&archive_path)? {
+        let skipped_entries = extract_7z_archive(
+            window,
+            cancel,
+            &archive_path,
+            &extract_root,
+            normalized_password.as_deref(),
+            options.strict_security,
+        )?;
+        return Ok(ExtractReport {
+            output_dir: extract_root.to_string_lossy().to_string(),
+            skipped_entries,
+            hash_mismatches: Vec::new(),
+            password_required_entries: Vec::new(),
+            salvage: None,
+            renamed_entries: Vec::new(),
+            space_check_skipped: false,
+        });
+    }
+    if is_zip_archive(&archive_path)? {
+        return extract_zip_archive(
+            window,
+            cancel,
+            &archive_path,
+            &extract_root,
+            normalized_password.as_deref(),
+            options.strict_security,
+        );
+    }
+    if let Some(compression) = detect_tarball(&archive_path)? {
+        return extract_tarball(
+            window,
+            cancel,
+            &archive_path,
+            compression,
+            &extract_root,
+            options,
+        );
+    }
+
+    // 头部记录了解压后的 tar 大小时先检查磁盘空间；旧归档与隐藏文件列表的归档没有该字段。
+    let mut space_unchecked = false;
+    if !options.ignore_space_check {
+        let file = File::open(&archive_path).map_err(|err| err.to_string())?;
+        let (header, _) = read_archive_preamble(&mut BufReader::new(file))?;
+        match header.content_size {
+            Some(content_size) => ensure_free_space(&output_parent, content_size.tar_bytes)?,
+            None => space_unchecked = true,
+        }
+    }
+
+    let (volume_reader, total_bytes) = open_archive_reader(&archive_path)?;
+    let mut tracker = ArchiveProgressTracker::new("extract", "读取归档头", total_bytes, cancel);
+    tracker.set_stage(window, "读取归档头", "正在读取归档头");
+
+    let progress_reader =
+        ProgressReader::new(volume_reader, &mut tracker, window, "正在读取归档头");
+    let counters = PayloadCounters::default();
+    let mut report = with_archive_payload(
+        progress_reader,
+        normalized_password,
+        key_file.as_deref(),
+        "正在校验密码并解压",
+        "正在解压归档",
+        Some(&counters),
+        |payload, compression| {
+            extract_archive_contents(
+                payload,
+                compression,
+                &extract_root,
+                options,
+                counters.clone(),
+            )
+        },
+    )?;
+    // salvage 模式把读取错误当作损坏处理，取消需要单独识别。
+    if tracker.is_cancelled() {
+        let _ = fs::remove_dir_all(&extract_root);
+        return Err(ARCHIVE_CANCELLED_ERROR.to_string());
+    }
+
+    report.space_check_skipped = space_unchecked;
+    tracker.finish(window, "解压完成", "解压完成");
+    Ok(report)
+}
+
+// 依次应用完整归档与各增量归档：每个归档先解到同级临时目录，再覆盖合并到目标目录，
+// 最后按该归档清单记录的删除列表移除文件。
+async fn extract_archive_chain_impl(
+    window: Option<&Window>,
+    cancel: Arc<OperationControl>,
+    archives: Vec<String>,
+    output_dir: String,
+    password: Option<String>,
+    key_file: Option<String>,
+) -> Result<ExtractReport, String> {
+    let first_archive = archives.first().ok_or("请至少选择一个归档".to_string())?;
+    let first_archive = absolute_path(Path::new(first_archive))?;
+    let output_parent = absolute_path(Path::new(&output_dir))?;
+    let root_name_path = volume_base_path(&first_archive).unwrap_or(first_archive);
+    let extract_root = prepare_extract_output_dir(&root_name_path, &output_parent)?;
+    let staging_parent = unique_output_dir(&output_parent, OsStr::new(".krate-chain"));
+    fs::create_dir(&extract_root).map_err(|err| err.to_string())?;
+
+    let mut report = ExtractReport {
+        output_dir: extract_root.to_string_lossy().to_string(),
+        skipped_entries: Vec::new(),
+        hash_mismatches: Vec::new(),
+        password_required_entries: Vec::new(),
+        salvage: None,
+        renamed_entries: Vec::new(),
+        space_check_skipped: false,
+    };
+    let mut result = Ok(());
+    for archive in archives {
+        match apply_chain_archive(
+            window,
+            cancel.clone(),
+            archive,
+            &staging_parent,
+            &extract_root,
+            password.clone(),
+            key_file.clone(),
+        )
+        .await
+        {
+            Ok(staged) => {
+                report.skipped_entries.extend(staged.skipped_entries);
+                report
+                    .password_required_entries
+                    .extend(staged.password_required_entries);
+                report.renamed_entries.extend(staged.renamed_entries);
+            }
+            Err(err) => {
+                result = Err(err);
+                break;
+            }
+        }
+    }
+
+    let _ = fs::remove_dir_all(&staging_parent);
+    if let Err(err) = result {
+        let _ = fs::remove_dir_all(&extract_root);
+        return Err(cancelled_or(&cancel, err));
+    }
+    Ok(report)
+}
+
+async fn apply_chain_archive(
+    window: Option<&Window>,
+    cancel: Arc<OperationControl>,
+    archive: String,
+    staging_parent: &Path,
+    extract_root: &Path,
+    password: Option<String>,
+    key_file: Option<String>,
+) -> Result<ExtractReport, String> {
+    let staged = extract_archive_inner(
+        window,
+        cancel,
+        archive,
+        staging_parent.to_string_lossy().to_string(),
+        password,
+        key_file,
+        ExtractOptions {
+            include_manifest: true,
+            ..Default::default()
+        },
+    )
+    .await?;
+    let staging_dir = PathBuf::from(&staged.output_dir);
+
+    let manifest_path = staging_dir.join(MANIFEST_ENTRY_NAME);
+    let deleted = if manifest_path.is_file() {
+        let mut file = File::open(&manifest_path).map_err(|err| err.to_string())?;
+        let manifest = parse_manifest(&mut file)?;
+        drop(file);
+        fs::remove_file(&manifest_path).map_err(|err| err.to_string())?;
+        manifest.deleted
+    } else {
+        Vec::new()
+    };
+
+    merge_extracted_tree(&staging_dir, extract_root)?;
+    fs::remove_dir_all(&staging_dir).map_err(|err| err.to_string())?;
+    remove_deleted_entries(extract_root, &deleted)?;
+    Ok(staged)
+}
+
+// 把临时目录中的条目移动到目标目录：同名条目被覆盖，两边都是目录时递归合并。
+fn merge_extracted_tree(source: &Path, target: &Path) -> Result<(), String> {
+    for entry in fs::read_dir(source).map_err(|err| err.to_string())? {
+        let entry = entry.map_err(|err| err.to_string())?;
+        let from = entry.path();
+        let to = target.join(entry.file_name());
+        let from_is_dir = entry.file_type().map_err(|err| err.to_string())?.is_dir();
+
+        match fs::symlink_metadata(&to) {
+            Ok(existing) if existing.is_dir() && from_is_dir => {
+                merge_extracted_tree(&from, &to)?;
+                continue;
+            }
+            Ok(existing) if existing.is_dir() => {
+                fs::remove_dir_all(&to).map_err(|err| err.to_string())?
+            }
+            Ok(_) => fs::remove_file(&to).map_err(|err| err.to_string())?,
+            Err(_) => {}
+        }
+        fs::rename(&from, &to).map_err(|err| err.to_string())?;
+    }
+    Ok(())
+}
+
+// 删除列表来自归档内容，同样要防止路径越出输出目录。
+fn remove_deleted_entries(extract_root: &Path, deleted: &[String]) -> Result<(), String> {
+    let root = extract_root.canonicalize().map_err(|err| err.to_string())?;
+    for name in deleted {
+        let Some(relative) = sanitized_entry_path(name) else {
+            continue;
+        };
+        let target = root.join(relative);
+        let (Some(parent), Some(file_name)) = (target.parent(), target.file_name()) else {
+            continue;
+        };
+        let Ok(parent) = parent.canonicalize() else {
+            continue;
+        };
+        if !parent.starts_with(&root) {
+            continue;
+        }
+        // 删除列表同时包含文件与目录；目录被删除时其中的文件也一并列出。
+        let path = parent.join(file_name);
+        let removed = match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(&path),
+            Ok(_) => fs::remove_file(&path),
+            Err(err) => Err(err),
+        };
+        match removed {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(format!("删除文件失败 {}: {}", name, err)),
+        }
+    }
+    Ok(())
+}
+
+// 读取归档头，按需校验密码并解密，再把（仍处于压缩状态的）负载交给 `consume`。
+fn with_archive_payload<R: Read, T>(
+    mut progress_reader: ProgressReader<'_, R>,
+    password: Option<String>,
+    key_file: Option<&Path>,
+    encrypted_message: &'static str,
+    plain_message: &'static str,
+    counters: Option<&PayloadCounters>,
+    consume: impl FnOnce(&mut dyn Read, ArchiveCompression) -> Result<T, String>,
+) -> Result<T, String> {
+    let (header, compression) = read_archive_preamble(&mut progress_reader)?;
+    // 头部记录了明文大小时改按解压后的字节计算进度；旧归档仍按读取的归档字节计算。
+    if let (Some(counters), Some(content_size)) = (counters, header.content_size) {
+        progress_reader.tracker.reset_total(content_size.tar_bytes);
+        progress_reader.plain_bytes = Some(counters.plain.clone());
+    }
+
+    if let Some(metadata) = header.encryption.as_ref() {
+        let secret = ArchiveSecret::for_header(password, key_file, header.flags)?;
+        let key = secret.unlock(metadata)?;
+        progress_reader.message = encrypted_message;
+        progress_reader.tracker.set_stage(
+            progress_reader.window,
+            encrypted_message,
+            encrypted_message,
+        );
+
+        let mut payload_reader = EncryptedPayloadReader::new(
+            progress_reader,
+            key,
+            metadata.stream_nonce,
+            header.aad_bytes(),
+        );
+        if let Some(counters) = counters {
+            counters
+                .intact
+                .0
+                .set((MAGIC_HEADER.len() + header.encoded_bytes().len()) as u64);
+            payload_reader.intact = counters.intact.clone();
+        }
+        payload_reader.prime().map_err(|err| err.to_string())?;
+        if header.hides_names() {
+            read_hidden_metadata(&mut payload_reader)?;
+        }
+        consume(&mut payload_reader, compression)
+    } else {
+        progress_reader.message = plain_message;
+        progress_reader
+            .tracker
+            .set_stage(progress_reader.window, plain_message, plain_message);
+        consume(&mut progress_reader, compression)
+    }
+}
+
+async fn verify_archive_impl(
+    window: Option<&Window>,
+    cancel: Arc<OperationControl>,
+    archive_path: String,
+    password: Option<String>,
+    key_file: Option<String>,
+) -> Result<VerifyReport, String> {
+    let normalized_password = normalized_password(password);
+    let key_file = key_file
+        .map(|path| absolute_path(Path::new(&path)))
+        .transpose()?;
+    let archive_path = absolute_path(Path::new(&archive_path))?;
+    if is_7z_archive(&archive_path)? {
+        return Err("7z 归档不包含文件清单，无法校验哈希".to_string());
+    }
+
+    let (volume_reader, total_bytes) = open_archive_reader(&archive_path)?;
+    let mut tracker =
+        ArchiveProgressTracker::new("verify", "读取归档头", total_bytes, cancel.clone());
+    tracker.set_stage(window, "读取归档头", "正在读取归档头");
+
+    let progress_reader =
+        ProgressReader::new(volume_reader, &mut tracker, window, "正在读取归档头");
+    let report = with_archive_payload(
+        progress_reader,
+        normalized_password,
+        key_file.as_deref(),
+        "正在校验密码与文件哈希",
+        "正在校验文件哈希",
+        None,
+        |payload, compression| verify_archive_contents(payload, compression),
+    )
+    .map_err(|err| cancelled_or(&cancel, err))?;
+
+    tracker.finish(window, "校验完成", "校验完成");
+    Ok(report)
+}
+
+// `operation_id` 由前端生成并传入，用于随后调用 `cancel_archive`；未传入时自动生成，并随进度事件的 `operationId` 返回。
+#[command]
+pub async fn create_archive(
+    window: Window,
+    state: State<'_, ArchiveState>,
+    operation_id: Option<String>,
+    request: CreateArchiveRequest,
+) -> Result<CreateReport, String> {
+    let operation = state.register(operation_id)?;
+    let symlinks = request
+        .symlinks
+        .unwrap_or(if request.follow_symlinks.unwrap_or(false) {
+            SymlinkMode::Follow
+        } else {
+            SymlinkMode::Preserve
+        });
+    create_archive_impl(
+        Some(&window),
+        operation.cancel.clone(),
+        request.inputs,
+        request.output_path,
+        request.password,
+        request.key_file_path,
+        CreateOptions {
+            level: request.gzip_level,
+            compression: request.compression.unwrap_or_default(),
+            symlinks,
+            comment: request.comment,
+            volume_size: request.volume_size_bytes,
+            raw_key: request.raw_key.unwrap_or(false),
+            kdf: request.kdf.unwrap_or_default(),
+            threads: request.threads,
+            incremental: None,
+            skip_errors: request.skip_errors.unwrap_or(false),
+            hide_file_names: request.hide_file_names.unwrap_or(false),
+            dedupe: request.dedupe.unwrap_or(false),
+            filter: EntryFilter {
+                min_size: request.min_size_bytes,
+                max_size: request.max_size_bytes,
+                modified_after: request.modified_after,
+                modified_before: request.modified_before,
+            },
+            top_level_names: request.top_level_names,
+            min_password_score: request
+                .min_password_score
+                .filter(|_| !request.force.unwrap_or(false)),
+            checksum_trailer: request.checksum_trailer.unwrap_or(false),
+            ..Default::default()
+        },
+    )
+    .await
+}
+
+// 评估密码强度，供前端在输入密码时实时显示；密码只用于计算，不会被记录。
+#[command]
+pub async fn validate_archive_password(password: String) -> PasswordReport {
+    password_report(&password)
+}
+
+// 按显式列表打包：每个源文件写到调用方指定的归档内路径，可用于扁平化或重新组织目录。
+// 列表中的文件夹默认报错，`expand_directories` 为 true 时把其内容打包到给定路径下。
+#[command]
+pub async fn create_archive_from_entries(
+    window: Window,
+    state: State<'_, ArchiveState>,
+    operation_id: Option<String>,
+    request: ArchiveEntriesRequest,
+) -> Result<CreateReport, String> {
+    let operation = state.register(operation_id)?;
+    create_archive_from_entries_impl(
+        Some(&window),
+        operation.cancel.clone(),
+        request.entries,
+        request.output_path,
+        request.password,
+        request.key_file_path,
+        CreateOptions {
+            compression: request.compression.unwrap_or_default(),
+            expand_directories: request.expand_directories.unwrap_or(false),
+            ..Default::default()
+        },
+    )
+    .await
+}
+
+// 增量打包：只打包相对 `base_manifest_path` 快照有变化的文件，并在归档旁写出
+// `<output>.manifest.json` 作为下一次的基准；不传基准时打包全部文件。
+#[command]
+pub async fn create_archive_incremental(
+    window: Window,
+    state: State<'_, ArchiveState>,
+    operation_id: Option<String>,
+    request: IncrementalArchiveRequest,
+) -> Result<CreateReport, String> {
+    let operation = state.register(operation_id)?;
+    let base_manifest = request
+        .base_manifest_path
+        .map(|path| absolute_path(Path::new(&path)))
+        .transpose()?;
+    create_archive_impl(
+        Some(&window),
+        operation.cancel.clone(),
+        request.inputs,
+        request.output_path,
+        request.password,
+        request.key_file_path,
+        CreateOptions {
+            level: request.gzip_level,
+            compression: request.compression.unwrap_or_default(),
+            incremental: Some(IncrementalOptions {
+                base_manifest,
+                compare_hashes: request.compare_hashes.unwrap_or(false),
+            }),
+            ..Default::default()
+        },
+    )
+    .await
+}
+
+// 按顺序解出完整归档及其后的增量归档，并应用各增量记录的删除。
+#[command]
+pub async fn extract_archive_chain(
+    window: Window,
+    state: State<'_, ArchiveState>,
+    operation_id: Option<String>,
+    archives: Vec<String>,
+    output_dir: String,
+    password: Option<String>,
+    key_file_path: Option<String>,
+) -> Result<ExtractReport, String> {
+    let operation = state.register(operation_id)?;
+    extract_archive_chain_impl(
+        Some(&window),
+        operation.cancel.clone(),
+        archives,
+        output_dir,
+        password,
+        key_file_path,
+    )
+    .await
+}
+
+// `strict_security` 为 true 时遇到越界条目直接失败，否则跳过并在结果中列出；
+// `preserve_metadata` 默认开启，`preserve_ownership` 默认关闭。
+#[command]
+pub async fn extract_archive(
+    window: Window,
+    state: State<'_, ArchiveState>,
+    operation_id: Option<String>,
+    request: ExtractArchiveRequest,
+) -> Result<ExtractReport, String> {
+    let operation = state.register(operation_id)?;
+    let defaults = ExtractOptions::default();
+    let options = ExtractOptions {
+        strict_security: request.strict_security.unwrap_or(defaults.strict_security),
+        preserve_metadata: request
+            .preserve_metadata
+            .unwrap_or(defaults.preserve_metadata),
+        preserve_ownership: request
+            .preserve_ownership
+            .unwrap_or(defaults.preserve_ownership),
+        allow_external_symlinks: request
+            .allow_external_symlinks
+            .unwrap_or(defaults.allow_external_symlinks),
+        verify_hashes: request.verify_hashes.unwrap_or(defaults.verify_hashes),
+        include_manifest: request
+            .include_manifest
+            .unwrap_or(defaults.include_manifest),
+        salvage: request.salvage.unwrap_or(defaults.salvage),
+        dedupe_restore: request.dedupe_restore.unwrap_or(defaults.dedupe_restore),
+        strip_components: request
+            .strip_components
+            .unwrap_or(defaults.strip_components),
+        ignore_space_check: request
+            .ignore_space_check
+            .unwrap_or(defaults.ignore_space_check),
+    };
+    extract_archive_impl(
+        Some(&window),
+        operation.cancel.clone(),
+        request.archive_path,
+        request.output_dir,
+        request.password,
+        request.key_file_path,
+        options,
+    )
+    .await
+}
+
+// 重新计算归档内每个文件的 SHA-256 并与清单比对，不写出任何文件。
+#[command]
+pub async fn verify_archive(
+    window: Window,
+    state: State<'_, ArchiveState>,
+    operation_id: Option<String>,
+    archive_path: String,
+    password: Option<String>,
+    key_file_path: Option<String>,
+) -> Result<VerifyReport, String> {
+    let operation = state.register(operation_id)?;
+    verify_archive_impl(
+        Some(&window),
+        operation.cancel.clone(),
+        archive_path,
+        password,
+        key_file_path,
+    )
+    .await
+}
+
+// 打包前估算文件数、原始大小与压缩后大小；`sample_bytes` 为采样总量上限。
+#[command]
+pub async fn estimate_archive(
+    inputs: Vec<String>,
+    gzip_level: Option<u32>,
+    compression: Option<ArchiveCompression>,
+    sample_bytes: Option<u64>,
+) -> Result<EstimateResult, String> {
+    estimate_archive_impl(
+        inputs,
+        compression.unwrap_or_default(),
+        gzip_level,
+        sample_bytes,
+    )
+}
+
+// 用同一份样本（最多 64 MiB）依次测试各压缩等级，返回压缩后大小、压缩率与速度。
+// 测试在阻塞线程池中运行，进度通过 `archive://progress` 事件上报，可取消。
+#[command]
+pub async fn benchmark_compression(
+    window: Window,
+    state: State<'_, ArchiveState>,
+    operation_id: Option<String>,
+    sample_path: String,
+    levels: Vec<u32>,
+    compression: Option<ArchiveCompression>,
+) -> Result<Vec<BenchmarkResult>, String> {
+    let operation = state.register(operation_id)?;
+    let cancel = operation.cancel.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        benchmark_compression_impl(
+            Some(&window),
+            cancel,
+            sample_path,
+            compression.unwrap_or_default(),
+            levels,
+        )
+    })
+    .await
+    .map_err(|err| format!("压缩测试异常退出: {}", err))?
+}
+
+// 读取归档中单个文件的开头部分用于预览，不写入磁盘。
+#[command]
+pub async fn read_archive_entry(
+    archive_path: String,
+    entry_path: String,
+    password: Option<String>,
+    max_bytes: Option<u64>,
+    key_file_path: Option<String>,
+) -> Result<EntryPreview, String> {
+    read_archive_entry_impl(archive_path, entry_path, password, key_file_path, max_bytes)
+}
+
+// 按校验尾快速检查归档文件是否完好（复制或存储过程中的位翻转等），不需要密码也不解压；
+// 只能发现文件层面的损坏，内容校验仍需 `verify_archive`。
+#[command]
+pub async fn quick_check_archive(path: String) -> Result<bool, String> {
+    let archive_path = absolute_path(Path::new(&path))?;
+    archive_checksum_matches(&archive_path)?
+        .ok_or("该归档没有校验尾，无法快速校验，请使用完整校验".to_string())
+}
+
+// 在单个归档中按名称搜索条目；`pattern` 含 `*`、`?` 时为通配符，否则为子串，默认不区分大小写。
+#[command]
+pub async fn search_archive(
+    window: Window,
+    state: State<'_, ArchiveState>,
+    operation_id: Option<String>,
+    archive_path: String,
+    query: ArchiveSearchQuery,
+) -> Result<Vec<ArchiveEntryInfo>, String> {
+    let operation = state.register(operation_id)?;
+    let matcher = EntryMatcher::new(&query.pattern, query.case_sensitive.unwrap_or(false))?;
+    let mut report = search_archives_impl(
+        Some(&window),
+        operation.cancel.clone(),
+        vec![archive_path],
+        &matcher,
+        query.password,
+        query.key_file_path,
+        query.limit.unwrap_or(DEFAULT_SEARCH_LIMIT),
+    )?;
+    match report.skipped_archives.pop() {
+        Some(skipped) => Err(skipped.reason),
+        None => Ok(report.matches),
+    }
+}
+
+// 依次在多个归档中搜索，命中条目带有所属归档；加密但未提供密码的归档记为跳过。
+#[command]
+pub async fn search_archives(
+    window: Window,
+    state: State<'_, ArchiveState>,
+    operation_id: Option<String>,
+    paths: Vec<String>,
+    query: ArchiveSearchQuery,
+) -> Result<ArchiveSearchReport, String> {
+    let operation = state.register(operation_id)?;
+    let matcher = EntryMatcher::new(&query.pattern, query.case_sensitive.unwrap_or(false))?;
+    search_archives_impl(
+        Some(&window),
+        operation.cancel.clone(),
+        paths,
+        &matcher,
+        query.password,
+        query.key_file_path,
+        query.limit.unwrap_or(DEFAULT_SEARCH_LIMIT),
+    )
+}
+
+// 读取 .krate 归档头部信息；只有隐藏了文件列表的归档需要密码或密钥文件。
+#[command]
+pub async fn get_archive_info(
+    path: String,
+    password: Option<String>,
+    key_file_path: Option<String>,
+) -> Result<ArchiveInfo, String> {
+    let key_file = key_file_path
+        .map(|path| absolute_path(Path::new(&path)))
+        .transpose()?;
+    archive_info(
+        &absolute_path(Path::new(&path))?,
+        password,
+        key_file.as_deref(),
+    )
+}
+
+// 向未加密的 .krate 归档追加文件；重写到临时文件后替换原归档。
+#[command]
+pub async fn append_to_archive(
+    window: Window,
+    state: State<'_, ArchiveState>,
+    operation_id: Option<String>,
+    archive_path: String,
+    inputs: Vec<String>,
+) -> Result<(), String> {
+    let operation = state.register(operation_id)?;
+    append_to_archive_impl(
+        Some(&window),
+        operation.cancel.clone(),
+        archive_path,
+        inputs,
+    )
+    .await
+}
+
+// 校验原密码后用新密码重新加密归档，成功后替换原文件。
+#[command]
+pub async fn change_archive_password(
+    window: Window,
+    state: State<'_, ArchiveState>,
+    operation_id: Option<String>,
+    archive_path: String,
+    old_password: String,
+    new_password: String,
+) -> Result<(), String> {
+    let operation = state.register(operation_id)?;
+    change_archive_password_impl(
+        Some(&window),
+        operation.cancel.clone(),
+        archive_path,
+        old_password,
+        new_password,
+    )
+    .await
+}
+
+// 把 .krate 归档转换为标准 .tar.gz，便于在未安装 Krate 的环境中解压。
+#[command]
+pub async fn convert_archive(
+    window: Window,
+    state: State<'_, ArchiveState>,
+    operation_id: Option<String>,
+    request: ConvertArchiveRequest,
+) -> Result<(), String> {
+    let operation = state.register(operation_id)?;
+    convert_archive_impl(
+        Some(&window),
+        operation.cancel.clone(),
+        request.input_path,
+        request.output_path,
+        request.password,
+        request.key_file_path,
+        request.ignore_space_check.unwrap_or(false),
+    )
+    .await
+}
+
+// 取消正在运行的归档任务；任务随后以“已取消”错误结束。
+#[command]
+pub fn cancel_archive(state: State<'_, ArchiveState>, operation_id: String) -> Result<(), String> {
+    state.cancel(&operation_id)
+}
+
+// 暂停正在运行的归档任务，进度事件的阶段变为 `paused`；仅在本次运行内有效。
+#[command]
+pub fn pause_archive(state: State<'_, ArchiveState>, operation_id: String) -> Result<(), String> {
+    state.set_paused(&operation_id, true)
+}
+
+// 恢复被暂停的归档任务。
+#[command]
+pub fn resume_archive(state: State<'_, ArchiveState>, operation_id: String) -> Result<(), String> {
+    state.set_paused(&operation_id, false)
+}
+
+// 列出正在运行的归档任务及其最近一次进度，按任务 ID 排序。
+#[command]
+pub fn list_archive_operations(
+    state: State<'_, ArchiveState>,
+) -> Result<Vec<ArchiveOperationInfo>, String> {
+    state.list()
+}
+
+#[command]
+pub async fn open_output_dir(path: String) -> Result<(), String> {
+    let target = Path::new(&path);
+
+    if !target.exists() {
+        return Err("输出目录不存在".to_string());
+    }
+
+    if !target.is_dir() {
+        return Err("目标路径不是文件夹".to_string());
+    }
+
+    #[cfg(target_os = "windows")]
+    let mut command = {
+        let mut cmd = Command::new("explorer");
+        cmd.arg(target);
+        cmd
+    };
+
+    #[cfg(target_os = "macos")]
+    let mut command = {
+        let mut cmd = Command::new("open");
+        cmd.arg(target);
+        cmd
+    };
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    let mut command = {
+        let mut cmd = Command::new("xdg-open");
+        cmd.arg(target);
+        cmd
+    };
+
+    command
+        .spawn()
+        .map_err(|err| format!("打开输出目录失败: {}", err))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::{Path, PathBuf};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[cfg(unix)]
+    use std::os::unix::fs::symlink;
+
+    fn temp_case_dir(name: &str) -> PathBuf {
+        let mut path = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        path.push(format!(
+            "krate-archive-{name}-{}-{nanos}",
+            std::process::id()
+        ));
+        path
+    }
+
+    fn write_text_file(path: &Path, contents: &str) {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(path, contents).unwrap();
+    }
+
+    fn append_raw_tar_entry(
+        builder: &mut tar::Builder<Vec<u8>>,
+        name: &str,
+        entry_type: tar::EntryType,
+        link_name: Option<&str>,
+        data: &[u8],
+    ) {
+        let mut header = tar::Header::new_gnu();
+        let gnu = header.as_gnu_mut().unwrap();
+        gnu.name[..name.len()].copy_from_slice(name.as_bytes());
+        if let Some(link_name) = link_name {
+            gnu.linkname[..link_name.len()].copy_from_slice(link_name.as_bytes());
+        }
+        header.set_entry_type(entry_type);
+        header.set_mode(0o644);
+        header.set_size(data.len() as u64);
+        header.set_cksum();
+        builder.append(&header, data).unwrap();
+    }
+
+    fn write_raw_krate(path: &Path, tar_bytes: &[u8]) {
+        let mut bytes = MAGIC_HEADER.to_vec();
+        bytes
+            .extend_from_slice(&ArchiveHeader::new_plain(ArchiveCompression::None).encoded_bytes());
+        bytes.extend_from_slice(tar_bytes);
+        fs::write(path, bytes).unwrap();
+    }
+
+    // 打包 `input` 后立即解压到 `output_dir`；实际解压目录见返回的 `ExtractReport::output_dir`。
+    async fn pack_and_extract(
+        input: &Path,
+        archive_file: &Path,
+        output_dir: &Path,
+        password: Option<&str>,
+        create: CreateOptions,
+        extract: ExtractOptions,
+    ) -> (CreateReport, ExtractReport) {
+        let created = create_archive_impl(
+            None,
+            Arc::default(),
+            vec![input.to_string_lossy().to_string()],
+            archive_file.to_string_lossy().to_string(),
+            password.map(str::to_string),
+            None,
+            create,
+        )
+        .await
+        .unwrap();
+        let extracted = extract_archive_impl(
+            None,
+            Arc::default(),
+            archive_file.to_string_lossy().to_string(),
+            output_dir.to_string_lossy().to_string(),
+            password.map(str::to_string),
+            None,
+            extract,
+        )
+        .await
+        .unwrap();
+        (created, extracted)
+    }
+
+    #[tokio::test]
+    async fn plain_archive_roundtrip_preserves_contents() {
+        let root = temp_case_dir("plain");
+        let input_file = root.join("input").join("notes.txt");
+        let archive_file = root.join("plain.krate");
+        let output_dir = root.join("output");
+        let expected = "plain archive roundtrip";
+
+        write_text_file(&input_file, expected);
+
+        create_archive_impl(
+            None,
+            Arc::default(),
+            vec![input_file.to_string_lossy().to_string()],
+            archive_file.to_string_lossy().to_string(),
+            None,
+            None,
+            CreateOptions {
+                level: Some(1),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let extracted_dir = extract_archive_impl(
+            None,
+            Arc::default(),
+            archive_file.to_string_lossy().to_string(),
+            output_dir.to_string_lossy().to_string(),
+            None,
+            None,
+            ExtractOptions::default(),
+        )
+        .await
+        .unwrap()
+        .output_dir;
+
+        assert_eq!(PathBuf::from(&extracted_dir), output_dir.join("plain"));
+        let extracted = fs::read_to_string(Path::new(&extracted_dir).join("notes.txt")).unwrap();
+        assert_eq!(extracted, expected);
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn encrypted_archive_roundtrip_requires_password() {
+        let root = temp_case_dir("encrypted");
+        let input_file = root.join("input").join("secret.txt");
+        let archive_file = root.join("secret.krate");
+        let output_dir = root.join("output");
+        let password = "correct horse battery staple";
+        let expected = "encrypted archive roundtrip";
+
+        write_text_file(&input_file, expected);
+
+        create_archive_impl(
+            None,
+            Arc::default(),
+            vec![input_file.to_string_lossy().to_string()],
+            archive_file.to_string_lossy().to_string(),
+            Some(password.to_string()),
+            None,
+            CreateOptions {
+                level: Some(1),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let extracted_dir = extract_archive_impl(
+            None,
+            Arc::default(),
+            archive_file.to_string_lossy().to_string(),
+            output_dir.to_string_lossy().to_string(),
+            Some(password.to_string()),
+            None,
+            ExtractOptions::default(),
+        )
+        .await
+        .unwrap()
+        .output_dir;
+
+        assert_eq!(PathBuf::from(&extracted_dir), output_dir.join("secret"));
+        let extracted = fs::read_to_string(Path::new(&extracted_dir).join("secret.txt")).unwrap();
+        assert_eq!(extracted, expected);
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn encrypted_archive_rejects_wrong_password() {
+        let root = temp_case_dir("wrong-password");
+        let input_file = root.join("input").join("secret.txt");
+        let archive_file = root.join("secret.krate");
+        let output_dir = root.join("output");
+
+        write_text_file(&input_file, "do not leak");
+
+        create_archive_impl(
+            None,
+            Arc::default(),
+            vec![input_file.to_string_lossy().to_string()],
+            archive_file.to_string_lossy().to_string(),
+            Some("right-password".to_string()),
+            None,
+            CreateOptions {
+                level: Some(1),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let error = extract_archive_impl(
+            None,
+            Arc::default(),
+            archive_file.to_string_lossy().to_string(),
+            output_dir.to_string_lossy().to_string(),
+            Some("wrong-password".to_string()),
+            None,
+            ExtractOptions::default(),
+        )
+        .await
+        .unwrap_err();
+
+        assert!(error.contains("归档解密失败"));
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn password_preserves_leading_and_trailing_spaces() {
+        let root = temp_case_dir("whitespace-password");
+        let input_file = root.join("input").join("secret.txt");
+        let archive_file = root.join("secret.krate");
+        let output_dir = root.join("output-ok");
+        let failed_output_dir = root.join("output-failed");
+        let password = "  padded password  ";
+
+        write_text_file(&input_file, "keep spaces");
+
+        create_archive_impl(
+            None,
+            Arc::default(),
+            vec![input_file.to_string_lossy().to_string()],
+            archive_file.to_string_lossy().to_string(),
+            Some(password.to_string()),
+            None,
+            CreateOptions {
+                level: Some(1),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let extracted_dir = extract_archive_impl(
+            None,
+            Arc::default(),
+            archive_file.to_string_lossy().to_string(),
+            output_dir.to_string_lossy().to_string(),
+            Some(password.to_string()),
+            None,
+            ExtractOptions::default(),
+        )
+        .await
+        .unwrap()
+        .output_dir;
+        assert_eq!(
+            fs::read_to_string(Path::new(&extracted_dir).join("secret.txt")).unwrap(),
+            "keep spaces"
+        );
+
+        let error = extract_archive_impl(
+            None,
+            Arc::default(),
+            archive_file.to_string_lossy().to_string(),
+            failed_output_dir.to_string_lossy().to_string(),
+            Some(password.trim().to_string()),
+            None,
+            ExtractOptions::default(),
+        )
+        .await
+        .unwrap_err();
+        assert!(error.contains("归档解密失败"));
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn create_archive_rejects_overlapping_output_paths() {
+        let root = temp_case_dir("overlap");
+        let input_dir = root.join("input");
+        let input_file = input_dir.join("notes.txt");
+        let nested_output = input_dir.join("nested.krate");
+
+        write_text_file(&input_file, "source data");
+
+        let same_file_error = create_archive_impl(
+            None,
+            Arc::default(),
+            vec![input_file.to_string_lossy().to_string()],
+            input_file.to_string_lossy().to_string(),
+            None,
+            None,
+            CreateOptions {
+                level: Some(1),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
+        assert!(same_file_error.contains("输出文件不能与输入路径相同"));
+        assert_eq!(fs::read_to_string(&input_file).unwrap(), "source data");
+
+        let nested_error = create_archive_impl(
+            None,
+            Arc::default(),
+            vec![input_dir.to_string_lossy().to_string()],
+            nested_output.to_string_lossy().to_string(),
+            None,
+            None,
+            CreateOptions {
+                level: Some(1),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
+        assert!(nested_error.contains("输出文件不能位于待归档目录内"));
+        assert!(!nested_output.exists());
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn duplicate_root_names_are_disambiguated() {
+        let root = temp_case_dir("duplicate-roots");
+        let left = root.join("left").join("config.json");
+        let right = root.join("right").join("config.json");
+        let archive_file = root.join("bundle.krate");
+        let output_dir = root.join("output");
+
+        write_text_file(&left, "left");
+        write_text_file(&right, "right");
+
+        // 输入顺序与编号无关：按源路径排序后编号。
+        let report = create_archive_impl(
+            None,
+            Arc::default(),
+            vec![
+                right.to_string_lossy().to_string(),
+                left.to_string_lossy().to_string(),
+            ],
+            archive_file.to_string_lossy().to_string(),
+            None,
+            None,
+            CreateOptions {
+                level: Some(1),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        let mapped = report
+            .input_mapping
+            .iter()
+            .map(|mapping| mapping.archive_name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(mapped, ["config (2).json", "config (1).json"]);
+        assert_eq!(report.input_mapping[0].input, right.display().to_string());
+
+        let extracted_dir = extract_archive_impl(
+            None,
+            Arc::default(),
+            archive_file.to_string_lossy().to_string(),
+            output_dir.to_string_lossy().to_string(),
+            None,
+            None,
+            ExtractOptions::default(),
+        )
+        .await
+        .unwrap()
+        .output_dir;
+
+        assert!(!Path::new(&extracted_dir).join("config.json").exists());
+        assert_eq!(
+            fs::read_to_string(Path::new(&extracted_dir).join("config (1).json")).unwrap(),
+            "left"
+        );
+        assert_eq!(
+            fs::read_to_string(Path::new(&extracted_dir).join("config (2).json")).unwrap(),
+            "right"
+        );
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn extract_archive_uses_unique_output_directories() {
+        let root = temp_case_dir("extract-output");
+        let input_file = root.join("input").join("notes.txt");
+        let archive_file = root.join("plain.krate");
+        let output_dir = root.join("output");
+        let existing_file = output_dir.join("notes.txt");
+
+        write_text_file(&input_file, "archive data");
+        write_text_file(&existing_file, "existing data");
+
+        create_archive_impl(
+            None,
+            Arc::default(),
+            vec![input_file.to_string_lossy().to_string()],
+            archive_file.to_string_lossy().to_string(),
+            None,
+            None,
+            CreateOptions {
+                level: Some(1),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let first_extract_dir = PathBuf::from(
+            extract_archive_impl(
+                None,
+                Arc::default(),
+                archive_file.to_string_lossy().to_string(),
+                output_dir.to_string_lossy().to_string(),
+                None,
+                None,
+                ExtractOptions::default(),
+            )
+            .await
+            .unwrap()
+            .output_dir,
+        );
+        let second_extract_dir = PathBuf::from(
+            extract_archive_impl(
+                None,
+                Arc::default(),
+                archive_file.to_string_lossy().to_string(),
+                output_dir.to_string_lossy().to_string(),
+                None,
+                None,
+                ExtractOptions::default(),
+            )
+            .await
+            .unwrap()
+            .output_dir,
+        );
+
+        assert_eq!(fs::read_to_string(&existing_file).unwrap(), "existing data");
+        assert_eq!(
+            fs::read_to_string(first_extract_dir.join("notes.txt")).unwrap(),
+            "archive data"
+        );
+        assert_eq!(
+            fs::read_to_string(second_extract_dir.join("notes.txt")).unwrap(),
+            "archive data"
+        );
+        assert_ne!(first_extract_dir, second_extract_dir);
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn tampered_kdf_parameters_are_rejected() {
+        let root = temp_case_dir("tampered-kdf");
+        let input_file = root.join("input").join("secret.txt");
+        let archive_file = root.join("secret.krate");
+        let output_dir = root.join("output");
+
+        write_text_file(&input_file, "sensitive");
+
+        create_archive_impl(
+            None,
+            Arc::default(),
+            vec![input_file.to_string_lossy().to_string()],
+            archive_file.to_string_lossy().to_string(),
+            Some("password".to_string()),
+            None,
+            CreateOptions {
+                level: Some(1),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let mut bytes = fs::read(&archive_file).unwrap();
+        let memory_offset = MAGIC_HEADER.len() + FORMAT_MARKER.len() + 2;
+        bytes[memory_offset..memory_offset + 4]
+            .copy_from_slice(&(MAX_ARCHIVE_ARGON2_MEMORY_KIB + 1).to_le_bytes());
+        fs::write(&archive_file, bytes).unwrap();
+
+        let error = extract_archive_impl(
+            None,
+            Arc::default(),
+            archive_file.to_string_lossy().to_string(),
+            output_dir.to_string_lossy().to_string(),
+            Some("password".to_string()),
+            None,
+            ExtractOptions::default(),
+        )
+        .await
+        .unwrap_err();
+
+        assert!(error.contains("支持范围"));
+        assert!(!output_dir.join("secret").exists());
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn symlinks_are_archived_without_following() {
+        let root = temp_case_dir("symlink");
+        let input_dir = root.join("input");
+        let target_file = input_dir.join("real.txt");
+        let alias_file = input_dir.join("alias.txt");
+        let archive_file = root.join("links.krate");
+        let output_dir = root.join("output");
+
+        write_text_file(&target_file, "symlink payload");
+        fs::create_dir_all(&input_dir).unwrap();
+        symlink(Path::new("real.txt"), &alias_file).unwrap();
+
+        create_archive_impl(
+            None,
+            Arc::default(),
+            vec![input_dir.to_string_lossy().to_string()],
+            archive_file.to_string_lossy().to_string(),
+            None,
+            None,
+            CreateOptions {
+                level: Some(1),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let extracted_dir = PathBuf::from(
+            extract_archive_impl(
+                None,
+                Arc::default(),
+                archive_file.to_string_lossy().to_string(),
+                output_dir.to_string_lossy().to_string(),
+                None,
+                None,
+                ExtractOptions::default(),
+            )
+            .await
+            .unwrap()
+            .output_dir,
+        );
+        let extracted_alias = extracted_dir.join("input").join("alias.txt");
+
+        assert!(fs::symlink_metadata(&extracted_alias)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(
+            fs::read_link(&extracted_alias).unwrap(),
+            PathBuf::from("real.txt")
+        );
+        assert_eq!(
+            fs::read_to_string(&extracted_alias).unwrap(),
+            "symlink payload"
+        );
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn progress_tracker_estimates_speed_and_eta() {
+        let mut tracker = ArchiveProgressTracker::new("pack", "正在压缩打包", 1000, Arc::default());
+        let started = Instant::now();
+
+        tracker.record_sample(started);
+        assert_eq!(tracker.bytes_per_second(), None);
+
+        tracker.processed_bytes = 200;
+        tracker.record_sample(started + Duration::from_secs(1));
+        tracker.processed_bytes = 400;
+        tracker.record_sample(started + Duration::from_secs(2));
+        let speed = tracker.bytes_per_second();
+        assert_eq!(speed, Some(200.0));
+        assert_eq!(tracker.eta_seconds(speed), Some(3));
+
+        // 窗口外的旧样本被丢弃，速度只反映最近的吞吐。
+        tracker.processed_bytes = 1000;
+        tracker.record_sample(started + Duration::from_secs(6));
+        assert_eq!(tracker.samples.len(), 2);
+        assert_eq!(tracker.bytes_per_second(), Some(150.0));
+        assert_eq!(tracker.eta_seconds(tracker.bytes_per_second()), Some(0));
+    }
+
+    #[tokio::test]
+    async fn cancelled_archive_removes_partial_output() {
+        let root = temp_case_dir("cancelled");
+        let input_file = root.join("input").join("big.txt");
+        let archive_file = root.join("cancelled.krate");
+
+        write_text_file(&input_file, "will never be packed");
+
+        let state = ArchiveState::new();
+        let operation = state.register(Some("op-1".to_string())).unwrap();
+        assert!(state.register(Some("op-1".to_string())).is_err());
+        state.cancel("op-1").unwrap();
+
+        let error = create_archive_impl(
+            None,
+            operation.cancel.clone(),
+            vec![input_file.to_string_lossy().to_string()],
+            archive_file.to_string_lossy().to_string(),
+            None,
+            None,
+            CreateOptions {
+                level: Some(1),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error, ARCHIVE_CANCELLED_ERROR);
+        assert!(!archive_file.exists());
+        assert_eq!(fs::read_dir(&root).unwrap().count(), 1);
+
+        drop(operation);
+        assert!(state.cancel("op-1").is_err());
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn zstd_and_stored_archives_roundtrip() {
+        let root = temp_case_dir("compression");
+        let input_file = root.join("input").join("notes.txt");
+        let expected = "zstd and stored roundtrip";
+
+        write_text_file(&input_file, expected);
+
+        for (name, compression, password) in [
+            ("zstd", ArchiveCompression::Zstd, None),
+            ("zstd-encrypted", ArchiveCompression::Zstd, Some("password")),
+            ("stored", ArchiveCompression::None, None),
+        ] {
+            let archive_file = root.join(format!("{name}.krate"));
+            let (_, extracted) = pack_and_extract(
+                &input_file,
+                &archive_file,
+                &root.join("output"),
+                password,
+                CreateOptions {
+                    compression,
+                    ..Default::default()
+                },
+                ExtractOptions::default(),
+            )
+            .await;
+
+            let bytes = fs::read(&archive_file).unwrap();
+            assert_eq!(
+                bytes[MAGIC_HEADER.len() + FORMAT_MARKER.len() + 1],
+                compression.code()
+            );
+            let extracted_dir = extracted.output_dir;
+            assert_eq!(
+                fs::read_to_string(Path::new(&extracted_dir).join("notes.txt")).unwrap(),
+                expected
+            );
+        }
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn compression_level_is_clamped() {
+        assert_eq!(
+            compression_level(None, DEFAULT_XZ_PRESET),
+            DEFAULT_XZ_PRESET
+        );
+        assert_eq!(compression_level(Some(0), DEFAULT_XZ_PRESET), 0);
+        assert_eq!(compression_level(Some(42), DEFAULT_XZ_PRESET), 9);
+    }
+
+    #[tokio::test]
+    async fn xz_archive_is_smaller_than_gzip() {
+        let root = temp_case_dir("xz");
+        let input_file = root.join("input").join("data.bin");
+
+        // 64 KiB 伪随机块重复 4 次：超出 gzip 的 32 KiB 窗口，但在 xz 字典范围内。
+        let mut seed = 0x2545_f491_u32;
+        let block = (0..64 * 1024)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (seed >> 16) as u8
+            })
+            .collect::<Vec<_>>();
+        fs::create_dir_all(input_file.parent().unwrap()).unwrap();
+        fs::write(&input_file, block.repeat(4)).unwrap();
+
+        let mut sizes = Vec::new();
+        for compression in [ArchiveCompression::Gzip, ArchiveCompression::Xz] {
+            let archive_file = root.join(format!("{:?}.krate", compression));
+            let (_, extracted) = pack_and_extract(
+                &input_file,
+                &archive_file,
+                &root.join("output"),
+                None,
+                CreateOptions {
+                    level: Some(42),
+                    compression,
+                    ..Default::default()
+                },
+                ExtractOptions::default(),
+            )
+            .await;
+            sizes.push(fs::metadata(&archive_file).unwrap().len());
+            let extracted_dir = extracted.output_dir;
+            assert_eq!(
+                fs::read(Path::new(&extracted_dir).join("data.bin")).unwrap(),
+                block.repeat(4)
+            );
+        }
+
+        assert!(sizes[1] < sizes[0], "xz {} >= gzip {}", sizes[1], sizes[0]);
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn stored_archives_skip_compression_with_and_without_password() {
+        let root = temp_case_dir("stored");
+        let input_file = root.join("input").join("photo.jpg");
+        let marker = "already-compressed-payload-marker";
+
+        write_text_file(&input_file, &marker.repeat(64));
+
+        for (name, password) in [
+            ("stored-plain", None),
+            ("stored-encrypted", Some("password")),
+        ] {
+            let archive_file = root.join(format!("{name}.krate"));
+            let (_, extracted) = pack_and_extract(
+                &input_file,
+                &archive_file,
+                &root.join("output"),
+                password,
+                CreateOptions {
+                    level: Some(9),
+                    compression: ArchiveCompression::None,
+                    ..Default::default()
+                },
+                ExtractOptions::default(),
+            )
+            .await;
+
+            // 未压缩时 tar 流原样写入：明文包内可直接看到文件内容，加密包则不能。
+            let bytes = fs::read(&archive_file).unwrap();
+            let contains_marker = bytes
+                .windows(marker.len())
+                .any(|window| window == marker.as_bytes());
+            assert_eq!(contains_marker, password.is_none());
+            let extracted_dir = extracted.output_dir;
+            assert_eq!(
+                fs::read_to_string(Path::new(&extracted_dir).join("photo.jpg")).unwrap(),
+                marker.repeat(64)
+            );
+        }
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn unsafe_entry_paths_are_rejected() {
+        assert_eq!(
+            sanitized_entry_path("docs/./notes.txt"),
+            Some(PathBuf::from("docs").join("notes.txt"))
+        );
+        assert_eq!(
+            sanitized_entry_path("docs\\notes.txt"),
+            Some(PathBuf::from("docs").join("notes.txt"))
+        );
+        assert_eq!(sanitized_entry_path("../escape.txt"), None);
+        assert_eq!(sanitized_entry_path("/etc/passwd"), None);
+        assert_eq!(sanitized_entry_path("."), None);
+    }
+
+    #[tokio::test]
+    async fn seven_zip_archives_are_extracted() {
+        let root = temp_case_dir("seven-zip");
+        let input_dir = root.join("input");
+        let plain_archive = root.join("plain.7z");
+        let encrypted_archive = root.join("secret.7z");
+        let output_dir = root.join("output");
+
+        write_text_file(&input_dir.join("docs").join("notes.txt"), "7z payload");
+        sevenz_rust::compress_to_path(&input_dir, &plain_archive).unwrap();
+        sevenz_rust::compress_to_path_encrypted(&input_dir, &encrypted_archive, "password".into())
+            .unwrap();
+
+        let extracted_dir = extract_archive_impl(
+            None,
+            Arc::default(),
+            plain_archive.to_string_lossy().to_string(),
+            output_dir.to_string_lossy().to_string(),
+            None,
+            None,
+            ExtractOptions::default(),
+        )
+        .await
+        .unwrap()
+        .output_dir;
+        assert_eq!(
+            fs::read_to_string(Path::new(&extracted_dir).join("docs").join("notes.txt")).unwrap(),
+            "7z payload"
+        );
+
+        let missing_password = extract_archive_impl(
+            None,
+            Arc::default(),
+            encrypted_archive.to_string_lossy().to_string(),
+            output_dir.to_string_lossy().to_string(),
+            None,
+            None,
+            ExtractOptions::default(),
+        )
+        .await
+        .unwrap_err();
+        assert!(missing_password.contains("请输入密码"));
+        assert!(!output_dir.join("secret").exists());
+
+        let extracted_dir = extract_archive_impl(
+            None,
+            Arc::default(),
+            encrypted_archive.to_string_lossy().to_string(),
+            output_dir.to_string_lossy().to_string(),
+            Some("password".to_string()),
+            None,
+            ExtractOptions::default(),
+        )
+        .await
+        .unwrap()
+        .output_dir;
+        assert_eq!(
+            fs::read_to_string(Path::new(&extracted_dir).join("docs").join("notes.txt")).unwrap(),
+            "7z payload"
+        );
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn traversal_entries_are_skipped_or_rejected() {
+        let root = temp_case_dir("traversal");
+        let archive_file = root.join("evil.krate");
+        let output_dir = root.join("output");
+        let outside_dir = output_dir.join("outside");
+        fs::create_dir_all(&outside_dir).unwrap();
+
+        let mut builder = tar::Builder::new(Vec::new());
+        append_raw_tar_entry(
+            &mut builder,
+            "ok.txt",
+            tar::EntryType::Regular,
+            None,
+            b"safe",
+        );
+        append_raw_tar_entry(
+            &mut builder,
+            "../escape.txt",
+            tar::EntryType::Regular,
+            None,
+            b"x",
+        );
+        append_raw_tar_entry(
+            &mut builder,
+            "/krate-absolute.txt",
+            tar::EntryType::Regular,
+            None,
+            b"x",
+        );
+        append_raw_tar_entry(
+            &mut builder,
+            "link",
+            tar::EntryType::Symlink,
+            Some("../outside"),
+            b"",
+        );
+        append_raw_tar_entry(
+            &mut builder,
+            "link/pwned.txt",
+            tar::EntryType::Regular,
+            None,
+            b"x",
+        );
+        append_raw_tar_entry(
+            &mut builder,
+            "hard.txt",
+            tar::EntryType::Link,
+            Some("../outside/target"),
+            b"",
+        );
+        write_raw_krate(&archive_file, &builder.into_inner().unwrap());
+
+        let report = extract_archive_impl(
+            None,
+            Arc::default(),
+            archive_file.to_string_lossy().to_string(),
+            output_dir.to_string_lossy().to_string(),
+            None,
+            None,
+            ExtractOptions {
+                allow_external_symlinks: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            report.skipped_entries,
+            vec![
+                "../escape.txt",
+                "/krate-absolute.txt",
+                "link/pwned.txt",
+                "hard.txt"
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(Path::new(&report.output_dir).join("ok.txt")).unwrap(),
+            "safe"
+        );
+        assert!(!outside_dir.join("pwned.txt").exists());
+        assert!(!output_dir.join("escape.txt").exists());
+        assert!(!Path::new("/krate-absolute.txt").exists());
+
+        let error = extract_archive_impl(
+            None,
+            Arc::default(),
+            archive_file.to_string_lossy().to_string(),
+            output_dir.to_string_lossy().to_string(),
+            None,
+            None,
+            ExtractOptions {
+                strict_security: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
+        assert!(error.contains("../escape.txt"));
+        assert!(!output_dir.join("evil (2)").exists());
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn key_check_rejects_other_keys() {
+        let stream_nonce = [7u8; STREAM_NONCE_LEN];
+        let mut metadata = EncryptionMetadata {
+            memory_kib: DEFAULT_ARGON2_MEMORY_KIB,
+            iterations: DEFAULT_ARGON2_ITERATIONS,
+            lanes: 1,
+            salt: [0u8; SALT_LEN],
+            stream_nonce,
+            key_check: None,
+        };
+        // 没有校验块的旧归档直接放行。
+        assert!(verify_key_check(&[1u8; KEY_LEN], &metadata).is_ok());
+
+        metadata.key_check = Some(compute_key_check(&[1u8; KEY_LEN], &stream_nonce).unwrap());
+        assert!(verify_key_check(&[1u8; KEY_LEN], &metadata).is_ok());
+        assert_eq!(
+            verify_key_check(&[2u8; KEY_LEN], &metadata).unwrap_err(),
+            WRONG_PASSWORD_ERROR
+        );
+    }
+
+    #[tokio::test]
+    async fn wrong_password_fails_before_writing_output() {
+        let root = temp_case_dir("key-check");
+        let input_file = root.join("input").join("secret.txt");
+        let archive_file = root.join("secret.krate");
+        let output_dir = root.join("output");
+
+        write_text_file(&input_file, "guarded");
+
+        create_archive_impl(
+            None,
+            Arc::default(),
+            vec![input_file.to_string_lossy().to_string()],
+            archive_file.to_string_lossy().to_string(),
+            Some("right-password".to_string()),
+            None,
+            CreateOptions {
+                level: Some(1),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let header_flags =
+            fs::read(&archive_file).unwrap()[MAGIC_HEADER.len() + FORMAT_MARKER.len()];
+        assert_ne!(header_flags & FLAG_KEY_CHECK, 0);
+
+        let error = extract_archive_impl(
+            None,
+            Arc::default(),
+            archive_file.to_string_lossy().to_string(),
+            output_dir.to_string_lossy().to_string(),
+            Some("wrong-password".to_string()),
+            None,
+            ExtractOptions::default(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error, WRONG_PASSWORD_ERROR);
+        assert_eq!(fs::read_dir(&output_dir).unwrap().count(), 0);
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn modes_and_mtimes_survive_roundtrip() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = temp_case_dir("metadata");
+        let input_dir = root.join("input");
+        let script = input_dir.join("bin").join("build.sh");
+        let archive_file = root.join("meta.krate");
+        let mtime = UNIX_EPOCH + std::time::Duration::from_secs(1_600_000_000);
+
+        write_text_file(&script, "#!/bin/sh\necho ok\n");
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        for path in [&script, &input_dir.join("bin")] {
+            File::open(path).unwrap().set_modified(mtime).unwrap();
+        }
+
+        create_archive_impl(
+            None,
+            Arc::default(),
+            vec![input_dir.to_string_lossy().to_string()],
+            archive_file.to_string_lossy().to_string(),
+            None,
+            None,
+            CreateOptions {
+                level: Some(1),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let preserved = PathBuf::from(
+            extract_archive_impl(
+                None,
+                Arc::default(),
+                archive_file.to_string_lossy().to_string(),
+                root.join("preserved").to_string_lossy().to_string(),
+                None,
+                None,
+                ExtractOptions::default(),
+            )
+            .await
+            .unwrap()
+            .output_dir,
+        );
+        let extracted_script = preserved.join("input").join("bin").join("build.sh");
+        let metadata = fs::metadata(&extracted_script).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o755);
+        assert_eq!(metadata.modified().unwrap(), mtime);
+        assert_eq!(
+            fs::metadata(preserved.join("input").join("bin"))
+                .unwrap()
+                .modified()
+                .unwrap(),
+            mtime
+        );
+
+        let plain = PathBuf::from(
+            extract_archive_impl(
+                None,
+                Arc::default(),
+                archive_file.to_string_lossy().to_string(),
+                root.join("plain").to_string_lossy().to_string(),
+                None,
+                None,
+                ExtractOptions {
+                    preserve_metadata: false,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap()
+            .output_dir,
+        );
+        let metadata = fs::metadata(plain.join("input").join("bin").join("build.sh")).unwrap();
+        assert_ne!(metadata.modified().unwrap(), mtime);
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn symlink_targets_are_checked_against_output_root() {
+        let root = temp_case_dir("symlink-inside");
+        fs::create_dir_all(root.join("a")).unwrap();
+        let output_root = root.canonicalize().unwrap();
+        let dir = root.join("a");
+
+        assert!(symlink_stays_inside(&output_root, &dir, "target.txt"));
+        assert!(symlink_stays_inside(&output_root, &dir, "../b/target.txt"));
+        assert!(!symlink_stays_inside(
+            &output_root,
+            &dir,
+            "../../target.txt"
+        ));
+        assert!(!symlink_stays_inside(&output_root, &root, "/etc/passwd"));
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn symlink_modes_control_packing_and_extraction() {
+        let root = temp_case_dir("symlink-modes");
+        let input_dir = root.join("input");
+        let real_file = input_dir.join("real.txt");
+
+        write_text_file(&real_file, "link target");
+        symlink(Path::new("real.txt"), input_dir.join("relative.txt")).unwrap();
+        symlink(&real_file, input_dir.join("absolute.txt")).unwrap();
+        symlink(Path::new("missing.txt"), input_dir.join("dangling.txt")).unwrap();
+        symlink(Path::new("."), input_dir.join("loop")).unwrap();
+
+        let pack = |mode: SymlinkMode| {
+            let archive_file = root.join(format!("{:?}.krate", mode));
+            let input = input_dir.to_string_lossy().to_string();
+            async move {
+                let report = create_archive_impl(
+                    None,
+                    Arc::default(),
+                    vec![input],
+                    archive_file.to_string_lossy().to_string(),
+                    None,
+                    None,
+                    CreateOptions {
+                        symlinks: mode,
+                        ..Default::default()
+                    },
+                )
+                .await
+                .unwrap();
+                (archive_file, report)
+            }
+        };
+        let extract = |archive_file: PathBuf, allow_external_symlinks: bool| {
+            let output_dir = root.join("output").to_string_lossy().to_string();
+            async move {
+                extract_archive_impl(
+                    None,
+                    Arc::default(),
+                    archive_file.to_string_lossy().to_string(),
+                    output_dir,
+                    None,
+                    None,
+                    ExtractOptions {
+                        allow_external_symlinks,
+                        ..Default::default()
+                    },
+                )
+                .await
+                .unwrap()
+            }
+        };
+
+        // preserve：相对链接与悬空链接原样还原，绝对链接默认被拒绝。
+        let (archive_file, report) = pack(SymlinkMode::Preserve).await;
+        assert!(report.skipped_entries.is_empty());
+        let extracted = extract(archive_file.clone(), false).await;
+        let extracted_dir = PathBuf::from(&extracted.output_dir).join("input");
+        assert_eq!(extracted.skipped_entries, vec!["input/absolute.txt"]);
+        assert_eq!(
+            fs::read_link(extracted_dir.join("relative.txt")).unwrap(),
+            PathBuf::from("real.txt")
+        );
+        assert_eq!(
+            fs::read_link(extracted_dir.join("dangling.txt")).unwrap(),
+            PathBuf::from("missing.txt")
+        );
+        let extracted = extract(archive_file, true).await;
+        assert!(extracted.skipped_entries.is_empty());
+        assert_eq!(
+            fs::read_link(
+                PathBuf::from(&extracted.output_dir)
+                    .join("input")
+                    .join("absolute.txt")
+            )
+            .unwrap(),
+            real_file
+        );
+
+        // follow：链接内容作为普通文件打包，悬空链接与循环链接被跳过。
+        let (archive_file, report) = pack(SymlinkMode::Follow).await;
+        assert_eq!(
+            report.skipped_entries,
+            vec![
+                input_dir.join("dangling.txt").display().to_string(),
+                input_dir.join("loop").display().to_string(),
+            ]
+        );
+        let extracted_dir =
+            PathBuf::from(extract(archive_file, false).await.output_dir).join("input");
+        for name in ["relative.txt", "absolute.txt"] {
+            let path = extracted_dir.join(name);
+            assert!(!fs::symlink_metadata(&path)
+                .unwrap()
+                .file_type()
+                .is_symlink());
+            assert_eq!(fs::read_to_string(&path).unwrap(), "link target");
+        }
+
+        // skip：所有链接都不进入归档。
+        let (archive_file, report) = pack(SymlinkMode::Skip).await;
+        assert_eq!(report.skipped_entries.len(), 4);
+        let extracted_dir =
+            PathBuf::from(extract(archive_file, false).await.output_dir).join("input");
+        assert_eq!(fs::read_dir(&extracted_dir).unwrap().count(), 1);
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn followed_directory_links_are_packed_and_counted() {
+        let root = temp_case_dir("follow-dirs");
+        let project_dir = root.join("project");
+        let shared_dir = root.join("shared-assets");
+        let archive_file = root.join("project.krate");
+
+        write_text_file(&project_dir.join("main.rs"), "fn main() {}");
+        write_text_file(&shared_dir.join("logo.svg"), "<svg/>");
+        write_text_file(&shared_dir.join("fonts").join("mono.ttf"), "font");
+        symlink(Path::new("../shared-assets"), project_dir.join("assets")).unwrap();
+        symlink(Path::new(".."), shared_dir.join("fonts").join("up")).unwrap();
+
+        let (report, extracted) = pack_and_extract(
+            &project_dir,
+            &archive_file,
+            &root.join("output"),
+            None,
+            CreateOptions {
+                symlinks: SymlinkMode::Follow,
+                ..Default::default()
+            },
+            ExtractOptions::default(),
+        )
+        .await;
+        // assets、logo.svg、fonts、mono.ttf；指回祖先的 up 作为循环链接被跳过。
+        assert_eq!(report.linked_entries, 4);
+        assert_eq!(
+            report.skipped_entries,
+            vec![project_dir
+                .join("assets")
+                .join("fonts")
+                .join("up")
+                .display()
+                .to_string()]
+        );
+
+        let assets_dir = PathBuf::from(extracted.output_dir)
+            .join("project")
+            .join("assets");
+        assert!(fs::symlink_metadata(&assets_dir).unwrap().is_dir());
+        assert_eq!(
+            fs::read_to_string(assets_dir.join("fonts").join("mono.ttf")).unwrap(),
+            "font"
+        );
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn hardlinks_are_stored_once_and_restored_as_links() {
+        use std::os::unix::fs::MetadataExt;
+
+        let root = temp_case_dir("hardlinks");
+        let input_dir = root.join("input");
+        let archive_file = root.join("links.krate");
+        let original = input_dir.join("original.bin");
+
+        let mut seed = 7u64;
+        let data = (0..256 * 1024)
+            .map(|_| {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+                (seed >> 56) as u8
+            })
+            .collect::<Vec<_>>();
+        fs::create_dir_all(input_dir.join("sub")).unwrap();
+        fs::write(&original, &data).unwrap();
+        fs::hard_link(&original, input_dir.join("copy.bin")).unwrap();
+        fs::hard_link(&original, input_dir.join("sub").join("again.bin")).unwrap();
+
+        let (_, report) = pack_and_extract(
+            &input_dir,
+            &archive_file,
+            &root.join("output"),
+            None,
+            CreateOptions {
+                compression: ArchiveCompression::None,
+                ..Default::default()
+            },
+            ExtractOptions {
+                verify_hashes: true,
+                ..Default::default()
+            },
+        )
+        .await;
+        let archive_len = fs::metadata(&archive_file).unwrap().len();
+        assert!(archive_len < 2 * data.len() as u64, "{archive_len}");
+        assert!(report.hash_mismatches.is_empty());
+        let extracted_dir = PathBuf::from(&report.output_dir).join("input");
+        let inodes = ["copy.bin", "original.bin", "sub/again.bin"]
+            .iter()
+            .map(|name| fs::metadata(extracted_dir.join(name)).unwrap().ino())
+            .collect::<Vec<_>>();
+        assert!(inodes.iter().all(|inode| *inode == inodes[0]));
+        assert_eq!(
+            fs::read(extracted_dir.join("sub").join("again.bin")).unwrap(),
+            data
+        );
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn unreadable_files_are_reported_when_skipping_errors() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = temp_case_dir("skip-errors");
+        let input_dir = root.join("input");
+        let locked = input_dir.join("locked.txt");
+        write_text_file(&input_dir.join("ok.txt"), "readable");
+        write_text_file(&locked, "secret");
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+        // 以 root 运行时权限位不生效，无法构造读取失败。
+        if File::open(&locked).is_ok() {
+            let _ = fs::remove_dir_all(root);
+            return;
+        }
+
+        let pack = |skip_errors: bool| {
+            create_archive_impl(
+                None,
+                Arc::default(),
+                vec![input_dir.to_string_lossy().to_string()],
+                root.join(format!("skip-{skip_errors}.krate"))
+                    .to_string_lossy()
+                    .to_string(),
+                None,
+                None,
+                CreateOptions {
+                    skip_errors,
+                    ..Default::default()
+                },
+            )
+        };
+
+        assert!(pack(false).await.is_err());
+        let report = pack(true).await.unwrap();
+        assert_eq!(report.warnings.len(), 1);
+        assert_eq!(report.warnings[0].path, locked.display().to_string());
+
+        let extracted = extract_archive_impl(
+            None,
+            Arc::default(),
+            root.join("skip-true.krate").to_string_lossy().to_string(),
+            root.join("output").to_string_lossy().to_string(),
+            None,
+            None,
+            ExtractOptions {
+                verify_hashes: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        let extracted_dir = PathBuf::from(extracted.output_dir).join("input");
+        assert!(extracted.hash_mismatches.is_empty());
+        assert!(extracted_dir.join("ok.txt").exists());
+        assert!(!extracted_dir.join("locked.txt").exists());
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn append_adds_entries_and_rejects_collisions() {
+        let root = temp_case_dir("append");
+        let first = root.join("input").join("monday.md");
+        let second = root.join("input").join("tuesday.md");
+        let archive_file = root.join("notes.krate");
+        let encrypted_file = root.join("secret.krate");
+
+        write_text_file(&first, "monday");
+        write_text_file(&second, "tuesday");
+
+        for (archive, password) in [(&archive_file, None), (&encrypted_file, Some("password"))] {
+            create_archive_impl(
+                None,
+                Arc::default(),
+                vec![first.to_string_lossy().to_string()],
+                archive.to_string_lossy().to_string(),
+                password.map(str::to_string),
+                None,
+                CreateOptions {
+                    compression: ArchiveCompression::Zstd,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        append_to_archive_impl(
+            None,
+            Arc::default(),
+            archive_file.to_string_lossy().to_string(),
+            vec![second.to_string_lossy().to_string()],
+        )
+        .await
+        .unwrap();
+
+        let before = fs::read(&archive_file).unwrap();
+        let collision = append_to_archive_impl(
+            None,
+            Arc::default(),
+            archive_file.to_string_lossy().to_string(),
+            vec![first.to_string_lossy().to_string()],
+        )
+        .await
+        .unwrap_err();
+        assert!(collision.contains("monday.md"));
+        assert_eq!(fs::read(&archive_file).unwrap(), before);
+
+        let encrypted = append_to_archive_impl(
+            None,
+            Arc::default(),
+            encrypted_file.to_string_lossy().to_string(),
+            vec![second.to_string_lossy().to_string()],
+        )
+        .await
+        .unwrap_err();
+        assert!(encrypted.contains("加密归档"));
+
+        let extracted_dir = PathBuf::from(
+            extract_archive_impl(
+                None,
+                Arc::default(),
+                archive_file.to_string_lossy().to_string(),
+                root.join("output").to_string_lossy().to_string(),
+                None,
+                None,
+                ExtractOptions::default(),
+            )
+            .await
+            .unwrap()
+            .output_dir,
+        );
+        assert_eq!(
+            fs::read_to_string(extracted_dir.join("monday.md")).unwrap(),
+            "monday"
+        );
+        assert_eq!(
+            fs::read_to_string(extracted_dir.join("tuesday.md")).unwrap(),
+            "tuesday"
+        );
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn archive_info_reports_metadata() {
+        let root = temp_case_dir("info");
+        let input_file = root.join("input").join("notes.txt");
+        let archive_file = root.join("notes.krate");
+
+        write_text_file(&input_file, "twelve bytes");
+
+        create_archive_impl(
+            None,
+            Arc::default(),
+            vec![input_file.to_string_lossy().to_string()],
+            archive_file.to_string_lossy().to_string(),
+            Some("password".to_string()),
+            None,
+            CreateOptions {
+                compression: ArchiveCompression::Xz,
+                comment: Some("weekly backup".to_string()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let info = archive_info(&archive_file, None, None).unwrap();
+        assert_eq!(info.version, "V003");
+        assert!(info.encrypted);
+        assert_eq!(info.compression, ArchiveCompression::Xz);
+        assert_eq!(info.file_size, fs::metadata(&archive_file).unwrap().len());
+        let metadata = info.metadata.unwrap();
+        assert_eq!(metadata.comment.as_deref(), Some("weekly backup"));
+        assert_eq!(metadata.total_bytes, 12);
+        assert_eq!(metadata.total_files, 1);
+        assert_eq!(metadata.app_version, env!("CARGO_PKG_VERSION"));
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn legacy_archives_without_metadata_are_readable() {
+        let root = temp_case_dir("legacy");
+        let archive_file = root.join("legacy.krate");
+        fs::create_dir_all(&root).unwrap();
+
+        let mut builder = tar::Builder::new(Vec::new());
+        append_raw_tar_entry(
+            &mut builder,
+            "old.txt",
+            tar::EntryType::Regular,
+            None,
+            b"v2",
+        );
+        let mut bytes = MAGIC_HEADER.to_vec();
+        bytes.extend_from_slice(LEGACY_FORMAT_MARKER);
+        bytes.extend_from_slice(&[0, COMPRESSION_NONE]);
+        bytes.extend_from_slice(&builder.into_inner().unwrap());
+        fs::write(&archive_file, bytes).unwrap();
+
+        let info = archive_info(&archive_file, None, None).unwrap();
+        assert_eq!(info.version, "V002");
+        assert!(info.metadata.is_none());
+
+        let extracted_dir = extract_archive_impl(
+            None,
+            Arc::default(),
+            archive_file.to_string_lossy().to_string(),
+            root.join("output").to_string_lossy().to_string(),
+            None,
+            None,
+            ExtractOptions::default(),
+        )
+        .await
+        .unwrap()
+        .output_dir;
+        assert_eq!(
+            fs::read_to_string(Path::new(&extracted_dir).join("old.txt")).unwrap(),
+            "v2"
+        );
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn manifest_hashes_detect_tampered_entries() {
+        let root = temp_case_dir("manifest");
+        let input_dir = root.join("input");
+        let archive_file = root.join("input.krate");
+
+        write_text_file(&input_dir.join("a.txt"), "original");
+        write_text_file(&input_dir.join("nested").join("b.txt"), "nested");
+
+        create_archive_impl(
+            None,
+            Arc::default(),
+            vec![input_dir.to_string_lossy().to_string()],
+            archive_file.to_string_lossy().to_string(),
+            Some("password".to_string()),
+            None,
+            CreateOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        let verified = verify_archive_impl(
+            None,
+            Arc::default(),
+            archive_file.to_string_lossy().to_string(),
+            Some("password".to_string()),
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(verified.checked_files, 2);
+        assert!(verified.hash_mismatches.is_empty());
+
+        let report = extract_archive_impl(
+            None,
+            Arc::default(),
+            archive_file.to_string_lossy().to_string(),
+            root.join("output").to_string_lossy().to_string(),
+            Some("password".to_string()),
+            None,
+            ExtractOptions {
+                verify_hashes: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(report.hash_mismatches.is_empty());
+        let output_dir = Path::new(&report.output_dir);
+        assert!(!output_dir.join(MANIFEST_ENTRY_NAME).exists());
+        assert_eq!(
+            fs::read_to_string(output_dir.join("input").join("a.txt")).unwrap(),
+            "original"
+        );
+
+        let with_manifest = extract_archive_impl(
+            None,
+            Arc::default(),
+            archive_file.to_string_lossy().to_string(),
+            root.join("with-manifest").to_string_lossy().to_string(),
+            Some("password".to_string()),
+            None,
+            ExtractOptions {
+                include_manifest: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        let manifest: ArchiveManifest = serde_json::from_slice(
+            &fs::read(Path::new(&with_manifest.output_dir).join(MANIFEST_ENTRY_NAME)).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            manifest.files.keys().collect::<Vec<_>>(),
+            vec!["input/a.txt", "input/nested/b.txt"]
+        );
+
+        let tampered_file = root.join("tampered.krate");
+        let mut builder = tar::Builder::new(Vec::new());
+        append_raw_tar_entry(
+            &mut builder,
+            "input/a.txt",
+            tar::EntryType::Regular,
+            None,
+            b"tampered",
+        );
+        append_manifest(&mut builder, &manifest).unwrap();
+        write_raw_krate(&tampered_file, &builder.into_inner().unwrap());
+
+        let verified = verify_archive_impl(
+            None,
+            Arc::default(),
+            tampered_file.to_string_lossy().to_string(),
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            verified.hash_mismatches,
+            vec!["input/a.txt", "input/nested/b.txt"]
+        );
+
+        let report = extract_archive_impl(
+            None,
+            Arc::default(),
+            tampered_file.to_string_lossy().to_string(),
+            root.join("tampered").to_string_lossy().to_string(),
+            None,
+            None,
+            ExtractOptions {
+                verify_hashes: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            report.hash_mismatches,
+            vec!["input/a.txt", "input/nested/b.txt"]
+        );
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn volumes_split_and_extract_in_order() {
+        let root = temp_case_dir("volumes");
+        let input_file = root.join("input").join("large.bin");
+        let archive_file = root.join("large.krate");
+
+        let mut seed = 7u32;
+        let data = (0..MIN_VOLUME_SIZE as usize * 5 / 2)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (seed >> 24) as u8
+            })
+            .collect::<Vec<_>>();
+        fs::create_dir_all(input_file.parent().unwrap()).unwrap();
+        fs::write(&input_file, &data).unwrap();
+
+        assert!(create_archive_impl(
+            None,
+            Arc::default(),
+            vec![input_file.to_string_lossy().to_string()],
+            archive_file.to_string_lossy().to_string(),
+            None,
+            None,
+            CreateOptions {
+                volume_size: Some(MIN_VOLUME_SIZE - 1),
+                ..Default::default()
+            },
+        )
+        .await
+        .is_err());
+
+        create_archive_impl(
+            None,
+            Arc::default(),
+            vec![input_file.to_string_lossy().to_string()],
+            archive_file.to_string_lossy().to_string(),
+            Some("password".to_string()),
+            None,
+            CreateOptions {
+                compression: ArchiveCompression::None,
+                volume_size: Some(MIN_VOLUME_SIZE),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let first_part = volume_path(&archive_file, 1);
+        assert!(!archive_file.exists());
+        assert_eq!(fs::metadata(&first_part).unwrap().len(), MIN_VOLUME_SIZE);
+        assert_eq!(
+            fs::metadata(volume_path(&archive_file, 2)).unwrap().len(),
+            MIN_VOLUME_SIZE
+        );
+        assert!(volume_path(&archive_file, 3).exists());
+        assert!(!volume_path(&archive_file, 4).exists());
+        assert_eq!(
+            archive_info(&first_part, None, None).unwrap().volume_size,
+            Some(MIN_VOLUME_SIZE)
+        );
+
+        let report = extract_archive_impl(
+            None,
+            Arc::default(),
+            first_part.to_string_lossy().to_string(),
+            root.join("output").to_string_lossy().to_string(),
+            Some("password".to_string()),
+            None,
+            ExtractOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert!(report.output_dir.ends_with("large"));
+        assert_eq!(
+            fs::read(Path::new(&report.output_dir).join("large.bin")).unwrap(),
+            data
+        );
+
+        fs::remove_file(volume_path(&archive_file, 3)).unwrap();
+        let err = extract_archive_impl(
+            None,
+            Arc::default(),
+            first_part.to_string_lossy().to_string(),
+            root.join("missing").to_string_lossy().to_string(),
+            Some("password".to_string()),
+            None,
+            ExtractOptions::default(),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.contains("large.krate.003"), "{err}");
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn change_password_reencrypts_without_touching_payload() {
+        let root = temp_case_dir("rekey");
+        let input_file = root.join("input").join("secret.txt");
+        let archive_file = root.join("secret.krate");
+
+        write_text_file(&input_file, "rotate me");
+
+        create_archive_impl(
+            None,
+            Arc::default(),
+            vec![input_file.to_string_lossy().to_string()],
+            archive_file.to_string_lossy().to_string(),
+            Some("old-password".to_string()),
+            None,
+            CreateOptions {
+                comment: Some("keep me".to_string()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        let original = fs::read(&archive_file).unwrap();
+
+        let err = change_archive_password_impl(
+            None,
+            Arc::default(),
+            archive_file.to_string_lossy().to_string(),
+            "wrong-password".to_string(),
+            "new-password".to_string(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err, WRONG_PASSWORD_ERROR);
+        assert_eq!(fs::read(&archive_file).unwrap(), original);
+
+        change_archive_password_impl(
+            None,
+            Arc::default(),
+            archive_file.to_string_lossy().to_string(),
+            "old-password".to_string(),
+            "new-password".to_string(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(fs::read_dir(&root).unwrap().count(), 2);
+        assert_eq!(
+            archive_info(&archive_file, None, None)
+                .unwrap()
+                .metadata
+                .unwrap()
+                .comment
+                .as_deref(),
+            Some("keep me")
+        );
+
+        for (password, expected) in [("old-password", false), ("new-password", true)] {
+            let result = extract_archive_impl(
+                None,
+                Arc::default(),
+                archive_file.to_string_lossy().to_string(),
+                root.join(password).to_string_lossy().to_string(),
+                Some(password.to_string()),
+                None,
+                ExtractOptions::default(),
+            )
+            .await;
+            assert_eq!(result.is_ok(), expected);
+            if let Ok(report) = result {
+                assert_eq!(
+                    fs::read_to_string(Path::new(&report.output_dir).join("secret.txt")).unwrap(),
+                    "rotate me"
+                );
+            }
+        }
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn key_files_unlock_archives_alone_or_with_password() {
+        let root = temp_case_dir("keyfile");
+        let input_file = root.join("input").join("backup.txt");
+        let key_file = root.join("backup.key");
+        let other_key_file = root.join("other.key");
+
+        write_text_file(&input_file, "keyed");
+        fs::write(&key_file, [7u8; KEY_LEN]).unwrap();
+        fs::write(&other_key_file, [8u8; KEY_LEN]).unwrap();
+
+        let path = |path: &Path| Some(path.to_string_lossy().to_string());
+        let cases = [
+            ("keyfile", None, false),
+            ("raw", None, true),
+            ("two-factor", Some("password"), false),
+        ];
+        for (name, password, raw_key) in cases {
+            let archive_file = root.join(format!("{name}.krate"));
+            create_archive_impl(
+                None,
+                Arc::default(),
+                vec![input_file.to_string_lossy().to_string()],
+                archive_file.to_string_lossy().to_string(),
+                password.map(str::to_string),
+                path(&key_file),
+                CreateOptions {
+                    raw_key,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+            let extract = |password: Option<&str>, key_file: Option<String>| {
+                extract_archive_impl(
+                    None,
+                    Arc::default(),
+                    archive_file.to_string_lossy().to_string(),
+                    root.join("output").to_string_lossy().to_string(),
+                    password.map(str::to_string),
+                    key_file,
+                    ExtractOptions::default(),
+                )
+            };
+
+            assert!(extract(password, None)
+                .await
+                .unwrap_err()
+                .contains("密钥文件"));
+            assert!(extract(password, path(&root.join("missing.key")))
+                .await
+                .unwrap_err()
+                .contains("密钥文件不存在"));
+            let wrong_key = extract(password, path(&other_key_file)).await.unwrap_err();
+            if password.is_some() {
+                assert_eq!(wrong_key, WRONG_CREDENTIALS_ERROR);
+                assert!(extract(None, path(&key_file)).await.is_err());
+            } else {
+                assert_eq!(wrong_key, WRONG_KEY_FILE_ERROR);
+            }
+
+            let report = extract(password, path(&key_file)).await.unwrap();
+            assert_eq!(
+                fs::read_to_string(Path::new(&report.output_dir).join("backup.txt")).unwrap(),
+                "keyed"
+            );
+        }
+
+        write_text_file(&root.join("short.key"), "short");
+        let err = create_archive_impl(
+            None,
+            Arc::default(),
+            vec![input_file.to_string_lossy().to_string()],
+            root.join("short.krate").to_string_lossy().to_string(),
+            None,
+            path(&root.join("short.key")),
+            CreateOptions {
+                raw_key: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
+        assert!(err.contains("32"), "{err}");
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn custom_kdf_parameters_are_stored_and_used() {
+        let root = temp_case_dir("kdf");
+        let input_file = root.join("input").join("vault.txt");
+        let archive_file = root.join("vault.krate");
+
+        write_text_file(&input_file, "expensive");
+
+        let err = create_archive_impl(
+            None,
+            Arc::default(),
+            vec![input_file.to_string_lossy().to_string()],
+            archive_file.to_string_lossy().to_string(),
+            Some("password".to_string()),
+            None,
+            CreateOptions {
+                kdf: KdfOptions {
+                    memory_kib: Some(3 * 1024 * 1024),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
+        assert!(err.contains("2 GiB"), "{err}");
+        assert!(!archive_file.exists());
+
+        create_archive_impl(
+            None,
+            Arc::default(),
+            vec![input_file.to_string_lossy().to_string()],
+            archive_file.to_string_lossy().to_string(),
+            Some("password".to_string()),
+            None,
+            CreateOptions {
+                kdf: KdfOptions {
+                    memory_kib: Some(16 * 1024),
+                    iterations: Some(3),
+                    parallelism: Some(2),
+                },
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let mut reader = BufReader::new(File::open(&archive_file).unwrap());
+        let (header, _) = read_archive_preamble(&mut reader).unwrap();
+        let metadata = header.encryption.unwrap();
+        assert_eq!(
+            (metadata.memory_kib, metadata.iterations, metadata.lanes),
+            (16 * 1024, 3, 2)
+        );
+
+        let report = extract_archive_impl(
+            None,
+            Arc::default(),
+            archive_file.to_string_lossy().to_string(),
+            root.join("output").to_string_lossy().to_string(),
+            Some("password".to_string()),
+            None,
+            ExtractOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(Path::new(&report.output_dir).join("vault.txt")).unwrap(),
+            "expensive"
+        );
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn parallel_gzip_extracts_like_single_threaded() {
+        let root = temp_case_dir("parallel");
+        let input_dir = root.join("input");
+
+        let mut seed = 11u32;
+        let noise = (0..PARALLEL_GZIP_BLOCK_SIZE * 3 / 2)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (seed >> 24) as u8
+            })
+            .collect::<Vec<_>>();
+        fs::create_dir_all(&input_dir).unwrap();
+        fs::write(input_dir.join("noise.bin"), &noise).unwrap();
+        write_text_file(
+            &input_dir.join("text.txt"),
+            &"parallel gzip block\n".repeat(PARALLEL_GZIP_BLOCK_SIZE / 10),
+        );
+        write_text_file(&input_dir.join("small.txt"), "small");
+
+        let mut outputs = Vec::new();
+        for threads in [1, 4] {
+            let (_, report) = pack_and_extract(
+                &input_dir,
+                &root.join(format!("threads-{threads}.krate")),
+                &root.join(format!("output-{threads}")),
+                Some("password"),
+                CreateOptions {
+                    level: Some(1),
+                    threads: Some(threads),
+                    ..Default::default()
+                },
+                ExtractOptions {
+                    verify_hashes: true,
+                    ..Default::default()
+                },
+            )
+            .await;
+            assert!(report.hash_mismatches.is_empty());
+
+            let extracted = Path::new(&report.output_dir).join("input");
+            outputs.push(
+                ["noise.bin", "text.txt", "small.txt"]
+                    .map(|name| fs::read(extracted.join(name)).unwrap()),
+            );
+        }
+
+        assert_eq!(outputs[0], outputs[1]);
+        assert_eq!(outputs[1][0], noise);
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn estimate_extrapolates_from_samples() {
+        let root = temp_case_dir("estimate");
+        let input_dir = root.join("input");
+
+        let mut seed = 3u32;
+        let noise = (0..ESTIMATE_CHUNK_SIZE * 4)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (seed >> 24) as u8
+            })
+            .collect::<Vec<_>>();
+        fs::create_dir_all(&input_dir).unwrap();
+        fs::write(input_dir.join("noise.bin"), &noise).unwrap();
+        for index in 0..20 {
+            write_text_file(
+                &input_dir.join(format!("log-{index}.txt")),
+                &"estimate me\n".repeat(20_000),
+            );
+        }
+
+        let estimate = estimate_archive_impl(
+            vec![input_dir.to_string_lossy().to_string()],
+            ArchiveCompression::Gzip,
+            None,
+            Some(ESTIMATE_CHUNK_SIZE * 4),
+        )
+        .unwrap();
+        assert_eq!(estimate.total_files, 21);
+        assert_eq!(estimate.total_bytes, noise.len() as u64 + 20 * 240_000);
+        assert!(estimate.sampled_bytes <= ESTIMATE_CHUNK_SIZE * 4);
+        assert!(estimate.estimated_low_bytes <= estimate.estimated_bytes);
+        assert!(estimate.estimated_bytes <= estimate.estimated_high_bytes);
+        assert!(estimate.estimated_bytes < estimate.total_bytes);
+        assert!(estimate.estimated_bytes > noise.len() as u64 / 2);
+
+        let exact = estimate_archive_impl(
+            vec![input_dir.join("noise.bin").to_string_lossy().to_string()],
+            ArchiveCompression::None,
+            None,
+            Some(MAX_ESTIMATE_SAMPLE_BYTES),
+        )
+        .unwrap();
+        assert_eq!(exact.sampled_bytes, noise.len() as u64);
+        assert_eq!(exact.estimated_low_bytes, exact.estimated_high_bytes);
+        assert_eq!(
+            exact.estimated_bytes,
+            noise.len() as u64 + ESTIMATE_TAR_OVERHEAD_PER_FILE
+        );
+
+        let _ = fs::remove_dir_all(root);
     }
 
-    let archive_inputs = build_archive_inputs(&inputs)?;
-    let output_path = absolute_path(Path::new(&output_path))?;
-    ensure_output_path_is_safe(&archive_inputs, &output_path)?;
-    let temp_output_path = unique_temp_output_path(&output_path)?;
+    #[tokio::test]
+    async fn entries_can_be_previewed_without_extracting() {
+        let root = temp_case_dir("preview");
+        let input_dir = root.join("input");
+        let archive_file = root.join("preview.krate");
 
-    let result = (|| -> Result<(), String> {
-        let stats = collect_input_stats(&archive_inputs)?;
-        let mut tracker = ArchiveProgressTracker::new("pack", "准备归档", stats.total_bytes);
-        tracker.set_stage(window, "准备归档", "正在准备归档");
+        write_text_file(&input_dir.join("notes.txt"), "hello preview");
+        write_text_file(&input_dir.join("data").join("config.json"), "{\"a\":1}");
+        write_text_file(&input_dir.join("large.log"), &"x".repeat(4096));
 
-        let normalized_password = normalized_password(password);
-        let header = if normalized_password.is_some() {
-            ArchiveHeader::new_encrypted()?
-        } else {
-            ArchiveHeader::new_plain()
+        create_archive_impl(
+            None,
+            Arc::default(),
+            vec![input_dir.to_string_lossy().to_string()],
+            archive_file.to_string_lossy().to_string(),
+            Some("password".to_string()),
+            None,
+            CreateOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        let preview = |entry: &str, max_bytes: Option<u64>| {
+            read_archive_entry_impl(
+                archive_file.to_string_lossy().to_string(),
+                entry.to_string(),
+                Some("password".to_string()),
+                None,
+                max_bytes,
+            )
         };
-        let level = gzip_level.unwrap_or(DEFAULT_GZIP_LEVEL);
-        let progress_message = if normalized_password.is_some() {
-            "正在压缩并加密"
-        } else {
-            "正在压缩打包"
+        let decode = |data: &str| {
+            base64::engine::general_purpose::STANDARD
+                .decode(data)
+                .unwrap()
         };
 
-        let file = OpenOptions::new()
-            .create_new(true)
-            .write(true)
-            .open(&temp_output_path)
-            .map_err(|err| err.to_string())?;
-        let mut writer = BufWriter::new(file);
-        let aad = write_archive_header(&mut writer, &header)?;
-
-        tracker.set_stage(window, progress_message, progress_message);
-
-        if let (Some(password), Some(metadata)) =
-            (normalized_password.as_deref(), header.encryption.as_ref())
-        {
-            let key = derive_archive_key(password, metadata)?;
-            let payload_writer =
-                EncryptedPayloadWriter::new(writer, key, metadata.stream_nonce, aad);
-            let compressor = GzEncoder::new(payload_writer, Compression::new(level));
-            let mut tar = tar::Builder::new(compressor);
-            tar.follow_symlinks(false);
+        let notes = preview("./input/notes.txt", None).unwrap();
+        assert_eq!(notes.path, "input/notes.txt");
+        assert_eq!(notes.mime_type, "text/plain");
+        assert!(!notes.truncated);
+        assert_eq!(decode(&notes.data), b"hello preview");
 
-            append_inputs_to_tar(
-                &mut tar,
-                &archive_inputs,
-                &mut tracker,
-                window,
-                progress_message,
-            )?;
+        let config = preview("input/data/config.json", None).unwrap();
+        assert_eq!(config.mime_type, "application/json");
 
-            let compressor = tar
-                .into_inner()
-                .map_err(|err| format!("Tar finish failed: {}", err))?;
-            let payload_writer = compressor
-                .finish()
-                .map_err(|err| format!("Gzip finish failed: {}", err))?;
-            let mut writer = payload_writer.finish().map_err(|err| err.to_string())?;
-            writer.flush().map_err(|err| err.to_string())?;
-            tracker.finish(window, "归档完成", "归档完成");
-            return Ok(());
-        }
+        let large = preview("input/large.log", Some(16)).unwrap();
+        assert_eq!(large.size, 4096);
+        assert!(large.truncated);
+        assert_eq!(decode(&large.data), b"xxxxxxxxxxxxxxxx");
 
-        let compressor = GzEncoder::new(writer, Compression::new(level));
-        let mut tar = tar::Builder::new(compressor);
-        tar.follow_symlinks(false);
+        let err = preview("input/note.txt", None).unwrap_err();
+        assert!(err.contains("input/notes.txt"), "{err}");
+        let err = preview("config.json", None).unwrap_err();
+        assert!(err.contains("input/data/config.json"), "{err}");
 
-        append_inputs_to_tar(
-            &mut tar,
-            &archive_inputs,
-            &mut tracker,
-            window,
-            progress_message,
-        )?;
+        let _ = fs::remove_dir_all(root);
+    }
 
-        let compressor = tar
-            .into_inner()
-            .map_err(|err| format!("Tar finish failed: {}", err))?;
-        let mut writer = compressor
-            .finish()
-            .map_err(|err| format!("Gzip finish failed: {}", err))?;
-        writer.flush().map_err(|err| err.to_string())?;
+    #[tokio::test]
+    async fn standard_tarballs_are_extracted_safely() {
+        let root = temp_case_dir("tarball");
+        let output_dir = root.join("output");
+        fs::create_dir_all(&root).unwrap();
 
-        tracker.finish(window, "归档完成", "归档完成");
-        Ok(())
-    })();
+        let mut builder = tar::Builder::new(Vec::new());
+        append_raw_tar_entry(
+            &mut builder,
+            "docs/readme.txt",
+            tar::EntryType::Regular,
+            None,
+            b"from tarball",
+        );
+        append_raw_tar_entry(
+            &mut builder,
+            "../escape.txt",
+            tar::EntryType::Regular,
+            None,
+            b"x",
+        );
+        let tar_bytes = builder.into_inner().unwrap();
 
-    if let Err(err) = result {
-        let _ = fs::remove_file(&temp_output_path);
-        return Err(err);
-    }
+        let gzip_file = root.join("release.tar.gz");
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&tar_bytes).unwrap();
+        fs::write(&gzip_file, encoder.finish().unwrap()).unwrap();
+        let plain_file = root.join("release.tar");
+        fs::write(&plain_file, &tar_bytes).unwrap();
 
-    if let Err(err) = persist_temp_output(&temp_output_path, &output_path) {
-        let _ = fs::remove_file(&temp_output_path);
-        return Err(err);
-    }
+        assert_eq!(
+            detect_tarball(&gzip_file).unwrap(),
+            Some(ArchiveCompression::Gzip)
+        );
+        assert_eq!(
+            detect_tarball(&plain_file).unwrap(),
+            Some(ArchiveCompression::None)
+        );
 
-    Ok(())
-}
+        for archive_file in [&gzip_file, &plain_file] {
+            let report = extract_archive_impl(
+                None,
+                Arc::default(),
+                archive_file.to_string_lossy().to_string(),
+                output_dir.to_string_lossy().to_string(),
+                None,
+                None,
+                ExtractOptions::default(),
+            )
+            .await
+            .unwrap();
 
-async fn extract_archive_impl(
-    window: Option<&Window>,
-    archive_path: String,
-    output_dir: String,
-    password: Option<String>,
-) -> Result<String, String> {
-    let normalized_password = normalized_password(password);
-    let archive_path = absolute_path(Path::new(&archive_path))?;
-    let output_parent = absolute_path(Path::new(&output_dir))?;
-    let extract_root = prepare_extract_output_dir(&archive_path, &output_parent)?;
-    let total_bytes = fs::metadata(&archive_path)
-        .map_err(|err| err.to_string())?
-        .len();
-    let mut tracker = ArchiveProgressTracker::new("extract", "读取归档头", total_bytes);
-    tracker.set_stage(window, "读取归档头", "正在读取归档头");
+            let extracted = PathBuf::from(&report.output_dir);
+            // 第二次解压时 `release` 已存在，目录名会带上序号。
+            assert!(extracted
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .starts_with("release"));
+            assert_eq!(
+                fs::read_to_string(extracted.join("docs").join("readme.txt")).unwrap(),
+                "from tarball"
+            );
+            assert_eq!(report.skipped_entries, vec!["../escape.txt".to_string()]);
+        }
+        assert!(!output_dir.join("escape.txt").exists());
 
-    let file = File::open(&archive_path).map_err(|err| err.to_string())?;
-    let buffered = BufReader::new(file);
-    let mut progress_reader = ProgressReader::new(buffered, &mut tracker, window, "正在读取归档头");
+        let preview = read_archive_entry_impl(
+            gzip_file.to_string_lossy().to_string(),
+            "docs/readme.txt".to_string(),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(preview.size, 12);
 
-    let mut magic = [0u8; MAGIC_HEADER.len()];
-    if progress_reader.read_exact(&mut magic).is_err() || magic != *MAGIC_HEADER {
-        return Err("文件损坏或格式不正确：无法识别的 Krate 包".to_string());
+        let _ = fs::remove_dir_all(root);
     }
 
-    let mut marker = [0u8; FORMAT_MARKER.len()];
-    progress_reader
-        .read_exact(&mut marker)
-        .map_err(|err| err.to_string())?;
+    #[tokio::test]
+    async fn encrypted_zip_entries_are_decrypted_or_reported() {
+        // ZipCrypto 写入接口位于 unstable 模块。
+        use zip::unstable::write::FileOptionsExt;
 
-    if marker != *FORMAT_MARKER {
-        return Err("不支持的 .krate 版本，请使用当前版本重新生成归档".to_string());
-    }
+        let root = temp_case_dir("zip-encrypted");
+        let archive_file = root.join("mixed.zip");
+        let output_dir = root.join("output");
+        fs::create_dir_all(&root).unwrap();
+
+        let mut writer = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default();
+        writer.start_file("plain.txt", options).unwrap();
+        writer.write_all(b"plain").unwrap();
+        writer
+            .start_file(
+                "aes.txt",
+                options.with_aes_encryption(zip::AesMode::Aes256, "secret"),
+            )
+            .unwrap();
+        writer.write_all(b"aes").unwrap();
+        writer
+            .start_file("legacy.txt", options.with_deprecated_encryption(b"secret"))
+            .unwrap();
+        writer.write_all(b"legacy").unwrap();
+        fs::write(&archive_file, writer.finish().unwrap().into_inner()).unwrap();
+
+        let extract = |password: Option<&str>| {
+            extract_archive_impl(
+                None,
+                Arc::default(),
+                archive_file.to_string_lossy().to_string(),
+                output_dir.to_string_lossy().to_string(),
+                password.map(str::to_string),
+                None,
+                ExtractOptions::default(),
+            )
+        };
 
-    let header = read_archive_header(&mut progress_reader)?;
+        let partial = extract(None).await.unwrap();
+        let partial_dir = PathBuf::from(&partial.output_dir);
+        assert_eq!(
+            fs::read_to_string(partial_dir.join("plain.txt")).unwrap(),
+            "plain"
+        );
+        assert!(!partial_dir.join("aes.txt").exists());
+        assert_eq!(
+            partial.password_required_entries,
+            vec!["aes.txt".to_string(), "legacy.txt".to_string()]
+        );
 
-    if let Some(metadata) = header.encryption.as_ref() {
-        let password = normalized_password
-            .as_deref()
-            .ok_or("该 .krate 归档已加密，请输入密码后再解压".to_string())?;
-        let key = derive_archive_key(password, metadata)?;
-        progress_reader.message = "正在校验密码并解压";
-        progress_reader.tracker.set_stage(
-            progress_reader.window,
-            "正在校验密码并解压",
-            "正在校验密码并解压",
+        let full = extract(Some("secret")).await.unwrap();
+        let full_dir = PathBuf::from(&full.output_dir);
+        assert_eq!(fs::read_to_string(full_dir.join("aes.txt")).unwrap(), "aes");
+        assert_eq!(
+            fs::read_to_string(full_dir.join("legacy.txt")).unwrap(),
+            "legacy"
         );
+        assert!(full.password_required_entries.is_empty());
 
-        let mut payload_reader = EncryptedPayloadReader::new(
-            progress_reader,
-            key,
-            metadata.stream_nonce,
-            header.aad_bytes(),
+        let err = extract(Some("wrong")).await.unwrap_err();
+        assert!(err.contains("密码错误"), "{err}");
+
+        let (gbk_name, _, _) = encoding_rs::GBK.encode("中文/说明.txt");
+        assert_eq!(
+            decode_zip_entry_name(&gbk_name, "mojibake"),
+            "中文/说明.txt"
         );
-        payload_reader.prime().map_err(|err| err.to_string())?;
-        extract_archive_contents(payload_reader, &extract_root)?;
-    } else {
-        progress_reader.message = "正在解压归档";
-        progress_reader
-            .tracker
-            .set_stage(progress_reader.window, "正在解压归档", "正在解压归档");
-        extract_archive_contents(progress_reader, &extract_root)?;
-    }
 
-    tracker.finish(window, "解压完成", "解压完成");
-    Ok(extract_root.to_string_lossy().to_string())
-}
+        let _ = fs::remove_dir_all(root);
+    }
 
-#[command]
-pub async fn create_archive(
-    window: Window,
-    inputs: Vec<String>,
-    output_path: String,
-    password: Option<String>,
-    gzip_level: Option<u32>,
-) -> Result<(), String> {
-    create_archive_impl(Some(&window), inputs, output_path, password, gzip_level).await
-}
+    #[tokio::test]
+    async fn krate_archives_convert_to_standard_tar_gz() {
+        let root = temp_case_dir("convert");
+        let input_dir = root.join("input");
+        let output_dir = root.join("output");
+        write_text_file(&input_dir.join("nested").join("data.txt"), "convert me");
+
+        for (name, compression, password) in [
+            ("plain", ArchiveCompression::Gzip, None),
+            ("encrypted", ArchiveCompression::Zstd, Some("password")),
+        ] {
+            let archive_file = root.join(format!("{name}.krate"));
+            let tarball = root.join(format!("{name}.tar.gz"));
+            create_archive_impl(
+                None,
+                Arc::default(),
+                vec![input_dir.to_string_lossy().to_string()],
+                archive_file.to_string_lossy().to_string(),
+                password.map(str::to_string),
+                None,
+                CreateOptions {
+                    compression,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
 
-#[command]
-pub async fn extract_archive(
-    window: Window,
-    archive_path: String,
-    output_dir: String,
-    password: Option<String>,
-) -> Result<String, String> {
-    extract_archive_impl(Some(&window), archive_path, output_dir, password).await
-}
+            convert_archive_impl(
+                None,
+                Arc::default(),
+                archive_file.to_string_lossy().to_string(),
+                tarball.to_string_lossy().to_string(),
+                password.map(str::to_string),
+                None,
+                false,
+            )
+            .await
+            .unwrap();
 
-#[command]
-pub async fn open_output_dir(path: String) -> Result<(), String> {
-    let target = Path::new(&path);
+            assert_eq!(
+                detect_tarball(&tarball).unwrap(),
+                Some(ArchiveCompression::Gzip)
+            );
+            let report = extract_archive_impl(
+                None,
+                Arc::default(),
+                tarball.to_string_lossy().to_string(),
+                output_dir.to_string_lossy().to_string(),
+                None,
+                None,
+                ExtractOptions::default(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(
+                fs::read_to_string(
+                    PathBuf::from(report.output_dir)
+                        .join("input")
+                        .join("nested")
+                        .join("data.txt")
+                )
+                .unwrap(),
+                "convert me"
+            );
+        }
 
-    if !target.exists() {
-        return Err("输出目录不存在".to_string());
+        let _ = fs::remove_dir_all(root);
     }
 
-    if !target.is_dir() {
-        return Err("目标路径不是文件夹".to_string());
-    }
+    #[tokio::test]
+    async fn incremental_chain_restores_latest_tree() {
+        let root = temp_case_dir("incremental");
+        let input_dir = root.join("input");
+        let output_dir = root.join("output");
+        let full_archive = root.join("full.krate");
+        let first_increment = root.join("inc1.krate");
+        let second_increment = root.join("inc2.krate");
+        write_text_file(&input_dir.join("a.txt"), "old");
+        write_text_file(&input_dir.join("b.txt"), "removed later");
+        write_text_file(&input_dir.join("sub").join("c.txt"), "unchanged");
+        write_text_file(
+            &input_dir.join("gone").join("e.txt"),
+            "removed with its folder",
+        );
 
-    #[cfg(target_os = "windows")]
-    let mut command = {
-        let mut cmd = Command::new("explorer");
-        cmd.arg(target);
-        cmd
-    };
+        let pack = |output: &Path, base: Option<PathBuf>, compare_hashes: bool| {
+            create_archive_impl(
+                None,
+                Arc::default(),
+                vec![input_dir.to_string_lossy().to_string()],
+                output.to_string_lossy().to_string(),
+                Some("password".to_string()),
+                None,
+                CreateOptions {
+                    incremental: Some(IncrementalOptions {
+                        base_manifest: base,
+                        compare_hashes,
+                    }),
+                    ..Default::default()
+                },
+            )
+        };
 
-    #[cfg(target_os = "macos")]
-    let mut command = {
-        let mut cmd = Command::new("open");
-        cmd.arg(target);
-        cmd
-    };
+        let full = pack(&full_archive, None, false).await.unwrap();
+        assert_eq!(full.unchanged_files, 0);
+        assert!(incremental_manifest_path(&full_archive).is_file());
+
+        write_text_file(&input_dir.join("a.txt"), "new content");
+        fs::remove_file(input_dir.join("b.txt")).unwrap();
+        fs::remove_dir_all(input_dir.join("gone")).unwrap();
+        write_text_file(&input_dir.join("d.txt"), "added");
+        let first = pack(
+            &first_increment,
+            Some(incremental_manifest_path(&full_archive)),
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(first.unchanged_files, 1);
+        assert_eq!(
+            first.deleted_entries,
+            vec![
+                "input/b.txt".to_string(),
+                "input/gone/e.txt".to_string(),
+                "input/gone".to_string(),
+            ]
+        );
+        let err = read_archive_entry_impl(
+            first_increment.to_string_lossy().to_string(),
+            "input/sub/c.txt".to_string(),
+            Some("password".to_string()),
+            None,
+            None,
+        )
+        .unwrap_err();
+        assert!(err.contains("不存在"), "{err}");
 
-    #[cfg(all(unix, not(target_os = "macos")))]
-    let mut command = {
-        let mut cmd = Command::new("xdg-open");
-        cmd.arg(target);
-        cmd
-    };
+        let second = pack(
+            &second_increment,
+            Some(incremental_manifest_path(&first_increment)),
+            true,
+        )
+        .await
+        .unwrap();
+        assert_eq!(second.unchanged_files, 3);
+        assert!(second.deleted_entries.is_empty());
+
+        let report = extract_archive_chain_impl(
+            None,
+            Arc::default(),
+            [&full_archive, &first_increment, &second_increment]
+                .iter()
+                .map(|path| path.to_string_lossy().to_string())
+                .collect(),
+            output_dir.to_string_lossy().to_string(),
+            Some("password".to_string()),
+            None,
+        )
+        .await
+        .unwrap();
+        let restored = PathBuf::from(&report.output_dir).join("input");
+        assert_eq!(
+            fs::read_to_string(restored.join("a.txt")).unwrap(),
+            "new content"
+        );
+        assert!(!restored.join("b.txt").exists());
+        assert!(!restored.join("gone").exists());
+        assert_eq!(
+            fs::read_to_string(restored.join("sub").join("c.txt")).unwrap(),
+            "unchanged"
+        );
+        assert_eq!(fs::read_to_string(restored.join("d.txt")).unwrap(), "added");
+        assert!(!PathBuf::from(&report.output_dir)
+            .join(MANIFEST_ENTRY_NAME)
+            .exists());
+        assert_eq!(fs::read_dir(&output_dir).unwrap().count(), 1);
 
-    command
-        .spawn()
-        .map_err(|err| format!("打开输出目录失败: {}", err))?;
+        let _ = fs::remove_dir_all(root);
+    }
 
-    Ok(())
-}
+    #[tokio::test]
+    async fn header_records_plaintext_content_size() {
+        let root = temp_case_dir("content-size");
+        let input_dir = root.join("input");
+        let archive_file = root.join("sized.krate");
+        write_text_file(&input_dir.join("a.txt"), "alpha");
+        write_text_file(&input_dir.join("nested").join("b.txt"), &"b".repeat(3000));
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::path::{Path, PathBuf};
-    use std::time::{SystemTime, UNIX_EPOCH};
+        create_archive_impl(
+            None,
+            Arc::default(),
+            vec![input_dir.to_string_lossy().to_string()],
+            archive_file.to_string_lossy().to_string(),
+            None,
+            None,
+            CreateOptions {
+                compression: ArchiveCompression::None,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
 
-    #[cfg(unix)]
-    use std::os::unix::fs::symlink;
+        // 未压缩、未加密时，文件体积正好是魔数 + 头部 + tar 流。
+        let info = archive_info(&archive_file, None, None).unwrap();
+        let tar_bytes = info.content_bytes.unwrap();
+        assert_eq!(info.content_files, Some(2));
+        assert_eq!(tar_bytes % 512, 0);
+        let mut reader = BufReader::new(File::open(&archive_file).unwrap());
+        let (header, _) = read_archive_preamble(&mut reader).unwrap();
+        assert_eq!(
+            info.file_size,
+            (MAGIC_HEADER.len() + header.encoded_bytes().len()) as u64 + tar_bytes
+        );
 
-    fn temp_case_dir(name: &str) -> PathBuf {
-        let mut path = std::env::temp_dir();
-        let nanos = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_nanos();
-        path.push(format!(
-            "krate-archive-{name}-{}-{nanos}",
-            std::process::id()
-        ));
-        path
+        // 回填的字段不参与 AAD，加密归档依然可以正常解压。
+        let encrypted_file = root.join("sized-encrypted.krate");
+        create_archive_impl(
+            None,
+            Arc::default(),
+            vec![input_dir.to_string_lossy().to_string()],
+            encrypted_file.to_string_lossy().to_string(),
+            Some("password".to_string()),
+            None,
+            CreateOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            archive_info(&encrypted_file, None, None)
+                .unwrap()
+                .content_bytes,
+            Some(tar_bytes)
+        );
+        let report = extract_archive_impl(
+            None,
+            Arc::default(),
+            encrypted_file.to_string_lossy().to_string(),
+            root.join("output").to_string_lossy().to_string(),
+            Some("password".to_string()),
+            None,
+            ExtractOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(PathBuf::from(report.output_dir).join("input").join("a.txt"))
+                .unwrap(),
+            "alpha"
+        );
+
+        let _ = fs::remove_dir_all(root);
     }
 
-    fn write_text_file(path: &Path, contents: &str) {
-        if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent).unwrap();
-        }
-        fs::write(path, contents).unwrap();
+    #[test]
+    fn paused_reads_block_until_resumed_or_cancelled() {
+        let run = |control: Arc<OperationControl>| {
+            std::thread::spawn(move || {
+                let mut tracker = ArchiveProgressTracker::new("pack", "正在打包", 4, control);
+                let mut reader = ProgressReader::new(
+                    io::Cursor::new(b"data".to_vec()),
+                    &mut tracker,
+                    None,
+                    "正在打包",
+                );
+                let mut buf = Vec::new();
+                reader.read_to_end(&mut buf).map(|_| buf)
+            })
+        };
+
+        let control = Arc::new(OperationControl::default());
+        control.set_paused(true);
+        let worker = run(control.clone());
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(!worker.is_finished());
+        control.set_paused(false);
+        assert_eq!(worker.join().unwrap().unwrap(), b"data");
+
+        // 暂停中的任务被取消后立即以取消错误结束。
+        control.set_paused(true);
+        let worker = run(control.clone());
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(!worker.is_finished());
+        control.cancel();
+        let err = worker.join().unwrap().unwrap_err();
+        assert_eq!(err.to_string(), ARCHIVE_CANCELLED_ERROR);
     }
 
     #[tokio::test]
-    async fn plain_archive_roundtrip_preserves_contents() {
-        let root = temp_case_dir("plain");
-        let input_file = root.join("input").join("notes.txt");
-        let archive_file = root.join("plain.krate");
-        let output_dir = root.join("output");
-        let expected = "plain archive roundtrip";
+    async fn hidden_file_names_require_password_for_info() {
+        let root = temp_case_dir("hidden-names");
+        let input_file = root.join("secret-plan.txt");
+        let archive_file = root.join("hidden.krate");
+        write_text_file(&input_file, "hidden payload");
+
+        let pack = |password: Option<String>| {
+            create_archive_impl(
+                None,
+                Arc::default(),
+                vec![input_file.to_string_lossy().to_string()],
+                archive_file.to_string_lossy().to_string(),
+                password,
+                None,
+                CreateOptions {
+                    comment: Some("quarterly".to_string()),
+                    hide_file_names: true,
+                    ..Default::default()
+                },
+            )
+        };
+        assert!(pack(None).await.is_err());
+        pack(Some("secret".to_string())).await.unwrap();
 
-        write_text_file(&input_file, expected);
+        let bytes = fs::read(&archive_file).unwrap();
+        let header_flags = bytes[MAGIC_HEADER.len() + FORMAT_MARKER.len()];
+        assert_eq!(header_flags & (FLAG_METADATA | FLAG_CONTENT_SIZE), 0);
+        assert!(!bytes.windows(b"quarterly".len()).any(|w| w == b"quarterly"));
 
-        create_archive_impl(
+        assert_eq!(
+            archive_info(&archive_file, None, None).unwrap_err(),
+            HIDDEN_NAMES_ERROR
+        );
+        assert_eq!(
+            archive_info(&archive_file, Some("wrong".to_string()), None).unwrap_err(),
+            WRONG_PASSWORD_ERROR
+        );
+        let info = archive_info(&archive_file, Some("secret".to_string()), None).unwrap();
+        assert!(info.hidden_names);
+        let metadata = info.metadata.unwrap();
+        assert_eq!(metadata.comment.as_deref(), Some("quarterly"));
+        assert_eq!(metadata.total_files, 1);
+
+        let report = extract_archive_impl(
             None,
-            vec![input_file.to_string_lossy().to_string()],
+            Arc::default(),
             archive_file.to_string_lossy().to_string(),
+            root.join("output").to_string_lossy().to_string(),
+            Some("secret".to_string()),
             None,
-            Some(1),
+            ExtractOptions::default(),
         )
         .await
         .unwrap();
+        // 隐藏文件列表的归档不记录解压后大小，报告中标明跳过了磁盘空间检查。
+        assert!(report.space_check_skipped);
+        assert_eq!(
+            fs::read_to_string(Path::new(&report.output_dir).join("secret-plan.txt")).unwrap(),
+            "hidden payload"
+        );
 
-        let extracted_dir = extract_archive_impl(
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn salvage_recovers_entries_before_truncation() {
+        let root = temp_case_dir("salvage");
+        let input_dir = root.join("input");
+        let archive_file = root.join("damaged.krate");
+        write_text_file(&input_dir.join("a.txt"), "intact");
+        fs::write(
+            input_dir.join("big.bin"),
+            (0..CHUNK_PLAINTEXT_SIZE * 4)
+                .map(|index| (index % 251) as u8)
+                .collect::<Vec<_>>(),
+        )
+        .unwrap();
+        write_text_file(&input_dir.join("z.txt"), "lost");
+
+        create_archive_impl(
             None,
+            Arc::default(),
+            vec![input_dir.to_string_lossy().to_string()],
             archive_file.to_string_lossy().to_string(),
-            output_dir.to_string_lossy().to_string(),
+            Some("secret".to_string()),
             None,
+            CreateOptions {
+                compression: ArchiveCompression::None,
+                ..Default::default()
+            },
         )
         .await
         .unwrap();
+        let bytes = fs::read(&archive_file).unwrap();
+        fs::write(&archive_file, &bytes[..bytes.len() / 2]).unwrap();
 
-        assert_eq!(PathBuf::from(&extracted_dir), output_dir.join("plain"));
-        let extracted = fs::read_to_string(Path::new(&extracted_dir).join("notes.txt")).unwrap();
-        assert_eq!(extracted, expected);
+        let extract = |salvage: bool| {
+            extract_archive_impl(
+                None,
+                Arc::default(),
+                archive_file.to_string_lossy().to_string(),
+                root.join(format!("output-{salvage}"))
+                    .to_string_lossy()
+                    .to_string(),
+                Some("secret".to_string()),
+                None,
+                ExtractOptions {
+                    salvage,
+                    ..Default::default()
+                },
+            )
+        };
+        assert!(extract(false).await.is_err());
+
+        let report = extract(true).await.unwrap();
+        let salvage = report.salvage.unwrap();
+        assert!(salvage.error.is_some());
+        assert!(salvage
+            .recovered_entries
+            .contains(&"input/a.txt".to_string()));
+        assert_eq!(salvage.partial_entries, vec!["input/big.bin".to_string()]);
+        let offset = salvage.corruption_offset.unwrap();
+        assert!(offset > 0 && offset <= (bytes.len() / 2) as u64);
+
+        let extracted_dir = PathBuf::from(report.output_dir).join("input");
+        assert_eq!(
+            fs::read_to_string(extracted_dir.join("a.txt")).unwrap(),
+            "intact"
+        );
+        assert!(!extracted_dir.join("big.bin").exists());
+        assert!(extracted_dir.join("big.bin.partial").exists());
+        assert!(!extracted_dir.join("z.txt").exists());
 
         let _ = fs::remove_dir_all(root);
     }
 
     #[tokio::test]
-    async fn encrypted_archive_roundtrip_requires_password() {
-        let root = temp_case_dir("encrypted");
-        let input_file = root.join("input").join("secret.txt");
-        let archive_file = root.join("secret.krate");
-        let output_dir = root.join("output");
-        let password = "correct horse battery staple";
-        let expected = "encrypted archive roundtrip";
-
-        write_text_file(&input_file, expected);
+    async fn dedupe_stores_identical_files_once() {
+        let root = temp_case_dir("dedupe");
+        let input_dir = root.join("input");
+        let model = "weights ".repeat(4096);
+        write_text_file(&input_dir.join("exp1").join("model.bin"), &model);
+        write_text_file(&input_dir.join("exp2").join("model.bin"), &model);
+        write_text_file(&input_dir.join("exp2").join("notes.txt"), "different");
 
-        create_archive_impl(
+        let report = create_archive_impl(
             None,
-            vec![input_file.to_string_lossy().to_string()],
-            archive_file.to_string_lossy().to_string(),
-            Some(password.to_string()),
-            Some(1),
+            Arc::default(),
+            vec![input_dir.to_string_lossy().to_string()],
+            root.join("dedupe.krate").to_string_lossy().to_string(),
+            None,
+            None,
+            CreateOptions {
+                compression: ArchiveCompression::None,
+                dedupe: true,
+                ..Default::default()
+            },
         )
         .await
         .unwrap();
+        assert_eq!(report.deduped_files, 1);
+        assert_eq!(report.deduped_bytes, model.len() as u64);
+
+        for (restore, output) in [
+            (DedupeRestore::Copy, "copy"),
+            (DedupeRestore::Hardlink, "hardlink"),
+        ] {
+            let extracted = extract_archive_impl(
+                None,
+                Arc::default(),
+                root.join("dedupe.krate").to_string_lossy().to_string(),
+                root.join(output).to_string_lossy().to_string(),
+                None,
+                None,
+                ExtractOptions {
+                    dedupe_restore: restore,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+            let extracted_dir = PathBuf::from(extracted.output_dir).join("input");
+            let copy = extracted_dir.join("exp2").join("model.bin");
+            assert_eq!(fs::read_to_string(&copy).unwrap(), model);
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::MetadataExt;
+                let linked = restore == DedupeRestore::Hardlink;
+                assert_eq!(fs::metadata(&copy).unwrap().nlink() > 1, linked);
+            }
+        }
 
-        let extracted_dir = extract_archive_impl(
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn size_and_date_filters_limit_packed_files() {
+        let root = temp_case_dir("filters");
+        let input_dir = root.join("input");
+        let set_mtime = |path: &Path, secs: u64| {
+            File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(UNIX_EPOCH + Duration::from_secs(secs))
+                .unwrap();
+        };
+        write_text_file(&input_dir.join("recent.txt"), "this week");
+        write_text_file(&input_dir.join("old.txt"), "last year");
+        write_text_file(&input_dir.join("large.txt"), &"x".repeat(4096));
+        set_mtime(&input_dir.join("recent.txt"), 2_000_000_000);
+        set_mtime(&input_dir.join("old.txt"), 1_000_000_000);
+        set_mtime(&input_dir.join("large.txt"), 2_000_000_000);
+
+        let (report, extracted) = pack_and_extract(
+            &input_dir,
+            &root.join("filtered.krate"),
+            &root.join("output"),
+            None,
+            CreateOptions {
+                filter: EntryFilter {
+                    max_size: Some(1024),
+                    modified_after: Some(1_500_000_000),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ExtractOptions::default(),
+        )
+        .await;
+        assert_eq!(report.filtered_files, 2);
+        let extracted_dir = PathBuf::from(extracted.output_dir).join("input");
+        assert!(extracted_dir.join("recent.txt").exists());
+        assert!(!extracted_dir.join("old.txt").exists());
+        assert!(!extracted_dir.join("large.txt").exists());
+
+        let empty = create_archive_impl(
+            None,
+            Arc::default(),
+            vec![input_dir.to_string_lossy().to_string()],
+            root.join("empty.krate").to_string_lossy().to_string(),
+            None,
+            None,
+            CreateOptions {
+                filter: EntryFilter {
+                    modified_before: Some(1),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .await;
+        assert_eq!(empty.unwrap_err(), "没有可打包的文件");
+        assert!(!root.join("empty.krate").exists());
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn portable_entry_names_are_normalized_and_sanitized() {
+        let nfd = "cafe\u{301}/nai\u{308}ve.txt";
+        assert_eq!(portable_entry_name(nfd, false), "caf\u{e9}/na\u{ef}ve.txt");
+        assert_eq!(portable_entry_name("a:b/c?.txt", false), "a:b/c?.txt");
+        assert_eq!(
+            portable_entry_name("a:b/c?*.txt", true),
+            "a\u{ff1a}b/c\u{ff1f}\u{ff0a}.txt"
+        );
+        assert_eq!(portable_entry_name("dir/con.txt", true), "dir/con.txt_");
+        assert_eq!(portable_entry_name("dir/trailing.", true), "dir/trailing._");
+        assert_eq!(portable_entry_name("../x", true), "../x");
+    }
+
+    #[tokio::test]
+    async fn nfd_entry_names_are_extracted_as_nfc_and_reported() {
+        let root = temp_case_dir("nfd-names");
+        let archive_file = root.join("mac.krate");
+        fs::create_dir_all(&root).unwrap();
+        let nfd = "cafe\u{301}.txt";
+
+        let mut builder = tar::Builder::new(Vec::new());
+        append_raw_tar_entry(&mut builder, nfd, tar::EntryType::Regular, None, b"menu");
+        write_raw_krate(&archive_file, &builder.into_inner().unwrap());
+
+        let report = extract_archive_impl(
             None,
+            Arc::default(),
             archive_file.to_string_lossy().to_string(),
-            output_dir.to_string_lossy().to_string(),
-            Some(password.to_string()),
+            root.join("output").to_string_lossy().to_string(),
+            None,
+            None,
+            ExtractOptions::default(),
         )
         .await
         .unwrap();
-
-        assert_eq!(PathBuf::from(&extracted_dir), output_dir.join("secret"));
-        let extracted = fs::read_to_string(Path::new(&extracted_dir).join("secret.txt")).unwrap();
-        assert_eq!(extracted, expected);
+        let nfc = "caf\u{e9}.txt";
+        assert_eq!(
+            fs::read_to_string(Path::new(&report.output_dir).join(nfc)).unwrap(),
+            "menu"
+        );
+        assert_eq!(
+            report.renamed_entries,
+            vec![RenamedEntry {
+                original: nfd.to_string(),
+                extracted: nfc.to_string(),
+            }]
+        );
 
         let _ = fs::remove_dir_all(root);
     }
 
+    #[cfg(windows)]
     #[tokio::test]
-    async fn encrypted_archive_rejects_wrong_password() {
-        let root = temp_case_dir("wrong-password");
-        let input_file = root.join("input").join("secret.txt");
-        let archive_file = root.join("secret.krate");
-        let output_dir = root.join("output");
-
-        write_text_file(&input_file, "do not leak");
+    async fn long_windows_paths_roundtrip() {
+        let root = temp_case_dir("long-paths");
+        let mut deep = root.join("input");
+        while deep.as_os_str().len() < 300 {
+            deep.push("node_modules_package_directory");
+        }
+        write_text_file(&long_path(&deep.join("index.js")), "module.exports = 1;");
 
-        create_archive_impl(
+        let (_, report) = pack_and_extract(
+            &root.join("input"),
+            &root.join("long.krate"),
+            &root.join("output"),
             None,
-            vec![input_file.to_string_lossy().to_string()],
-            archive_file.to_string_lossy().to_string(),
-            Some("right-password".to_string()),
-            Some(1),
+            CreateOptions::default(),
+            ExtractOptions::default(),
+        )
+        .await;
+        let relative = deep.strip_prefix(&root).unwrap().join("index.js");
+        let extracted = long_path(&Path::new(&report.output_dir).join(relative));
+        assert_eq!(
+            fs::read_to_string(extracted).unwrap(),
+            "module.exports = 1;"
+        );
+
+        let _ = fs::remove_dir_all(long_path(&root));
+    }
+
+    #[tokio::test]
+    async fn explicit_entries_control_archive_paths() {
+        let root = temp_case_dir("explicit-entries");
+        let first = root.join("a").join("report.txt");
+        let second = root.join("b").join("deep").join("data.csv");
+        let folder = root.join("assets");
+        write_text_file(&first, "report");
+        write_text_file(&second, "1,2,3");
+        write_text_file(&folder.join("logo.svg"), "<svg/>");
+
+        let spec = |src: &Path, archive_path: &str| EntrySpec {
+            src_path: src.to_string_lossy().to_string(),
+            archive_path: archive_path.to_string(),
+        };
+        let pack = |entries: Vec<EntrySpec>, expand_directories: bool| {
+            create_archive_from_entries_impl(
+                None,
+                Arc::default(),
+                entries,
+                root.join("entries.krate").to_string_lossy().to_string(),
+                None,
+                None,
+                CreateOptions {
+                    expand_directories,
+                    ..Default::default()
+                },
+            )
+        };
+
+        for entries in [
+            vec![spec(&first, "flat/x.txt"), spec(&second, "flat/x.txt")],
+            vec![spec(&first, "../escape.txt")],
+            vec![spec(&first, "flat"), spec(&second, "flat/data.csv")],
+            vec![spec(&folder, "static")],
+        ] {
+            assert!(pack(entries, false).await.is_err());
+        }
+        assert!(!root.join("entries.krate").exists());
+
+        let report = pack(
+            vec![
+                spec(&first, "flat/report.txt"),
+                spec(&second, "flat/data.csv"),
+                spec(&folder, "web/static"),
+            ],
+            true,
         )
         .await
         .unwrap();
+        assert_eq!(report.input_mapping[2].archive_name, "web/static");
 
-        let error = extract_archive_impl(
+        let extracted = extract_archive_impl(
             None,
-            archive_file.to_string_lossy().to_string(),
-            output_dir.to_string_lossy().to_string(),
-            Some("wrong-password".to_string()),
+            Arc::default(),
+            root.join("entries.krate").to_string_lossy().to_string(),
+            root.join("output").to_string_lossy().to_string(),
+            None,
+            None,
+            ExtractOptions::default(),
         )
         .await
-        .unwrap_err();
+        .unwrap();
+        let extracted_dir = PathBuf::from(extracted.output_dir);
+        assert_eq!(
+            fs::read_to_string(extracted_dir.join("flat").join("report.txt")).unwrap(),
+            "report"
+        );
+        assert_eq!(
+            fs::read_to_string(extracted_dir.join("flat").join("data.csv")).unwrap(),
+            "1,2,3"
+        );
+        assert!(extracted_dir
+            .join("web")
+            .join("static")
+            .join("logo.svg")
+            .exists());
 
-        assert!(error.contains("归档解密失败"));
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn benchmark_reuses_sample_across_levels() {
+        let root = temp_case_dir("benchmark");
+        let text = "Krate benchmark sample line with some repetition.\n".repeat(4096);
+        write_text_file(&root.join("sample").join("a.txt"), &text);
+        write_text_file(&root.join("sample").join("b.txt"), &text);
+        let sample_path = root.join("sample").to_string_lossy().to_string();
+
+        let results = benchmark_compression_impl(
+            None,
+            Arc::default(),
+            sample_path.clone(),
+            ArchiveCompression::Gzip,
+            vec![0, 1, 9],
+        )
+        .unwrap();
+        assert_eq!(
+            results
+                .iter()
+                .map(|result| result.level)
+                .collect::<Vec<_>>(),
+            vec![0, 1, 9]
+        );
+        for result in &results {
+            assert_eq!(result.sample_bytes, text.len() as u64 * 2);
+            assert!(result.megabytes_per_second > 0.0);
+        }
+        assert!(results[0].ratio >= 1.0);
+        assert!(results[2].compressed_bytes <= results[1].compressed_bytes);
+        assert!(results[2].ratio < 0.1);
+
+        for (compression, levels) in [
+            (ArchiveCompression::Gzip, vec![10]),
+            (ArchiveCompression::Gzip, vec![]),
+            (ArchiveCompression::Zstd, vec![3]),
+        ] {
+            assert!(benchmark_compression_impl(
+                None,
+                Arc::default(),
+                sample_path.clone(),
+                compression,
+                levels
+            )
+            .is_err());
+        }
 
         let _ = fs::remove_dir_all(root);
     }
 
     #[tokio::test]
-    async fn password_preserves_leading_and_trailing_spaces() {
-        let root = temp_case_dir("whitespace-password");
-        let input_file = root.join("input").join("secret.txt");
+    async fn weak_passwords_are_scored_low_and_rejected_unless_forced() {
+        let scores = [
+            "1",
+            "aaaaaaaaaaaaaaaa",
+            "12345678",
+            "abcdefghij",
+            "Tr0ub4dor&3",
+        ]
+        .map(|password| password_report(password).score);
+        assert_eq!(scores, [0, 0, 0, 0, 3]);
+        let strong = password_report("correct horse battery staple 42");
+        assert_eq!(strong.score, MAX_PASSWORD_SCORE);
+        assert_eq!(strong.length, 31);
+        assert!(strong.has_lowercase && strong.has_digits && strong.has_symbols);
+        assert!(!strong.has_uppercase && !strong.has_other);
+        assert_eq!(password_report("").entropy_bits, 0.0);
+
+        let root = temp_case_dir("password-strength");
+        let input = root.join("secret.txt");
         let archive_file = root.join("secret.krate");
-        let output_dir = root.join("output-ok");
-        let failed_output_dir = root.join("output-failed");
-        let password = "  padded password  ";
+        write_text_file(&input, "secret");
+        let create = |password: &str, min_password_score: Option<u8>| {
+            create_archive_impl(
+                None,
+                Arc::default(),
+                vec![input.to_string_lossy().to_string()],
+                archive_file.to_string_lossy().to_string(),
+                Some(password.to_string()),
+                None,
+                CreateOptions {
+                    min_password_score,
+                    ..Default::default()
+                },
+            )
+        };
 
-        write_text_file(&input_file, "keep spaces");
+        let error = create("1", Some(2)).await.unwrap_err();
+        assert!(error.contains("密码强度不足"));
+        assert!(!archive_file.exists());
+        // 强制创建时命令层不传入评分要求。
+        create("1", None).await.unwrap();
+        create("Tr0ub4dor&3", Some(3)).await.unwrap();
 
-        create_archive_impl(
-            None,
-            vec![input_file.to_string_lossy().to_string()],
-            archive_file.to_string_lossy().to_string(),
-            Some(password.to_string()),
-            Some(1),
-        )
-        .await
-        .unwrap();
+        let _ = fs::remove_dir_all(root);
+    }
 
-        let extracted_dir = extract_archive_impl(
+    #[test]
+    fn operations_are_listed_with_their_latest_progress() {
+        let state = ArchiveState::new();
+        let generated = state.register(None).unwrap();
+        let named = state.register(Some("job-b".to_string())).unwrap();
+        assert!(generated.cancel.id.starts_with("krate-"));
+        assert!(state.register(Some("job-b".to_string())).is_err());
+
+        let mut tracker = ArchiveProgressTracker::new("create", "打包", 100, named.cancel.clone());
+        tracker.advance_bytes(None, 40, "正在打包");
+        state.set_paused("job-b", true).unwrap();
+
+        let operations = state.list().unwrap();
+        assert_eq!(operations.len(), 2);
+        let job = operations
+            .iter()
+            .find(|operation| operation.operation_id == "job-b")
+            .unwrap();
+        assert!(job.paused && !job.cancelled);
+        let progress = job.progress.as_ref().unwrap();
+        assert_eq!(progress.operation_id, "job-b");
+        assert_eq!(progress.processed_bytes, 40);
+        assert!(operations
+            .iter()
+            .any(|operation| operation.operation_id == generated.cancel.id
+                && operation.progress.is_none()));
+
+        drop(generated);
+        drop(named);
+        assert!(state.list().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn strip_components_flattens_top_level_and_skips_collisions() {
+        let root = temp_case_dir("strip-components");
+        write_text_file(&root.join("left").join("notes.txt"), "left");
+        write_text_file(&root.join("left").join("docs").join("guide.md"), "guide");
+        write_text_file(&root.join("right").join("notes.txt"), "right");
+        let archive_file = root.join("bundle.krate");
+        create_archive_impl(
             None,
+            Arc::default(),
+            vec![
+                root.join("left").to_string_lossy().to_string(),
+                root.join("right").to_string_lossy().to_string(),
+            ],
             archive_file.to_string_lossy().to_string(),
-            output_dir.to_string_lossy().to_string(),
-            Some(password.to_string()),
+            None,
+            None,
+            CreateOptions::default(),
         )
         .await
         .unwrap();
+
+        let extract = |output: &str, strip_components: u32| {
+            extract_archive_impl(
+                None,
+                Arc::default(),
+                archive_file.to_string_lossy().to_string(),
+                root.join(output).to_string_lossy().to_string(),
+                None,
+                None,
+                ExtractOptions {
+                    verify_hashes: true,
+                    strip_components,
+                    ..Default::default()
+                },
+            )
+        };
+
+        let report = extract("flat", 1).await.unwrap();
+        let extracted_dir = PathBuf::from(&report.output_dir);
         assert_eq!(
-            fs::read_to_string(Path::new(&extracted_dir).join("secret.txt")).unwrap(),
-            "keep spaces"
+            fs::read_to_string(extracted_dir.join("notes.txt")).unwrap(),
+            "left"
+        );
+        assert_eq!(
+            fs::read_to_string(extracted_dir.join("docs").join("guide.md")).unwrap(),
+            "guide"
         );
+        assert!(!extracted_dir.join("left").exists());
+        assert_eq!(report.skipped_entries, vec!["right/notes.txt".to_string()]);
+        assert!(report.hash_mismatches.is_empty());
+        assert!(report.renamed_entries.is_empty());
 
-        let error = extract_archive_impl(
-            None,
-            archive_file.to_string_lossy().to_string(),
-            failed_output_dir.to_string_lossy().to_string(),
-            Some(password.trim().to_string()),
-        )
-        .await
-        .unwrap_err();
-        assert!(error.contains("归档解密失败"));
+        let report = extract("deep", 2).await.unwrap();
+        let extracted_dir = PathBuf::from(&report.output_dir);
+        assert!(extracted_dir.join("guide.md").exists());
+        assert!(!extracted_dir.join("notes.txt").exists());
+        assert!(report.hash_mismatches.is_empty());
 
         let _ = fs::remove_dir_all(root);
     }
 
     #[tokio::test]
-    async fn create_archive_rejects_overlapping_output_paths() {
-        let root = temp_case_dir("overlap");
-        let input_dir = root.join("input");
-        let input_file = input_dir.join("notes.txt");
-        let nested_output = input_dir.join("nested.krate");
-
-        write_text_file(&input_file, "source data");
-
-        let same_file_error = create_archive_impl(
+    async fn extraction_fails_early_when_content_exceeds_free_space() {
+        let root = temp_case_dir("free-space");
+        write_text_file(&root.join("input").join("data.txt"), "data");
+        let archive_file = root.join("data.krate");
+        create_archive_impl(
+            None,
+            Arc::default(),
+            vec![root.join("input").to_string_lossy().to_string()],
+            archive_file.to_string_lossy().to_string(),
             None,
-            vec![input_file.to_string_lossy().to_string()],
-            input_file.to_string_lossy().to_string(),
             None,
-            Some(1),
+            CreateOptions::default(),
         )
         .await
-        .unwrap_err();
-        assert!(same_file_error.contains("输出文件不能与输入路径相同"));
-        assert_eq!(fs::read_to_string(&input_file).unwrap(), "source data");
+        .unwrap();
+        // 沙箱里可能无法识别磁盘，此时检查被跳过。
+        let Some(available) = available_space(&root) else {
+            let _ = fs::remove_dir_all(root);
+            return;
+        };
 
-        let nested_error = create_archive_impl(
-            None,
-            vec![input_dir.to_string_lossy().to_string()],
-            nested_output.to_string_lossy().to_string(),
-            None,
-            Some(1),
+        let (header, _) =
+            read_archive_preamble(&mut BufReader::new(File::open(&archive_file).unwrap())).unwrap();
+        let recorded = header.content_size.unwrap();
+        patch_content_size(
+            &archive_file,
+            &header,
+            ContentSize {
+                tar_bytes: available.saturating_add(1),
+                ..recorded
+            },
         )
-        .await
-        .unwrap_err();
-        assert!(nested_error.contains("输出文件不能位于待归档目录内"));
-        assert!(!nested_output.exists());
+        .unwrap();
+
+        let extract = |output: &str, ignore_space_check: bool| {
+            extract_archive_impl(
+                None,
+                Arc::default(),
+                archive_file.to_string_lossy().to_string(),
+                root.join(output).to_string_lossy().to_string(),
+                None,
+                None,
+                ExtractOptions {
+                    ignore_space_check,
+                    ..Default::default()
+                },
+            )
+        };
+        let error = extract("checked", false).await.unwrap_err();
+        assert!(error.contains("磁盘空间不足"));
+        assert!(!root.join("checked").join("data").exists());
+
+        let report = extract("ignored", true).await.unwrap();
+        assert!(!report.space_check_skipped);
+        assert_eq!(
+            fs::read_to_string(
+                PathBuf::from(report.output_dir)
+                    .join("input")
+                    .join("data.txt")
+            )
+            .unwrap(),
+            "data"
+        );
 
         let _ = fs::remove_dir_all(root);
     }
 
     #[tokio::test]
-    async fn duplicate_root_names_are_disambiguated() {
-        let root = temp_case_dir("duplicate-roots");
-        let left = root.join("left").join("config.json");
-        let right = root.join("right").join("config.json");
-        let archive_file = root.join("bundle.krate");
-        let output_dir = root.join("output");
-
-        write_text_file(&left, "left");
-        write_text_file(&right, "right");
-
+    async fn checksum_trailer_detects_flipped_bytes() {
+        let root = temp_case_dir("checksum-trailer");
+        write_text_file(
+            &root.join("input").join("data.txt"),
+            &"payload ".repeat(4096),
+        );
+        let archive_file = root.join("data.krate");
         create_archive_impl(
             None,
-            vec![
-                left.to_string_lossy().to_string(),
-                right.to_string_lossy().to_string(),
-            ],
+            Arc::default(),
+            vec![root.join("input").to_string_lossy().to_string()],
             archive_file.to_string_lossy().to_string(),
+            Some("secret".to_string()),
             None,
-            Some(1),
+            CreateOptions {
+                checksum_trailer: true,
+                ..Default::default()
+            },
         )
         .await
         .unwrap();
 
-        let extracted_dir = extract_archive_impl(
+        assert_eq!(archive_checksum_matches(&archive_file).unwrap(), Some(true));
+        let info = archive_info(&archive_file, None, None).unwrap();
+        assert!(info.has_checksum);
+        assert_eq!(info.checksum_matches, Some(true));
+        verify_archive_impl(
             None,
+            Arc::default(),
             archive_file.to_string_lossy().to_string(),
-            output_dir.to_string_lossy().to_string(),
+            Some("secret".to_string()),
             None,
         )
         .await
         .unwrap();
 
+        let mut bytes = fs::read(&archive_file).unwrap();
+        let middle = bytes.len() / 2;
+        bytes[middle] ^= 0x01;
+        fs::write(&archive_file, &bytes).unwrap();
         assert_eq!(
-            fs::read_to_string(Path::new(&extracted_dir).join("config.json")).unwrap(),
-            "left"
+            archive_checksum_matches(&archive_file).unwrap(),
+            Some(false)
         );
-        assert_eq!(
-            fs::read_to_string(Path::new(&extracted_dir).join("config (2).json")).unwrap(),
-            "right"
+
+        // 没有校验尾的旧归档无法快速校验。
+        let legacy_file = root.join("legacy.krate");
+        write_raw_krate(
+            &legacy_file,
+            &tar::Builder::new(Vec::new()).into_inner().unwrap(),
         );
+        assert_eq!(archive_checksum_matches(&legacy_file).unwrap(), None);
 
         let _ = fs::remove_dir_all(root);
     }
 
     #[tokio::test]
-    async fn extract_archive_uses_unique_output_directories() {
-        let root = temp_case_dir("extract-output");
-        let input_file = root.join("input").join("notes.txt");
-        let archive_file = root.join("plain.krate");
-        let output_dir = root.join("output");
-        let existing_file = output_dir.join("notes.txt");
-
-        write_text_file(&input_file, "archive data");
-        write_text_file(&existing_file, "existing data");
-
-        create_archive_impl(
-            None,
-            vec![input_file.to_string_lossy().to_string()],
-            archive_file.to_string_lossy().to_string(),
-            None,
-            Some(1),
-        )
-        .await
-        .unwrap();
-
-        let first_extract_dir = PathBuf::from(
-            extract_archive_impl(
+    async fn search_matches_entries_across_archives() {
+        let root = temp_case_dir("search");
+        write_text_file(&root.join("2023").join("Report_2023.xlsx"), "q4");
+        write_text_file(&root.join("2023").join("notes").join("report.txt"), "notes");
+        write_text_file(&root.join("2024").join("budget.xlsx"), "budget");
+        for (input, password) in [("2023", None), ("2024", Some("secret"))] {
+            create_archive_impl(
                 None,
-                archive_file.to_string_lossy().to_string(),
-                output_dir.to_string_lossy().to_string(),
+                Arc::default(),
+                vec![root.join(input).to_string_lossy().to_string()],
+                root.join(format!("{input}.krate"))
+                    .to_string_lossy()
+                    .to_string(),
+                password.map(str::to_string),
                 None,
+                CreateOptions::default(),
             )
             .await
-            .unwrap(),
-        );
-        let second_extract_dir = PathBuf::from(
-            extract_archive_impl(
+            .unwrap();
+        }
+        let archives = ["2023", "2024"]
+            .map(|name| {
+                root.join(format!("{name}.krate"))
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .to_vec();
+        let search = |pattern: &str, case_sensitive: bool, password: Option<&str>, limit| {
+            search_archives_impl(
                 None,
-                archive_file.to_string_lossy().to_string(),
-                output_dir.to_string_lossy().to_string(),
+                Arc::default(),
+                archives.clone(),
+                &EntryMatcher::new(pattern, case_sensitive).unwrap(),
+                password.map(str::to_string),
                 None,
+                limit,
             )
-            .await
-            .unwrap(),
+            .unwrap()
+        };
+        let paths = |report: &ArchiveSearchReport| {
+            report
+                .matches
+                .iter()
+                .map(|entry| entry.path.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let report = search("REPORT", false, None, DEFAULT_SEARCH_LIMIT);
+        assert_eq!(
+            paths(&report),
+            vec!["2023/Report_2023.xlsx", "2023/notes/report.txt"]
         );
+        assert!(report.matches[0].archive_path.ends_with("2023.krate"));
+        assert_eq!(report.skipped_archives.len(), 1);
+        assert_eq!(report.skipped_archives[0].reason, SEARCH_PASSWORD_REQUIRED);
+        assert!(report.skipped_archives[0]
+            .archive_path
+            .ends_with("2024.krate"));
 
-        assert_eq!(fs::read_to_string(&existing_file).unwrap(), "existing data");
         assert_eq!(
-            fs::read_to_string(first_extract_dir.join("notes.txt")).unwrap(),
-            "archive data"
+            paths(&search("report", true, None, DEFAULT_SEARCH_LIMIT)),
+            vec!["2023/notes/report.txt"]
         );
+
+        let report = search("*.xlsx", false, Some("secret"), DEFAULT_SEARCH_LIMIT);
         assert_eq!(
-            fs::read_to_string(second_extract_dir.join("notes.txt")).unwrap(),
-            "archive data"
+            paths(&report),
+            vec!["2023/Report_2023.xlsx", "2024/budget.xlsx"]
         );
-        assert_ne!(first_extract_dir, second_extract_dir);
+        assert!(report.skipped_archives.is_empty());
+        assert_eq!(report.matches[1].kind, "file");
+        assert_eq!(report.matches[1].size, 6);
+
+        let report = search("20??/*", false, Some("secret"), 1);
+        assert_eq!(report.matches.len(), 1);
+        assert!(report.truncated);
+
+        assert!(glob_matches(&['a', '*', 'c'], &['a', 'b', 'b', 'c']));
+        assert!(!glob_matches(&['a', '?', 'c'], &['a', 'c']));
+        assert!(EntryMatcher::new("  ", false).is_err());
 
         let _ = fs::remove_dir_all(root);
     }
 
+    #[cfg(unix)]
     #[tokio::test]
-    async fn tampered_kdf_parameters_are_rejected() {
-        let root = temp_case_dir("tampered-kdf");
-        let input_file = root.join("input").join("secret.txt");
-        let archive_file = root.join("secret.krate");
-        let output_dir = root.join("output");
+    async fn failed_pack_leaves_no_file_at_output_path() {
+        let root = temp_case_dir("failed-pack");
+        let input_dir = root.join("input");
+        let archive_file = root.join("backup.krate");
+        write_text_file(&input_dir.join("a.txt"), "written before the failure");
+        // 套接字无法归档，打包会在写入前面的文件之后中途失败。
+        let _socket = std::os::unix::net::UnixListener::bind(input_dir.join("z.sock")).unwrap();
 
-        write_text_file(&input_file, "sensitive");
+        for volume_size in [None, Some(MIN_VOLUME_SIZE)] {
+            let error = create_archive_impl(
+                None,
+                Arc::default(),
+                vec![input_dir.to_string_lossy().to_string()],
+                archive_file.to_string_lossy().to_string(),
+                None,
+                None,
+                CreateOptions {
+                    volume_size,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap_err();
+            assert!(error.contains("不支持归档的路径类型"));
+            assert!(!archive_file.exists());
+            assert!(!volume_path(&archive_file, 1).exists());
+            let leftovers = fs::read_dir(&root)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name())
+                .filter(|name| name != "input")
+                .collect::<Vec<_>>();
+            assert!(leftovers.is_empty(), "{leftovers:?}");
+        }
 
-        create_archive_impl(
-            None,
-            vec![input_file.to_string_lossy().to_string()],
-            archive_file.to_string_lossy().to_string(),
-            Some("password".to_string()),
-            Some(1),
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn explicit_top_level_names_are_validated() {
+        let inputs = vec!["/data/a/src".to_string(), "/data/b/src".to_string()];
+        let named = build_archive_inputs(
+            &inputs,
+            Some(["frontend".to_string(), "backend".to_string()].as_slice()),
         )
-        .await
         .unwrap();
+        assert_eq!(named[0].archive_root, PathBuf::from("frontend"));
+        assert_eq!(named[1].archive_root, PathBuf::from("backend"));
+
+        for names in [
+            vec!["same".to_string(), "same".to_string()],
+            vec!["a/b".to_string(), "ok".to_string()],
+            vec!["trailing.".to_string(), "ok".to_string()],
+            vec![MANIFEST_ENTRY_NAME.to_string(), "ok".to_string()],
+            vec!["only-one".to_string()],
+        ] {
+            assert!(build_archive_inputs(&inputs, Some(names.as_slice())).is_err());
+        }
+    }
 
-        let mut bytes = fs::read(&archive_file).unwrap();
-        let memory_offset = MAGIC_HEADER.len() + FORMAT_MARKER.len() + 2;
-        bytes[memory_offset..memory_offset + 4]
-            .copy_from_slice(&(MAX_ARCHIVE_ARGON2_MEMORY_KIB + 1).to_le_bytes());
-        fs::write(&archive_file, bytes).unwrap();
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn symlink_created_through_earlier_symlink_cannot_escape() {
+        let root = temp_case_dir("symlink-chain");
+        fs::create_dir_all(&root).unwrap();
+        let archive_file = root.join("chain.krate");
+
+        let mut builder = tar::Builder::new(Vec::new());
+        append_raw_tar_entry(&mut builder, "up", tar::EntryType::Symlink, Some("."), b"");
+        append_raw_tar_entry(
+            &mut builder,
+            "up/esc",
+            tar::EntryType::Symlink,
+            Some("../etc"),
+            b"",
+        );
+        write_raw_krate(&archive_file, &builder.into_inner().unwrap());
 
-        let error = extract_archive_impl(
+        let report = extract_archive_impl(
             None,
+            Arc::default(),
             archive_file.to_string_lossy().to_string(),
-            output_dir.to_string_lossy().to_string(),
-            Some("password".to_string()),
+            root.join("out").to_string_lossy().to_string(),
+            None,
+            None,
+            ExtractOptions::default(),
         )
         .await
-        .unwrap_err();
+        .unwrap();
 
-        assert!(error.contains("支持范围"));
-        assert!(!output_dir.join("secret").exists());
+        assert_eq!(report.skipped_entries, vec!["up/esc"]);
+        let output_dir = Path::new(&report.output_dir);
+        assert!(fs::symlink_metadata(output_dir.join("up")).is_ok());
+        assert!(fs::symlink_metadata(output_dir.join("esc")).is_err());
 
         let _ = fs::remove_dir_all(root);
     }
 
+    #[test]
+    fn incremental_fast_mode_compares_nanoseconds() {
+        let modified = Duration::new(1_600_000_000, 500);
+        let entry = ManifestEntry {
+            sha256: String::new(),
+            size: 4,
+            mtime: None,
+            mtime_nanos: None,
+        };
+        let state = IncrementalState {
+            base: BTreeMap::from([("a.txt".to_string(), entry.with_modified(Some(modified)))]),
+            base_dirs: BTreeSet::new(),
+            compare_hashes: false,
+            snapshot: ArchiveManifest::default(),
+            unchanged_files: 0,
+        };
+        let unchanged = |modified| {
+            state
+                .unchanged_entry("a.txt", Path::new("a.txt"), 4, Some(modified))
+                .unwrap()
+                .is_some()
+        };
+
+        assert!(unchanged(modified));
+        assert!(!unchanged(Duration::new(1_600_000_000, 501)));
+    }
+
     #[cfg(unix)]
     #[tokio::test]
-    async fn symlinks_are_archived_without_following() {
-        let root = temp_case_dir("symlink");
-        let input_dir = root.join("input");
-        let target_file = input_dir.join("real.txt");
-        let alias_file = input_dir.join("alias.txt");
+    async fn hard_links_never_overwrite_existing_entries() {
+        let root = temp_case_dir("hardlink-collision");
         let archive_file = root.join("links.krate");
-        let output_dir = root.join("output");
+        fs::create_dir_all(&root).unwrap();
 
-        write_text_file(&target_file, "symlink payload");
-        fs::create_dir_all(&input_dir).unwrap();
-        symlink(Path::new("real.txt"), &alias_file).unwrap();
+        let mut builder = tar::Builder::new(Vec::new());
+        append_raw_tar_entry(
+            &mut builder,
+            "a.txt",
+            tar::EntryType::Regular,
+            None,
+            b"source",
+        );
+        append_raw_tar_entry(
+            &mut builder,
+            "b.txt",
+            tar::EntryType::Regular,
+            None,
+            b"keep",
+        );
+        append_raw_tar_entry(
+            &mut builder,
+            "link",
+            tar::EntryType::Symlink,
+            Some("b.txt"),
+            b"",
+        );
+        append_raw_tar_entry(
+            &mut builder,
+            "b.txt",
+            tar::EntryType::Link,
+            Some("a.txt"),
+            b"",
+        );
+        append_raw_tar_entry(
+            &mut builder,
+            "link",
+            tar::EntryType::Link,
+            Some("a.txt"),
+            b"",
+        );
+        write_raw_krate(&archive_file, &builder.into_inner().unwrap());
 
-        create_archive_impl(
+        let report = extract_archive_impl(
             None,
-            vec![input_dir.to_string_lossy().to_string()],
+            Arc::default(),
             archive_file.to_string_lossy().to_string(),
+            root.join("output").to_string_lossy().to_string(),
+            None,
             None,
-            Some(1),
+            ExtractOptions::default(),
         )
         .await
         .unwrap();
-
-        let extracted_dir = PathBuf::from(
-            extract_archive_impl(
-                None,
-                archive_file.to_string_lossy().to_string(),
-                output_dir.to_string_lossy().to_string(),
-                None,
-            )
-            .await
-            .unwrap(),
+        let output_dir = PathBuf::from(&report.output_dir);
+        assert_eq!(
+            fs::read_to_string(output_dir.join("b.txt")).unwrap(),
+            "keep"
         );
-        let extracted_alias = extracted_dir.join("input").join("alias.txt");
-
-        assert!(fs::symlink_metadata(&extracted_alias)
+        assert!(fs::symlink_metadata(output_dir.join("link"))
             .unwrap()
             .file_type()
             .is_symlink());
         assert_eq!(
-            fs::read_link(&extracted_alias).unwrap(),
-            PathBuf::from("real.txt")
+            report.skipped_entries,
+            vec!["b.txt".to_string(), "link".to_string()]
         );
-        assert_eq!(
-            fs::read_to_string(&extracted_alias).unwrap(),
-            "symlink payload"
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn salvage_lists_manifest_entries_that_were_not_recovered() {
+        let root = temp_case_dir("salvage-missing");
+        let archive_file = root.join("damaged.krate");
+        fs::create_dir_all(&root).unwrap();
+
+        let manifest = serde_json::json!({
+            "files": {
+                "a.txt": { "sha256": "00", "size": 6 },
+                "b.txt": { "sha256": "00", "size": 4 },
+            }
+        });
+        let mut builder = tar::Builder::new(Vec::new());
+        append_raw_tar_entry(
+            &mut builder,
+            "a.txt",
+            tar::EntryType::Regular,
+            None,
+            b"intact",
+        );
+        append_raw_tar_entry(
+            &mut builder,
+            MANIFEST_ENTRY_NAME,
+            tar::EntryType::Regular,
+            None,
+            manifest.to_string().as_bytes(),
         );
+        // 去掉结尾的两个全零块，让损坏的头紧跟在清单之后。
+        let mut tar_bytes = builder.into_inner().unwrap();
+        tar_bytes.truncate(tar_bytes.len() - 1024);
+        tar_bytes.extend_from_slice(&[0xff; 512]);
+        write_raw_krate(&archive_file, &tar_bytes);
+
+        let report = extract_archive_impl(
+            None,
+            Arc::default(),
+            archive_file.to_string_lossy().to_string(),
+            root.join("output").to_string_lossy().to_string(),
+            None,
+            None,
+            ExtractOptions {
+                salvage: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        let salvage = report.salvage.unwrap();
+        assert!(salvage.error.is_some());
+        assert_eq!(salvage.recovered_entries, vec!["a.txt".to_string()]);
+        assert_eq!(salvage.missing_entries, vec!["b.txt".to_string()]);
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn read_ahead_reader_returns_file_contents_in_order() {
+        let root = temp_case_dir("read-ahead");
+        fs::create_dir_all(&root).unwrap();
+        let path = root.join("data.bin");
+        let data = (0..READ_AHEAD_CHUNK_SIZE * (READ_AHEAD_CHUNKS + 2) + 123)
+            .map(|index| (index % 241) as u8)
+            .collect::<Vec<_>>();
+        fs::write(&path, &data).unwrap();
+
+        let mut read = Vec::new();
+        ReadAheadReader::spawn(File::open(&path).unwrap())
+            .read_to_end(&mut read)
+            .unwrap();
+        assert_eq!(read, data);
 
         let _ = fs::remove_dir_all(root);
     }
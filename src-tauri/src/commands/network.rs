@@ -1,19 +1,764 @@
-// 只在 Windows 下引入 HashMap
-#[cfg(target_os = "windows")]
 use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, ToSocketAddrs};
 use std::process::Command;
-use tauri::command;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use sysinfo::{
+    Pid, Process, ProcessRefreshKind, ProcessStatus, ProcessesToUpdate, System, UpdateKind, Users,
+};
+use tauri::{command, Emitter, State, Window};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+// 未指定 `states` 时只返回监听中的 TCP 端口，与旧版行为一致
+const DEFAULT_TCP_STATES: [&str; 1] = ["LISTEN"];
+// 正常结束进程时默认等待的时间，超时后强制结束
+const DEFAULT_KILL_TIMEOUT_MS: u64 = 5000;
+// 等待进程退出时的轮询间隔
+const KILL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+// 远程端口扫描每完成一个端口推送一次
+const PORT_SCAN_PROGRESS_EVENT: &str = "krate://portscan-progress";
+// 远程端口扫描的默认连接超时，超时的端口记为 filtered
+const DEFAULT_REMOTE_SCAN_TIMEOUT_MS: u64 = 1000;
+const DEFAULT_REMOTE_SCAN_CONCURRENCY: usize = 200;
+// 并发连接数上限，避免耗尽文件描述符
+const MAX_REMOTE_SCAN_CONCURRENCY: usize = 1000;
+// 最先完成的这么多个端口全部超时时，认为主机不可达并结束扫描
+const UNRESPONSIVE_PROBE_LIMIT: usize = 64;
+
+// 前端未传入任务 ID 时用于生成 ID
+static NEXT_PORT_SCAN_ID: AtomicU64 = AtomicU64::new(1);
 
 #[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct PortInfo {
     pid: String,
     port: String,
     protocol: String,
     program: String,
+    // 绑定的本地地址，IPv6 不带方括号，例如 0.0.0.0、::、::1、*
+    local_address: String,
+    // 对端地址与端口，监听中或未连接的套接字为空字符串
+    remote_address: String,
+    remote_port: String,
+    // TCP 状态（统一为 lsof 的写法，如 LISTEN、ESTABLISHED），UDP 为空字符串
+    state: String,
+    #[serde(flatten)]
+    process: ProcessIdentity,
 }
 
-#[command]
-pub fn scan_ports() -> Result<Vec<PortInfo>, String> {
+// 进程的可执行文件、命令行、所属用户与启动时间，进程已退出或无权查看时为空
+#[derive(serde::Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessIdentity {
+    exe_path: String,
+    cmdline: String,
+    user: String,
+    // 启动时间，Unix 时间戳（秒）
+    start_time: Option<u64>,
+}
+
+impl ProcessIdentity {
+    fn from_process(process: &Process, users: &Users) -> Self {
+        Self {
+            exe_path: process
+                .exe()
+                .map(|path| path.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            cmdline: process
+                .cmd()
+                .iter()
+                .map(|arg| arg.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(" "),
+            user: process
+                .user_id()
+                .and_then(|uid| users.get_user_by_id(uid))
+                .map(|user| user.name().to_string())
+                .unwrap_or_default(),
+            start_time: Some(process.start_time()),
+        }
+    }
+}
+
+// get_process_details 的返回值，在 ProcessIdentity 之外附带资源占用
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessDetails {
+    pid: String,
+    name: String,
+    #[serde(flatten)]
+    process: ProcessIdentity,
+    // 常驻内存，字节
+    memory: u64,
+    // CPU 使用率，100 表示占满一个核心
+    cpu_usage: f32,
+}
+
+// 填充 ProcessIdentity 所需的刷新项，已经取到的字段不再重复读取
+fn identity_refresh_kind() -> ProcessRefreshKind {
+    ProcessRefreshKind::nothing()
+        .with_exe(UpdateKind::OnlyIfNotSet)
+        .with_cmd(UpdateKind::OnlyIfNotSet)
+        .with_user(UpdateKind::OnlyIfNotSet)
+}
+
+// 为扫描结果补充进程信息，只刷新结果中出现的 PID
+fn attach_process_details(ports: &mut [PortInfo]) {
+    let pids: Vec<Pid> = ports
+        .iter()
+        .filter_map(|info| info.pid.parse::<u32>().ok())
+        .map(Pid::from_u32)
+        .collect();
+    if pids.is_empty() {
+        return;
+    }
+
+    let mut system = System::new();
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&pids),
+        true,
+        identity_refresh_kind(),
+    );
+    let users = Users::new_with_refreshed_list();
+
+    for info in ports.iter_mut() {
+        // 扫描与查询之间进程可能已经退出，此时保留空的进程信息
+        let process = info
+            .pid
+            .parse::<u32>()
+            .ok()
+            .and_then(|pid| system.process(Pid::from_u32(pid)));
+        if let Some(process) = process {
+            info.process = ProcessIdentity::from_process(process, &users);
+        }
+    }
+}
+
+impl PortInfo {
+    // 排序与去重用的键：端口按数值排序，解析不出的排在最后
+    fn sort_key(&self) -> (&str, u32, &str, &str, u32, u32) {
+        let number = |value: &str| value.parse::<u32>().unwrap_or(u32::MAX);
+        (
+            &self.protocol,
+            number(&self.port),
+            &self.local_address,
+            &self.remote_address,
+            number(&self.remote_port),
+            number(&self.pid),
+        )
+    }
+}
+
+// 把 netstat 与 lsof 的状态名统一，例如 LISTENING -> LISTEN、FIN_WAIT1 -> FIN_WAIT_1
+fn normalize_tcp_state(state: &str) -> String {
+    let state = state
+        .trim_matches(|c| c == '(' || c == ')')
+        .to_ascii_uppercase();
+    match state.as_str() {
+        "LISTENING" => "LISTEN".to_string(),
+        "FIN_WAIT1" => "FIN_WAIT_1".to_string(),
+        "FIN_WAIT2" => "FIN_WAIT_2".to_string(),
+        "SYN_RECEIVED" | "SYN_RCVD" => "SYN_RECV".to_string(),
+        "CLOSE" => "CLOSED".to_string(),
+        _ => state,
+    }
+}
+
+// 解析对端地址，`*:*`、`0.0.0.0:0` 这类占位写法视为没有对端
+#[cfg(any(feature = "shell-port-scan", test))]
+fn split_remote_address(addr: &str) -> (String, String) {
+    let (host, port) = split_socket_address(addr);
+    if port == "*" || port == "0" {
+        (String::new(), String::new())
+    } else {
+        (host, port)
+    }
+}
+
+// 把 `地址:端口` 拆成地址与端口，兼容 `[::]:8080`、`*:3000` 以及 `::1.8080` 这类写法
+#[cfg(any(feature = "shell-port-scan", test))]
+fn split_socket_address(addr: &str) -> (String, String) {
+    // 带方括号的 IPv6：[::1]:3000、[fe80::1%12]:546
+    if let Some(rest) = addr.strip_prefix('[') {
+        if let Some((host, port)) = rest.split_once("]:") {
+            return (host.to_string(), port.to_string());
+        }
+    }
+
+    match addr.rsplit_once(':') {
+        // 只有一个冒号：IPv4 或通配符
+        Some((host, port)) if !host.contains(':') => (host.to_string(), port.to_string()),
+        // 不带方括号的 IPv6 用点号分隔端口：::1.8080
+        Some(_) => match addr.rsplit_once('.') {
+            Some((host, port)) if port.chars().all(|c| c.is_ascii_digit()) => {
+                (host.to_string(), port.to_string())
+            }
+            _ => (addr.to_string(), "?".to_string()),
+        },
+        None => (addr.to_string(), "?".to_string()),
+    }
+}
+
+// 解析 `netstat -ano` 的输出，返回所有 TCP 连接与 UDP 套接字，状态过滤交给调用方
+#[cfg(any(all(target_os = "windows", feature = "shell-port-scan"), test))]
+fn parse_netstat_output(stdout: &str, pid_map: &HashMap<String, String>) -> Vec<PortInfo> {
+    let mut ports = Vec::new();
+
+    for line in stdout.lines() {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        // 典型格式: TCP 0.0.0.0:80 0.0.0.0:0 LISTENING 1234
+        //          UDP 0.0.0.0:5353 *:* 2468（UDP 没有状态列，PID 仍在最后一列）
+        let (protocol, state) = match parts.first() {
+            Some(proto) if proto.eq_ignore_ascii_case("TCP") && parts.len() >= 5 => {
+                ("TCP", normalize_tcp_state(parts[3]))
+            }
+            Some(proto) if proto.eq_ignore_ascii_case("UDP") && parts.len() >= 4 => {
+                ("UDP", String::new())
+            }
+            _ => continue,
+        };
+
+        let (local_address, port) = split_socket_address(parts[1]);
+        let (remote_address, remote_port) = split_remote_address(parts[2]);
+        let pid = parts[parts.len() - 1];
+
+        // --- 查表获取进程名 ---
+        // 如果查不到，就默认显示为空字符串或者再次显示 PID
+        let program = pid_map.get(pid).cloned().unwrap_or_default();
+
+        ports.push(PortInfo {
+            pid: pid.to_string(),
+            port,
+            protocol: protocol.to_string(),
+            program,
+            local_address,
+            remote_address,
+            remote_port,
+            state,
+            process: ProcessIdentity::default(),
+        });
+    }
+
+    ports
+}
+
+// 解析 `lsof -P -n` 的输出，`protocol` 为本次查询的协议（TCP 或 UDP）
+#[cfg(any(
+    all(
+        any(target_os = "linux", target_os = "macos"),
+        feature = "shell-port-scan"
+    ),
+    test
+))]
+fn parse_lsof_output(stdout: &str, protocol: &str) -> Vec<PortInfo> {
+    let mut ports = Vec::new();
+
+    for line in stdout.lines().skip(1) {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        // 典型格式: command pid user fd type device size/off node name
+        // node name 也就是 TCP *:80 (LISTEN)、TCP 10.0.0.2:50312->203.0.113.7:443 (ESTABLISHED) 或 UDP *:5353
+        if parts.len() >= 9 {
+            let program = parts[0];
+            let pid = parts[1];
+            // 已建立的连接形如 本地地址->对端地址
+            let (local_part, remote_part) = match parts[8].split_once("->") {
+                Some((local, remote)) => (local, Some(remote)),
+                None => (parts[8], None),
+            };
+
+            let (local_address, port) = split_socket_address(local_part);
+            let (remote_address, remote_port) =
+                remote_part.map(split_remote_address).unwrap_or_default();
+            let state = match (protocol, parts.get(9)) {
+                ("TCP", Some(state)) => normalize_tcp_state(state),
+                _ => String::new(),
+            };
+
+            ports.push(PortInfo {
+                pid: pid.to_string(),
+                port,
+                protocol: protocol.to_string(),
+                program: program.to_string(),
+                local_address,
+                remote_address,
+                remote_port,
+                state,
+                process: ProcessIdentity::default(),
+            });
+        }
+    }
+
+    ports
+}
+
+// 由原生接口得到的套接字生成 PortInfo，对端为通配地址或端口 0 时视为没有对端
+#[cfg(any(target_os = "linux", target_os = "windows", target_os = "macos"))]
+fn native_port_info(
+    protocol: &str,
+    owner: Option<(u32, String)>,
+    local: SocketAddr,
+    remote: Option<SocketAddr>,
+    state: String,
+) -> PortInfo {
+    let (pid, program) = owner
+        .map(|(pid, program)| (pid.to_string(), program))
+        .unwrap_or_default();
+    let (remote_address, remote_port) = remote
+        .filter(|addr| addr.port() != 0 && !addr.ip().is_unspecified())
+        .map(|addr| (addr.ip().to_string(), addr.port().to_string()))
+        .unwrap_or_default();
+
+    PortInfo {
+        pid,
+        port: local.port().to_string(),
+        protocol: protocol.to_string(),
+        program,
+        local_address: local.ip().to_string(),
+        remote_address,
+        remote_port,
+        state,
+        process: ProcessIdentity::default(),
+    }
+}
+
+// /proc/net/{tcp,udp}{,6} 中的一行
+#[cfg(target_os = "linux")]
+struct ProcNetSocket {
+    local: SocketAddr,
+    remote: SocketAddr,
+    state: String,
+    inode: u64,
+}
+
+// 解析 /proc/net 中形如 `0100007F:1F90` 的地址：IP 按 32 位字以主机字节序打印，端口是大端十六进制
+#[cfg(target_os = "linux")]
+fn parse_proc_net_address(field: &str) -> Option<SocketAddr> {
+    let (ip_hex, port_hex) = field.split_once(':')?;
+    let port = u16::from_str_radix(port_hex, 16).ok()?;
+    if ip_hex.len() != 8 && ip_hex.len() != 32 {
+        return None;
+    }
+
+    let mut bytes = [0u8; 16];
+    for (index, chunk) in bytes[..ip_hex.len() / 2].chunks_mut(4).enumerate() {
+        let word = u32::from_str_radix(ip_hex.get(index * 8..index * 8 + 8)?, 16).ok()?;
+        chunk.copy_from_slice(&word.to_ne_bytes());
+    }
+
+    let ip = if ip_hex.len() == 8 {
+        IpAddr::V4(Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]))
+    } else {
+        IpAddr::V6(Ipv6Addr::from(bytes))
+    };
+    Some(SocketAddr::new(ip, port))
+}
+
+// 解析 /proc/net/tcp 等文件的内容，`protocol` 为 TCP 时把状态码转换为状态名
+#[cfg(target_os = "linux")]
+fn parse_proc_net(content: &str, protocol: &str) -> Vec<ProcNetSocket> {
+    let mut sockets = Vec::new();
+
+    for line in content.lines().skip(1) {
+        // 格式: sl local_address rem_address st tx_queue:rx_queue tr:tm->when retrnsmt uid timeout inode ...
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() < 10 {
+            continue;
+        }
+        let (Some(local), Some(remote), Ok(inode)) = (
+            parse_proc_net_address(parts[1]),
+            parse_proc_net_address(parts[2]),
+            parts[9].parse::<u64>(),
+        ) else {
+            continue;
+        };
+
+        let state = if protocol == "TCP" {
+            // 状态码定义见内核 include/net/tcp_states.h
+            match parts[3] {
+                "01" => "ESTABLISHED",
+                "02" => "SYN_SENT",
+                "03" => "SYN_RECV",
+                "04" => "FIN_WAIT_1",
+                "05" => "FIN_WAIT_2",
+                "06" => "TIME_WAIT",
+                "07" => "CLOSED",
+                "08" => "CLOSE_WAIT",
+                "09" => "LAST_ACK",
+                "0A" => "LISTEN",
+                "0B" => "CLOSING",
+                other => other,
+            }
+            .to_string()
+        } else {
+            String::new()
+        };
+
+        sockets.push(ProcNetSocket {
+            local,
+            remote,
+            state,
+            inode,
+        });
+    }
+
+    sockets
+}
+
+// 遍历 /proc/<pid>/fd 建立 socket inode -> (PID, 进程名) 的映射，无权查看的进程直接跳过
+#[cfg(target_os = "linux")]
+fn socket_owners() -> HashMap<u64, (u32, String)> {
+    let mut owners = HashMap::new();
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return owners;
+    };
+
+    for entry in entries.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|s| s.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        let Ok(fds) = std::fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+        let program = std::fs::read_to_string(entry.path().join("comm"))
+            .map(|comm| comm.trim_end().to_string())
+            .unwrap_or_default();
+
+        for fd in fds.flatten() {
+            let Ok(target) = std::fs::read_link(fd.path()) else {
+                continue;
+            };
+            // 套接字的链接目标形如 socket:[12345]
+            let inode = target
+                .to_str()
+                .and_then(|s| s.strip_prefix("socket:["))
+                .and_then(|s| s.strip_suffix(']'))
+                .and_then(|s| s.parse::<u64>().ok());
+            if let Some(inode) = inode {
+                owners
+                    .entry(inode)
+                    .or_insert_with(|| (pid, program.clone()));
+            }
+        }
+    }
+
+    owners
+}
+
+// Linux：读取 /proc/net 下的套接字表，再通过 inode 找到所属进程
+#[cfg(target_os = "linux")]
+fn native_sockets(want_tcp: bool, want_udp: bool) -> Result<Vec<PortInfo>, String> {
+    let mut tables = Vec::new();
+    if want_tcp {
+        tables.extend([("TCP", "/proc/net/tcp"), ("TCP", "/proc/net/tcp6")]);
+    }
+    if want_udp {
+        tables.extend([("UDP", "/proc/net/udp"), ("UDP", "/proc/net/udp6")]);
+    }
+
+    let owners = socket_owners();
+    let mut ports = Vec::new();
+    for (protocol, path) in tables {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            // 内核关闭了 IPv6 时没有 tcp6 / udp6
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && path.ends_with('6') => continue,
+            Err(e) => return Err(format!("Failed to read {}: {}", path, e)),
+        };
+
+        for socket in parse_proc_net(&content, protocol) {
+            // TIME_WAIT 等已不属于任何进程的连接 inode 为 0
+            let owner = owners.get(&socket.inode).cloned();
+            ports.push(native_port_info(
+                protocol,
+                owner,
+                socket.local,
+                Some(socket.remote),
+                socket.state,
+            ));
+        }
+    }
+
+    Ok(ports)
+}
+
+// 调用 GetExtendedTcpTable / GetExtendedUdpTable，缓冲区不够时按返回的大小重试
+#[cfg(target_os = "windows")]
+fn read_ip_helper_table(
+    fetch: impl Fn(Option<*mut std::ffi::c_void>, *mut u32) -> u32,
+) -> Result<Vec<u32>, String> {
+    const ERROR_INSUFFICIENT_BUFFER: u32 = 122;
+
+    // 用 u32 作为元素保证表头与各行的对齐，至少容纳 dwNumEntries
+    let mut buffer = vec![0u32; 1];
+    let mut size = 4u32;
+    loop {
+        match fetch(Some(buffer.as_mut_ptr().cast()), &mut size) {
+            0 => return Ok(buffer),
+            // 两次调用之间可能有新连接建立，所以放在循环里
+            ERROR_INSUFFICIENT_BUFFER => buffer = vec![0u32; (size as usize).div_ceil(4)],
+            code => return Err(format!("Failed to read socket table (error {})", code)),
+        }
+    }
+}
+
+// MIB_TCP_STATE 转换为与 lsof 一致的状态名
+#[cfg(target_os = "windows")]
+fn windows_tcp_state(state: u32) -> String {
+    match state {
+        1 => "CLOSED",
+        2 => "LISTEN",
+        3 => "SYN_SENT",
+        4 => "SYN_RECV",
+        5 => "ESTABLISHED",
+        6 => "FIN_WAIT_1",
+        7 => "FIN_WAIT_2",
+        8 => "CLOSE_WAIT",
+        9 => "CLOSING",
+        10 => "LAST_ACK",
+        11 => "TIME_WAIT",
+        12 => "DELETE_TCB",
+        _ => "UNKNOWN",
+    }
+    .to_string()
+}
+
+// Windows：通过 IP Helper 读取带 PID 的连接表，进程名来自 sysinfo，避免 tasklist 的 GBK 输出
+#[cfg(target_os = "windows")]
+fn native_sockets(want_tcp: bool, want_udp: bool) -> Result<Vec<PortInfo>, String> {
+    use windows::Win32::NetworkManagement::IpHelper::{
+        GetExtendedTcpTable, GetExtendedUdpTable, MIB_TCP6TABLE_OWNER_PID, MIB_TCPTABLE_OWNER_PID,
+        MIB_UDP6TABLE_OWNER_PID, MIB_UDPTABLE_OWNER_PID, TCP_TABLE_OWNER_PID_ALL,
+        UDP_TABLE_OWNER_PID,
+    };
+    use windows::Win32::Networking::WinSock::{AF_INET, AF_INET6};
+
+    let mut system = System::new();
+    system.refresh_processes(ProcessesToUpdate::All, true);
+    let owner = |pid: u32| {
+        let program = system
+            .process(Pid::from_u32(pid))
+            .map(|process| process.name().to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Some((pid, program))
+    };
+    // 表中的端口以网络字节序存放在 DWORD 的低 16 位
+    let port = |value: u32| u16::from_be(value as u16);
+    let v4 = |addr: u32, value: u32| {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::from(addr.to_ne_bytes())), port(value))
+    };
+    let v6 =
+        |addr: [u8; 16], value: u32| SocketAddr::new(IpAddr::V6(Ipv6Addr::from(addr)), port(value));
+
+    let mut ports = Vec::new();
+    if want_tcp {
+        let buffer = read_ip_helper_table(|table, size| unsafe {
+            GetExtendedTcpTable(
+                table,
+                size,
+                false,
+                AF_INET.0 as u32,
+                TCP_TABLE_OWNER_PID_ALL,
+                0,
+            )
+        })?;
+        // SAFETY: 调用成功后缓冲区以 MIB_TCPTABLE_OWNER_PID 开头，后面紧跟 dwNumEntries 行
+        let rows = unsafe {
+            let table = &*buffer.as_ptr().cast::<MIB_TCPTABLE_OWNER_PID>();
+            std::slice::from_raw_parts(table.table.as_ptr(), table.dwNumEntries as usize)
+        };
+        for row in rows {
+            ports.push(native_port_info(
+                "TCP",
+                owner(row.dwOwningPid),
+                v4(row.dwLocalAddr, row.dwLocalPort),
+                Some(v4(row.dwRemoteAddr, row.dwRemotePort)),
+                windows_tcp_state(row.dwState),
+            ));
+        }
+
+        let buffer = read_ip_helper_table(|table, size| unsafe {
+            GetExtendedTcpTable(
+                table,
+                size,
+                false,
+                AF_INET6.0 as u32,
+                TCP_TABLE_OWNER_PID_ALL,
+                0,
+            )
+        })?;
+        // SAFETY: 同上，IPv6 表的行类型为 MIB_TCP6ROW_OWNER_PID
+        let rows = unsafe {
+            let table = &*buffer.as_ptr().cast::<MIB_TCP6TABLE_OWNER_PID>();
+            std::slice::from_raw_parts(table.table.as_ptr(), table.dwNumEntries as usize)
+        };
+        for row in rows {
+            ports.push(native_port_info(
+                "TCP",
+                owner(row.dwOwningPid),
+                v6(row.ucLocalAddr, row.dwLocalPort),
+                Some(v6(row.ucRemoteAddr, row.dwRemotePort)),
+                windows_tcp_state(row.dwState),
+            ));
+        }
+    }
+
+    if want_udp {
+        let buffer = read_ip_helper_table(|table, size| unsafe {
+            GetExtendedUdpTable(table, size, false, AF_INET.0 as u32, UDP_TABLE_OWNER_PID, 0)
+        })?;
+        // SAFETY: 同上，行类型为 MIB_UDPROW_OWNER_PID
+        let rows = unsafe {
+            let table = &*buffer.as_ptr().cast::<MIB_UDPTABLE_OWNER_PID>();
+            std::slice::from_raw_parts(table.table.as_ptr(), table.dwNumEntries as usize)
+        };
+        for row in rows {
+            let local = v4(row.dwLocalAddr, row.dwLocalPort);
+            ports.push(native_port_info(
+                "UDP",
+                owner(row.dwOwningPid),
+                local,
+                None,
+                String::new(),
+            ));
+        }
+
+        let buffer = read_ip_helper_table(|table, size| unsafe {
+            GetExtendedUdpTable(
+                table,
+                size,
+                false,
+                AF_INET6.0 as u32,
+                UDP_TABLE_OWNER_PID,
+                0,
+            )
+        })?;
+        // SAFETY: 同上，行类型为 MIB_UDP6ROW_OWNER_PID
+        let rows = unsafe {
+            let table = &*buffer.as_ptr().cast::<MIB_UDP6TABLE_OWNER_PID>();
+            std::slice::from_raw_parts(table.table.as_ptr(), table.dwNumEntries as usize)
+        };
+        for row in rows {
+            let local = v6(row.ucLocalAddr, row.dwLocalPort);
+            ports.push(native_port_info(
+                "UDP",
+                owner(row.dwOwningPid),
+                local,
+                None,
+                String::new(),
+            ));
+        }
+    }
+
+    Ok(ports)
+}
+
+// TSI_S_* 状态码转换为与 lsof 一致的状态名
+#[cfg(target_os = "macos")]
+fn macos_tcp_state(state: i32) -> String {
+    match state {
+        0 => "CLOSED",
+        1 => "LISTEN",
+        2 => "SYN_SENT",
+        3 => "SYN_RECV",
+        4 => "ESTABLISHED",
+        5 => "CLOSE_WAIT",
+        6 => "FIN_WAIT_1",
+        7 => "CLOSING",
+        8 => "LAST_ACK",
+        9 => "FIN_WAIT_2",
+        10 => "TIME_WAIT",
+        _ => "UNKNOWN",
+    }
+    .to_string()
+}
+
+// macOS：用 libproc 遍历每个进程的文件描述符，取出其中的 TCP / UDP 套接字
+#[cfg(target_os = "macos")]
+fn native_sockets(want_tcp: bool, want_udp: bool) -> Result<Vec<PortInfo>, String> {
+    use libproc::bsd_info::BSDInfo;
+    use libproc::file_info::{pidfdinfo, ListFDs, ProcFDType};
+    use libproc::net_info::{InSIAddr, InSockInfo, SocketFDInfo, SocketInfoKind};
+    use libproc::proc_pid::{listpidinfo, name, pidinfo};
+    use libproc::processes::{pids_by_type, ProcFilter};
+
+    const IPPROTO_UDP: i32 = 17;
+    const INI_IPV6: u8 = 0x2;
+
+    let addr = |info: &InSockInfo, addr: &InSIAddr, port: i32| {
+        // SAFETY: insi_vflag 标明了联合体中实际存放的是 IPv4 还是 IPv6 地址
+        let ip: IpAddr = unsafe {
+            if info.insi_vflag & INI_IPV6 != 0 {
+                Ipv6Addr::from(addr.ina_6.s6_addr).into()
+            } else {
+                Ipv4Addr::from(addr.ina_46.i46a_addr4.s_addr.to_ne_bytes()).into()
+            }
+        };
+        SocketAddr::new(ip, u16::from_be(port as u16))
+    };
+
+    let pids = pids_by_type(ProcFilter::All).map_err(|e| e.to_string())?;
+    let mut ports = Vec::new();
+    for pid in pids {
+        let pid = pid as i32;
+        // 无权查看的进程直接跳过
+        let Ok(info) = pidinfo::<BSDInfo>(pid, 0) else {
+            continue;
+        };
+        let Ok(fds) = listpidinfo::<ListFDs>(pid, info.pbi_nfiles as usize) else {
+            continue;
+        };
+        let program = name(pid).unwrap_or_default();
+
+        for fd in fds {
+            if !matches!(ProcFDType::from(fd.proc_fdtype), ProcFDType::Socket) {
+                continue;
+            }
+            let Ok(socket) = pidfdinfo::<SocketFDInfo>(pid, fd.proc_fd) else {
+                continue;
+            };
+            let psi = &socket.psi;
+
+            // SAFETY: soi_kind 标明了 soi_proto 联合体中实际存放的结构
+            let (protocol, info, state) = match SocketInfoKind::from(psi.soi_kind) {
+                SocketInfoKind::Tcp if want_tcp => {
+                    let tcp = unsafe { &psi.soi_proto.pri_tcp };
+                    ("TCP", &tcp.tcpsi_ini, macos_tcp_state(tcp.tcpsi_state))
+                }
+                SocketInfoKind::In if want_udp && psi.soi_protocol == IPPROTO_UDP => {
+                    ("UDP", unsafe { &psi.soi_proto.pri_in }, String::new())
+                }
+                _ => continue,
+            };
+
+            ports.push(native_port_info(
+                protocol,
+                Some((pid as u32, program.clone())),
+                addr(info, &info.insi_laddr, info.insi_lport),
+                Some(addr(info, &info.insi_faddr, info.insi_fport)),
+                state,
+            ));
+        }
+    }
+
+    Ok(ports)
+}
+
+// 其他平台没有原生实现
+#[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+fn native_sockets(_want_tcp: bool, _want_udp: bool) -> Result<Vec<PortInfo>, String> {
+    Err("Port scanning is not supported on this platform".to_string())
+}
+
+// 命令行后备方案：Windows 下解析 netstat，macOS / Linux 下解析 lsof
+#[cfg(feature = "shell-port-scan")]
+fn command_sockets() -> Result<Vec<PortInfo>, String> {
     let mut ports = Vec::new();
 
     #[cfg(target_os = "windows")]
@@ -57,104 +802,1046 @@ pub fn scan_ports() -> Result<Vec<PortInfo>, String> {
             .map_err(|e| e.to_string())?;
 
         let stdout = String::from_utf8_lossy(&output.stdout);
+        ports.extend(parse_netstat_output(&stdout, &pid_map));
+    }
 
-        for line in stdout.lines() {
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            // 典型格式: TCP 0.0.0.0:80 0.0.0.0:0 LISTENING 1234
-            if parts.len() >= 5 && parts[0].eq_ignore_ascii_case("TCP") {
-                let local_addr = parts[1];
-                let state = parts[3];
-                let pid = parts[parts.len() - 1];
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    {
+        // 不用 -sTCP:LISTEN 限制状态，协议与状态都由 scan_ports 在解析后过滤；
+        // UDP 没有 LISTEN 状态，所有绑定的套接字都算占用端口
+        for protocol in ["TCP", "UDP"] {
+            let output = Command::new("lsof")
+                .args([format!("-i{}", protocol).as_str(), "-P", "-n"])
+                .output()
+                .map_err(|e| e.to_string())?;
 
-                if state == "LISTENING" {
-                    let port = local_addr.split(':').last().unwrap_or("?").to_string();
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            ports.extend(parse_lsof_output(&stdout, protocol));
+        }
+    }
 
-                    // --- 查表获取进程名 ---
-                    // 如果查不到，就默认显示为空字符串或者再次显示 PID
-                    let program = pid_map.get(pid).cloned().unwrap_or_default();
+    Ok(ports)
+}
 
-                    ports.push(PortInfo {
-                        pid: pid.to_string(),
-                        port,
-                        protocol: "TCP".to_string(),
-                        program,
-                    });
-                }
-            }
+// 排序并去重：同一进程的同一个套接字只保留一行（lsof 会为每个文件描述符各列一次）
+fn sort_and_dedup_ports(mut ports: Vec<PortInfo>) -> Vec<PortInfo> {
+    ports.sort_by(|a, b| a.sort_key().cmp(&b.sort_key()));
+    ports.dedup_by(|a, b| a.sort_key() == b.sort_key());
+    ports
+}
+
+// 扫描本机占用的端口，`protocol` 为空时同时返回 TCP 与 UDP；
+// `states` 只作用于 TCP，默认只返回 LISTEN。结果已排序去重，但不含进程详情
+fn collect_ports(
+    protocol: Option<&str>,
+    states: Option<Vec<String>>,
+) -> Result<Vec<PortInfo>, String> {
+    let (want_tcp, want_udp) = match protocol.map(str::to_ascii_uppercase) {
+        None => (true, true),
+        Some(proto) if proto == "TCP" => (true, false),
+        Some(proto) if proto == "UDP" => (false, true),
+        Some(proto) => return Err(format!("Unsupported protocol: {}", proto)),
+    };
+    let states: Vec<String> = match states {
+        Some(states) => states.iter().map(|s| normalize_tcp_state(s)).collect(),
+        None => DEFAULT_TCP_STATES.iter().map(|s| s.to_string()).collect(),
+    };
+    let wanted = |info: &PortInfo| {
+        if info.protocol == "TCP" {
+            want_tcp && states.contains(&info.state)
+        } else {
+            want_udp
         }
-    }
+    };
 
-    #[cfg(any(target_os = "linux", target_os = "macos"))]
-    {
-        // 执行 lsof -i -P -n -sTCP:LISTEN
-        let output = Command::new("lsof")
-            .args(["-iTCP", "-sTCP:LISTEN", "-P", "-n"])
+    let ports = match native_sockets(want_tcp, want_udp) {
+        Ok(ports) => ports,
+        // 原生接口失败时（例如 /proc 被隐藏）退回到 netstat / lsof
+        #[cfg(feature = "shell-port-scan")]
+        Err(_) => command_sockets()?,
+        #[cfg(not(feature = "shell-port-scan"))]
+        Err(err) => return Err(err),
+    };
+
+    Ok(sort_and_dedup_ports(
+        ports.into_iter().filter(wanted).collect(),
+    ))
+}
+
+// 扫描本机占用的端口并附带进程详情，参数含义见 collect_ports
+#[command]
+pub fn scan_ports(
+    protocol: Option<String>,
+    states: Option<Vec<String>>,
+) -> Result<Vec<PortInfo>, String> {
+    let mut ports = collect_ports(protocol.as_deref(), states)?;
+    attach_process_details(&mut ports);
+    Ok(ports)
+}
+
+// 按需查询单个进程的详细信息与资源占用，供结束进程前确认
+#[command]
+pub async fn get_process_details(pid: String) -> Result<ProcessDetails, String> {
+    let pids = [pid
+        .parse::<u32>()
+        .map(Pid::from_u32)
+        .map_err(|_| format!("Invalid PID: {}", pid))?];
+    let refresh_kind = identity_refresh_kind().with_cpu().with_memory();
+
+    let mut system = System::new();
+    system.refresh_processes_specifics(ProcessesToUpdate::Some(&pids), true, refresh_kind);
+    // CPU 使用率需要间隔两次采样才能计算
+    tokio::time::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL).await;
+    system.refresh_processes_specifics(ProcessesToUpdate::Some(&pids), true, refresh_kind);
+
+    let process = system
+        .process(pids[0])
+        .ok_or_else(|| format!("Process {} not found", pid))?;
+    let users = Users::new_with_refreshed_list();
+
+    Ok(ProcessDetails {
+        name: process.name().to_string_lossy().into_owned(),
+        process: ProcessIdentity::from_process(process, &users),
+        memory: process.memory(),
+        cpu_usage: process.cpu_usage(),
+        pid,
+    })
+}
+
+// 结束进程的结果，`forced` 为 false 表示进程在超时前自行退出
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KillResult {
+    pid: String,
+    forced: bool,
+    // 实际生效的方式：SIGTERM / SIGKILL，Windows 下为 taskkill / taskkill /F
+    signal: String,
+    message: String,
+}
+
+// 发送结束信号，`force` 为 false 时只请求进程退出，返回所用信号的名称
+fn send_kill_signal(pid: u32, force: bool) -> Result<&'static str, String> {
+    let pid = pid.to_string();
+
+    #[cfg(target_os = "windows")]
+    let (signal, output) = {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+        // 不带 /F 时 taskkill 向窗口发送 WM_CLOSE
+        let (signal, args) = if force {
+            ("taskkill /F", vec!["/F", "/PID", pid.as_str()])
+        } else {
+            ("taskkill", vec!["/PID", pid.as_str()])
+        };
+        let output = Command::new("taskkill")
+            .args(args)
+            .creation_flags(CREATE_NO_WINDOW)
             .output()
             .map_err(|e| e.to_string())?;
+        (signal, output)
+    };
 
-        let stdout = String::from_utf8_lossy(&output.stdout);
+    #[cfg(not(target_os = "windows"))]
+    let (signal, output) = {
+        let signal = if force { "SIGKILL" } else { "SIGTERM" };
+        let output = Command::new("kill")
+            .args([if force { "-KILL" } else { "-TERM" }, pid.as_str()])
+            .output()
+            .map_err(|e| e.to_string())?;
+        (signal, output)
+    };
 
-        // 解析输出
-        for line in stdout.lines().skip(1) {
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            // 典型格式: command pid user fd type device size/off node name
-            // node name 也就是 TCP *:80 (LISTEN)
-            if parts.len() >= 9 {
-                let program = parts[0];
-                let pid = parts[1];
-                let address_part = parts[8]; // *:8080
-
-                if let Some(port_str) = address_part.split(':').next_back() {
-                    let port = port_str.to_string();
-                    ports.push(PortInfo {
+    if output.status.success() {
+        Ok(signal)
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).to_string())
+    }
+}
+
+// 进程是否仍在运行，僵尸进程视为已经退出
+fn process_alive(system: &mut System, pid: Pid) -> bool {
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&[pid]),
+        true,
+        ProcessRefreshKind::nothing(),
+    );
+    system
+        .process(pid)
+        .is_some_and(|process| process.status() != ProcessStatus::Zombie)
+}
+
+// 先请求进程退出并轮询等待，超时仍未退出时再强制结束
+async fn terminate_process(
+    pid: u32,
+    graceful: bool,
+    timeout: Duration,
+) -> Result<KillResult, String> {
+    if graceful {
+        // 没有窗口的进程无法用 taskkill 正常关闭，此时直接升级为强制结束
+        if let Ok(signal) = send_kill_signal(pid, false) {
+            let mut system = System::new();
+            let deadline = Instant::now() + timeout;
+            loop {
+                if !process_alive(&mut system, Pid::from_u32(pid)) {
+                    return Ok(KillResult {
                         pid: pid.to_string(),
-                        port,
-                        protocol: "TCP".to_string(),
-                        program: program.to_string(),
+                        forced: false,
+                        signal: signal.to_string(),
+                        message: format!("Process {} exited after {}", pid, signal),
                     });
                 }
+                if Instant::now() >= deadline {
+                    break;
+                }
+                tokio::time::sleep(KILL_POLL_INTERVAL).await;
             }
         }
     }
 
-    Ok(ports)
+    let signal = send_kill_signal(pid, true)?;
+    Ok(KillResult {
+        pid: pid.to_string(),
+        forced: true,
+        signal: signal.to_string(),
+        message: format!("Process {} killed with {}", pid, signal),
+    })
 }
 
+// kill_process_on_port 中单个进程的处理结果，`result` 与 `error` 只有一个有值
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KillOutcome {
+    pid: String,
+    program: String,
+    result: Option<KillResult>,
+    error: Option<String>,
+}
+
+// 按端口结束进程的汇总，端口上没有进程时 `found` 为 false
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KillReport {
+    port: u16,
+    found: bool,
+    message: String,
+    outcomes: Vec<KillOutcome>,
+}
+
+// 结束后会导致系统崩溃或无意义的进程：Windows 的 System Idle Process / System，Unix 的 init
+fn is_critical_pid(pid: u32) -> bool {
+    if cfg!(target_os = "windows") {
+        pid == 0 || pid == 4
+    } else {
+        pid == 1
+    }
+}
+
+// 决定能否结束占用端口的进程：返回可结束的 PID，或不结束的原因
+fn killable_pid(pid_text: &str, force: bool) -> Result<u32, String> {
+    match pid_text.parse::<u32>() {
+        // Linux 下无权查看的进程拿不到 PID
+        Err(_) => Err("Owning process is not visible to the current user".to_string()),
+        Ok(pid) if is_critical_pid(pid) && !force => Err(format!(
+            "Refusing to kill critical system process {} without force",
+            pid
+        )),
+        Ok(pid) => Ok(pid),
+    }
+}
+
+// 结束进程，默认先正常终止，等待 `timeout_ms`（默认 5 秒）后仍存活才强制结束
 #[command]
-pub fn kill_process(pid: String) -> Result<String, String> {
-    if pid.is_empty() {
-        return Err("PID cannot be empty".to_string());
+pub async fn kill_process(
+    pid: String,
+    graceful: Option<bool>,
+    timeout_ms: Option<u64>,
+) -> Result<KillResult, String> {
+    let pid = pid
+        .trim()
+        .parse::<u32>()
+        .map_err(|_| format!("Invalid PID: {}", pid))?;
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(DEFAULT_KILL_TIMEOUT_MS));
+
+    terminate_process(pid, graceful.unwrap_or(true), timeout).await
+}
+
+// 结束所有占用指定端口（TCP 监听或 UDP 绑定）的进程，关键系统进程需要 `force` 才会处理
+#[command]
+pub async fn kill_process_on_port(
+    port: u16,
+    protocol: Option<String>,
+    graceful: Option<bool>,
+    force: Option<bool>,
+    timeout_ms: Option<u64>,
+) -> Result<KillReport, String> {
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(DEFAULT_KILL_TIMEOUT_MS));
+    let port_text = port.to_string();
+
+    // 同一进程可能同时监听 IPv4 与 IPv6，按 PID 合并
+    let mut owners: Vec<(String, String)> = Vec::new();
+    for info in collect_ports(protocol.as_deref(), None)? {
+        if info.port == port_text && !owners.iter().any(|(pid, _)| *pid == info.pid) {
+            owners.push((info.pid, info.program));
+        }
     }
 
-    #[cfg(target_os = "windows")]
-    {
-        use std::os::windows::process::CommandExt;
-        const CREATE_NO_WINDOW: u32 = 0x08000000;
+    if owners.is_empty() {
+        return Ok(KillReport {
+            port,
+            found: false,
+            message: format!("Nothing is listening on port {}", port),
+            outcomes: Vec::new(),
+        });
+    }
 
-        let output = Command::new("taskkill")
-            .args(["/F", "/PID", &pid])
-            .creation_flags(CREATE_NO_WINDOW)
-            .output()
-            .map_err(|e| e.to_string())?;
+    let mut outcomes = Vec::new();
+    for (pid_text, program) in owners {
+        let outcome = match killable_pid(&pid_text, force.unwrap_or(false)) {
+            Ok(pid) => terminate_process(pid, graceful.unwrap_or(true), timeout).await,
+            Err(err) => Err(err),
+        };
 
-        if output.status.success() {
-            Ok("Process killed".to_string())
-        } else {
-            Err(String::from_utf8_lossy(&output.stderr).to_string())
+        let (result, error) = match outcome {
+            Ok(result) => (Some(result), None),
+            Err(err) => (None, Some(err)),
+        };
+        outcomes.push(KillOutcome {
+            pid: pid_text,
+            program,
+            result,
+            error,
+        });
+    }
+
+    let stopped = outcomes.iter().filter(|o| o.result.is_some()).count();
+    Ok(KillReport {
+        port,
+        found: true,
+        message: format!(
+            "Stopped {} of {} processes on port {}",
+            stopped,
+            outcomes.len(),
+            port
+        ),
+        outcomes,
+    })
+}
+
+// scan_remote_ports 的参数：`ports` 与 `range_start`/`range_end` 二选一
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteScanRequest {
+    host: String,
+    ports: Option<Vec<u16>>,
+    range_start: Option<u16>,
+    range_end: Option<u16>,
+    timeout_ms: Option<u64>,
+    concurrency: Option<usize>,
+}
+
+// 单个远程端口的扫描结果，`status` 为 open / closed / filtered
+#[derive(serde::Serialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct RemotePortResult {
+    port: u16,
+    status: &'static str,
+    // 建立连接耗时，只有 open 的端口有值
+    latency_ms: Option<f64>,
+}
+
+// `krate://portscan-progress` 事件负载
+#[derive(serde::Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct PortScanProgressPayload<'a> {
+    operation_id: &'a str,
+    host: &'a str,
+    // 解析后实际连接的地址
+    address: String,
+    total: usize,
+    completed: usize,
+    open: usize,
+    result: RemotePortResult,
+}
+
+// 正在运行的远程端口扫描（按任务 ID 保存取消标记）
+#[derive(Default)]
+pub struct PortScanState {
+    operations: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+// 扫描结束时从 `PortScanState` 中注销
+struct PortScanGuard<'a> {
+    state: &'a PortScanState,
+    operation_id: String,
+    cancel: Arc<AtomicBool>,
+}
+
+impl PortScanState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn register(&self, operation_id: Option<String>) -> Result<PortScanGuard<'_>, String> {
+        let operation_id = operation_id.unwrap_or_else(|| {
+            format!(
+                "portscan-{}",
+                NEXT_PORT_SCAN_ID.fetch_add(1, Ordering::Relaxed)
+            )
+        });
+        let mut operations = self
+            .operations
+            .lock()
+            .map_err(|_| "Port scan state lock poisoned".to_string())?;
+        if operations.contains_key(&operation_id) {
+            return Err(format!("Port scan already exists: {}", operation_id));
         }
+        let cancel = Arc::new(AtomicBool::new(false));
+        operations.insert(operation_id.clone(), cancel.clone());
+
+        Ok(PortScanGuard {
+            state: self,
+            operation_id,
+            cancel,
+        })
     }
 
-    #[cfg(not(target_os = "windows"))]
-    {
-        let output = Command::new("kill")
-            .args(["-9", &pid])
-            .output()
+    fn cancel(&self, operation_id: &str) -> Result<(), String> {
+        let operations = self
+            .operations
+            .lock()
+            .map_err(|_| "Port scan state lock poisoned".to_string())?;
+        operations
+            .get(operation_id)
+            .ok_or_else(|| format!("Port scan not found or finished: {}", operation_id))?
+            .store(true, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+impl Drop for PortScanGuard<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut operations) = self.state.operations.lock() {
+            operations.remove(&self.operation_id);
+        }
+    }
+}
+
+// 由显式端口列表或闭区间得到去重排序后的端口列表，显式列表优先
+fn remote_port_list(
+    ports: Option<Vec<u16>>,
+    range_start: Option<u16>,
+    range_end: Option<u16>,
+) -> Result<Vec<u16>, String> {
+    let mut ports = match (ports, range_start, range_end) {
+        (Some(ports), _, _) if !ports.is_empty() => ports,
+        (_, Some(start), Some(end)) if start <= end => (start..=end).collect(),
+        (_, Some(start), Some(end)) => {
+            return Err(format!("Invalid port range: {}-{}", start, end));
+        }
+        _ => return Err("No ports to scan".to_string()),
+    };
+    // 端口 0 无法连接
+    ports.retain(|port| *port != 0);
+    ports.sort_unstable();
+    ports.dedup();
+    if ports.is_empty() {
+        return Err("No ports to scan".to_string());
+    }
+    Ok(ports)
+}
+
+// 尝试连接一个端口；主机或网络不可达时返回错误，让整个扫描立即结束
+async fn probe_port(ip: IpAddr, port: u16, timeout: Duration) -> Result<RemotePortResult, String> {
+    let started = Instant::now();
+    let connect = tokio::net::TcpStream::connect((ip, port));
+    let status = match tokio::time::timeout(timeout, connect).await {
+        Ok(Ok(_)) => {
+            return Ok(RemotePortResult {
+                port,
+                status: "open",
+                latency_ms: Some(started.elapsed().as_secs_f64() * 1000.0),
+            });
+        }
+        Ok(Err(e)) if e.kind() == ErrorKind::ConnectionRefused => "closed",
+        Ok(Err(e))
+            if matches!(
+                e.kind(),
+                ErrorKind::HostUnreachable | ErrorKind::NetworkUnreachable
+            ) =>
+        {
+            return Err(format!("Host {} is unreachable: {}", ip, e));
+        }
+        // 超时或其他错误，多半是被防火墙丢弃
+        _ => "filtered",
+    };
+
+    Ok(RemotePortResult {
+        port,
+        status,
+        latency_ms: None,
+    })
+}
+
+// 最先完成的 `UNRESPONSIVE_PROBE_LIMIT` 个端口全部超时，且还有端口未扫描时，
+// 多半是主机离线或丢弃所有连接，继续扫描只会让每个端口各等一次超时
+fn host_looks_unresponsive(completed: usize, responded: usize, total: usize) -> bool {
+    responded == 0 && completed == UNRESPONSIVE_PROBE_LIMIT && total > UNRESPONSIVE_PROBE_LIMIT
+}
+
+// 以有限并发对 `host` 做 TCP connect 扫描，结果按端口排序
+async fn scan_remote_ports_impl(
+    window: Option<&Window>,
+    cancel: &AtomicBool,
+    operation_id: &str,
+    host: &str,
+    ports: Vec<u16>,
+    timeout: Duration,
+    concurrency: usize,
+) -> Result<Vec<RemotePortResult>, String> {
+    // 解析失败时只报一次错，而不是每个端口各超时一次
+    let ip = tokio::net::lookup_host((host, 0))
+        .await
+        .map_err(|e| format!("Failed to resolve host {}: {}", host, e))?
+        .next()
+        .map(|addr| addr.ip())
+        .ok_or_else(|| format!("Host {} has no addresses", host))?;
+
+    let total = ports.len();
+    let semaphore = Arc::new(Semaphore::new(
+        concurrency.clamp(1, MAX_REMOTE_SCAN_CONCURRENCY),
+    ));
+    let mut tasks = JoinSet::new();
+    let mut results = Vec::with_capacity(total);
+    let mut open = 0;
+    let mut responded = 0;
+
+    let mut record = |joined: Result<Result<RemotePortResult, String>, tokio::task::JoinError>| {
+        let result = joined.map_err(|e| e.to_string())??;
+        if result.status == "open" {
+            open += 1;
+        }
+        if result.status != "filtered" {
+            responded += 1;
+        }
+        results.push(result);
+        if let Some(window) = window {
+            let _ = window.emit(
+                PORT_SCAN_PROGRESS_EVENT,
+                PortScanProgressPayload {
+                    operation_id,
+                    host,
+                    address: ip.to_string(),
+                    total,
+                    completed: results.len(),
+                    open,
+                    result,
+                },
+            );
+        }
+        if host_looks_unresponsive(results.len(), responded, total) {
+            return Err(format!(
+                "Host {} did not respond: the first {} ports all timed out",
+                host, UNRESPONSIVE_PROBE_LIMIT
+            ));
+        }
+        Ok::<(), String>(())
+    };
+
+    // 先拿到许可再创建任务，同时在途的连接数不超过 concurrency；
+    // 提前返回时 JoinSet 被丢弃，未完成的连接随之中止
+    for port in ports {
+        let permit = semaphore
+            .clone()
+            .acquire_owned()
+            .await
             .map_err(|e| e.to_string())?;
+        if cancel.load(Ordering::Relaxed) {
+            return Err("Port scan cancelled".to_string());
+        }
+        tasks.spawn(async move {
+            let _permit = permit;
+            probe_port(ip, port, timeout).await
+        });
+        while let Some(joined) = tasks.try_join_next() {
+            record(joined)?;
+        }
+    }
+    while let Some(joined) = tasks.join_next().await {
+        if cancel.load(Ordering::Relaxed) {
+            return Err("Port scan cancelled".to_string());
+        }
+        record(joined)?;
+    }
 
-        if output.status.success() {
-            Ok("Process killed".to_string())
-        } else {
-            Err(String::from_utf8_lossy(&output.stderr).to_string())
+    results.sort_by_key(|result| result.port);
+    Ok(results)
+}
+
+// 扫描远程主机的 TCP 端口，每完成一个端口推送 `krate://portscan-progress` 事件，
+// 可通过 `cancel_port_scan` 取消
+#[command]
+pub async fn scan_remote_ports(
+    window: Window,
+    state: State<'_, PortScanState>,
+    operation_id: Option<String>,
+    request: RemoteScanRequest,
+) -> Result<Vec<RemotePortResult>, String> {
+    let ports = remote_port_list(request.ports, request.range_start, request.range_end)?;
+    let operation = state.register(operation_id)?;
+    let timeout =
+        Duration::from_millis(request.timeout_ms.unwrap_or(DEFAULT_REMOTE_SCAN_TIMEOUT_MS));
+
+    scan_remote_ports_impl(
+        Some(&window),
+        &operation.cancel,
+        &operation.operation_id,
+        request.host.trim(),
+        ports,
+        timeout,
+        request
+            .concurrency
+            .unwrap_or(DEFAULT_REMOTE_SCAN_CONCURRENCY),
+    )
+    .await
+}
+
+// 取消正在运行的远程端口扫描，扫描随后以“cancelled”错误结束
+#[command]
+pub fn cancel_port_scan(
+    state: State<'_, PortScanState>,
+    operation_id: String,
+) -> Result<(), String> {
+    state.cancel(&operation_id)
+}
+
+// check_port 的结果；任一地址绑定失败时 `occupants` 列出占用该端口的进程
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PortCheckResult {
+    port: u16,
+    host: String,
+    // 指定地址与对应通配地址都能绑定时才算可用
+    available: bool,
+    host_available: bool,
+    ipv4_wildcard_available: bool,
+    // 系统不支持 IPv6 时为 None
+    ipv6_wildcard_available: Option<bool>,
+    // 指定地址绑定失败的原因
+    error: Option<String>,
+    occupants: Vec<PortInfo>,
+}
+
+// 尝试绑定后立即释放；地址在本机不存在或系统不支持该协议族时返回 None
+fn try_bind(addr: SocketAddr) -> Option<Result<(), String>> {
+    match TcpListener::bind(addr) {
+        Ok(_) => Some(Ok(())),
+        Err(e)
+            if matches!(
+                e.kind(),
+                ErrorKind::AddrNotAvailable | ErrorKind::Unsupported
+            ) =>
+        {
+            None
+        }
+        Err(e) => Some(Err(e.to_string())),
+    }
+}
+
+// 检查本机端口能否绑定。除了指定地址（默认 127.0.0.1）外还会检查 0.0.0.0 与 [::]：
+// Windows 允许在 0.0.0.0 被占用时绑定更具体的 127.0.0.1，只检查回环地址会误报可用
+#[command]
+pub fn check_port(port: u16, host: Option<String>) -> Result<PortCheckResult, String> {
+    if port == 0 {
+        return Err("Port must be between 1 and 65535".to_string());
+    }
+    let host = host
+        .map(|host| host.trim().to_string())
+        .filter(|host| !host.is_empty())
+        .unwrap_or_else(|| "127.0.0.1".to_string());
+    let addr = (host.as_str(), port)
+        .to_socket_addrs()
+        .map_err(|e| format!("Failed to resolve host {}: {}", host, e))?
+        .next()
+        .ok_or_else(|| format!("Host {} has no addresses", host))?;
+
+    let host_bind = try_bind(addr)
+        .ok_or_else(|| format!("Address {} is not available on this machine", addr.ip()))?;
+    let ipv4_wildcard_available =
+        try_bind(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port)).is_some_and(|r| r.is_ok());
+    let ipv6_wildcard_available =
+        try_bind(SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), port)).map(|r| r.is_ok());
+
+    let host_available = host_bind.is_ok();
+    let wildcard_available = if addr.is_ipv4() {
+        ipv4_wildcard_available
+    } else {
+        ipv6_wildcard_available.unwrap_or(true)
+    };
+    let available = host_available && wildcard_available;
+
+    // 被占用时借助扫描结果说明是谁；扫描失败不影响检查结果
+    let mut occupants = Vec::new();
+    if !available || ipv6_wildcard_available == Some(false) {
+        let port_text = port.to_string();
+        occupants = collect_ports(Some("TCP"), None)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|info| info.port == port_text)
+            .collect();
+        attach_process_details(&mut occupants);
+    }
+
+    Ok(PortCheckResult {
+        port,
+        host,
+        available,
+        host_available,
+        ipv4_wildcard_available,
+        ipv6_wildcard_available,
+        error: host_bind.err(),
+        occupants,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summarize(ports: &[PortInfo]) -> Vec<(String, String, String, String)> {
+        ports
+            .iter()
+            .map(|info| {
+                (
+                    info.protocol.clone(),
+                    info.port.clone(),
+                    info.pid.clone(),
+                    info.program.clone(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn netstat_output_reports_tcp_and_udp_sockets() {
+        let stdout = "
+Active Connections
+
+  Proto  Local Address          Foreign Address        State           PID
+  TCP    0.0.0.0:135            0.0.0.0:0              LISTENING       1012
+  TCP    127.0.0.1:5939         127.0.0.1:49702        ESTABLISHED     4420
+  UDP    0.0.0.0:5353           *:*                                    2468
+  UDP    127.0.0.1:1900         *:*                                    5120
+";
+        let pid_map = HashMap::from([
+            ("1012".to_string(), "svchost.exe".to_string()),
+            ("2468".to_string(), "chrome.exe".to_string()),
+        ]);
+
+        let ports = parse_netstat_output(stdout, &pid_map);
+        assert_eq!(
+            summarize(&ports),
+            vec![
+                (
+                    "TCP".into(),
+                    "135".into(),
+                    "1012".into(),
+                    "svchost.exe".into()
+                ),
+                ("TCP".into(), "5939".into(), "4420".into(), String::new()),
+                (
+                    "UDP".into(),
+                    "5353".into(),
+                    "2468".into(),
+                    "chrome.exe".into()
+                ),
+                ("UDP".into(), "1900".into(), "5120".into(), String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn lsof_udp_output_uses_local_address() {
+        let stdout = "\
+COMMAND     PID USER   FD   TYPE             DEVICE SIZE/OFF NODE NAME
+mDNSRespo   312 root    8u  IPv4 0x5c1d3e0b2f1a7c01      0t0  UDP *:5353
+dnsmasq    1188 root    4u  IPv4              23417      0t0  UDP 127.0.0.1:53
+node       4031 dev    21u  IPv4              88120      0t0  UDP 127.0.0.1:61234->127.0.0.1:53
+";
+
+        let ports = parse_lsof_output(stdout, "UDP");
+        assert_eq!(
+            summarize(&ports),
+            vec![
+                (
+                    "UDP".into(),
+                    "5353".into(),
+                    "312".into(),
+                    "mDNSRespo".into()
+                ),
+                ("UDP".into(), "53".into(), "1188".into(), "dnsmasq".into()),
+                ("UDP".into(), "61234".into(), "4031".into(), "node".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn ipv6_and_wildcard_addresses_keep_their_ports() {
+        let netstat = "
+  TCP    0.0.0.0:445            0.0.0.0:0              LISTENING       4
+  TCP    [::]:445               [::]:0                 LISTENING       4
+  TCP    [::1]:6463             [::]:0                 LISTENING       9876
+  UDP    [fe80::1c2b:3d4e:5f60:7182%12]:546  *:*                       1300
+";
+        let lsof = "\
+COMMAND     PID USER   FD   TYPE DEVICE SIZE/OFF NODE NAME
+node       4031 dev    23u  IPv6  88121      0t0  TCP [::1]:3000 (LISTEN)
+node       4031 dev    24u  IPv6  88122      0t0  TCP *:3001 (LISTEN)
+postgres    911 dev     7u  IPv4  20211      0t0  TCP 127.0.0.1:5432 (LISTEN)
+";
+
+        let addresses = |ports: Vec<PortInfo>| {
+            ports
+                .into_iter()
+                .map(|info| (info.local_address, info.port))
+                .collect::<Vec<_>>()
+        };
+        let pair = |addr: &str, port: &str| (addr.to_string(), port.to_string());
+
+        assert_eq!(
+            addresses(parse_netstat_output(netstat, &HashMap::new())),
+            vec![
+                pair("0.0.0.0", "445"),
+                pair("::", "445"),
+                pair("::1", "6463"),
+                pair("fe80::1c2b:3d4e:5f60:7182%12", "546"),
+            ]
+        );
+        assert_eq!(
+            addresses(parse_lsof_output(lsof, "TCP")),
+            vec![
+                pair("::1", "3000"),
+                pair("*", "3001"),
+                pair("127.0.0.1", "5432")
+            ]
+        );
+        assert_eq!(split_socket_address("::1.8080"), pair("::1", "8080"));
+    }
+
+    #[test]
+    fn connection_states_carry_remote_endpoints_and_are_deduplicated() {
+        let lsof = "\
+COMMAND     PID USER   FD   TYPE DEVICE SIZE/OFF NODE NAME
+curl       7001 dev     5u  IPv4  90001      0t0  TCP 10.0.0.2:50312->203.0.113.7:443 (ESTABLISHED)
+nginx       880 root    6u  IPv4  18001      0t0  TCP *:80 (LISTEN)
+nginx       880 root    9u  IPv4  18001      0t0  TCP *:80 (LISTEN)
+sshd        640 root    3u  IPv6  17002      0t0  TCP [2001:db8::5]:22->[2001:db8::9]:61022 (FIN_WAIT1)
+";
+        let netstat = "
+  TCP    10.0.0.2:50313         203.0.113.7:443        TIME_WAIT       0
+";
+
+        let ports = parse_lsof_output(lsof, "TCP");
+        assert_eq!(ports[0].remote_address, "203.0.113.7");
+        assert_eq!(ports[0].remote_port, "443");
+        assert_eq!(ports[0].state, "ESTABLISHED");
+        assert_eq!(ports[1].remote_address, "");
+        assert_eq!(ports[1].state, "LISTEN");
+        assert_eq!(ports[3].remote_address, "2001:db8::9");
+        assert_eq!(ports[3].state, "FIN_WAIT_1");
+
+        let windows = parse_netstat_output(netstat, &HashMap::new());
+        assert_eq!(windows[0].state, "TIME_WAIT");
+        assert_eq!(windows[0].remote_port, "443");
+
+        // 同一进程的两个文件描述符指向同一个监听套接字，只保留一行，并按端口排序
+        let sorted = sort_and_dedup_ports(ports);
+        let order: Vec<&str> = sorted.iter().map(|info| info.port.as_str()).collect();
+        assert_eq!(order, vec!["22", "80", "50312"]);
+    }
+
+    #[cfg(all(target_os = "linux", target_endian = "little"))]
+    #[test]
+    fn proc_net_lines_decode_addresses_and_states() {
+        let tcp = "\
+  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:0CEA 00000000:0000 0A 00000000:00000000 00:00000000 00000000   999        0 41234 1 0000000000000000 100 0 0 10 0
+   1: 0200000A:C488 077100CB:01BB 01 00000000:00000000 02:000A7B2C 00000000  1000        0 52011 2 0000000000000000 20 4 30 10 -1
+";
+        let tcp6 = "\
+  sl  local_address                         remote_address                        st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000000000000000000001000000:0BB8 00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 60001 1 0000000000000000 100 0 0 10 0
+";
+
+        let sockets = parse_proc_net(tcp, "TCP");
+        assert_eq!(sockets[0].local, "127.0.0.1:3306".parse().unwrap());
+        assert_eq!(sockets[0].state, "LISTEN");
+        assert_eq!(sockets[0].inode, 41234);
+        assert_eq!(sockets[1].remote, "203.0.113.7:443".parse().unwrap());
+        assert_eq!(sockets[1].state, "ESTABLISHED");
+
+        let sockets = parse_proc_net(tcp6, "TCP");
+        assert_eq!(sockets[0].local, "[::1]:3000".parse().unwrap());
+
+        // 监听套接字的对端是 0.0.0.0:0，转换后不应带对端信息
+        let info = native_port_info(
+            "TCP",
+            None,
+            sockets[0].local,
+            Some(sockets[0].remote),
+            "LISTEN".into(),
+        );
+        assert_eq!(info.local_address, "::1");
+        assert_eq!(info.remote_address, "");
+        assert_eq!(info.pid, "");
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "windows", target_os = "macos"))]
+    #[test]
+    fn native_scan_finds_bound_sockets() {
+        let tcp = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let udp = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let tcp_port = tcp.local_addr().unwrap().port().to_string();
+        let udp_port = udp.local_addr().unwrap().port().to_string();
+        let pid = std::process::id().to_string();
+
+        let ports = native_sockets(true, true).unwrap();
+        assert!(ports.iter().any(|info| info.protocol == "TCP"
+            && info.port == tcp_port
+            && info.local_address == "127.0.0.1"
+            && info.state == "LISTEN"
+            && info.pid == pid));
+        assert!(ports
+            .iter()
+            .any(|info| info.protocol == "UDP" && info.port == udp_port && info.pid == pid));
+    }
+
+    #[test]
+    fn process_details_are_attached_by_pid() {
+        let exited = port_info_for_pid(u32::MAX);
+        let mut ports = vec![port_info_for_pid(std::process::id()), exited];
+
+        attach_process_details(&mut ports);
+        assert!(!ports[0].process.exe_path.is_empty());
+        assert!(ports[0].process.start_time.is_some());
+        // 找不到的进程保持空信息而不是报错
+        assert!(ports[1].process.exe_path.is_empty());
+        assert_eq!(ports[1].process.start_time, None);
+    }
+
+    fn port_info_for_pid(pid: u32) -> PortInfo {
+        PortInfo {
+            pid: pid.to_string(),
+            port: "8080".to_string(),
+            protocol: "TCP".to_string(),
+            program: String::new(),
+            local_address: "127.0.0.1".to_string(),
+            remote_address: String::new(),
+            remote_port: String::new(),
+            state: "LISTEN".to_string(),
+            process: ProcessIdentity::default(),
         }
     }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn graceful_kill_terminates_without_escalating() {
+        assert!(kill_process("12ab".to_string(), None, None).await.is_err());
+
+        let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+        let result = kill_process(child.id().to_string(), None, Some(5000))
+            .await
+            .unwrap();
+        assert!(!result.forced);
+        assert_eq!(result.signal, "SIGTERM");
+        child.wait().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn killing_an_unused_port_reports_nothing_found() {
+        // 先绑定再释放，拿到一个当前无人监听的端口
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let report = kill_process_on_port(port, Some("TCP".to_string()), None, None, None)
+            .await
+            .unwrap();
+        assert!(!report.found);
+        assert!(report.outcomes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn remote_scan_reports_open_and_closed_ports() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let open_port = listener.local_addr().unwrap().port();
+        let closed_port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let ports =
+            remote_port_list(Some(vec![closed_port, open_port, open_port, 0]), None, None).unwrap();
+        let cancel = AtomicBool::new(false);
+        let results = scan_remote_ports_impl(
+            None,
+            &cancel,
+            "test",
+            "127.0.0.1",
+            ports,
+            Duration::from_secs(2),
+            4,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(results.len(), 2);
+        let status = |port| results.iter().find(|r| r.port == port).unwrap();
+        assert_eq!(status(open_port).status, "open");
+        assert!(status(open_port).latency_ms.is_some());
+        assert_eq!(status(closed_port).status, "closed");
+        assert!(remote_port_list(None, Some(90), Some(80)).is_err());
+
+        let limit = UNRESPONSIVE_PROBE_LIMIT;
+        assert!(host_looks_unresponsive(limit, 0, 1000));
+        assert!(!host_looks_unresponsive(limit, 1, 1000));
+        assert!(!host_looks_unresponsive(limit - 1, 0, 1000));
+        // 端口数不超过阈值时扫描本就结束了，照常返回结果
+        assert!(!host_looks_unresponsive(limit, 0, limit));
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "windows", target_os = "macos"))]
+    #[test]
+    fn check_port_explains_who_holds_the_port() {
+        let listener = std::net::TcpListener::bind("0.0.0.0:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let busy = check_port(port, None).unwrap();
+        assert!(!busy.available);
+        assert!(!busy.ipv4_wildcard_available);
+        let pid = std::process::id().to_string();
+        assert!(busy.occupants.iter().any(|info| info.pid == pid));
+
+        drop(listener);
+        let free = check_port(port, Some("127.0.0.1".to_string())).unwrap();
+        assert!(free.available);
+        assert!(free.occupants.is_empty());
+        assert!(check_port(0, None).is_err());
+    }
+
+    #[test]
+    fn critical_processes_need_force() {
+        let critical = if cfg!(target_os = "windows") { 4 } else { 1 };
+        let critical_text = critical.to_string();
+
+        let refused = killable_pid(&critical_text, false).unwrap_err();
+        assert!(refused.contains("without force"), "{refused}");
+        assert_eq!(killable_pid(&critical_text, true), Ok(critical));
+        assert_eq!(killable_pid("4242", false), Ok(4242));
+        assert!(killable_pid("", true).is_err());
+    }
 }
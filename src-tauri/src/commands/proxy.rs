@@ -2,47 +2,347 @@
 //!
 //! 设计目标：
 //! - 作为 Tauri 后端命令，提供“启动/停止/状态查询”能力；
-//! - 支持 HTTP/HTTPS 反向代理与 WebSocket 透传；
+//! - 支持 HTTP/HTTPS 反向代理与 WebSocket 透传，上游也可以是本地 Unix 套接字 / Windows 命名管道；
 //! - 提供按 Host + 路径前缀匹配的路由能力；
 //! - 提供按路由粒度控制的“不安全 TLS 校验”开关（仅调试场景建议开启）。
 
+use base64::Engine;
 use bytes::Bytes;
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use http::header::{self, HeaderName};
 use http::{HeaderMap, HeaderValue, StatusCode, Uri};
-use http_body_util::{Either, Full};
-use hyper::body::Incoming;
+use http_body_util::{BodyExt, Either, Full};
+use hyper::body::{Body, Frame, Incoming};
 use hyper::server::conn::http1;
 use hyper::service::service_fn;
 use hyper::{Request, Response};
-use hyper_rustls::HttpsConnectorBuilder;
-use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_rustls::{FixedServerNameResolver, HttpsConnectorBuilder};
+use hyper_util::client::legacy::connect::{Connected, Connection, HttpConnector};
 use hyper_util::client::legacy::Client;
 use hyper_util::rt::{TokioExecutor, TokioIo};
 use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
 use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
-use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
-use rustls::{ClientConfig, DigitallySignedStruct, Error as TlsError, SignatureScheme};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{
+    ClientConfig, DigitallySignedStruct, Error as TlsError, RootCertStore, SignatureScheme,
+};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
 use std::convert::Infallible;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::io::Write;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::{SystemTime, UNIX_EPOCH};
-use tauri::{command, State};
-use tokio::io::copy_bidirectional;
+use std::task::{ready, Context, Poll};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tauri::{command, AppHandle, Emitter, Manager, State};
+use tokio::io::{copy_bidirectional, AsyncRead, AsyncWrite, ReadBuf};
 use tokio::net::TcpListener;
-use tokio::sync::oneshot;
-use tokio::time::{sleep, Duration};
-
-type ProxyResponse = Response<Either<Incoming, Full<Bytes>>>;
+use tokio::sync::{oneshot, watch, Notify, OwnedSemaphorePermit, Semaphore};
+use tokio::time::{sleep, timeout, Duration};
+
+const PROXY_LOG_EVENT: &str = "krate://proxy-log";
+/// WebSocket 隧道建立与关闭事件。
+const PROXY_WS_EVENT: &str = "krate://proxy-ws";
+/// 上游健康状态变化事件。
+const PROXY_HEALTH_EVENT: &str = "krate://proxy-health";
+/// 慢请求事件。
+const PROXY_SLOW_EVENT: &str = "krate://proxy-slow";
+/// 未指定间隔时的健康检查周期（秒）。
+const DEFAULT_HEALTH_CHECK_INTERVAL_SECS: u64 = 10;
+/// 单次健康检查的超时时长。
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+/// 最近请求环形缓冲区容量。
+const RECENT_REQUESTS_CAPACITY: usize = 500;
+/// 慢请求列表容量。
+const SLOW_REQUESTS_CAPACITY: usize = 100;
+/// 状态中保留的最近错误条数。
+const RECENT_ERRORS_CAPACITY: usize = 10;
+/// 为了失败重放而缓冲的请求体上限。
+const REPLAY_BODY_LIMIT: u64 = 64 * 1024;
+/// 重试退避基数（第 N 次重试等待 N 倍）。
+const RETRY_BACKOFF_MS: u64 = 100;
+/// 停止代理时等待在途连接结束的默认时长（毫秒）。
+const DEFAULT_DRAIN_TIMEOUT_MS: u64 = 3000;
+/// 小于该字节数的响应不压缩。
+const GZIP_MIN_BYTES: u64 = 1024;
+/// 未设置时响应缓存的有效期（秒）。
+const DEFAULT_CACHE_TTL_SECS: u64 = 60;
+/// 未设置时单个可缓存响应的最大字节数。
+const DEFAULT_CACHE_MAX_BYTES: u64 = 1024 * 1024;
+/// 每个代理实例最多缓存的响应条数。
+const CACHE_MAX_ENTRIES: usize = 256;
+/// 每个代理实例缓存响应体的总字节上限。
+const CACHE_MAX_TOTAL_BYTES: usize = 64 * 1024 * 1024;
+/// 开启请求体截取但未指定上限时，每个请求/响应体最多保存的字节数。
+const DEFAULT_CAPTURE_MAX_BYTES: usize = 64 * 1024;
+/// 持久化代理配置的文件名（位于应用配置目录）。
+const SAVED_CONFIG_FILE: &str = "proxy-config.json";
+/// 路由导出文件的格式版本，格式变化时递增并保持旧版本可读。
+const ROUTES_SCHEMA_VERSION: u64 = 1;
+/// 单条路由幂等请求的最大重试次数。
+const MAX_ROUTE_RETRIES: u8 = 5;
+/// 默认的请求 ID 头。
+const DEFAULT_REQUEST_ID_HEADER: &str = "x-request-id";
+/// 客户端自带请求 ID 的最大长度，超出时改用代理生成的 ID。
+const REQUEST_ID_MAX_LEN: usize = 128;
+/// 开启 HTML 地址改写但未指定上限时，可缓冲改写的最大 HTML 字节数。
+const DEFAULT_HTML_REWRITE_MAX_BYTES: usize = 2 * 1024 * 1024;
+/// 未指定实例 ID 时使用的默认代理实例。
+const DEFAULT_INSTANCE_ID: &str = "default";
+/// 计算近期吞吐所用的滑动窗口长度（秒），每秒采样一次。
+const THROUGHPUT_WINDOW_SECS: usize = 10;
+/// 粘性会话 Cookie 名称，值为分流目标地址的哈希。
+const STICKY_COOKIE_NAME: &str = "krate_upstream";
+
+type ProxyResponse = Response<Either<PrefixedBody<Incoming>, Full<Bytes>>>;
+type UpstreamBody = Either<PrefixedBody<Incoming>, Full<Bytes>>;
+/// 发往上游的请求体：原始流（可能被截取），或缓冲后的完整请求体。
+type UpstreamRequestBody = Either<CaptureBody<Incoming>, Full<Bytes>>;
 type HttpsConnector = hyper_rustls::HttpsConnector<HttpConnector>;
-type HttpsClient = Client<HttpsConnector, Incoming>;
+type HttpsClient = Client<TimedConnector<HttpsConnector>, UpstreamRequestBody>;
+type LocalSocketClient = Client<TimedConnector<LocalSocketConnector>, UpstreamRequestBody>;
+#[cfg(unix)]
+type LocalSocketStream = tokio::net::UnixStream;
+#[cfg(windows)]
+type LocalSocketStream = tokio::net::windows::named_pipe::NamedPipeClient;
+type UpstreamError = hyper_util::client::legacy::Error;
+/// 最终返回给客户端的响应：原样透传，或经 gzip 压缩。
+type ClientResponse = Response<Either<CaptureBody<UpstreamBody>, GzipBody>>;
 
 /// 一组可复用的上游客户端：
 /// - `secure`: 标准证书校验
 /// - `insecure`: 跳过证书链与主机名校验（调试用途）
+/// - `profiles`: 按路由 TLS 定制参数（自定义 CA、客户端证书等）缓存的客户端
 #[derive(Clone)]
 struct ProxyClients {
     secure: HttpsClient,
     insecure: HttpsClient,
+    profiles: HashMap<TlsProfile, HttpsClient>,
+    /// 本地套接字路由按套接字路径共享客户端（连接池）。
+    local_sockets: HashMap<String, LocalSocketClient>,
+}
+
+/// 单条路由实际使用的上游客户端。
+#[derive(Clone)]
+enum UpstreamClient {
+    Https(HttpsClient),
+    LocalSocket(LocalSocketClient),
+}
+
+impl UpstreamClient {
+    fn request(
+        &self,
+        request: Request<UpstreamRequestBody>,
+    ) -> hyper_util::client::legacy::ResponseFuture {
+        match self {
+            UpstreamClient::Https(client) => client.request(request),
+            UpstreamClient::LocalSocket(client) => client.request(request),
+        }
+    }
+}
+
+/// 连接本地 Unix 套接字 / Windows 命名管道的上游连接器。
+///
+/// 请求 URI 中的主机与端口只用于 `Host` 头，实际连接总是指向 `path`。
+#[derive(Clone)]
+struct LocalSocketConnector {
+    path: Arc<str>,
+}
+
+impl tower_service::Service<Uri> for LocalSocketConnector {
+    type Response = LocalSocketIo;
+    type Error = std::io::Error;
+    type Future = Pin<Box<dyn std::future::Future<Output = std::io::Result<LocalSocketIo>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _uri: Uri) -> Self::Future {
+        let path = self.path.clone();
+        Box::pin(async move {
+            connect_local_socket(&path)
+                .await
+                .map(|stream| LocalSocketIo(TokioIo::new(stream)))
+        })
+    }
+}
+
+/// 本地套接字连接，适配 hyper 的 IO trait 与连接池元信息。
+struct LocalSocketIo(TokioIo<LocalSocketStream>);
+
+impl hyper::rt::Read for LocalSocketIo {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: hyper::rt::ReadBufCursor<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        hyper::rt::Read::poll_read(Pin::new(&mut self.get_mut().0), cx, buf)
+    }
+}
+
+impl hyper::rt::Write for LocalSocketIo {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        hyper::rt::Write::poll_write(Pin::new(&mut self.get_mut().0), cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        hyper::rt::Write::poll_flush(Pin::new(&mut self.get_mut().0), cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        hyper::rt::Write::poll_shutdown(Pin::new(&mut self.get_mut().0), cx)
+    }
+}
+
+impl Connection for LocalSocketIo {
+    fn connected(&self) -> Connected {
+        Connected::new()
+    }
+}
+
+#[cfg(unix)]
+async fn connect_local_socket(path: &str) -> std::io::Result<LocalSocketStream> {
+    tokio::net::UnixStream::connect(path).await
+}
+
+/// 打开命名管道；服务端实例全部繁忙时短暂等待后重试。
+#[cfg(windows)]
+async fn connect_local_socket(path: &str) -> std::io::Result<LocalSocketStream> {
+    const ERROR_PIPE_BUSY: i32 = 231;
+    let mut attempts = 0;
+    loop {
+        match tokio::net::windows::named_pipe::ClientOptions::new().open(path) {
+            Err(err) if err.raw_os_error() == Some(ERROR_PIPE_BUSY) && attempts < 20 => {
+                attempts += 1;
+                sleep(Duration::from_millis(50)).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn local_socket_client(path: &str) -> LocalSocketClient {
+    Client::builder(TokioExecutor::new()).build(TimedConnector {
+        inner: LocalSocketConnector {
+            path: Arc::from(path),
+        },
+    })
+}
+
+/// 新建上游连接的耗时；随连接信息写入响应扩展，复用的连接沿用建立时的记录。
+#[derive(Clone, Copy, Debug)]
+struct ConnectTiming {
+    established_at: Instant,
+    duration: Duration,
+}
+
+/// 记录建连耗时的连接器包装。
+#[derive(Clone)]
+struct TimedConnector<C> {
+    inner: C,
+}
+
+impl<C> tower_service::Service<Uri> for TimedConnector<C>
+where
+    C: tower_service::Service<Uri>,
+    C::Future: Send + 'static,
+    C::Response: Send + 'static,
+    C::Error: Send + 'static,
+{
+    type Response = TimedIo<C::Response>;
+    type Error = C::Error;
+    type Future =
+        Pin<Box<dyn std::future::Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let started = Instant::now();
+        let connecting = self.inner.call(uri);
+        Box::pin(async move {
+            let inner = connecting.await?;
+            Ok(TimedIo {
+                inner,
+                timing: ConnectTiming {
+                    established_at: Instant::now(),
+                    duration: started.elapsed(),
+                },
+            })
+        })
+    }
+}
+
+/// 携带建连耗时的连接，IO 原样委托给内部连接。
+struct TimedIo<T> {
+    inner: T,
+    timing: ConnectTiming,
+}
+
+impl<T: hyper::rt::Read + Unpin> hyper::rt::Read for TimedIo<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: hyper::rt::ReadBufCursor<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        hyper::rt::Read::poll_read(Pin::new(&mut self.get_mut().inner), cx, buf)
+    }
+}
+
+impl<T: hyper::rt::Write + Unpin> hyper::rt::Write for TimedIo<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        hyper::rt::Write::poll_write(Pin::new(&mut self.get_mut().inner), cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        hyper::rt::Write::poll_flush(Pin::new(&mut self.get_mut().inner), cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        hyper::rt::Write::poll_shutdown(Pin::new(&mut self.get_mut().inner), cx)
+    }
+}
+
+impl<T: Connection> Connection for TimedIo<T> {
+    fn connected(&self) -> Connected {
+        self.inner.connected().extra(self.timing)
+    }
+}
+
+/// 若响应所用的连接是在 `since` 之后新建的，返回建连耗时（毫秒）。
+fn fresh_connect_ms<B>(response: &Response<B>, since: Instant) -> Option<u64> {
+    response
+        .extensions()
+        .get::<ConnectTiming>()
+        .filter(|timing| timing.established_at >= since)
+        .map(|timing| timing.duration.as_millis() as u64)
+}
+
+/// 路由级 TLS 定制参数；参数相同的路由共享同一个上游客户端。
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct TlsProfile {
+    /// 自定义信任根 CA 证书路径（设置后优先于 `insecure`）。
+    ca_cert_path: Option<String>,
+    /// mTLS 客户端证书链与私钥路径。
+    client_cert: Option<(String, String)>,
+    /// 是否跳过服务端证书校验。
+    insecure: bool,
+    /// TLS 握手使用的 SNI / 证书校验主机名；`None` 表示沿用连接主机。
+    server_name: Option<String>,
 }
 
 /// 不安全 TLS 校验器。
@@ -101,19 +401,146 @@ impl ServerCertVerifier for InsecureTlsVerifier {
     }
 }
 
-#[derive(serde::Deserialize)]
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ProxyStartRequest {
-    /// 监听地址（例如 `127.0.0.1` 或 `0.0.0.0`）。
+    /// 监听地址（例如 `127.0.0.1` 或 `0.0.0.0`）；与 `listen_hosts` 同时提供时排在最前。
+    #[serde(default)]
     listen_host: String,
-    /// 监听端口。
+    /// 同时监听的多个地址，共用同一端口。
+    #[serde(default)]
+    listen_hosts: Vec<String>,
+    /// 监听端口；为 0 时由系统分配空闲端口。
     listen_port: u16,
     /// 路由配置列表。
     routes: Vec<ProxyRouteInput>,
+    /// 是否向前端推送逐请求访问日志事件。
+    #[serde(default)]
+    access_log: bool,
+    /// 允许访问的客户端 IP / CIDR；为空表示不限制。
+    #[serde(default)]
+    allow_ips: Vec<String>,
+    /// 拒绝访问的客户端 IP / CIDR（优先于允许列表）。
+    #[serde(default)]
+    deny_ips: Vec<String>,
+    /// 向上游写入哪些转发类请求头，默认全部写入。
+    #[serde(default)]
+    forwarded_headers: ForwardedHeaders,
+    /// 对文本类响应按需进行 gzip 压缩。
+    #[serde(default)]
+    compress_responses: bool,
+    /// 同时处理的最大连接数；`None` 表示不限制。
+    #[serde(default)]
+    max_connections: Option<usize>,
+    /// 连接数达到上限时的处理方式。
+    #[serde(default)]
+    connection_limit_mode: ConnectionLimitMode,
+    /// 是否与客户端保持长连接；关闭后每个请求结束即断开连接。
+    #[serde(default = "default_keep_alive")]
+    keep_alive: bool,
+    /// 自定义错误页面（HTML 文件路径）。
+    #[serde(default)]
+    error_pages: ErrorPagesInput,
+    /// 总耗时超过该值（毫秒）的请求记为慢请求并推送事件；`None` 表示不检测。
+    #[serde(default)]
+    slow_request_threshold_ms: Option<u64>,
+}
+
+/// 自定义错误页面的文件路径；页面中的 `{{error}}` 与 `{{route}}` 会被替换。
+#[derive(Clone, Default, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorPagesInput {
+    /// 未匹配到路由（404）。
+    #[serde(default)]
+    not_found: Option<String>,
+    /// 上游不可用（502）。
+    #[serde(default)]
+    bad_gateway: Option<String>,
+    /// 上游超时（504）。
+    #[serde(default)]
+    timeout: Option<String>,
+}
+
+/// 启动时加载好的错误页面模板。
+#[derive(Default)]
+struct ErrorPages {
+    not_found: Option<String>,
+    bad_gateway: Option<String>,
+    timeout: Option<String>,
+}
+
+fn default_keep_alive() -> bool {
+    true
+}
+
+/// 连接数达到上限时的处理方式。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionLimitMode {
+    /// 暂停 accept，新连接在系统 backlog 中排队。
+    #[default]
+    Backpressure,
+    /// 立即以 503 应答并关闭新连接。
+    Reject,
+}
+
+/// 连接数限制（许可在连接任务结束或 panic 时随任务一起释放）。
+struct ConnectionLimit {
+    semaphore: Arc<Semaphore>,
+    mode: ConnectionLimitMode,
+}
+
+/// 转发类请求头的写入策略。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ForwardedHeaders {
+    /// 写入 `X-Forwarded-*`、`X-Real-IP` 与 RFC 7239 `Forwarded`。
+    #[default]
+    All,
+    /// 仅写入 `X-Forwarded-For/-Host/-Proto`。
+    Xff,
+    /// 不写入任何转发类请求头。
+    None,
+}
+
+/// 客户端可能携带的转发类请求头；代理在当前模式下不负责写入的一律移除，避免上游信任伪造的值。
+const FORWARDED_REQUEST_HEADERS: [&str; 7] = [
+    "forwarded",
+    "x-forwarded-for",
+    "x-forwarded-host",
+    "x-forwarded-proto",
+    "x-forwarded-port",
+    "x-forwarded-prefix",
+    "x-real-ip",
+];
+
+impl ForwardedHeaders {
+    /// 该模式下由代理自己写入的转发类请求头。
+    fn writes(self, name: &str) -> bool {
+        match self {
+            Self::All => !matches!(name, "x-forwarded-port" | "x-forwarded-prefix"),
+            Self::Xff => matches!(
+                name,
+                "x-forwarded-for" | "x-forwarded-host" | "x-forwarded-proto"
+            ),
+            Self::None => false,
+        }
+    }
+}
+
+/// 路由路径的匹配方式。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchMode {
+    /// 按路径段前缀匹配（`/api` 命中 `/api` 与 `/api/...`）。
+    #[default]
+    Prefix,
+    /// 仅在路径与配置值相同时匹配（忽略尾部 `/`）。
+    Exact,
 }
 
 /// 前端传入的单条路由配置。
-#[derive(Clone, serde::Deserialize)]
+#[derive(Clone, Default, serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ProxyRouteInput {
     #[serde(default)]
@@ -126,962 +553,8210 @@ pub struct ProxyRouteInput {
     host: String,
     #[serde(default)]
     path_prefix: String,
+    /// 路由类型：转发到上游，或直接返回模拟响应。
+    #[serde(default, rename = "type")]
+    route_type: RouteType,
+    /// 上游目标地址（模拟路由可省略）。
+    #[serde(default)]
     target: String,
     #[serde(default)]
     strip_prefix: bool,
     #[serde(default)]
     allow_insecure_tls: bool,
+    /// 转发前写入的请求头，覆盖客户端同名头。
+    #[serde(default)]
+    set_request_headers: Vec<(String, String)>,
+    /// 转发前移除的请求头（大小写不敏感）。
+    #[serde(default)]
+    remove_request_headers: Vec<String>,
+    /// 返回客户端前写入的响应头，覆盖上游同名头。
+    #[serde(default)]
+    set_response_headers: Vec<(String, String)>,
+    /// 返回客户端前移除的响应头（大小写不敏感）。
+    #[serde(default)]
+    remove_response_headers: Vec<String>,
+    /// 是否自动注入 CORS 响应头并直接应答预检请求。
+    #[serde(default)]
+    cors: bool,
+    /// 回显 Origin 时是否同时允许携带凭据；会让任意网页带着登录态读取响应，默认关闭。
+    #[serde(default)]
+    cors_allow_credentials: bool,
+    /// 幂等请求遇到上游连接失败时的重试次数，最多 `MAX_ROUTE_RETRIES` 次。
+    #[serde(default)]
+    retries: u8,
+    /// Basic 认证用户名（与密码同时设置时启用认证）。
+    #[serde(default)]
+    basic_auth_user: Option<String>,
+    /// Basic 认证密码。
+    #[serde(default)]
+    basic_auth_password: Option<String>,
+    /// 自定义信任根 CA 证书（PEM）路径。
+    #[serde(default)]
+    ca_cert_path: Option<String>,
+    /// mTLS 客户端证书链（PEM）路径。
+    #[serde(default)]
+    client_cert_path: Option<String>,
+    /// mTLS 客户端私钥（PEM，未加密）路径。
+    #[serde(default)]
+    client_key_path: Option<String>,
+    /// 覆盖 TLS 握手的 SNI 与证书校验主机名（TCP 仍连接目标地址）。
+    #[serde(default)]
+    sni_hostname: Option<String>,
+    /// 覆盖转发时写入的 `Host` 头；默认使用目标地址。
+    #[serde(default)]
+    host_header: Option<String>,
+    /// 保留客户端原始 `Host` 头（此时不再写入 `X-Forwarded-Host`）。
+    #[serde(default)]
+    preserve_host_header: bool,
+    /// 健康检查路径（上游路径，例如 `/healthz`）；设置后启用主动健康检查。
+    #[serde(default)]
+    health_check_path: Option<String>,
+    /// 健康检查间隔（秒），默认 10 秒。
+    #[serde(default)]
+    health_check_interval_secs: Option<u64>,
+    /// 主上游连接失败时改为转发到的备用目标地址。
+    #[serde(default)]
+    fallback_target: Option<String>,
+    /// 将目标主机固定解析到该 IP（类似 hosts 文件），TLS 仍按原主机名校验。
+    #[serde(default)]
+    resolve_to: Option<String>,
+    /// 模拟响应状态码，默认 200。
+    #[serde(default)]
+    status: Option<u16>,
+    /// 模拟响应头。
+    #[serde(default)]
+    headers: Vec<(String, String)>,
+    /// 模拟响应的 `Content-Type`，默认 JSON。
+    #[serde(default)]
+    content_type: Option<String>,
+    /// 模拟响应体文本。
+    #[serde(default)]
+    body_text: Option<String>,
+    /// 模拟响应体文件路径（启动时读取并缓存）。
+    #[serde(default)]
+    body_file_path: Option<String>,
+    /// 模拟响应前的延迟（毫秒），用于模拟上游耗时。
+    #[serde(default)]
+    delay_ms: Option<u64>,
+    /// 是否在内存中缓存成功的 GET 响应。
+    #[serde(default)]
+    cache: bool,
+    /// 缓存有效期（秒），默认 60 秒。
+    #[serde(default)]
+    cache_ttl_secs: Option<u64>,
+    /// 单个可缓存响应的最大字节数，默认 1 MiB。
+    #[serde(default)]
+    cache_max_bytes: Option<u64>,
+    /// 截取请求/响应体保存到最近请求记录中（调试用，会占用内存）。
+    #[serde(default)]
+    capture_bodies: bool,
+    /// 每个请求/响应体最多截取的字节数，默认 64 KiB。
+    #[serde(default)]
+    capture_max_bytes: Option<usize>,
+    /// 请求 ID 头名称，默认 `X-Request-Id`。
+    #[serde(default)]
+    request_id_header: Option<String>,
+    /// 移除上游 `Set-Cookie` 的 Domain 属性，使 Cookie 归属于代理地址。
+    #[serde(default)]
+    rewrite_cookie_domain: bool,
+    /// 移除上游 `Set-Cookie` 的 Secure 标记（经 HTTP 访问代理时使用）。
+    #[serde(default)]
+    strip_cookie_secure: bool,
+    /// 把指向上游目标的 3xx `Location` 改写回代理地址。
+    #[serde(default)]
+    rewrite_redirects: bool,
+    /// 把 HTML 响应中指向上游目标的绝对地址改写为代理地址。
+    #[serde(default)]
+    rewrite_html_urls: bool,
+    /// 参与改写的 HTML 最大字节数，默认 2 MiB；更大的响应原样透传。
+    #[serde(default)]
+    html_rewrite_max_bytes: Option<usize>,
+    /// 下行（上游 → 客户端）限速，单位 KiB/s。
+    #[serde(default)]
+    download_limit_kbps: Option<u64>,
+    /// 上行（客户端 → 上游）限速，单位 KiB/s。
+    #[serde(default)]
+    upload_limit_kbps: Option<u64>,
+    /// 转发前注入的额外延迟（毫秒）。
+    #[serde(default)]
+    latency_ms: Option<u64>,
+    /// 查询参数条件：参数必须存在，给出值时还必须相等（按解码后的值比较）。
+    #[serde(default)]
+    query_match: Option<Vec<(String, Option<String>)>>,
+    /// 路径匹配方式，默认前缀匹配。
+    #[serde(default)]
+    match_mode: MatchMode,
+    /// 按权重分流的多个目标；配置后忽略 `target`，权重为 0 的目标不参与分流。
+    #[serde(default)]
+    targets: Vec<WeightedTargetInput>,
+    /// 按该请求头的值哈希固定分流目标（例如用户 ID 头），缺少该头时按权重随机。
+    #[serde(default)]
+    sticky_header: Option<String>,
+    /// 通过 `krate_upstream` Cookie 把客户端固定到首次分配的分流目标。
+    #[serde(default)]
+    sticky_sessions: bool,
+}
+
+/// 分流目标配置。
+#[derive(Clone, Default, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WeightedTargetInput {
+    target: String,
+    #[serde(default)]
+    weight: u32,
+}
+
+/// 路由类型。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RouteType {
+    /// 转发到上游。
+    #[default]
+    Proxy,
+    /// 不访问上游，直接返回配置的固定响应。
+    Mock,
 }
 
 /// 代理运行状态（返回给前端）。
 #[derive(serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ProxyStatus {
+    instance_id: String,
     running: bool,
     listen_host: Option<String>,
     listen_port: Option<u16>,
+    /// 实际绑定的全部监听地址（`host:port`）。
+    listen_addresses: Vec<String>,
     route_count: usize,
     total_requests: u64,
+    rejected_requests: u64,
     started_at: Option<u64>,
     last_error: Option<String>,
     message: String,
+    route_stats: Vec<RouteStatsStatus>,
+    cache: CacheStatus,
+    /// 当前正在处理的客户端连接数。
+    active_connections: usize,
+    /// 当前活跃的 WebSocket 隧道数。
+    active_websockets: u64,
+    /// 客户端经 WebSocket 隧道发往上游的累计字节数。
+    ws_bytes_in: u64,
+    /// 上游经 WebSocket 隧道发往客户端的累计字节数。
+    ws_bytes_out: u64,
+    /// 开启了请求体截取的路由 ID；非空时请求记录会额外占用内存。
+    body_capture_routes: Vec<String>,
+    /// 按状态码类别与失败原因统计的请求数。
+    outcomes: OutcomeStatus,
+    /// 最近的错误记录（新错误在前，最多 10 条）。
+    recent_errors: Vec<ProxyErrorEntry>,
+    /// 经代理传输的字节数与近期吞吐。
+    traffic: TrafficStatus,
 }
 
-/// 持有运行中的代理任务句柄和停止信号。
-struct ProxyRuntime {
-    stop_sender: Option<oneshot::Sender<()>>,
-    handle: tauri::async_runtime::JoinHandle<()>,
+/// 流量统计（返回给前端）。
+#[derive(Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrafficStatus {
+    /// 客户端发往上游的累计字节数（请求体与 WebSocket 上行）。
+    bytes_in: u64,
+    /// 返回给客户端的累计字节数（压缩前的响应体与 WebSocket 下行）。
+    bytes_out: u64,
+    /// 最近窗口内的上行吞吐（字节/秒）。
+    bytes_in_per_sec: u64,
+    /// 最近窗口内的下行吞吐（字节/秒）。
+    bytes_out_per_sec: u64,
+    /// 吞吐统计窗口（秒）。
+    window_secs: u64,
 }
 
-/// 代理状态快照（受互斥锁保护）。
-struct ProxySnapshot {
-    running: bool,
-    listen_host: Option<String>,
-    listen_port: Option<u16>,
-    route_count: usize,
-    started_at: Option<u64>,
-    last_error: Option<String>,
+/// 响应状态分布与失败原因统计（返回给前端）。
+#[derive(Debug, Default, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutcomeStatus {
+    status_2xx: u64,
+    status_3xx: u64,
+    status_4xx: u64,
+    status_5xx: u64,
+    /// 未匹配到路由的请求数。
+    no_route: u64,
+    /// 连接上游失败的次数。
+    upstream_connect_failed: u64,
+    /// 上游超时的次数。
+    upstream_timeout: u64,
+    /// 与上游 TLS 握手或证书校验失败的次数。
+    tls_error: u64,
+}
+
+/// 一条运行时错误记录。
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxyErrorEntry {
     message: String,
+    timestamp: u64,
 }
 
-/// 代理全局状态（Tauri `State`）。
-///
-/// - `runtime`：运行时句柄（用于停止）
-/// - `snapshot`：状态文本与错误等可观测信息
-/// - `total_requests`：累计转发请求数
-pub struct ProxyState {
-    runtime: Mutex<Option<ProxyRuntime>>,
-    snapshot: Arc<Mutex<ProxySnapshot>>,
-    total_requests: Arc<AtomicU64>,
+/// 响应缓存统计（返回给前端）。
+#[derive(Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStatus {
+    entries: usize,
+    hits: u64,
+    misses: u64,
+    /// 命中率（0~1），尚无查询时为 0。
+    hit_ratio: f64,
 }
 
-impl ProxyState {
-    pub fn new() -> Self {
-        Self {
-            runtime: Mutex::new(None),
-            snapshot: Arc::new(Mutex::new(ProxySnapshot {
-                running: false,
-                listen_host: None,
-                listen_port: None,
-                route_count: 0,
-                started_at: None,
-                last_error: None,
-                message: "代理服务未启动".to_string(),
-            })),
-            total_requests: Arc::new(AtomicU64::new(0)),
-        }
-    }
+/// 单条路由的命中统计（返回给前端）。
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteStatsStatus {
+    id: String,
+    name: String,
+    /// 路由当前是否参与匹配。
+    enabled: bool,
+    requests: u64,
+    last_hit_at: Option<u64>,
+    retries: u64,
+    /// 由备用目标应答的请求数。
+    fallbacks: u64,
+    /// 自启动以来的上游延迟统计；尚无样本时为 `None`。
+    latency: Option<LatencyStatus>,
+    /// 最近一次健康检查结果；未启用或尚未检查时为 `None`。
+    health: Option<RouteHealthStatus>,
+    /// 限速与延迟注入配置；未配置时为 `None`。
+    throttle: Option<RouteThrottleStatus>,
+    /// 分流目标的命中与错误统计；未配置分流时为空。
+    targets: Vec<TargetStatsStatus>,
+}
 
-    fn status(&self) -> ProxyStatus {
-        let snapshot = self.snapshot.lock().unwrap();
-        ProxyStatus {
-            running: snapshot.running,
-            listen_host: snapshot.listen_host.clone(),
-            listen_port: snapshot.listen_port,
-            route_count: snapshot.route_count,
-            total_requests: self.total_requests.load(Ordering::Relaxed),
-            started_at: snapshot.started_at,
-            last_error: snapshot.last_error.clone(),
-            message: snapshot.message.clone(),
-        }
-    }
+/// 单个分流目标的统计（返回给前端）。
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TargetStatsStatus {
+    target: String,
+    weight: u32,
+    requests: u64,
+    /// 以 5xx（含代理生成的 502/504）结束的请求数。
+    errors: u64,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-enum TargetScheme {
-    Http,
-    Https,
+/// 路由限速配置（返回给前端）。
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteThrottleStatus {
+    download_limit_kbps: Option<u64>,
+    upload_limit_kbps: Option<u64>,
+    latency_ms: Option<u64>,
+    /// 限速粒度：固定为 `perConnection`，每个请求 / WebSocket 隧道各自计速，不共享全局额度。
+    scope: &'static str,
 }
 
-impl TargetScheme {
-    fn as_str(self) -> &'static str {
-        match self {
-            TargetScheme::Http => "http",
-            TargetScheme::Https => "https",
-        }
+/// 路由上游健康状态（同时作为 `krate://proxy-health` 事件负载）。
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteHealthStatus {
+    route_id: String,
+    healthy: bool,
+    /// 最近一次检查拿到的 HTTP 状态码；连接失败时为 `None`。
+    last_status: Option<u16>,
+    last_error: Option<String>,
+    checked_at: u64,
+}
+
+/// 路由延迟统计（毫秒）。
+#[derive(Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencyStatus {
+    avg_ms: u64,
+    /// 按直方图桶上界估算的 p95。
+    p95_ms: u64,
+    max_ms: u64,
+}
+
+/// 延迟直方图各桶的上界（毫秒），超过最后一个上界的样本落入溢出桶。
+const LATENCY_BUCKETS_MS: [u64; 14] = [
+    1, 2, 5, 10, 20, 50, 100, 200, 500, 1_000, 2_000, 5_000, 10_000, 30_000,
+];
+
+/// 固定分桶的无锁延迟直方图。
+#[derive(Default)]
+struct LatencyHistogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS_MS.len() + 1],
+    count: AtomicU64,
+    total_ms: AtomicU64,
+    max_ms: AtomicU64,
+}
+
+impl LatencyHistogram {
+    fn record(&self, latency_ms: u64) {
+        let index = LATENCY_BUCKETS_MS
+            .iter()
+            .position(|bound| latency_ms <= *bound)
+            .unwrap_or(LATENCY_BUCKETS_MS.len());
+        self.buckets[index].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.total_ms.fetch_add(latency_ms, Ordering::Relaxed);
+        self.max_ms.fetch_max(latency_ms, Ordering::Relaxed);
     }
 
-    fn default_port(self) -> u16 {
-        match self {
-            TargetScheme::Http => 80,
-            TargetScheme::Https => 443,
+    fn summary(&self) -> Option<LatencyStatus> {
+        let count = self.count.load(Ordering::Relaxed);
+        if count == 0 {
+            return None;
         }
+        let max_ms = self.max_ms.load(Ordering::Relaxed);
+
+        let target = count.saturating_mul(95).div_ceil(100).max(1);
+        let mut cumulative = 0;
+        let mut p95_ms = max_ms;
+        for (index, bucket) in self.buckets.iter().enumerate() {
+            cumulative += bucket.load(Ordering::Relaxed);
+            if cumulative >= target {
+                p95_ms = LATENCY_BUCKETS_MS.get(index).copied().unwrap_or(max_ms);
+                break;
+            }
+        }
+
+        Some(LatencyStatus {
+            avg_ms: self.total_ms.load(Ordering::Relaxed) / count,
+            p95_ms: p95_ms.min(max_ms),
+            max_ms,
+        })
     }
 }
 
-#[derive(Clone)]
-struct ProxyRoute {
-    /// Host 条件；`None` 表示通配。
-    host: Option<String>,
-    /// 前缀匹配路径（已归一化，形如 `/api`）。
-    path_prefix: String,
-    target_scheme: TargetScheme,
-    target_host: String,
-    target_port: u16,
-    /// 是否剥离匹配前缀。
-    strip_prefix: bool,
-    /// 是否允许跳过 TLS 证书校验（仅 HTTPS/WSS 有意义）。
-    allow_insecure_tls: bool,
+/// 单条路由的命中计数器。
+///
+/// 每次 `proxy_start` 都会重新构建路由表，计数器随之归零。
+#[derive(Default)]
+struct RouteStats {
+    requests: AtomicU64,
+    /// 最近一次命中的 UNIX 秒级时间戳，0 表示尚未命中。
+    last_hit_at: AtomicU64,
+    /// 因上游连接失败触发的重试次数。
+    retries: AtomicU64,
+    /// 由备用目标应答的请求数。
+    fallbacks: AtomicU64,
+    /// 从收到请求到拿到上游响应头的耗时分布（WebSocket 仅统计握手）。
+    latency: LatencyHistogram,
+    /// 最近一次健康检查结果。
+    health: Mutex<Option<RouteHealthStatus>>,
 }
 
-impl ProxyRoute {
-    fn target_authority(&self) -> String {
-        if self.target_port == self.target_scheme.default_port() {
-            self.target_host.clone()
-        } else {
-            format!("{}:{}", self.target_host, self.target_port)
-        }
-    }
+/// 分流目标：派生出的目标路由与独立计数器。
+struct WeightedTarget {
+    /// 原始目标地址（用于展示）。
+    label: String,
+    /// 粘性 Cookie 记录的目标标识，取目标地址的哈希；增删或调整目标顺序后仍指向同一上游。
+    sticky_key: String,
+    weight: u32,
+    route: ProxyRoute,
+    stats: Arc<TargetStats>,
 }
 
-#[command]
-pub fn proxy_get_status(state: State<ProxyState>) -> ProxyStatus {
-    state.status()
+#[derive(Default)]
+struct TargetStats {
+    requests: AtomicU64,
+    errors: AtomicU64,
 }
 
-/// 启动反向代理服务。
-///
-/// 启动流程：
-/// 1. 校验监听参数和路由配置；
-/// 2. 绑定监听端口并初始化上游客户端；
-/// 3. 启动 accept 循环并写入运行时句柄；
-/// 4. 更新快照状态返回前端。
-#[command]
-pub async fn proxy_start(
-    state: State<'_, ProxyState>,
-    config: ProxyStartRequest,
-) -> Result<ProxyStatus, String> {
-    let listen_host = config.listen_host.trim().to_string();
-    if listen_host.is_empty() {
-        return Err("监听地址不能为空".to_string());
+impl RouteStats {
+    fn record_hit(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.last_hit_at
+            .store(current_timestamp(), Ordering::Relaxed);
     }
-    if config.listen_port == 0 {
-        return Err("监听端口非法".to_string());
+}
+
+/// 单次请求的访问日志（通过 `krate://proxy-log` 事件推送给前端）。
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ProxyLogPayload {
+    request_id: String,
+    method: String,
+    path: String,
+    route_id: Option<String>,
+    upstream_uri: Option<String>,
+    status: u16,
+    duration_ms: u64,
+    peer_ip: String,
+    /// 被改写的重定向地址（改写前、改写后）。
+    location_rewrite: Option<(String, String)>,
+}
+
+/// 最近一次代理请求记录（保存在环形缓冲区中，供前端事后查询）。
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecentProxyRequest {
+    timestamp: u64,
+    request_id: String,
+    method: String,
+    host: Option<String>,
+    path: String,
+    route_id: Option<String>,
+    status: u16,
+    upstream_latency_ms: Option<u64>,
+    error: Option<String>,
+    /// 截取的请求体（仅开启截取的路由）。
+    #[serde(serialize_with = "serialize_body_capture")]
+    request_body: Option<Arc<BodyCapture>>,
+    /// 截取的响应体（仅开启截取的路由）；响应仍在传输时为已收到的部分。
+    #[serde(serialize_with = "serialize_body_capture")]
+    response_body: Option<Arc<BodyCapture>>,
+}
+
+/// 慢请求记录（同时作为 `krate://proxy-slow` 事件负载）。
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlowProxyRequest {
+    timestamp: u64,
+    request_id: String,
+    method: String,
+    path: String,
+    route_id: Option<String>,
+    status: u16,
+    /// 从收到请求到返回响应头的总耗时。
+    duration_ms: u64,
+    /// 新建上游连接的耗时；复用连接或未到达上游时为 `None`。
+    connect_ms: Option<u64>,
+    /// 上游从发出请求到返回响应头的耗时（不含建连）。
+    response_ms: Option<u64>,
+}
+
+/// 截取的请求/响应体（返回给前端）。
+#[derive(Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CapturedBody {
+    /// 内容不是文本时为 `true`，此时 `content` 为 base64 编码。
+    binary: bool,
+    content: String,
+    captured_bytes: usize,
+    total_bytes: u64,
+    truncated: bool,
+}
+
+/// 请求/响应体截取缓冲：最多保存 `limit` 字节，超出部分照常转发但不保存。
+struct BodyCapture {
+    limit: usize,
+    data: Mutex<Vec<u8>>,
+    total_bytes: AtomicU64,
+}
+
+impl BodyCapture {
+    fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            data: Mutex::new(Vec::new()),
+            total_bytes: AtomicU64::new(0),
+        }
     }
 
-    let routes = build_routes(&config.routes)?;
-    if routes.is_empty() {
-        return Err("至少需要一条启用的路由规则".to_string());
+    fn record(&self, chunk: &[u8]) {
+        self.total_bytes
+            .fetch_add(chunk.len() as u64, Ordering::Relaxed);
+        if let Ok(mut data) = self.data.lock() {
+            let remaining = self.limit.saturating_sub(data.len());
+            data.extend_from_slice(&chunk[..chunk.len().min(remaining)]);
+        }
     }
 
-    {
-        let runtime_guard = state
-            .runtime
+    fn view(&self) -> CapturedBody {
+        let data = self
+            .data
             .lock()
-            .map_err(|_| "代理状态锁异常".to_string())?;
-        if runtime_guard.is_some() {
-            return Err("代理服务已经在运行，请先停止再启动".to_string());
+            .map(|data| data.clone())
+            .unwrap_or_default();
+        let total_bytes = self.total_bytes.load(Ordering::Relaxed);
+        let binary = is_binary_content(&data);
+        CapturedBody {
+            binary,
+            content: if binary {
+                base64::engine::general_purpose::STANDARD.encode(&data)
+            } else {
+                String::from_utf8_lossy(&data).into_owned()
+            },
+            captured_bytes: data.len(),
+            total_bytes,
+            truncated: total_bytes > data.len() as u64,
         }
     }
+}
 
-    let bind_addr = format!("{}:{}", listen_host, config.listen_port);
-    let listener = TcpListener::bind(&bind_addr)
-        .await
-        .map_err(|err| format!("监听失败 {}: {}", bind_addr, err))?;
-
-    state.total_requests.store(0, Ordering::Relaxed);
+/// 判断截取内容是否为二进制：含 NUL 或不是合法 UTF-8
+///（截断在多字节字符中间的结尾不算）。
+fn is_binary_content(data: &[u8]) -> bool {
+    if data.contains(&0) {
+        return true;
+    }
+    match std::str::from_utf8(data) {
+        Ok(_) => false,
+        Err(err) => err.error_len().is_some(),
+    }
+}
 
-    let clients = Arc::new(create_https_clients()?);
-    let routes = Arc::new(routes);
-    let total_requests = state.total_requests.clone();
-    let snapshot = state.snapshot.clone();
-    let (stop_sender, stop_receiver) = oneshot::channel::<()>();
+fn serialize_body_capture<S: serde::Serializer>(
+    capture: &Option<Arc<BodyCapture>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serde::Serialize::serialize(&capture.as_ref().map(|capture| capture.view()), serializer)
+}
 
-    let handle = tauri::async_runtime::spawn(run_proxy_server(
-        listener,
-        routes.clone(),
-        clients,
-        total_requests,
-        snapshot.clone(),
-        stop_receiver,
-    ));
-    let mut stop_sender = Some(stop_sender);
+/// 按目标速率给数据分批放行：已放行字节超出“已用时间 × 速率”时推迟下一批。
+struct Throttle {
+    bytes_per_sec: u64,
+    started: Option<Instant>,
+    sent: u64,
+    delay: Option<Pin<Box<tokio::time::Sleep>>>,
+}
 
-    let mut runtime_guard = state
-        .runtime
-        .lock()
-        .map_err(|_| "代理状态锁异常".to_string())?;
-    let already_running = runtime_guard.is_some();
-    if already_running {
-        // 并发启动时，已经有其他请求先成功注册了运行时。
-        // 这里需要主动清理当前任务，避免形成无法停止的孤儿代理。
-        if let Some(sender) = stop_sender.take() {
-            let _ = sender.send(());
+impl Throttle {
+    fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec,
+            started: None,
+            sent: 0,
+            delay: None,
         }
-        handle.abort();
-        return Err("代理服务已经在运行，请先停止再启动".to_string());
     }
 
-    *runtime_guard = Some(ProxyRuntime {
-        stop_sender,
-        handle,
-    });
-    drop(runtime_guard);
+    /// 上一批数据的配额尚未耗尽时返回 `Pending`。
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if let Some(delay) = self.delay.as_mut() {
+            ready!(std::future::Future::poll(delay.as_mut(), cx));
+            self.delay = None;
+        }
+        Poll::Ready(())
+    }
 
-    {
-        let mut snap = snapshot.lock().map_err(|_| "代理状态锁异常".to_string())?;
-        snap.running = true;
-        snap.listen_host = Some(listen_host);
-        snap.listen_port = Some(config.listen_port);
-        snap.route_count = routes.len();
-        snap.started_at = Some(current_timestamp());
-        snap.last_error = None;
-        snap.message = format!("代理服务运行中，共 {} 条路由", routes.len());
+    fn record(&mut self, bytes: usize) {
+        let started = *self.started.get_or_insert_with(Instant::now);
+        self.sent += bytes as u64;
+        let due = started + Duration::from_secs_f64(self.sent as f64 / self.bytes_per_sec as f64);
+        if due > Instant::now() {
+            self.delay = Some(Box::pin(tokio::time::sleep_until(due.into())));
+        }
     }
+}
 
-    Ok(state.status())
+/// 代理流量计数：累计字节数，以及后台每秒采样一次的滑动窗口（用于计算近期吞吐）。
+#[derive(Default)]
+struct TrafficStats {
+    bytes_in: Arc<AtomicU64>,
+    bytes_out: Arc<AtomicU64>,
+    /// `(采样时刻, bytes_in, bytes_out)`，最多保留窗口长度 + 1 个采样。
+    samples: Mutex<VecDeque<(Instant, u64, u64)>>,
 }
 
-/// 停止反向代理服务。
-#[command]
-pub async fn proxy_stop(state: State<'_, ProxyState>) -> Result<ProxyStatus, String> {
-    let runtime = {
-        let mut guard = state
-            .runtime
-            .lock()
-            .map_err(|_| "代理状态锁异常".to_string())?;
-        guard.take()
-    };
+impl TrafficStats {
+    fn sample(&self) {
+        if let Ok(mut samples) = self.samples.lock() {
+            samples.push_back((
+                Instant::now(),
+                self.bytes_in.load(Ordering::Relaxed),
+                self.bytes_out.load(Ordering::Relaxed),
+            ));
+            while samples.len() > THROUGHPUT_WINDOW_SECS + 1 {
+                samples.pop_front();
+            }
+        }
+    }
 
-    if let Some(mut runtime) = runtime {
-        if let Some(stop_sender) = runtime.stop_sender.take() {
-            let _ = stop_sender.send(());
+    fn reset(&self) {
+        self.bytes_in.store(0, Ordering::Relaxed);
+        self.bytes_out.store(0, Ordering::Relaxed);
+        if let Ok(mut samples) = self.samples.lock() {
+            samples.clear();
         }
-        let _ = runtime.handle.await;
     }
 
-    {
-        let mut snapshot = state
-            .snapshot
+    /// 按窗口内最早与最新的采样计算吞吐；采样不足两个时为 0。
+    fn status(&self) -> TrafficStatus {
+        let (bytes_in_per_sec, bytes_out_per_sec) = self
+            .samples
             .lock()
-            .map_err(|_| "代理状态锁异常".to_string())?;
-        snapshot.running = false;
-        snapshot.listen_host = None;
-        snapshot.listen_port = None;
-        snapshot.started_at = None;
-        snapshot.route_count = 0;
-        snapshot.message = "代理服务已停止".to_string();
+            .ok()
+            .and_then(|samples| {
+                let (first_at, first_in, first_out) = *samples.front()?;
+                let (last_at, last_in, last_out) = *samples.back()?;
+                let elapsed = last_at.duration_since(first_at).as_secs_f64();
+                (elapsed > 0.0).then(|| {
+                    (
+                        (last_in.saturating_sub(first_in) as f64 / elapsed) as u64,
+                        (last_out.saturating_sub(first_out) as f64 / elapsed) as u64,
+                    )
+                })
+            })
+            .unwrap_or((0, 0));
+        TrafficStatus {
+            bytes_in: self.bytes_in.load(Ordering::Relaxed),
+            bytes_out: self.bytes_out.load(Ordering::Relaxed),
+            bytes_in_per_sec,
+            bytes_out_per_sec,
+            window_secs: THROUGHPUT_WINDOW_SECS as u64,
+        }
     }
+}
 
-    Ok(state.status())
+/// 透传数据帧的同时按需截取内容、限速并累计字节数；都未开启时只做透传。
+struct CaptureBody<B> {
+    inner: B,
+    capture: Option<Arc<BodyCapture>>,
+    throttle: Option<Throttle>,
+    counter: Option<Arc<AtomicU64>>,
 }
 
-/// 代理主循环：接收入站连接，并为每个连接创建 HTTP/1 服务任务。
-async fn run_proxy_server(
-    listener: TcpListener,
-    routes: Arc<Vec<ProxyRoute>>,
-    clients: Arc<ProxyClients>,
-    total_requests: Arc<AtomicU64>,
-    snapshot: Arc<Mutex<ProxySnapshot>>,
-    mut stop_receiver: oneshot::Receiver<()>,
-) {
-    loop {
-        tokio::select! {
-            _ = &mut stop_receiver => {
-                break;
-            }
-            accept_result = listener.accept() => {
-                match accept_result {
-                    Ok((stream, peer)) => {
-                        let routes = routes.clone();
-                        let clients = clients.clone();
-                        let total_requests = total_requests.clone();
-                        let snapshot = snapshot.clone();
-
-                        tauri::async_runtime::spawn(async move {
-                            let io = TokioIo::new(stream);
-                            let snapshot_for_service = snapshot.clone();
-                            let service = service_fn(move |request| {
-                                handle_proxy_request(
-                                    request,
-                                    peer,
-                                    routes.clone(),
-                                    clients.clone(),
-                                    total_requests.clone(),
-                                    snapshot_for_service.clone(),
-                                )
-                            });
-
-                            if let Err(err) = http1::Builder::new()
-                                .preserve_header_case(true)
-                                .title_case_headers(true)
-                                .serve_connection(io, service)
-                                .with_upgrades()
-                                .await
-                            {
-                                set_runtime_error(&snapshot, format!("连接处理失败: {}", err));
-                            }
-                        });
-                    }
-                    Err(err) => {
-                        set_runtime_error(&snapshot, format!("监听 accept 失败: {}", err));
-                        sleep(Duration::from_millis(80)).await;
-                    }
-                }
-            }
+impl<B> CaptureBody<B> {
+    fn new(inner: B, capture: Option<Arc<BodyCapture>>) -> Self {
+        Self {
+            inner,
+            capture,
+            throttle: None,
+            counter: None,
         }
     }
-}
 
-/// 处理单个 HTTP 请求：
-/// - 路由匹配
-/// - 构造上游 URI
-/// - 代理头处理
-/// - HTTP 或 WebSocket 转发
-async fn handle_proxy_request(
-    mut request: Request<Incoming>,
-    peer: std::net::SocketAddr,
-    routes: Arc<Vec<ProxyRoute>>,
-    clients: Arc<ProxyClients>,
-    total_requests: Arc<AtomicU64>,
-    snapshot: Arc<Mutex<ProxySnapshot>>,
-) -> Result<ProxyResponse, Infallible> {
-    let request_host = extract_request_host(&request);
-    let request_path = request.uri().path().to_string();
+    /// 把经过的数据帧字节数累加到 `counter`。
+    fn counted(mut self, counter: &Arc<AtomicU64>) -> Self {
+        self.counter = Some(counter.clone());
+        self
+    }
 
-    let route = match select_route(&routes, request_host.as_deref(), &request_path) {
-        Some(route) => route.clone(),
-        None => {
-            return Ok(plain_response(
-                StatusCode::NOT_FOUND,
-                "未匹配到可用的反向代理路由",
-            ));
+    /// 按 `bytes_per_sec` 限速；`None` 表示不限速。
+    fn throttled(mut self, bytes_per_sec: Option<u64>) -> Self {
+        self.throttle = bytes_per_sec.map(Throttle::new);
+        self
+    }
+}
+
+impl<B> Body for CaptureBody<B>
+where
+    B: Body<Data = Bytes> + Unpin,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        if let Some(throttle) = this.throttle.as_mut() {
+            ready!(throttle.poll_ready(cx));
         }
-    };
+        let frame = ready!(Pin::new(&mut this.inner).poll_frame(cx));
+        if let Some(data) = frame
+            .as_ref()
+            .and_then(|frame| frame.as_ref().ok())
+            .and_then(Frame::data_ref)
+        {
+            if let Some(capture) = &this.capture {
+                capture.record(data);
+            }
+            if let Some(throttle) = this.throttle.as_mut() {
+                throttle.record(data.len());
+            }
+            if let Some(counter) = &this.counter {
+                counter.fetch_add(data.len() as u64, Ordering::Relaxed);
+            }
+        }
+        Poll::Ready(frame)
+    }
 
-    let upstream_uri = match build_upstream_uri(request.uri(), &route) {
-        Ok(uri) => uri,
-        Err(err) => return Ok(plain_response(StatusCode::BAD_REQUEST, &err)),
-    };
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
 
-    let original_host = request
-        .headers()
-        .get(header::HOST)
-        .and_then(|value| value.to_str().ok())
-        .unwrap_or("")
-        .to_string();
+    fn size_hint(&self) -> hyper::body::SizeHint {
+        self.inner.size_hint()
+    }
+}
 
-    let websocket_upgrade = is_websocket_upgrade(&request);
-    *request.uri_mut() = upstream_uri;
+/// 在数据流开头补回已读取的一段数据；HTML 改写读取分块响应超过上限时用它原样透传。
+struct PrefixedBody<B> {
+    prefix: Option<Bytes>,
+    inner: B,
+}
 
-    if let Err(err) = apply_proxy_headers(
-        request.headers_mut(),
-        &route,
-        peer,
-        &original_host,
-        websocket_upgrade,
-    ) {
-        return Ok(plain_response(StatusCode::BAD_REQUEST, &err));
+impl<B> PrefixedBody<B> {
+    fn new(inner: B) -> Self {
+        Self {
+            prefix: None,
+            inner,
+        }
     }
+}
 
-    let client = select_upstream_client(&route, &clients);
+impl<B> Body for PrefixedBody<B>
+where
+    B: Body<Data = Bytes> + Unpin,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        if let Some(prefix) = this.prefix.take() {
+            return Poll::Ready(Some(Ok(Frame::data(prefix))));
+        }
+        Pin::new(&mut this.inner).poll_frame(cx)
+    }
 
-    if websocket_upgrade {
-        let response = forward_websocket(request, client, total_requests, snapshot).await;
-        return Ok(response);
+    fn is_end_stream(&self) -> bool {
+        self.prefix.is_none() && self.inner.is_end_stream()
     }
 
-    match client.request(request).await {
-        Ok(response) => {
-            total_requests.fetch_add(1, Ordering::Relaxed);
-            Ok(response.map(Either::Left))
-        }
-        Err(err) => {
-            set_runtime_error(&snapshot, format!("转发请求失败: {}", err));
-            Ok(plain_response(
-                StatusCode::BAD_GATEWAY,
-                &format!("上游服务不可用: {}", err),
-            ))
+    fn size_hint(&self) -> hyper::body::SizeHint {
+        let inner = self.inner.size_hint();
+        let prefix = self.prefix.as_ref().map_or(0, |prefix| prefix.len() as u64);
+        let mut hint = hyper::body::SizeHint::new();
+        if let Some(upper) = inner.upper() {
+            hint.set_upper(upper + prefix);
         }
+        hint.set_lower(inner.lower() + prefix);
+        hint
     }
 }
 
-/// 按路由选择上游客户端：
-/// - HTTPS/WSS + `allow_insecure_tls=true` 使用不安全客户端；
-/// - 其余情况使用默认安全客户端。
-fn select_upstream_client(route: &ProxyRoute, clients: &ProxyClients) -> HttpsClient {
-    if route.allow_insecure_tls && route.target_scheme == TargetScheme::Https {
-        clients.insecure.clone()
-    } else {
-        clients.secure.clone()
+/// 读取数据帧直到结束或累计超过 `max_bytes`；超出上限时把已读取的数据放回 `body` 开头并返回 `None`。
+async fn buffer_limited<B>(
+    body: &mut PrefixedBody<B>,
+    max_bytes: usize,
+) -> Result<Option<Bytes>, B::Error>
+where
+    B: Body<Data = Bytes> + Unpin,
+{
+    let mut buffered = Vec::new();
+    while let Some(frame) = body.frame().await {
+        if let Ok(data) = frame?.into_data() {
+            buffered.extend_from_slice(&data);
+            if buffered.len() > max_bytes {
+                body.prefix = Some(Bytes::from(buffered));
+                return Ok(None);
+            }
+        }
     }
+    Ok(Some(Bytes::from(buffered)))
 }
 
-/// 处理 WebSocket 握手与双向流量透传。
-async fn forward_websocket(
-    mut request: Request<Incoming>,
-    client: HttpsClient,
-    total_requests: Arc<AtomicU64>,
-    snapshot: Arc<Mutex<ProxySnapshot>>,
-) -> ProxyResponse {
-    let on_client_upgrade = hyper::upgrade::on(&mut request);
-
-    match client.request(request).await {
-        Ok(mut response) => {
-            let on_upstream_upgrade = if response.status() == StatusCode::SWITCHING_PROTOCOLS {
-                Some(hyper::upgrade::on(&mut response))
-            } else {
-                None
-            };
+/// WebSocket 隧道事件（通过 `krate://proxy-ws` 推送）。
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WebSocketEventPayload {
+    /// `open` 或 `close`。
+    event: &'static str,
+    route_id: Option<String>,
+    duration_ms: u64,
+    bytes_in: u64,
+    bytes_out: u64,
+}
 
-            let response_to_client = response.map(Either::Left);
-            total_requests.fetch_add(1, Ordering::Relaxed);
+/// WebSocket 隧道计数器（字节数随数据流动实时累加）。
+#[derive(Default)]
+struct WebSocketStats {
+    active: AtomicU64,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+}
 
-            if let Some(on_upstream_upgrade) = on_upstream_upgrade {
-                tauri::async_runtime::spawn(async move {
-                    match tokio::try_join!(on_client_upgrade, on_upstream_upgrade) {
-                        Ok((client_upgraded, upstream_upgraded)) => {
-                            let mut client_io = TokioIo::new(client_upgraded);
-                            let mut upstream_io = TokioIo::new(upstream_upgraded);
-                            let _ = copy_bidirectional(&mut client_io, &mut upstream_io).await;
-                        }
-                        Err(err) => {
-                            set_runtime_error(&snapshot, format!("WebSocket 升级失败: {}", err));
-                        }
-                    }
-                });
-            }
+/// 统计读取字节数的 IO 包装（可按速率限制读取），写方向原样透传。
+///
+/// 字节数在读取时即时累加，出错或被中止的隧道也不会漏记。
+struct CountingIo<T> {
+    inner: T,
+    stats: Arc<WebSocketStats>,
+    /// `true` 统计到 `bytes_in`（客户端侧），否则统计到 `bytes_out`（上游侧）。
+    inbound: bool,
+    /// 代理总流量中对应方向的计数器。
+    traffic: Arc<AtomicU64>,
+    /// 本条隧道这一方向已读取的字节数，用于关闭事件。
+    transferred: u64,
+    /// 读取限速；客户端侧对应上行，上游侧对应下行。
+    throttle: Option<Throttle>,
+}
 
-            response_to_client
+impl<T: AsyncRead + Unpin> AsyncRead for CountingIo<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        if let Some(throttle) = self.throttle.as_mut() {
+            ready!(throttle.poll_ready(cx));
         }
-        Err(err) => {
-            set_runtime_error(&snapshot, format!("WebSocket 握手转发失败: {}", err));
-            plain_response(
-                StatusCode::BAD_GATEWAY,
-                &format!("WebSocket 上游连接失败: {}", err),
-            )
+        let before = buf.filled().len();
+        ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+        let read = (buf.filled().len() - before) as u64;
+        if let Some(throttle) = self.throttle.as_mut() {
+            throttle.record(read as usize);
         }
+        let counter = if self.inbound {
+            &self.stats.bytes_in
+        } else {
+            &self.stats.bytes_out
+        };
+        counter.fetch_add(read, Ordering::Relaxed);
+        self.traffic.fetch_add(read, Ordering::Relaxed);
+        self.transferred += read;
+        Poll::Ready(Ok(()))
     }
 }
 
-/// 创建上游客户端集合。
-fn create_https_clients() -> Result<ProxyClients, String> {
-    let secure = create_secure_https_client()?;
-    let insecure = create_insecure_https_client()?;
-    Ok(ProxyClients { secure, insecure })
+impl<T: AsyncWrite + Unpin> AsyncWrite for CountingIo<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
 }
 
-/// 创建默认安全客户端（使用系统信任根证书）。
-fn create_secure_https_client() -> Result<HttpsClient, String> {
-    let https_connector = HttpsConnectorBuilder::new()
-        .with_native_roots()
-        .map_err(|err| format!("加载系统证书失败: {}", err))?
-        .https_or_http()
-        .enable_http1()
-        .build();
-
-    Ok(Client::builder(TokioExecutor::new()).build(https_connector))
+/// 请求处理过程中逐步补全的追踪信息，处理结束后用于输出访问日志与最近请求记录。
+#[derive(Default)]
+struct RequestTrace {
+    /// 请求 ID（客户端自带或由代理生成），会回显给客户端。
+    request_id: String,
+    /// 命中路由配置的请求 ID 头；未命中路由时使用默认头。
+    request_id_header: Option<HeaderName>,
+    route_id: Option<String>,
+    /// 命中路由的统计计数器，用于记录延迟。
+    route_stats: Option<Arc<RouteStats>>,
+    /// 命中路由的限速配置。
+    throttle: RouteThrottle,
+    /// 命中的分流目标计数器。
+    target_stats: Option<Arc<TargetStats>>,
+    /// 本次请求新建上游连接的耗时（毫秒）。
+    upstream_connect_ms: Option<u64>,
+    upstream_uri: Option<String>,
+    /// 从发出上游请求到拿到响应头的耗时。
+    upstream_latency_ms: Option<u64>,
+    error: Option<String>,
+    /// 被改写的重定向地址（改写前、改写后）。
+    location_rewrite: Option<(String, String)>,
+    /// 路由开启截取时的请求体缓冲。
+    request_capture: Option<Arc<BodyCapture>>,
+    /// 路由开启截取时的响应体缓冲。
+    response_capture: Option<Arc<BodyCapture>>,
 }
 
-/// 创建“不安全 TLS”客户端。
-///
-/// 说明：这里先调用一次 `ClientConfig::builder()`，用于确保 rustls 的默认
-/// crypto provider 已初始化，然后再读取 provider 构建自定义 verifier。
-fn create_insecure_https_client() -> Result<HttpsClient, String> {
-    let _ = ClientConfig::builder();
-    let provider = CryptoProvider::get_default()
-        .cloned()
-        .ok_or_else(|| "TLS 加密提供方初始化失败".to_string())?;
+/// 代理运行期间所有连接共享的上下文。
+struct ProxyContext {
+    app: AppHandle,
+    routes: Arc<Vec<ProxyRoute>>,
+    clients: Arc<ProxyClients>,
+    total_requests: Arc<AtomicU64>,
+    rejected_requests: Arc<AtomicU64>,
+    snapshot: Arc<Mutex<ProxySnapshot>>,
+    recent_requests: Arc<Mutex<VecDeque<RecentProxyRequest>>>,
+    /// 客户端 IP 访问控制。
+    access_control: IpAccessControl,
+    /// 是否推送访问日志事件。
+    access_log: bool,
+    forwarded_headers: ForwardedHeaders,
+    /// 响应缓存。
+    cache: Arc<ResponseCache>,
+    /// 是否对客户端响应进行 gzip 压缩。
+    compress_responses: bool,
+    /// WebSocket 隧道计数器。
+    websockets: Arc<WebSocketStats>,
+    /// 响应状态分布与失败原因计数器。
+    outcomes: Arc<OutcomeStats>,
+    traffic: Arc<TrafficStats>,
+    slow_requests: Arc<Mutex<VecDeque<SlowProxyRequest>>>,
+    /// 慢请求阈值；`None` 表示不检测。
+    slow_request_threshold: Option<Duration>,
+    /// 并发连接数限制；`None` 表示不限制。
+    connection_limit: Option<ConnectionLimit>,
+    /// 是否与客户端保持长连接。
+    keep_alive: bool,
+    /// 在途连接计数；升级后的 WebSocket 隧道同样计入，停止时一并排空。
+    connections: Arc<ConnectionTracker>,
+    /// 停止代理时下发的排空 / 断开信号。
+    connection_signal: watch::Receiver<ConnectionSignal>,
+    /// 客户端请求缺少 `Host` 时用于改写重定向的代理地址。
+    listen_authority: String,
+    /// 自定义错误页面。
+    error_pages: ErrorPages,
+}
 
-    let tls_config = ClientConfig::builder()
-        .dangerous()
-        .with_custom_certificate_verifier(Arc::new(InsecureTlsVerifier { provider }))
-        .with_no_client_auth();
+/// 持有运行中的代理任务句柄和停止信号。
+struct ProxyRuntime {
+    stop_sender: Option<oneshot::Sender<()>>,
+    handle: tauri::async_runtime::JoinHandle<()>,
+    /// 通知各连接任务进入排空或强制关闭阶段。
+    connection_signal: watch::Sender<ConnectionSignal>,
+}
 
-    let https_connector = HttpsConnectorBuilder::new()
-        .with_tls_config(tls_config)
-        .https_or_http()
-        .enable_http1()
-        .build();
+/// 停止代理时下发给连接任务的信号。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ConnectionSignal {
+    Running,
+    /// 处理完当前请求后关闭连接（不再复用 keep-alive）。
+    Drain,
+    /// 立即断开连接。
+    Abort,
+}
 
-    Ok(Client::builder(TokioExecutor::new()).build(https_connector))
+/// 在途连接计数，用于停止时等待连接排空。
+#[derive(Default)]
+struct ConnectionTracker {
+    active: AtomicUsize,
+    draining: AtomicBool,
+    idle: Notify,
 }
 
-/// 写入代理转发相关请求头。
-///
-/// 关键行为：
-/// - 清理 hop-by-hop 头；
-/// - 重写 `Host` 为目标上游；
-/// - 追加 `X-Forwarded-For`；
-/// - 写入 `X-Forwarded-Host` 与 `X-Forwarded-Proto`。
-fn apply_proxy_headers(
-    headers: &mut HeaderMap<HeaderValue>,
-    route: &ProxyRoute,
-    peer: std::net::SocketAddr,
-    original_host: &str,
-    keep_upgrade: bool,
-) -> Result<(), String> {
-    sanitize_hop_headers(headers, keep_upgrade);
+impl ConnectionTracker {
+    fn enter(self: &Arc<Self>) -> ConnectionGuard {
+        self.active.fetch_add(1, Ordering::SeqCst);
+        ConnectionGuard(self.clone())
+    }
 
-    let target_host_header = route.target_authority();
-    headers.insert(
-        header::HOST,
-        HeaderValue::from_str(&target_host_header)
-            .map_err(|_| "目标主机格式非法，无法写入 Host 头".to_string())?,
-    );
+    fn active(&self) -> usize {
+        self.active.load(Ordering::SeqCst)
+    }
 
-    append_x_forwarded_for(headers, peer)?;
+    /// 等待所有连接结束。
+    async fn wait_idle(&self) {
+        loop {
+            let notified = self.idle.notified();
+            if self.active() == 0 {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
 
-    if !original_host.is_empty() {
-        headers.insert(
-            HeaderName::from_static("x-forwarded-host"),
-            HeaderValue::from_str(original_host)
-                .map_err(|_| "原始 Host 非法，无法写入 X-Forwarded-Host".to_string())?,
-        );
+/// 连接任务结束（包括被中止）时自动减少计数。
+struct ConnectionGuard(Arc<ConnectionTracker>);
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        if self.0.active.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.idle.notify_waiters();
+        }
     }
+}
 
-    headers.insert(
-        HeaderName::from_static("x-forwarded-proto"),
-        HeaderValue::from_static("http"),
-    );
+/// 响应状态分布与失败原因计数器（启动时清零）。
+#[derive(Default)]
+struct OutcomeStats {
+    status_2xx: AtomicU64,
+    status_3xx: AtomicU64,
+    status_4xx: AtomicU64,
+    status_5xx: AtomicU64,
+    no_route: AtomicU64,
+    upstream_connect_failed: AtomicU64,
+    upstream_timeout: AtomicU64,
+    tls_error: AtomicU64,
+}
 
-    Ok(())
+/// 上游请求失败的原因分类。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum UpstreamErrorKind {
+    Connect,
+    Timeout,
+    Tls,
+    Other,
 }
 
-/// 清理 hop-by-hop 头，避免这些头被错误地转发到上游。
-fn sanitize_hop_headers(headers: &mut HeaderMap<HeaderValue>, keep_upgrade: bool) {
-    let connection_tokens = headers
-        .get(header::CONNECTION)
-        .and_then(|value| value.to_str().ok())
-        .map(|value| value.to_string())
-        .unwrap_or_default();
+impl OutcomeStats {
+    fn record_status(&self, status: u16) {
+        let counter = match status {
+            200..=299 => &self.status_2xx,
+            300..=399 => &self.status_3xx,
+            400..=499 => &self.status_4xx,
+            500..=599 => &self.status_5xx,
+            _ => return,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
 
-    for token in connection_tokens.split(',') {
-        let item = token.trim().to_ascii_lowercase();
-        if item.is_empty() || (keep_upgrade && item == "upgrade") {
-            continue;
-        }
-        if let Ok(name) = HeaderName::from_bytes(item.as_bytes()) {
-            headers.remove(name);
-        }
+    fn record_upstream_error(&self, kind: UpstreamErrorKind) {
+        let counter = match kind {
+            UpstreamErrorKind::Connect => &self.upstream_connect_failed,
+            UpstreamErrorKind::Timeout => &self.upstream_timeout,
+            UpstreamErrorKind::Tls => &self.tls_error,
+            UpstreamErrorKind::Other => return,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
     }
 
-    for name in [
-        header::CONNECTION,
-        header::PROXY_AUTHENTICATE,
-        header::PROXY_AUTHORIZATION,
-        header::TE,
-        header::TRAILER,
-        header::TRANSFER_ENCODING,
-    ] {
-        headers.remove(name);
+    fn counters(&self) -> [&AtomicU64; 8] {
+        [
+            &self.status_2xx,
+            &self.status_3xx,
+            &self.status_4xx,
+            &self.status_5xx,
+            &self.no_route,
+            &self.upstream_connect_failed,
+            &self.upstream_timeout,
+            &self.tls_error,
+        ]
     }
 
-    headers.remove(HeaderName::from_static("proxy-connection"));
-    headers.remove(HeaderName::from_static("keep-alive"));
+    fn reset(&self) {
+        for counter in self.counters() {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
 
-    if keep_upgrade {
-        headers.insert(header::CONNECTION, HeaderValue::from_static("upgrade"));
-    } else {
-        headers.remove(header::UPGRADE);
-        headers.insert(header::CONNECTION, HeaderValue::from_static("close"));
+    fn status(&self) -> OutcomeStatus {
+        let [status_2xx, status_3xx, status_4xx, status_5xx, no_route, upstream_connect_failed, upstream_timeout, tls_error] =
+            self.counters()
+                .map(|counter| counter.load(Ordering::Relaxed));
+        OutcomeStatus {
+            status_2xx,
+            status_3xx,
+            status_4xx,
+            status_5xx,
+            no_route,
+            upstream_connect_failed,
+            upstream_timeout,
+            tls_error,
+        }
     }
 }
 
-/// 追加 `X-Forwarded-For` 链路信息。
-fn append_x_forwarded_for(
-    headers: &mut HeaderMap<HeaderValue>,
-    peer: std::net::SocketAddr,
-) -> Result<(), String> {
-    let xff_name = HeaderName::from_static("x-forwarded-for");
-    let current = headers
-        .get(&xff_name)
-        .and_then(|value| value.to_str().ok())
-        .unwrap_or("")
-        .trim();
+/// 沿错误链判断上游失败原因：TLS 优先于超时，其次为连接失败。
+fn classify_upstream_error(err: &UpstreamError) -> UpstreamErrorKind {
+    let mut timed_out = false;
+    let mut source: Option<&(dyn std::error::Error + 'static)> = std::error::Error::source(err);
+    while let Some(current) = source {
+        if current.downcast_ref::<rustls::Error>().is_some() {
+            return UpstreamErrorKind::Tls;
+        }
+        if let Some(io_err) = current.downcast_ref::<std::io::Error>() {
+            if io_err
+                .get_ref()
+                .is_some_and(|inner| inner.downcast_ref::<rustls::Error>().is_some())
+            {
+                return UpstreamErrorKind::Tls;
+            }
+            timed_out |= io_err.kind() == std::io::ErrorKind::TimedOut;
+        }
+        if let Some(hyper_err) = current.downcast_ref::<hyper::Error>() {
+            timed_out |= hyper_err.is_timeout();
+        }
+        source = current.source();
+    }
 
-    let next = if current.is_empty() {
-        peer.ip().to_string()
+    if timed_out {
+        UpstreamErrorKind::Timeout
+    } else if err.is_connect() {
+        UpstreamErrorKind::Connect
     } else {
-        format!("{}, {}", current, peer.ip())
-    };
+        UpstreamErrorKind::Other
+    }
+}
 
-    headers.insert(
-        xff_name,
-        HeaderValue::from_str(&next)
-            .map_err(|_| "X-Forwarded-For 构建失败，IP 字段非法".to_string())?,
-    );
+/// 代理状态快照（受互斥锁保护）。
+struct ProxySnapshot {
+    running: bool,
+    listen_host: Option<String>,
+    listen_port: Option<u16>,
+    listen_addresses: Vec<String>,
+    route_count: usize,
+    started_at: Option<u64>,
+    last_error: Option<String>,
+    /// 最近的错误记录（旧错误在前）。
+    recent_errors: VecDeque<ProxyErrorEntry>,
+    message: String,
+    /// 当前运行配置的转发头策略（供路由试匹配复用）。
+    forwarded_headers: ForwardedHeaders,
+    /// 当前运行配置是否保持客户端长连接。
+    keep_alive: bool,
+}
 
-    Ok(())
+/// 代理全局状态（Tauri `State`）：按实例 ID 管理多个互相独立的代理实例。
+pub struct ProxyState {
+    instances: Mutex<HashMap<String, Arc<ProxyInstance>>>,
 }
 
-/// 判断请求是否为 WebSocket Upgrade。
-fn is_websocket_upgrade(request: &Request<Incoming>) -> bool {
-    let upgrade = request
-        .headers()
-        .get(header::UPGRADE)
-        .and_then(|value| value.to_str().ok())
-        .map(|value| value.to_ascii_lowercase())
-        .unwrap_or_default();
+impl ProxyState {
+    pub fn new() -> Self {
+        let mut instances = HashMap::new();
+        instances.insert(
+            DEFAULT_INSTANCE_ID.to_string(),
+            Arc::new(ProxyInstance::new(DEFAULT_INSTANCE_ID)),
+        );
+        Self {
+            instances: Mutex::new(instances),
+        }
+    }
 
-    let connection = request
-        .headers()
-        .get(header::CONNECTION)
-        .and_then(|value| value.to_str().ok())
-        .map(|value| value.to_ascii_lowercase())
-        .unwrap_or_default();
+    /// 查找已存在的实例。
+    fn instance(&self, id: &str) -> Result<Arc<ProxyInstance>, String> {
+        self.instances
+            .lock()
+            .map_err(|_| "代理状态锁异常".to_string())?
+            .get(id)
+            .cloned()
+            .ok_or_else(|| format!("代理实例不存在: {}", id))
+    }
 
-    upgrade == "websocket" && connection.contains("upgrade")
-}
+    /// 查找实例，不存在时创建。
+    fn instance_or_create(&self, id: &str) -> Result<Arc<ProxyInstance>, String> {
+        let mut instances = self
+            .instances
+            .lock()
+            .map_err(|_| "代理状态锁异常".to_string())?;
+        Ok(instances
+            .entry(id.to_string())
+            .or_insert_with(|| Arc::new(ProxyInstance::new(id)))
+            .clone())
+    }
 
-/// 提取请求 Host（优先 `Host` 头，其次 URI authority）。
-fn extract_request_host(request: &Request<Incoming>) -> Option<String> {
-    if let Some(host) = request
-        .headers()
-        .get(header::HOST)
-        .and_then(|value| value.to_str().ok())
-    {
-        return normalize_host_value(host);
+    /// 按实例 ID 排序的全部实例。
+    fn all_instances(&self) -> Result<Vec<Arc<ProxyInstance>>, String> {
+        let mut instances: Vec<_> = self
+            .instances
+            .lock()
+            .map_err(|_| "代理状态锁异常".to_string())?
+            .values()
+            .cloned()
+            .collect();
+        instances.sort_by(|left, right| left.id.cmp(&right.id));
+        Ok(instances)
     }
 
-    request
-        .uri()
-        .authority()
-        .and_then(|authority| normalize_host_value(authority.as_str()))
+    /// 检查端口是否已被其他运行中的实例占用，返回占用者的实例 ID。
+    fn port_owner(&self, port: u16, except: &str) -> Result<Option<String>, String> {
+        Ok(self
+            .all_instances()?
+            .into_iter()
+            .filter(|instance| instance.id != except)
+            .find(|instance| {
+                instance
+                    .snapshot
+                    .lock()
+                    .map(|snapshot| snapshot.running && snapshot.listen_port == Some(port))
+                    .unwrap_or(false)
+            })
+            .map(|instance| instance.id.clone()))
+    }
 }
 
-/// 构建转发后的上游 URI（包含路径和 query）。
-fn build_upstream_uri(original_uri: &Uri, route: &ProxyRoute) -> Result<Uri, String> {
-    let path_and_query = rewrite_path_and_query(original_uri, route);
-    let uri_text = format!(
-        "{}://{}{}",
-        route.target_scheme.as_str(),
-        route.target_authority(),
-        path_and_query
-    );
+impl Default for ProxyState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-    uri_text
-        .parse::<Uri>()
-        .map_err(|err| format!("构建上游地址失败: {}", err))
+/// 单个代理实例，拥有独立的监听、快照、计数器与停止通道。
+///
+/// - `runtime`：运行时句柄（用于停止）
+/// - `snapshot`：状态文本与错误等可观测信息
+/// - `total_requests`：累计转发请求数
+/// - `rejected_requests`：被 IP 访问控制拒绝的请求数
+/// - `routes`：最近一次启动时加载的路由表（用于输出路由统计）
+/// - `rejected_routes`：启动时因配置非法而未加载的停用路由（启用时报告其错误）
+/// - `recent_requests`：最近请求环形缓冲区（启动时清空，停止后保留）
+/// - `connections`：在途连接计数（停止时用于排空）
+/// - `cache`：GET 响应缓存（启动时清空）
+/// - `websockets`：WebSocket 隧道计数（字节数在启动时清零）
+/// - `traffic`：全部流量的字节计数与吞吐采样（启动时清零）
+/// - `slow_requests`：超过阈值的慢请求（启动时清空）
+struct ProxyInstance {
+    id: String,
+    runtime: Mutex<Option<ProxyRuntime>>,
+    connections: Arc<ConnectionTracker>,
+    snapshot: Arc<Mutex<ProxySnapshot>>,
+    recent_requests: Arc<Mutex<VecDeque<RecentProxyRequest>>>,
+    total_requests: Arc<AtomicU64>,
+    rejected_requests: Arc<AtomicU64>,
+    routes: Mutex<Arc<Vec<ProxyRoute>>>,
+    rejected_routes: Mutex<Vec<RejectedRoute>>,
+    cache: Arc<ResponseCache>,
+    websockets: Arc<WebSocketStats>,
+    outcomes: Arc<OutcomeStats>,
+    traffic: Arc<TrafficStats>,
+    slow_requests: Arc<Mutex<VecDeque<SlowProxyRequest>>>,
+    /// 最近一次成功启动时使用的配置。
+    last_config: Mutex<Option<ProxyStartRequest>>,
 }
 
-/// 基于路由策略重写 path 和 query。
-fn rewrite_path_and_query(uri: &Uri, route: &ProxyRoute) -> String {
-    let path = uri.path();
-    let mut rewritten_path = path.to_string();
+impl ProxyInstance {
+    fn new(id: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            runtime: Mutex::new(None),
+            connections: Arc::new(ConnectionTracker::default()),
+            snapshot: Arc::new(Mutex::new(ProxySnapshot {
+                running: false,
+                listen_host: None,
+                listen_port: None,
+                listen_addresses: Vec::new(),
+                route_count: 0,
+                started_at: None,
+                last_error: None,
+                recent_errors: VecDeque::with_capacity(RECENT_ERRORS_CAPACITY),
+                message: "代理服务未启动".to_string(),
+                forwarded_headers: ForwardedHeaders::default(),
+                keep_alive: true,
+            })),
+            recent_requests: Arc::new(Mutex::new(VecDeque::with_capacity(
+                RECENT_REQUESTS_CAPACITY,
+            ))),
+            total_requests: Arc::new(AtomicU64::new(0)),
+            rejected_requests: Arc::new(AtomicU64::new(0)),
+            routes: Mutex::new(Arc::new(Vec::new())),
+            rejected_routes: Mutex::new(Vec::new()),
+            cache: Arc::new(ResponseCache::default()),
+            websockets: Arc::new(WebSocketStats::default()),
+            outcomes: Arc::new(OutcomeStats::default()),
+            traffic: Arc::new(TrafficStats::default()),
+            slow_requests: Arc::new(Mutex::new(VecDeque::new())),
+            last_config: Mutex::new(None),
+        }
+    }
 
-    if route.strip_prefix && route.path_prefix != "/" {
-        if let Some(rest) = path.strip_prefix(&route.path_prefix) {
-            rewritten_path = if rest.is_empty() {
-                "/".to_string()
-            } else if rest.starts_with('/') {
-                rest.to_string()
+    fn status(&self) -> ProxyStatus {
+        let (route_stats, capture_routes): (Vec<_>, Vec<_>) = self
+            .routes
+            .lock()
+            .map(|routes| {
+                (
+                    routes.iter().map(ProxyRoute::stats_status).collect(),
+                    routes
+                        .iter()
+                        .filter(|route| route.capture_max_bytes.is_some())
+                        .map(|route| route.id.clone())
+                        .collect(),
+                )
+            })
+            .unwrap_or_default();
+        let snapshot = self.snapshot.lock().unwrap();
+        ProxyStatus {
+            instance_id: self.id.clone(),
+            running: snapshot.running,
+            listen_host: snapshot.listen_host.clone(),
+            listen_port: snapshot.listen_port,
+            listen_addresses: snapshot.listen_addresses.clone(),
+            route_count: snapshot.route_count,
+            total_requests: self.total_requests.load(Ordering::Relaxed),
+            rejected_requests: self.rejected_requests.load(Ordering::Relaxed),
+            started_at: snapshot.started_at,
+            last_error: snapshot.last_error.clone(),
+            message: if self.connections.draining.load(Ordering::SeqCst) {
+                format!("正在排空连接（剩余 {} 个）", self.connections.active())
             } else {
-                format!("/{}", rest)
-            };
+                snapshot.message.clone()
+            },
+            route_stats,
+            cache: self.cache.status(),
+            active_connections: self.connections.active(),
+            active_websockets: self.websockets.active.load(Ordering::Relaxed),
+            ws_bytes_in: self.websockets.bytes_in.load(Ordering::Relaxed),
+            ws_bytes_out: self.websockets.bytes_out.load(Ordering::Relaxed),
+            body_capture_routes: if snapshot.running {
+                capture_routes
+            } else {
+                Vec::new()
+            },
+            outcomes: self.outcomes.status(),
+            recent_errors: snapshot.recent_errors.iter().rev().cloned().collect(),
+            traffic: self.traffic.status(),
+        }
+    }
+}
+
+/// 已缓存的上游响应（未应用路由的响应头规则，命中时重新应用）。
+struct CachedResponse {
+    status: StatusCode,
+    headers: HeaderMap<HeaderValue>,
+    body: Bytes,
+    expires_at: Instant,
+}
+
+/// 缓存条目及按最近使用排序的键（队尾为最近使用）。
+#[derive(Default)]
+struct CacheStore {
+    entries: HashMap<String, CachedResponse>,
+    order: VecDeque<String>,
+    total_bytes: usize,
+}
+
+impl CacheStore {
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.total_bytes -= entry.body.len();
+        }
+        self.order.retain(|item| item != key);
+    }
+
+    fn touch(&mut self, key: &str) {
+        if let Some(index) = self.order.iter().position(|item| item == key) {
+            if let Some(item) = self.order.remove(index) {
+                self.order.push_back(item);
+            }
+        }
+    }
+}
+
+/// 代理实例内共享的 LRU 响应缓存。
+#[derive(Default)]
+struct ResponseCache {
+    store: Mutex<CacheStore>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ResponseCache {
+    /// 查询未过期的缓存并更新命中统计。
+    fn get(&self, key: &str) -> Option<ProxyResponse> {
+        let response = self.store.lock().ok().and_then(|mut store| {
+            let expired = store.entries.get(key)?.expires_at <= Instant::now();
+            if expired {
+                store.remove(key);
+                return None;
+            }
+            store.touch(key);
+            let entry = store.entries.get(key)?;
+            let mut response = Response::new(Either::Right(Full::new(entry.body.clone())));
+            *response.status_mut() = entry.status;
+            *response.headers_mut() = entry.headers.clone();
+            Some(response)
+        });
+
+        match response {
+            Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+        response
+    }
+
+    /// 写入缓存，并按条数与总字节上限淘汰最久未使用的条目。
+    fn insert(&self, key: String, entry: CachedResponse) {
+        let Ok(mut store) = self.store.lock() else {
+            return;
+        };
+        store.remove(&key);
+        store.total_bytes += entry.body.len();
+        store.entries.insert(key.clone(), entry);
+        store.order.push_back(key);
+
+        while store.entries.len() > CACHE_MAX_ENTRIES || store.total_bytes > CACHE_MAX_TOTAL_BYTES {
+            let Some(oldest) = store.order.pop_front() else {
+                break;
+            };
+            if let Some(entry) = store.entries.remove(&oldest) {
+                store.total_bytes -= entry.body.len();
+            }
+        }
+    }
+
+    /// 清空缓存条目与命中统计。
+    fn clear(&self) {
+        if let Ok(mut store) = self.store.lock() {
+            *store = CacheStore::default();
+        }
+        self.hits.store(0, Ordering::Relaxed);
+        self.misses.store(0, Ordering::Relaxed);
+    }
+
+    fn status(&self) -> CacheStatus {
+        let entries = self
+            .store
+            .lock()
+            .map(|store| store.entries.len())
+            .unwrap_or_default();
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        let lookups = hits + misses;
+        CacheStatus {
+            entries,
+            hits,
+            misses,
+            hit_ratio: if lookups == 0 {
+                0.0
+            } else {
+                hits as f64 / lookups as f64
+            },
+        }
+    }
+}
+
+/// 路由级缓存策略。
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct CachePolicy {
+    ttl: Duration,
+    max_bytes: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TargetScheme {
+    Http,
+    Https,
+    /// 本地 Unix 套接字 / Windows 命名管道，走明文 HTTP。
+    Unix,
+}
+
+impl TargetScheme {
+    fn as_str(self) -> &'static str {
+        match self {
+            TargetScheme::Http | TargetScheme::Unix => "http",
+            TargetScheme::Https => "https",
+        }
+    }
+
+    fn default_port(self) -> u16 {
+        match self {
+            TargetScheme::Http | TargetScheme::Unix => 80,
+            TargetScheme::Https => 443,
+        }
+    }
+}
+
+/// 启动时解析好的模拟响应（响应体已读入内存）。
+#[derive(Debug)]
+struct MockResponse {
+    status: StatusCode,
+    content_type: HeaderValue,
+    headers: Vec<(HeaderName, HeaderValue)>,
+    body: Bytes,
+    delay: Option<Duration>,
+}
+
+/// 路由的主动健康检查配置。
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct HealthCheck {
+    /// 上游请求路径（含可选 query）。
+    path: String,
+    interval: Duration,
+}
+
+/// `parse_target` 的解析结果。
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct ParsedTarget {
+    scheme: TargetScheme,
+    host: String,
+    port: u16,
+    /// 目标路径前缀（已归一化，形如 `/service/v2`；无路径时为空串）。
+    base_path: String,
+    /// 本地套接字路径（仅 `TargetScheme::Unix`）。
+    socket_path: Option<String>,
+}
+
+#[derive(Clone)]
+struct ProxyRoute {
+    /// 路由标识（前端传入；为空时按序号生成）。
+    id: String,
+    /// 路由展示名称。
+    name: String,
+    /// 是否参与匹配；运行期间可通过 `proxy_set_route_enabled` 切换。
+    enabled: Arc<AtomicBool>,
+    /// Host 条件；`None` 表示通配。
+    host: Option<String>,
+    /// 匹配路径（已归一化，形如 `/api`）；按 `match_mode` 做前缀或精确匹配。
+    path_prefix: String,
+    match_mode: MatchMode,
+    /// 查询参数条件；为空表示不限制。
+    query_match: Vec<(String, Option<String>)>,
+    target_scheme: TargetScheme,
+    target_host: String,
+    target_port: u16,
+    /// 目标路径前缀，转发时拼接在（剥离前缀后的）请求路径之前。
+    target_base_path: String,
+    /// 本地套接字路径；设置时忽略目标主机与端口，直接连接该套接字。
+    target_socket: Option<String>,
+    /// 是否剥离匹配前缀。
+    strip_prefix: bool,
+    /// 是否允许跳过 TLS 证书校验（仅 HTTPS/WSS 有意义）。
+    allow_insecure_tls: bool,
+    /// 转发前写入的请求头。
+    set_request_headers: Vec<(HeaderName, HeaderValue)>,
+    /// 转发前移除的请求头。
+    remove_request_headers: Vec<HeaderName>,
+    /// 返回客户端前写入的响应头。
+    set_response_headers: Vec<(HeaderName, HeaderValue)>,
+    /// 返回客户端前移除的响应头。
+    remove_response_headers: Vec<HeaderName>,
+    /// 是否自动注入 CORS 响应头。
+    cors: bool,
+    /// 是否写入 `Access-Control-Allow-Credentials: true`。
+    cors_allow_credentials: bool,
+    /// 幂等请求遇到上游连接失败时的重试次数。
+    retries: u8,
+    /// Basic 认证凭据（形如 `user:password`）；`None` 表示不启用认证。
+    basic_auth: Option<String>,
+    /// 路由级 TLS 定制参数（仅 HTTPS/WSS 有意义）；`None` 表示使用共享客户端。
+    tls_profile: Option<TlsProfile>,
+    /// 转发时写入的 `Host` 头覆盖值；`None` 表示使用目标地址。
+    host_header: Option<HeaderValue>,
+    /// 是否保留客户端原始 `Host` 头。
+    preserve_host_header: bool,
+    /// 目标主机的固定解析地址；`None` 表示走系统 DNS。
+    resolve_to: Option<IpAddr>,
+    /// 主动健康检查配置；`None` 表示不检查。
+    health_check: Option<HealthCheck>,
+    /// 备用上游（仅替换目标地址，与主路由共享统计）；`None` 表示不启用。
+    fallback: Option<Box<ProxyRoute>>,
+    /// 模拟响应；设置后请求不会转发到上游。
+    mock: Option<Arc<MockResponse>>,
+    /// GET 响应缓存策略；`None` 表示不缓存。
+    cache: Option<CachePolicy>,
+    /// 请求/响应体截取上限；`None` 表示不截取。
+    capture_max_bytes: Option<usize>,
+    /// 请求 ID 头名称。
+    request_id_header: HeaderName,
+    /// 是否移除 `Set-Cookie` 的 Domain 属性。
+    rewrite_cookie_domain: bool,
+    /// 是否移除 `Set-Cookie` 的 Secure 标记。
+    strip_cookie_secure: bool,
+    /// 是否把指向上游目标的重定向改写回代理地址。
+    rewrite_redirects: bool,
+    /// HTML 地址改写的大小上限；`None` 表示不改写。
+    html_rewrite_max_bytes: Option<usize>,
+    /// 限速与延迟注入配置。
+    throttle: RouteThrottle,
+    /// 按权重分流的目标；为空表示只使用本路由的目标。
+    targets: Arc<Vec<WeightedTarget>>,
+    /// 按请求头哈希固定分流目标。
+    sticky_header: Option<HeaderName>,
+    /// 是否通过 Cookie 固定分流目标。
+    sticky_sessions: bool,
+    /// 命中统计（克隆路由时共享同一组计数器）。
+    stats: Arc<RouteStats>,
+}
+
+/// 路由级限速与延迟注入；限速按单个请求 / WebSocket 隧道计算，而非全局共享。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct RouteThrottle {
+    /// 下行（上游 → 客户端）速率上限（字节/秒）。
+    download_bytes_per_sec: Option<u64>,
+    /// 上行（客户端 → 上游）速率上限（字节/秒）。
+    upload_bytes_per_sec: Option<u64>,
+    /// 转发前注入的延迟。
+    latency: Option<Duration>,
+}
+
+impl RouteThrottle {
+    fn is_active(&self) -> bool {
+        *self != RouteThrottle::default()
+    }
+
+    fn status(&self) -> Option<RouteThrottleStatus> {
+        self.is_active().then(|| RouteThrottleStatus {
+            download_limit_kbps: self.download_bytes_per_sec.map(|rate| rate / 1024),
+            upload_limit_kbps: self.upload_bytes_per_sec.map(|rate| rate / 1024),
+            latency_ms: self.latency.map(|latency| latency.as_millis() as u64),
+            scope: "perConnection",
+        })
+    }
+}
+
+impl ProxyRoute {
+    fn target_authority(&self) -> String {
+        if self.target_port == self.target_scheme.default_port() {
+            self.target_host.clone()
+        } else {
+            format!("{}:{}", self.target_host, self.target_port)
+        }
+    }
+
+    /// 实际建立 TCP 连接的地址：设置了 `resolve_to` 时使用该 IP，否则同目标地址。
+    fn connect_authority(&self) -> String {
+        let host = match self.resolve_to {
+            Some(IpAddr::V6(ip)) => format!("[{}]", ip),
+            Some(IpAddr::V4(ip)) => ip.to_string(),
+            None => return self.target_authority(),
+        };
+        if self.target_port == self.target_scheme.default_port() {
+            host
+        } else {
+            format!("{}:{}", host, self.target_port)
+        }
+    }
+
+    /// 以 `target` 替换目标地址，派生出备用上游路由。
+    ///
+    /// 主目标专属的连接参数（TLS 定制、固定解析、Host 覆盖、健康检查、备用目标）
+    /// 不会带到派生目标。
+    fn with_target(&self, target: ParsedTarget) -> ProxyRoute {
+        ProxyRoute {
+            target_scheme: target.scheme,
+            target_host: target.host,
+            target_port: target.port,
+            target_base_path: target.base_path,
+            target_socket: target.socket_path,
+            tls_profile: None,
+            host_header: None,
+            resolve_to: None,
+            health_check: None,
+            fallback: None,
+            targets: Arc::default(),
+            ..self.clone()
+        }
+    }
+
+    /// 派生分流目标：沿用路由的 TLS 定制、Host 覆盖与备用目标，只替换目标地址。
+    fn with_weighted_target(&self, target: ParsedTarget) -> ProxyRoute {
+        ProxyRoute {
+            tls_profile: self.tls_profile.clone(),
+            host_header: self.host_header.clone(),
+            fallback: self.fallback.clone(),
+            ..self.with_target(target)
+        }
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// 本路由可能访问的全部上游路由：自身、备用目标与分流目标。
+    fn upstream_routes(&self) -> Vec<&ProxyRoute> {
+        std::iter::once(self)
+            .chain(self.fallback.as_deref())
+            .chain(self.targets.iter().map(|target| &target.route))
+            .collect()
+    }
+
+    /// 按权重选出分流目标；配置了粘性请求头且请求携带该头时按其值哈希选取。
+    ///
+    /// 未配置分流时返回 `None`。
+    fn pick_target(&self, headers: &HeaderMap<HeaderValue>) -> Option<(usize, &WeightedTarget)> {
+        let total: u64 = self.targets.iter().map(|target| target.weight as u64).sum();
+        if total == 0 {
+            return None;
+        }
+        let point = match self
+            .sticky_header
+            .as_ref()
+            .and_then(|name| headers.get(name))
+        {
+            Some(value) => {
+                use std::hash::{Hash, Hasher};
+                let mut hasher = std::hash::DefaultHasher::new();
+                value.as_bytes().hash(&mut hasher);
+                hasher.finish()
+            }
+            None => random_u64(),
+        } % total;
+        pick_weighted(&self.targets, point)
+    }
+
+    /// 粘性会话：Cookie 指向的目标仍存在且权重大于 0 时沿用，否则按权重重新分配，
+    /// 并返回需要写回客户端的新 Cookie。
+    fn select_target(
+        &self,
+        sticky_key: Option<&str>,
+        headers: &HeaderMap<HeaderValue>,
+    ) -> Option<(usize, &WeightedTarget, Option<HeaderValue>)> {
+        let sticky = sticky_key.and_then(|key| {
+            self.targets
+                .iter()
+                .enumerate()
+                .find(|(_, target)| target.weight > 0 && target.sticky_key == key)
+        });
+        if let Some((index, target)) = sticky {
+            return Some((index, target, None));
+        }
+        let (index, target) = self.pick_target(headers)?;
+        let cookie = self
+            .sticky_sessions
+            .then(|| sticky_cookie_value(&target.sticky_key, &self.path_prefix))
+            .flatten();
+        Some((index, target, cookie))
+    }
+
+    fn stats_status(&self) -> RouteStatsStatus {
+        let last_hit_at = self.stats.last_hit_at.load(Ordering::Relaxed);
+        RouteStatsStatus {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            enabled: self.is_enabled(),
+            requests: self.stats.requests.load(Ordering::Relaxed),
+            last_hit_at: if last_hit_at == 0 {
+                None
+            } else {
+                Some(last_hit_at)
+            },
+            retries: self.stats.retries.load(Ordering::Relaxed),
+            fallbacks: self.stats.fallbacks.load(Ordering::Relaxed),
+            latency: self.stats.latency.summary(),
+            health: self
+                .stats
+                .health
+                .lock()
+                .ok()
+                .and_then(|health| health.clone()),
+            throttle: self.throttle.status(),
+            targets: self
+                .targets
+                .iter()
+                .map(|target| TargetStatsStatus {
+                    target: target.label.clone(),
+                    weight: target.weight,
+                    requests: target.stats.requests.load(Ordering::Relaxed),
+                    errors: target.stats.errors.load(Ordering::Relaxed),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// 规范化前端传入的实例 ID，未指定时使用默认实例。
+fn resolve_instance_id(instance_id: Option<String>) -> String {
+    instance_id
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty())
+        .unwrap_or_else(|| DEFAULT_INSTANCE_ID.to_string())
+}
+
+/// 查询代理状态：指定实例 ID 时只返回该实例，否则返回全部实例。
+#[command]
+pub fn proxy_get_status(
+    state: State<ProxyState>,
+    instance_id: Option<String>,
+) -> Result<Vec<ProxyStatus>, String> {
+    match instance_id {
+        Some(id) => Ok(vec![state
+            .instance(&resolve_instance_id(Some(id)))?
+            .status()]),
+        None => Ok(state
+            .all_instances()?
+            .iter()
+            .map(|instance| instance.status())
+            .collect()),
+    }
+}
+
+/// 查询最近的代理请求记录（新请求在前）。
+#[command]
+pub fn proxy_get_recent_requests(
+    state: State<ProxyState>,
+    instance_id: Option<String>,
+    limit: usize,
+) -> Result<Vec<RecentProxyRequest>, String> {
+    let instance = state.instance(&resolve_instance_id(instance_id))?;
+    let recent = instance
+        .recent_requests
+        .lock()
+        .map_err(|_| "代理状态锁异常".to_string())?;
+    Ok(recent.iter().rev().take(limit).cloned().collect())
+}
+
+/// 获取慢请求列表（新记录在前）。
+#[command]
+pub fn proxy_get_slow_requests(
+    state: State<ProxyState>,
+    instance_id: Option<String>,
+) -> Result<Vec<SlowProxyRequest>, String> {
+    let instance = state.instance(&resolve_instance_id(instance_id))?;
+    let slow = instance
+        .slow_requests
+        .lock()
+        .map_err(|_| "代理状态锁异常".to_string())?;
+    Ok(slow.iter().rev().cloned().collect())
+}
+
+/// 清空指定实例的响应缓存。
+#[command]
+pub fn proxy_clear_cache(
+    state: State<ProxyState>,
+    instance_id: Option<String>,
+) -> Result<ProxyStatus, String> {
+    let instance = state.instance(&resolve_instance_id(instance_id))?;
+    instance.cache.clear();
+    Ok(instance.status())
+}
+
+/// 在不重启代理的情况下启用或停用指定路由，立即作用于后续请求匹配。
+///
+/// 启动时配置非法的停用路由不会被加载，启用它时返回当时的校验错误。
+#[command]
+pub fn proxy_set_route_enabled(
+    state: State<ProxyState>,
+    instance_id: Option<String>,
+    route_id: String,
+    enabled: bool,
+) -> Result<ProxyStatus, String> {
+    let instance = state.instance(&resolve_instance_id(instance_id))?;
+    let routes = instance
+        .routes
+        .lock()
+        .map_err(|_| "代理状态锁异常".to_string())?
+        .clone();
+    let rejected = instance
+        .rejected_routes
+        .lock()
+        .map_err(|_| "代理状态锁异常".to_string())?;
+    set_route_enabled(&routes, &rejected, route_id.trim(), enabled)?;
+    Ok(instance.status())
+}
+
+/// 切换路由启用状态，路由不存在时列出已知路由 ID。
+fn set_route_enabled(
+    routes: &[ProxyRoute],
+    rejected: &[RejectedRoute],
+    route_id: &str,
+    enabled: bool,
+) -> Result<(), String> {
+    if let Some(route) = rejected.iter().find(|route| route.id == route_id) {
+        // 未加载的路由本就处于停用状态，停用请求直接视为成功。
+        return match enabled {
+            true => Err(format!(
+                "路由配置非法，无法启用 {}: {}",
+                route_id, route.error
+            )),
+            false => Ok(()),
+        };
+    }
+
+    let mut found = false;
+    for route in routes.iter().filter(|route| route.id == route_id) {
+        route.enabled.store(enabled, Ordering::Relaxed);
+        found = true;
+    }
+    if found {
+        return Ok(());
+    }
+
+    let known: Vec<&str> = routes.iter().map(|route| route.id.as_str()).collect();
+    if known.is_empty() {
+        Err(format!("路由不存在: {}（当前没有已加载的路由）", route_id))
+    } else {
+        Err(format!(
+            "路由不存在: {}（已知路由: {}）",
+            route_id,
+            known.join(", ")
+        ))
+    }
+}
+
+/// 路由试匹配结果（返回给前端）。
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteTestResult {
+    matched: bool,
+    route_id: Option<String>,
+    route_name: Option<String>,
+    /// 命中模拟路由时为 `true`，此时不会访问上游。
+    mock: bool,
+    method: String,
+    /// 改写后的上游 URI（模拟路由或未命中时为空）。
+    upstream_uri: Option<String>,
+    /// 转发给上游的请求头（按模拟客户端 127.0.0.1 计算转发头）。
+    request_headers: Vec<(String, String)>,
+}
+
+/// 路由试匹配：不发送真实流量，返回指定 Host/路径会命中的路由与改写结果。
+///
+/// 传入 `routes` 时按该配置试算；否则使用实例当前运行中的路由表。
+#[command]
+pub fn proxy_test_route(
+    state: State<ProxyState>,
+    instance_id: Option<String>,
+    host: Option<String>,
+    path: String,
+    method: Option<String>,
+    routes: Option<Vec<ProxyRouteInput>>,
+) -> Result<RouteTestResult, String> {
+    let (routes, forwarded, keep_alive) = match routes {
+        Some(inputs) => (
+            Arc::new(build_routes(&inputs)?),
+            ForwardedHeaders::default(),
+            true,
+        ),
+        None => {
+            let instance = state.instance(&resolve_instance_id(instance_id))?;
+            let snapshot = instance
+                .snapshot
+                .lock()
+                .map_err(|_| "代理状态锁异常".to_string())?;
+            if !snapshot.running {
+                return Err("代理未运行，请提供用于试匹配的路由配置".to_string());
+            }
+            let routes = instance
+                .routes
+                .lock()
+                .map_err(|_| "代理状态锁异常".to_string())?
+                .clone();
+            (routes, snapshot.forwarded_headers, snapshot.keep_alive)
+        }
+    };
+
+    test_route(
+        &routes,
+        host.as_deref(),
+        &path,
+        method.as_deref(),
+        forwarded,
+        keep_alive,
+    )
+}
+
+/// 构造模拟请求并走与实际转发相同的匹配与改写流程。
+fn test_route(
+    routes: &[ProxyRoute],
+    host: Option<&str>,
+    path: &str,
+    method: Option<&str>,
+    forwarded: ForwardedHeaders,
+    keep_alive: bool,
+) -> Result<RouteTestResult, String> {
+    let method = match method.map(str::trim).filter(|value| !value.is_empty()) {
+        Some(value) => http::Method::from_bytes(value.to_ascii_uppercase().as_bytes())
+            .map_err(|_| format!("请求方法无效: {}", value))?,
+        None => http::Method::GET,
+    };
+    let path = path.trim();
+    let path = if path.starts_with('/') {
+        path.to_string()
+    } else {
+        format!("/{}", path)
+    };
+
+    let mut builder = Request::builder().method(method.clone()).uri(path.as_str());
+    if let Some(host) = host.map(str::trim).filter(|value| !value.is_empty()) {
+        builder = builder.header(header::HOST, host);
+    }
+    let mut request = builder
+        .body(())
+        .map_err(|err| format!("构造试匹配请求失败: {}", err))?;
+
+    let request_host = extract_request_host(&request);
+    let request_path = request.uri().path().to_string();
+    let request_query = parse_query_pairs(request.uri().query());
+    let Some(route) = select_route(
+        routes,
+        request_host.as_deref(),
+        &request_path,
+        &request_query,
+    ) else {
+        return Ok(RouteTestResult {
+            matched: false,
+            route_id: None,
+            route_name: None,
+            mock: false,
+            method: method.to_string(),
+            upstream_uri: None,
+            request_headers: Vec::new(),
+        });
+    };
+
+    let route = match select_request_target(route, &mut request) {
+        Some((target, _)) => &target.route,
+        None => route,
+    };
+    assign_request_id(
+        request.headers_mut(),
+        &route.request_id_header,
+        generate_request_id(),
+    );
+
+    let mut result = RouteTestResult {
+        matched: true,
+        route_id: Some(route.id.clone()),
+        route_name: Some(route.name.clone()),
+        mock: route.mock.is_some(),
+        method: method.to_string(),
+        upstream_uri: None,
+        request_headers: Vec::new(),
+    };
+    if route.mock.is_some() {
+        return Ok(result);
+    }
+
+    let peer = std::net::SocketAddr::from(([127, 0, 0, 1], 0));
+    prepare_upstream_head(&mut request, route, peer, false, forwarded, keep_alive)?;
+    result.upstream_uri = Some(request.uri().to_string());
+    result.request_headers = request
+        .headers()
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.to_string(),
+                String::from_utf8_lossy(value.as_bytes()).into_owned(),
+            )
+        })
+        .collect();
+    Ok(result)
+}
+
+/// 启动反向代理服务。
+///
+/// 启动流程：
+/// 1. 校验监听参数和路由配置；
+/// 2. 绑定监听端口并初始化上游客户端；
+/// 3. 启动 accept 循环并写入运行时句柄；
+/// 4. 更新快照状态返回前端。
+#[command]
+pub async fn proxy_start(
+    app: AppHandle,
+    state: State<'_, ProxyState>,
+    instance_id: Option<String>,
+    config: ProxyStartRequest,
+) -> Result<ProxyStatus, String> {
+    let instance_id = resolve_instance_id(instance_id);
+    let listen_hosts = resolve_listen_hosts(&config.listen_host, &config.listen_hosts)?;
+    let (routes, rejected_routes) = build_route_table(&config.routes)?;
+    if !routes.iter().any(ProxyRoute::is_enabled) {
+        return Err("至少需要一条启用的路由规则".to_string());
+    }
+    let access_control = IpAccessControl::new(&config.allow_ips, &config.deny_ips)?;
+    let error_pages = load_error_pages(&config.error_pages)?;
+    let connection_limit = match config.max_connections {
+        Some(0) => return Err("最大连接数必须大于 0".to_string()),
+        Some(limit) => Some(ConnectionLimit {
+            semaphore: Arc::new(Semaphore::new(limit.min(Semaphore::MAX_PERMITS))),
+            mode: config.connection_limit_mode,
+        }),
+        None => None,
+    };
+
+    if config.listen_port != 0 {
+        if let Some(owner) = state.port_owner(config.listen_port, &instance_id)? {
+            return Err(format!(
+                "端口 {} 已被代理实例 {} 占用",
+                config.listen_port, owner
+            ));
+        }
+    }
+
+    let instance = state.instance_or_create(&instance_id)?;
+    {
+        let runtime_guard = instance
+            .runtime
+            .lock()
+            .map_err(|_| "代理状态锁异常".to_string())?;
+        if runtime_guard.is_some() {
+            return Err(format!(
+                "代理实例 {} 已经在运行，请先停止再启动",
+                instance_id
+            ));
+        }
+    }
+    let (listeners, listen_port) = bind_listeners(&listen_hosts, config.listen_port).await?;
+    let listen_addresses: Vec<String> = listeners
+        .iter()
+        .filter_map(|listener| listener.local_addr().ok())
+        .map(|addr| addr.to_string())
+        .collect();
+
+    instance.total_requests.store(0, Ordering::Relaxed);
+    instance.rejected_requests.store(0, Ordering::Relaxed);
+    instance
+        .recent_requests
+        .lock()
+        .map_err(|_| "代理状态锁异常".to_string())?
+        .clear();
+    instance.cache.clear();
+    instance.websockets.bytes_in.store(0, Ordering::Relaxed);
+    instance.websockets.bytes_out.store(0, Ordering::Relaxed);
+    instance.outcomes.reset();
+    instance.traffic.reset();
+    instance
+        .slow_requests
+        .lock()
+        .map_err(|_| "代理状态锁异常".to_string())?
+        .clear();
+
+    let clients = Arc::new(create_https_clients(&routes)?);
+    let routes = Arc::new(routes);
+    *instance
+        .routes
+        .lock()
+        .map_err(|_| "代理状态锁异常".to_string())? = routes.clone();
+    *instance
+        .rejected_routes
+        .lock()
+        .map_err(|_| "代理状态锁异常".to_string())? = rejected_routes;
+    let snapshot = instance.snapshot.clone();
+    let (connection_signal, signal_receiver) = watch::channel(ConnectionSignal::Running);
+    let context = Arc::new(ProxyContext {
+        app,
+        routes: routes.clone(),
+        clients,
+        total_requests: instance.total_requests.clone(),
+        rejected_requests: instance.rejected_requests.clone(),
+        outcomes: instance.outcomes.clone(),
+        traffic: instance.traffic.clone(),
+        slow_requests: instance.slow_requests.clone(),
+        slow_request_threshold: config
+            .slow_request_threshold_ms
+            .filter(|ms| *ms > 0)
+            .map(Duration::from_millis),
+        snapshot: snapshot.clone(),
+        recent_requests: instance.recent_requests.clone(),
+        access_control,
+        access_log: config.access_log,
+        forwarded_headers: config.forwarded_headers,
+        cache: instance.cache.clone(),
+        compress_responses: config.compress_responses,
+        websockets: instance.websockets.clone(),
+        connection_limit,
+        keep_alive: config.keep_alive,
+        connections: instance.connections.clone(),
+        connection_signal: signal_receiver,
+        listen_authority: proxy_authority(&listen_hosts[0], listen_port),
+        error_pages,
+    });
+    let (stop_sender, stop_receiver) = oneshot::channel::<()>();
+
+    let handle = tauri::async_runtime::spawn(run_proxy_server(listeners, context, stop_receiver));
+    let mut stop_sender = Some(stop_sender);
+
+    let mut runtime_guard = instance
+        .runtime
+        .lock()
+        .map_err(|_| "代理状态锁异常".to_string())?;
+    let already_running = runtime_guard.is_some();
+    if already_running {
+        // 并发启动时，已经有其他请求先成功注册了运行时。
+        // 这里需要主动清理当前任务，避免形成无法停止的孤儿代理。
+        if let Some(sender) = stop_sender.take() {
+            let _ = sender.send(());
+        }
+        handle.abort();
+        return Err(format!(
+            "代理实例 {} 已经在运行，请先停止再启动",
+            instance_id
+        ));
+    }
+
+    *runtime_guard = Some(ProxyRuntime {
+        stop_sender,
+        handle,
+        connection_signal,
+    });
+    drop(runtime_guard);
+
+    {
+        let mut snap = snapshot.lock().map_err(|_| "代理状态锁异常".to_string())?;
+        snap.running = true;
+        snap.listen_host = Some(listen_hosts[0].clone());
+        snap.listen_port = Some(listen_port);
+        snap.listen_addresses = listen_addresses;
+        snap.route_count = routes.len();
+        snap.started_at = Some(current_timestamp());
+        snap.last_error = None;
+        snap.recent_errors.clear();
+        snap.message = format!("代理服务运行中，共 {} 条路由", routes.len());
+        let capture_count = routes
+            .iter()
+            .filter(|route| route.capture_max_bytes.is_some())
+            .count();
+        if capture_count > 0 {
+            snap.message.push_str(&format!(
+                "（{} 条路由已开启请求体截取，会额外占用内存）",
+                capture_count
+            ));
+        }
+        let throttle_count = routes
+            .iter()
+            .filter(|route| route.throttle.is_active())
+            .count();
+        if throttle_count > 0 {
+            snap.message.push_str(&format!(
+                "（{} 条路由已限速，速率按单个连接计算而非全局共享）",
+                throttle_count
+            ));
+        }
+        snap.forwarded_headers = config.forwarded_headers;
+        snap.keep_alive = config.keep_alive;
+    }
+    if let Ok(mut last_config) = instance.last_config.lock() {
+        *last_config = Some(config);
+    }
+
+    Ok(instance.status())
+}
+
+/// 持久化的代理配置（保存在应用配置目录下）。
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedProxyConfig {
+    /// 应用启动时是否自动启动默认代理实例。
+    #[serde(default)]
+    auto_start: bool,
+    config: ProxyStartRequest,
+    /// 需要重新输入 Basic 认证密码的路由 ID；密码不写入配置文件，读取时据此提示。
+    #[serde(default, skip_deserializing, skip_serializing_if = "Vec::is_empty")]
+    missing_passwords: Vec<String>,
+}
+
+/// 保存代理配置；未传入配置时保存默认实例最近一次成功启动的配置。
+///
+/// 传入的配置先按启动时的规则校验路由，非法的数值设置不会写入文件；
+/// Basic 认证密码不会保存，读取后需要重新输入。
+#[command]
+pub fn proxy_save_config(
+    app: AppHandle,
+    state: State<ProxyState>,
+    config: Option<ProxyStartRequest>,
+    auto_start: bool,
+) -> Result<(), String> {
+    let config = match config {
+        Some(config) => {
+            build_route_table(&config.routes)?;
+            config
+        }
+        None => state
+            .instance(DEFAULT_INSTANCE_ID)?
+            .last_config
+            .lock()
+            .map_err(|_| "代理状态锁异常".to_string())?
+            .clone()
+            .ok_or_else(|| "没有可保存的代理配置，请先启动代理或传入配置".to_string())?,
+    };
+
+    let path = saved_config_path(&app)?;
+    write_saved_config(
+        &path,
+        &SavedProxyConfig {
+            auto_start,
+            config,
+            missing_passwords: Vec::new(),
+        },
+    )
+}
+
+/// 读取已保存的代理配置；文件不存在或内容损坏时返回 `None`。
+#[command]
+pub fn proxy_load_config(app: AppHandle) -> Option<SavedProxyConfig> {
+    saved_config_path(&app)
+        .ok()
+        .and_then(|path| read_saved_config(&path))
+}
+
+/// 应用启动时按已保存配置自动启动默认代理实例。
+///
+/// 启动失败（例如端口被占用）只写入实例状态的 `last_error`，不影响应用启动；
+/// 启用的路由需要重新输入 Basic 认证密码时不自动启动。
+pub fn autostart_saved_proxy(app: &AppHandle) {
+    let Some(saved) = saved_config_path(app)
+        .ok()
+        .and_then(|path| read_saved_config(&path))
+    else {
+        return;
+    };
+    if !saved.auto_start {
+        return;
+    }
+    let locked_routes: Vec<String> = saved
+        .config
+        .routes
+        .iter()
+        .enumerate()
+        .filter(|(_, route)| route.enabled)
+        .map(|(index, route)| route_input_id(index, route))
+        .filter(|id| saved.missing_passwords.contains(id))
+        .collect();
+    if !locked_routes.is_empty() {
+        if let Ok(instance) = app
+            .state::<ProxyState>()
+            .instance_or_create(DEFAULT_INSTANCE_ID)
+        {
+            set_runtime_error(
+                &instance.snapshot,
+                format!(
+                    "已保存的配置不含 Basic 认证密码，请重新输入后启动: {}",
+                    locked_routes.join(", ")
+                ),
+            );
+        }
+        return;
+    }
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let result = proxy_start(app.clone(), app.state::<ProxyState>(), None, saved.config).await;
+        if let Err(err) = result {
+            if let Ok(instance) = app.state::<ProxyState>().instance(DEFAULT_INSTANCE_ID) {
+                set_runtime_error(&instance.snapshot, format!("自动启动代理失败: {}", err));
+            }
+        }
+    });
+}
+
+fn saved_config_path(app: &AppHandle) -> Result<std::path::PathBuf, String> {
+    app.path()
+        .app_config_dir()
+        .map(|dir| dir.join(SAVED_CONFIG_FILE))
+        .map_err(|err| format!("获取应用配置目录失败: {}", err))
+}
+
+/// 写入前移除 Basic 认证密码，配置文件中不保存明文密码。
+fn write_saved_config(path: &std::path::Path, saved: &SavedProxyConfig) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|err| format!("创建配置目录失败: {}", err))?;
+    }
+    let mut config = saved.config.clone();
+    strip_route_secrets(&mut config);
+    let content = serde_json::to_string_pretty(&SavedProxyConfig {
+        auto_start: saved.auto_start,
+        config,
+        missing_passwords: Vec::new(),
+    })
+    .map_err(|err| format!("序列化代理配置失败: {}", err))?;
+    std::fs::write(path, content).map_err(|err| format!("写入代理配置失败: {}", err))
+}
+
+fn read_saved_config(path: &std::path::Path) -> Option<SavedProxyConfig> {
+    let content = std::fs::read_to_string(path).ok()?;
+    let mut saved: SavedProxyConfig = serde_json::from_str(&content).ok()?;
+    saved.missing_passwords = saved
+        .config
+        .routes
+        .iter()
+        .enumerate()
+        .filter(|(_, route)| {
+            non_empty_trimmed(route.basic_auth_user.as_deref()).is_some()
+                && route
+                    .basic_auth_password
+                    .as_deref()
+                    .is_none_or(str::is_empty)
+        })
+        .map(|(index, route)| route_input_id(index, route))
+        .collect();
+    Some(saved)
+}
+
+/// 路由导出文件内容。
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportedProxyConfig<'a> {
+    schema_version: u64,
+    config: &'a ProxyStartRequest,
+}
+
+/// 路由导入结果：规范化后的配置与校验提示（不会启动代理）。
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedProxyConfig {
+    schema_version: u64,
+    config: ProxyStartRequest,
+    warnings: Vec<String>,
+}
+
+/// 将代理配置导出为 JSON 文件；未传入配置时导出实例最近一次启动的配置。
+///
+/// 默认不导出 Basic 认证密码，`include_secrets` 为 `true` 时保留。
+#[command]
+pub fn proxy_export_routes(
+    state: State<ProxyState>,
+    instance_id: Option<String>,
+    path: String,
+    config: Option<ProxyStartRequest>,
+    include_secrets: Option<bool>,
+) -> Result<(), String> {
+    let mut config = match config {
+        Some(config) => config,
+        None => state
+            .instance(&resolve_instance_id(instance_id))?
+            .last_config
+            .lock()
+            .map_err(|_| "代理状态锁异常".to_string())?
+            .clone()
+            .ok_or_else(|| "没有可导出的代理配置，请先启动代理或传入配置".to_string())?,
+    };
+    if !include_secrets.unwrap_or(false) {
+        strip_route_secrets(&mut config);
+    }
+
+    let content = export_routes_json(&config)?;
+    std::fs::write(path.trim(), content).map_err(|err| format!("写入路由文件失败: {}", err))
+}
+
+/// 读取并校验路由文件，返回规范化后的配置与提示信息。
+#[command]
+pub fn proxy_import_routes(path: String) -> Result<ImportedProxyConfig, String> {
+    let content =
+        std::fs::read_to_string(path.trim()).map_err(|err| format!("读取路由文件失败: {}", err))?;
+    import_routes_json(&content)
+}
+
+/// 移除配置中的敏感信息（Basic 认证密码）。
+fn strip_route_secrets(config: &mut ProxyStartRequest) {
+    for route in &mut config.routes {
+        route.basic_auth_password = None;
+    }
+}
+
+fn export_routes_json(config: &ProxyStartRequest) -> Result<String, String> {
+    serde_json::to_string_pretty(&ExportedProxyConfig {
+        schema_version: ROUTES_SCHEMA_VERSION,
+        config,
+    })
+    .map_err(|err| format!("序列化路由配置失败: {}", err))
+}
+
+/// 解析路由文件：逐条反序列化并用 `build_routes` 校验，错误信息带路由下标与字段名。
+fn import_routes_json(content: &str) -> Result<ImportedProxyConfig, String> {
+    let value: serde_json::Value =
+        serde_json::from_str(content).map_err(|err| format!("路由文件不是合法的 JSON: {}", err))?;
+    let schema_version = value
+        .get("schemaVersion")
+        .and_then(serde_json::Value::as_u64)
+        .ok_or_else(|| "路由文件缺少 schemaVersion 字段".to_string())?;
+    let mut config_value = value
+        .get("config")
+        .cloned()
+        .ok_or_else(|| "路由文件缺少 config 字段".to_string())?;
+
+    let mut warnings = Vec::new();
+    if schema_version > ROUTES_SCHEMA_VERSION {
+        warnings.push(format!(
+            "路由文件版本 {} 高于当前支持的版本 {}，未识别的字段将被忽略",
+            schema_version, ROUTES_SCHEMA_VERSION
+        ));
+    }
+
+    let route_values = match config_value.get_mut("routes") {
+        Some(serde_json::Value::Array(routes)) => std::mem::take(routes),
+        _ => return Err("config.routes 必须是数组".to_string()),
+    };
+    let mut routes = Vec::with_capacity(route_values.len());
+    for (index, route_value) in route_values.into_iter().enumerate() {
+        let route =
+            serde_json::from_value::<ProxyRouteInput>(route_value.clone()).map_err(|err| {
+                match invalid_route_field(&route_value) {
+                    Some(field) => format!("routes[{}].{} 无效: {}", index, field, err),
+                    None => format!("routes[{}] 无效: {}", index, err),
+                }
+            })?;
+        routes.push(route);
+    }
+    let mut config: ProxyStartRequest =
+        serde_json::from_value(config_value).map_err(|err| format!("config 无效: {}", err))?;
+
+    for (index, route) in routes.iter_mut().enumerate() {
+        normalize_route_input(route, index);
+
+        let mut checked = route.clone();
+        if route.basic_auth_user.is_some() && route.basic_auth_password.is_none() {
+            warnings.push(format!(
+                "routes[{}] 未包含 Basic 认证密码，请补充后再启动",
+                index
+            ));
+            checked.basic_auth_user = None;
+        }
+        match build_routes(std::slice::from_ref(&checked)) {
+            Ok(_) => {}
+            // 停用路由不影响启动，仅提示需要修正后才能启用。
+            Err(err) if !route.enabled => warnings.push(format!(
+                "routes[{}] 已停用且配置非法，启用前需要修正: {}",
+                index, err
+            )),
+            Err(err) => return Err(format!("routes[{}] 校验失败: {}", index, err)),
+        }
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    for route in &routes {
+        if !seen.insert(route.id.as_str()) {
+            warnings.push(format!("路由 ID 重复: {}", route.id));
+        }
+    }
+    if !routes.iter().any(|route| route.enabled) {
+        warnings.push("没有启用的路由，启动前需要至少启用一条".to_string());
+    }
+
+    config.routes = routes;
+    Ok(ImportedProxyConfig {
+        schema_version,
+        config,
+        warnings,
+    })
+}
+
+/// 找出导致反序列化失败的字段：逐个字段单独反序列化（所有字段都有默认值）。
+fn invalid_route_field(route: &serde_json::Value) -> Option<String> {
+    let object = route.as_object()?;
+    object
+        .iter()
+        .find(|(key, value)| {
+            let single = serde_json::Value::Object(
+                [((*key).clone(), (*value).clone())].into_iter().collect(),
+            );
+            serde_json::from_value::<ProxyRouteInput>(single).is_err()
+        })
+        .map(|(key, _)| key.clone())
+}
+
+/// 按 `build_routes` 的规则规范化路由输入，便于前端直接展示与再次导出。
+fn normalize_route_input(route: &mut ProxyRouteInput, index: usize) {
+    route.id = match route.id.trim() {
+        "" => format!("route-{}", index + 1),
+        value => value.to_string(),
+    };
+    route.name = route.name.trim().to_string();
+    route.host = normalize_host_value(&route.host).unwrap_or_default();
+    route.path_prefix = normalize_path_prefix(&route.path_prefix);
+    route.target = route.target.trim().to_string();
+    route.basic_auth_user = non_empty_trimmed(route.basic_auth_user.as_deref());
+    route.basic_auth_password = route
+        .basic_auth_password
+        .take()
+        .filter(|password| !password.is_empty());
+}
+
+/// 停止反向代理服务。
+///
+/// 立即停止接收新连接，然后最多等待 `drain_timeout_ms`（默认 3 秒）让在途请求
+/// 完成，超时后强制断开剩余连接。
+#[command]
+pub async fn proxy_stop(
+    state: State<'_, ProxyState>,
+    instance_id: Option<String>,
+    drain_timeout_ms: Option<u64>,
+) -> Result<ProxyStatus, String> {
+    let instance = state.instance(&resolve_instance_id(instance_id))?;
+    let runtime = {
+        let mut guard = instance
+            .runtime
+            .lock()
+            .map_err(|_| "代理状态锁异常".to_string())?;
+        guard.take()
+    };
+
+    if let Some(mut runtime) = runtime {
+        if let Some(stop_sender) = runtime.stop_sender.take() {
+            let _ = stop_sender.send(());
+        }
+        let _ = runtime.handle.await;
+
+        let connections = instance.connections.clone();
+        connections.draining.store(true, Ordering::SeqCst);
+        let _ = runtime.connection_signal.send(ConnectionSignal::Drain);
+        let drain_timeout =
+            Duration::from_millis(drain_timeout_ms.unwrap_or(DEFAULT_DRAIN_TIMEOUT_MS));
+        if timeout(drain_timeout, connections.wait_idle())
+            .await
+            .is_err()
+        {
+            let _ = runtime.connection_signal.send(ConnectionSignal::Abort);
+            connections.wait_idle().await;
+        }
+        connections.draining.store(false, Ordering::SeqCst);
+    }
+
+    {
+        let mut snapshot = instance
+            .snapshot
+            .lock()
+            .map_err(|_| "代理状态锁异常".to_string())?;
+        snapshot.running = false;
+        snapshot.listen_host = None;
+        snapshot.listen_port = None;
+        snapshot.listen_addresses.clear();
+        snapshot.started_at = None;
+        snapshot.route_count = 0;
+        snapshot.message = "代理服务已停止".to_string();
+    }
+
+    Ok(instance.status())
+}
+
+/// 合并 `listenHost` 与 `listenHosts`，去除空白与重复项，至少保留一个地址。
+fn resolve_listen_hosts(listen_host: &str, listen_hosts: &[String]) -> Result<Vec<String>, String> {
+    let mut hosts: Vec<String> = Vec::new();
+    for host in std::iter::once(listen_host).chain(listen_hosts.iter().map(String::as_str)) {
+        let host = host.trim();
+        if !host.is_empty() && !hosts.iter().any(|existing| existing == host) {
+            hosts.push(host.to_string());
+        }
+    }
+    if hosts.is_empty() {
+        return Err("监听地址不能为空".to_string());
+    }
+    Ok(hosts)
+}
+
+/// 依次绑定全部监听地址，共用同一端口（`port` 为 0 时沿用首个地址分配到的端口）。
+///
+/// 任一地址绑定失败时，已绑定的监听器随返回值一起释放，保证启动要么全部成功要么全部回滚。
+async fn bind_listeners(hosts: &[String], port: u16) -> Result<(Vec<TcpListener>, u16), String> {
+    let mut listeners = Vec::with_capacity(hosts.len());
+    let mut bound_port = port;
+    for host in hosts {
+        let (listener, assigned) = bind_listener(host, bound_port).await?;
+        bound_port = assigned;
+        listeners.push(listener);
+    }
+    Ok((listeners, bound_port))
+}
+
+/// 在多个监听器上等待任意一个新连接；轮换起始位置，避免繁忙的地址饿死其他地址。
+async fn accept_any(
+    listeners: &[TcpListener],
+    next: &mut usize,
+) -> std::io::Result<(tokio::net::TcpStream, std::net::SocketAddr)> {
+    std::future::poll_fn(|cx| {
+        for offset in 0..listeners.len() {
+            let index = (*next + offset) % listeners.len();
+            if let std::task::Poll::Ready(result) = listeners[index].poll_accept(cx) {
+                *next = (index + 1) % listeners.len();
+                return std::task::Poll::Ready(result);
+            }
+        }
+        std::task::Poll::Pending
+    })
+    .await
+}
+
+/// 绑定监听地址，返回监听器与实际端口（`port` 为 0 时由系统分配空闲端口）。
+async fn bind_listener(host: &str, port: u16) -> Result<(TcpListener, u16), String> {
+    let bind_addr = format!("{}:{}", host, port);
+    let listener = TcpListener::bind(&bind_addr)
+        .await
+        .map_err(|err| format!("监听失败 {}: {}", bind_addr, err))?;
+    let bound_port = listener
+        .local_addr()
+        .map_err(|err| format!("读取监听地址失败 {}: {}", bind_addr, err))?
+        .port();
+    Ok((listener, bound_port))
+}
+
+/// 代理主循环：接收入站连接，并为每个连接创建 HTTP/1 服务任务。
+///
+/// 收到停止信号后立即退出循环并释放监听 socket；已建立的连接由
+/// `serve_proxy_connection` 根据 `context.connection_signal` 排空或中止。
+async fn run_proxy_server(
+    listeners: Vec<TcpListener>,
+    context: Arc<ProxyContext>,
+    mut stop_receiver: oneshot::Receiver<()>,
+) {
+    // 健康检查任务与代理同生命周期：随主循环启动，主循环退出时中止。
+    let health_checks: Vec<_> = context
+        .routes
+        .iter()
+        .filter(|route| route.health_check.is_some())
+        .map(|route| tauri::async_runtime::spawn(run_health_check(route.clone(), context.clone())))
+        .collect();
+    let traffic = context.traffic.clone();
+    let traffic_sampler = tauri::async_runtime::spawn(async move {
+        loop {
+            traffic.sample();
+            sleep(Duration::from_secs(1)).await;
+        }
+    });
+
+    let mut next_listener = 0;
+    loop {
+        // 背压模式：先拿到许可再 accept，连接数饱和时暂停接收新连接。
+        let mut permit = None;
+        if let Some(limit) = context
+            .connection_limit
+            .as_ref()
+            .filter(|limit| limit.mode == ConnectionLimitMode::Backpressure)
+        {
+            tokio::select! {
+                _ = &mut stop_receiver => {
+                    break;
+                }
+                acquired = limit.semaphore.clone().acquire_owned() => {
+                    permit = acquired.ok();
+                }
+            }
+        }
+
+        tokio::select! {
+            _ = &mut stop_receiver => {
+                break;
+            }
+            accept_result = accept_any(&listeners, &mut next_listener) => {
+                match accept_result {
+                    Ok((stream, peer)) => {
+                        if let Some(limit) = context
+                            .connection_limit
+                            .as_ref()
+                            .filter(|limit| limit.mode == ConnectionLimitMode::Reject)
+                        {
+                            match limit.semaphore.clone().try_acquire_owned() {
+                                Ok(acquired) => permit = Some(acquired),
+                                Err(_) => {
+                                    tauri::async_runtime::spawn(reject_overloaded_connection(stream));
+                                    continue;
+                                }
+                            }
+                        }
+
+                        let guard = context.connections.enter();
+                        tauri::async_runtime::spawn(serve_proxy_connection(
+                            stream,
+                            peer,
+                            context.clone(),
+                            context.connection_signal.clone(),
+                            guard,
+                            permit,
+                        ));
+                    }
+                    Err(err) => {
+                        set_runtime_error(&context.snapshot, format!("监听 accept 失败: {}", err));
+                        sleep(Duration::from_millis(80)).await;
+                    }
+                }
+            }
+        }
+    }
+
+    for handle in health_checks {
+        handle.abort();
+    }
+    traffic_sampler.abort();
+}
+
+/// 面向客户端的 HTTP/1 连接配置，长连接由 hyper 按请求头自行管理。
+fn proxy_http1_builder(keep_alive: bool) -> http1::Builder {
+    let mut builder = http1::Builder::new();
+    builder
+        .preserve_header_case(true)
+        .title_case_headers(true)
+        .keep_alive(keep_alive);
+    builder
+}
+
+/// 连接数已满时直接写回 503 并关闭连接。
+async fn reject_overloaded_connection(mut stream: tokio::net::TcpStream) {
+    use tokio::io::AsyncWriteExt;
+
+    const BODY: &str = "代理连接数已达上限，请稍后重试";
+    let response = format!(
+        "HTTP/1.1 503 Service Unavailable\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\nRetry-After: 1\r\n\r\n{}",
+        BODY.len(),
+        BODY
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+/// 周期性探测路由上游，记录结果并在健康状态变化时推送事件。
+async fn run_health_check(route: ProxyRoute, context: Arc<ProxyContext>) {
+    let Some(check) = route.health_check.clone() else {
+        return;
+    };
+    let client = select_upstream_client(&route, &context.clients);
+
+    loop {
+        // 路由停用期间暂停探测，重新启用后在下一个周期恢复。
+        if !route.is_enabled() {
+            sleep(check.interval).await;
+            continue;
+        }
+        let (last_status, last_error) = match probe_upstream(&client, &route, &check.path).await {
+            Ok(status) => (Some(status.as_u16()), None),
+            Err(err) => (None, Some(err)),
+        };
+        let healthy = last_status.is_some_and(|status| status < 400);
+        let health = RouteHealthStatus {
+            route_id: route.id.clone(),
+            healthy,
+            last_status,
+            last_error,
+            checked_at: current_timestamp(),
+        };
+
+        let changed = match route.stats.health.lock() {
+            Ok(mut current) => {
+                let changed = current
+                    .as_ref()
+                    .is_none_or(|previous| previous.healthy != healthy);
+                *current = Some(health.clone());
+                changed
+            }
+            Err(_) => false,
+        };
+        if changed {
+            let _ = context.app.emit(PROXY_HEALTH_EVENT, health);
+        }
+
+        sleep(check.interval).await;
+    }
+}
+
+/// 向上游发送一次健康检查 GET 请求，返回响应状态码。
+async fn probe_upstream(
+    client: &UpstreamClient,
+    route: &ProxyRoute,
+    path: &str,
+) -> Result<StatusCode, String> {
+    let uri = format!(
+        "{}://{}{}",
+        route.target_scheme.as_str(),
+        route.connect_authority(),
+        path
+    )
+    .parse::<Uri>()
+    .map_err(|err| format!("构建健康检查地址失败: {}", err))?;
+    let host = match &route.host_header {
+        Some(value) => value.clone(),
+        None => HeaderValue::from_str(&route.target_authority())
+            .map_err(|_| "目标主机格式非法，无法写入 Host 头".to_string())?,
+    };
+
+    let request = Request::get(uri)
+        .header(header::HOST, host)
+        .body(Either::Right(Full::new(Bytes::new())))
+        .map_err(|err| format!("构建健康检查请求失败: {}", err))?;
+
+    match timeout(HEALTH_CHECK_TIMEOUT, client.request(request)).await {
+        Ok(Ok(response)) => Ok(response.status()),
+        Ok(Err(err)) => Err(format!("健康检查请求失败: {}", err)),
+        Err(_) => Err("健康检查超时".to_string()),
+    }
+}
+
+/// 为单个入站连接提供 HTTP/1 服务。
+///
+/// 收到 `Drain` 时调用 hyper 的优雅关闭（处理完当前请求后断开），
+/// 收到 `Abort`（或信号源已释放）时直接丢弃连接。
+async fn serve_proxy_connection(
+    stream: tokio::net::TcpStream,
+    peer: std::net::SocketAddr,
+    context: Arc<ProxyContext>,
+    mut signal: watch::Receiver<ConnectionSignal>,
+    _guard: ConnectionGuard,
+    _permit: Option<OwnedSemaphorePermit>,
+) {
+    let io = TokioIo::new(stream);
+    let context_for_service = context.clone();
+    let service =
+        service_fn(move |request| handle_proxy_request(request, peer, context_for_service.clone()));
+
+    let connection = proxy_http1_builder(context.keep_alive)
+        .serve_connection(io, service)
+        .with_upgrades();
+    tokio::pin!(connection);
+
+    let mut draining = false;
+    let result = loop {
+        tokio::select! {
+            result = connection.as_mut() => break result,
+            changed = signal.changed() => {
+                let current = match changed {
+                    Ok(()) => *signal.borrow_and_update(),
+                    Err(_) => ConnectionSignal::Abort,
+                };
+                match current {
+                    ConnectionSignal::Running => {}
+                    ConnectionSignal::Drain => {
+                        if !draining {
+                            draining = true;
+                            connection.as_mut().graceful_shutdown();
+                        }
+                    }
+                    ConnectionSignal::Abort => return,
+                }
+            }
+        }
+    };
+
+    if let Err(err) = result {
+        set_runtime_error(&context.snapshot, format!("连接处理失败: {}", err));
+    }
+}
+
+/// 处理单个 HTTP 请求，并在结束后按需推送访问日志。
+async fn handle_proxy_request(
+    request: Request<Incoming>,
+    peer: std::net::SocketAddr,
+    context: Arc<ProxyContext>,
+) -> Result<ClientResponse, Infallible> {
+    let started = Instant::now();
+    let gzip_accepted = context.compress_responses
+        && *request.method() != http::Method::HEAD
+        && accepts_gzip(request.headers());
+    let method = request.method().to_string();
+    let host = extract_request_host(&request);
+    let path = request.uri().path().to_string();
+    let mut trace = RequestTrace {
+        request_id: generate_request_id(),
+        ..Default::default()
+    };
+
+    let mut response = proxy_request(request, peer, &context, &mut trace).await;
+    echo_request_id(response.headers_mut(), &trace);
+    let status = response.status().as_u16();
+    context.outcomes.record_status(status);
+    if let Some(stats) = trace.target_stats.as_ref().filter(|_| status >= 500) {
+        stats.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    let duration = started.elapsed();
+    if context
+        .slow_request_threshold
+        .is_some_and(|threshold| duration > threshold)
+    {
+        let slow = SlowProxyRequest {
+            timestamp: current_timestamp(),
+            request_id: trace.request_id.clone(),
+            method: method.clone(),
+            path: path.clone(),
+            route_id: trace.route_id.clone(),
+            status,
+            duration_ms: duration.as_millis() as u64,
+            connect_ms: trace.upstream_connect_ms,
+            response_ms: trace
+                .upstream_latency_ms
+                .map(|latency| latency.saturating_sub(trace.upstream_connect_ms.unwrap_or(0))),
+        };
+        let _ = context.app.emit(PROXY_SLOW_EVENT, slow.clone());
+        record_slow_request(&context.slow_requests, slow);
+    }
+
+    // 只统计真正到达上游的请求；响应体为流式转发，这里即拿到响应头的时刻。
+    if let (Some(stats), Some(_)) = (&trace.route_stats, trace.upstream_latency_ms) {
+        stats.latency.record(started.elapsed().as_millis() as u64);
+    }
+
+    if context.access_log {
+        emit_proxy_log(
+            &context.app,
+            ProxyLogPayload {
+                request_id: trace.request_id.clone(),
+                method: method.clone(),
+                path: path.clone(),
+                route_id: trace.route_id.clone(),
+                upstream_uri: trace.upstream_uri.clone(),
+                status,
+                duration_ms: started.elapsed().as_millis() as u64,
+                peer_ip: peer.ip().to_string(),
+                location_rewrite: trace.location_rewrite.clone(),
+            },
+        );
+    }
+
+    record_recent_request(
+        &context.recent_requests,
+        RecentProxyRequest {
+            timestamp: current_timestamp(),
+            request_id: trace.request_id,
+            method,
+            host,
+            path,
+            route_id: trace.route_id,
+            status,
+            upstream_latency_ms: trace.upstream_latency_ms,
+            error: trace.error,
+            request_body: trace.request_capture,
+            response_body: trace.response_capture.clone(),
+        },
+    );
+
+    let gzip = gzip_accepted && should_gzip_response(&response);
+    let response = response.map(|body| {
+        CaptureBody::new(body, trace.response_capture)
+            .throttled(trace.throttle.download_bytes_per_sec)
+            .counted(&context.traffic.bytes_out)
+    });
+    if gzip {
+        return Ok(gzip_response(response));
+    }
+    Ok(response.map(Either::Left))
+}
+
+/// 判断客户端是否接受 gzip 编码（`q=0` 视为拒绝）。
+fn accepts_gzip(headers: &HeaderMap<HeaderValue>) -> bool {
+    headers
+        .get_all(header::ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|item| {
+            let mut parts = item.split(';').map(str::trim);
+            let coding = parts.next().unwrap_or("");
+            let rejected = parts.any(|param| {
+                param
+                    .strip_prefix("q=")
+                    .and_then(|q| q.parse::<f32>().ok())
+                    .is_some_and(|q| q <= 0.0)
+            });
+            (coding.eq_ignore_ascii_case("gzip") || coding == "*") && !rejected
+        })
+}
+
+/// 判断响应是否值得压缩：未编码的文本类内容，且体积不小于阈值。
+fn should_gzip_response(response: &ProxyResponse) -> bool {
+    let status = response.status();
+    if status.is_informational()
+        || status == StatusCode::NO_CONTENT
+        || status == StatusCode::PARTIAL_CONTENT
+        || status == StatusCode::NOT_MODIFIED
+    {
+        return false;
+    }
+
+    let headers = response.headers();
+    let already_encoded = headers
+        .get(header::CONTENT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| !value.trim().eq_ignore_ascii_case("identity"));
+    if already_encoded {
+        return false;
+    }
+
+    let text_like = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_ascii_lowercase())
+        .is_some_and(|value| {
+            value.starts_with("text/")
+                || ["json", "javascript", "xml", "svg"]
+                    .iter()
+                    .any(|kind| value.contains(kind))
+        });
+    if !text_like {
+        return false;
+    }
+
+    // 长度未知（分块传输）的响应也压缩，只跳过确定很小的响应。
+    response
+        .body()
+        .size_hint()
+        .upper()
+        .is_none_or(|upper| upper >= GZIP_MIN_BYTES)
+}
+
+/// 将响应体改为流式 gzip 压缩，并相应调整响应头。
+///
+/// 限速作用于压缩前的字节，实际传输速率会低于配置值。
+fn gzip_response(response: Response<CaptureBody<UpstreamBody>>) -> ClientResponse {
+    let (mut parts, body) = response.into_parts();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts
+        .headers
+        .insert(header::CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+    parts
+        .headers
+        .append(header::VARY, HeaderValue::from_static("Accept-Encoding"));
+    Response::from_parts(parts, Either::Right(GzipBody::new(body)))
+}
+
+/// 边读取上游响应体边 gzip 压缩的响应体。
+///
+/// 每个数据帧写入后执行一次同步 flush，保证压缩结果及时下发给客户端；
+/// 上游 trailers 会被丢弃。
+struct GzipBody {
+    inner: CaptureBody<UpstreamBody>,
+    encoder: Option<GzEncoder<Vec<u8>>>,
+}
+
+impl GzipBody {
+    fn new(inner: CaptureBody<UpstreamBody>) -> Self {
+        Self {
+            inner,
+            encoder: Some(GzEncoder::new(Vec::new(), Compression::default())),
+        }
+    }
+}
+
+impl Body for GzipBody {
+    type Data = Bytes;
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        loop {
+            let Some(encoder) = this.encoder.as_mut() else {
+                return Poll::Ready(None);
+            };
+
+            match ready!(Pin::new(&mut this.inner).poll_frame(cx)) {
+                Some(Ok(frame)) => {
+                    let Ok(data) = frame.into_data() else {
+                        continue;
+                    };
+                    encoder.write_all(&data)?;
+                    encoder.flush()?;
+                    let compressed = std::mem::take(encoder.get_mut());
+                    if !compressed.is_empty() {
+                        return Poll::Ready(Some(Ok(Frame::data(Bytes::from(compressed)))));
+                    }
+                }
+                Some(Err(err)) => return Poll::Ready(Some(Err(err))),
+                None => {
+                    let Some(encoder) = this.encoder.take() else {
+                        return Poll::Ready(None);
+                    };
+                    let compressed = encoder.finish()?;
+                    return Poll::Ready(Some(Ok(Frame::data(Bytes::from(compressed)))));
+                }
+            }
+        }
+    }
+}
+
+/// 按粘性 Cookie 或权重为请求选出分流目标，并移除请求中的粘性 Cookie；
+/// 返回选中的目标与需要写回客户端的新 Cookie，未配置分流时返回 `None`。
+/// 实际转发与路由试匹配共用。
+fn select_request_target<'a, B>(
+    route: &'a ProxyRoute,
+    request: &mut Request<B>,
+) -> Option<(&'a WeightedTarget, Option<HeaderValue>)> {
+    let sticky_key = if route.sticky_sessions {
+        take_sticky_cookie(request.headers_mut())
+    } else {
+        None
+    };
+    route
+        .select_target(sticky_key.as_deref(), request.headers())
+        .map(|(_, target, cookie)| (target, cookie))
+}
+
+/// 转发单个 HTTP 请求：
+/// - 客户端 IP 访问控制
+/// - 路由匹配
+/// - CORS 预检短路
+/// - 转发到上游
+/// - 响应头改写
+async fn proxy_request(
+    mut request: Request<Incoming>,
+    peer: std::net::SocketAddr,
+    context: &Arc<ProxyContext>,
+    trace: &mut RequestTrace,
+) -> ProxyResponse {
+    if !context.access_control.permits(peer.ip()) {
+        context.rejected_requests.fetch_add(1, Ordering::Relaxed);
+        return plain_response(StatusCode::FORBIDDEN, "当前客户端地址不允许访问代理");
+    }
+
+    let request_host = extract_request_host(&request);
+    let request_path = request.uri().path().to_string();
+    let request_query = parse_query_pairs(request.uri().query());
+
+    let route = match select_route(
+        &context.routes,
+        request_host.as_deref(),
+        &request_path,
+        &request_query,
+    ) {
+        Some(route) => {
+            route.stats.record_hit();
+            route.clone()
+        }
+        None => {
+            context.outcomes.no_route.fetch_add(1, Ordering::Relaxed);
+            return error_response(
+                &context.error_pages,
+                StatusCode::NOT_FOUND,
+                "未匹配到可用的反向代理路由",
+                None,
+            );
+        }
+    };
+    let mut sticky_cookie = None;
+    let route = match select_request_target(&route, &mut request) {
+        Some((target, cookie)) => {
+            target.stats.requests.fetch_add(1, Ordering::Relaxed);
+            trace.target_stats = Some(target.stats.clone());
+            sticky_cookie = cookie;
+            target.route.clone()
+        }
+        None => route,
+    };
+    trace.route_id = Some(route.id.clone());
+    trace.route_stats = Some(route.stats.clone());
+    trace.throttle = route.throttle;
+    trace.request_id = assign_request_id(
+        request.headers_mut(),
+        &route.request_id_header,
+        std::mem::take(&mut trace.request_id),
+    );
+    trace.request_id_header = Some(route.request_id_header.clone());
+    if let Some(limit) = route.capture_max_bytes {
+        trace.request_capture = Some(Arc::new(BodyCapture::new(limit)));
+        trace.response_capture = Some(Arc::new(BodyCapture::new(limit)));
+    }
+
+    let cors_request = CorsRequest::from_headers(request.headers());
+
+    if route.cors && is_cors_preflight(&request) {
+        context.total_requests.fetch_add(1, Ordering::Relaxed);
+        let mut response = empty_response(StatusCode::NO_CONTENT);
+        apply_response_headers(response.headers_mut(), &route, &cors_request, true);
+        return response;
+    }
+
+    // 认证放在预检短路之后：浏览器发起 CORS 预检时不会携带凭据。
+    if let Some(credentials) = route.basic_auth.as_deref() {
+        if !basic_auth_matches(request.headers(), credentials) {
+            let mut response = unauthorized_response();
+            apply_response_headers(response.headers_mut(), &route, &cors_request, false);
+            return response;
+        }
+        // 代理层凭据只用于访问控制，不能泄露给上游。
+        request.headers_mut().remove(header::AUTHORIZATION);
+    }
+
+    if let Some(mock) = route.mock.as_deref() {
+        context.total_requests.fetch_add(1, Ordering::Relaxed);
+        let mut response = mock_response(mock).await;
+        apply_response_headers(response.headers_mut(), &route, &cors_request, false);
+        return response;
+    }
+
+    let cache_key = route
+        .cache
+        .as_ref()
+        .filter(|_| *request.method() == http::Method::GET && !is_websocket_upgrade(&request))
+        .map(|_| response_cache_key(&request, request_host.as_deref()));
+    if let Some(key) = &cache_key {
+        if let Some(mut response) = context.cache.get(key) {
+            context.total_requests.fetch_add(1, Ordering::Relaxed);
+            response
+                .headers_mut()
+                .insert(cache_status_header(), HeaderValue::from_static("HIT"));
+            apply_response_headers(response.headers_mut(), &route, &cors_request, false);
+            return response;
+        }
+    }
+
+    let mut response = forward_request(request, peer, &route, context, trace).await;
+    if let (Some(key), Some(policy)) = (cache_key, route.cache.as_ref()) {
+        response = store_cached_response(response, key, policy, &context.cache).await;
+    }
+    apply_response_headers(response.headers_mut(), &route, &cors_request, false);
+    // 在上游 Cookie 改写之后追加，不受 Domain / Secure 改写影响；出错的目标不做固定。
+    if let Some(cookie) = sticky_cookie.filter(|_| !response.status().is_server_error()) {
+        response.headers_mut().append(header::SET_COOKIE, cookie);
+    }
+    response
+}
+
+fn cache_status_header() -> HeaderName {
+    HeaderName::from_static("x-krate-cache")
+}
+
+/// 缓存键：方法 + Host + 路径 + query + `Accept-Encoding`。
+///
+/// 上游或代理自身的 gzip 会让响应随 `Accept-Encoding` 变化，因此按其取值区分缓存。
+fn response_cache_key<B>(request: &Request<B>, host: Option<&str>) -> String {
+    let path_and_query = request
+        .uri()
+        .path_and_query()
+        .map(|value| value.as_str())
+        .unwrap_or("/");
+    let accept_encoding = request
+        .headers()
+        .get_all(header::ACCEPT_ENCODING)
+        .iter()
+        .map(|value| String::from_utf8_lossy(value.as_bytes()).trim().to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "{} {}{} {}",
+        request.method(),
+        host.unwrap_or(""),
+        path_and_query,
+        accept_encoding
+    )
+}
+
+/// 响应的 `Vary` 是否只涉及缓存键已区分的请求头（目前只有 `Accept-Encoding`）。
+fn vary_is_honored(headers: &HeaderMap<HeaderValue>) -> bool {
+    headers.get_all(header::VARY).iter().all(|value| {
+        value.to_str().is_ok_and(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .all(|name| name.eq_ignore_ascii_case("accept-encoding"))
+        })
+    })
+}
+
+/// 判断上游响应是否允许缓存：仅 200、未声明 `no-store`、不含 `Set-Cookie`、
+/// `Vary` 可由缓存键区分，且响应体大小可预知并不超过上限。
+fn is_cacheable_response(response: &ProxyResponse, max_bytes: u64) -> bool {
+    let no_store = response
+        .headers()
+        .get_all(header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(|value| value.to_ascii_lowercase().contains("no-store"));
+
+    response.status() == StatusCode::OK
+        && !no_store
+        && !response.headers().contains_key(header::SET_COOKIE)
+        && vary_is_honored(response.headers())
+        && response
+            .body()
+            .size_hint()
+            .upper()
+            .is_some_and(|upper| upper <= max_bytes)
+}
+
+/// 标记缓存未命中；可缓存时读取完整响应体写入缓存后再返回。
+async fn store_cached_response(
+    mut response: ProxyResponse,
+    key: String,
+    policy: &CachePolicy,
+    cache: &ResponseCache,
+) -> ProxyResponse {
+    if !is_cacheable_response(&response, policy.max_bytes) {
+        response
+            .headers_mut()
+            .insert(cache_status_header(), HeaderValue::from_static("MISS"));
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let body = match body.collect().await {
+        Ok(collected) => collected.to_bytes(),
+        Err(err) => {
+            return plain_response(
+                StatusCode::BAD_GATEWAY,
+                &format!("读取上游响应失败: {}", err),
+            );
+        }
+    };
+
+    cache.insert(
+        key,
+        CachedResponse {
+            status: parts.status,
+            headers: parts.headers.clone(),
+            body: body.clone(),
+            expires_at: Instant::now() + policy.ttl,
+        },
+    );
+
+    parts
+        .headers
+        .insert(cache_status_header(), HeaderValue::from_static("MISS"));
+    Response::from_parts(parts, Either::Right(Full::new(body)))
+}
+
+/// 按配置构建模拟响应（可选延迟后返回）。
+async fn mock_response(mock: &MockResponse) -> ProxyResponse {
+    if let Some(delay) = mock.delay {
+        sleep(delay).await;
+    }
+
+    let mut response = Response::new(Either::Right(Full::new(mock.body.clone())));
+    *response.status_mut() = mock.status;
+    let headers = response.headers_mut();
+    headers.insert(header::CONTENT_TYPE, mock.content_type.clone());
+    for (name, value) in &mock.headers {
+        headers.insert(name.clone(), value.clone());
+    }
+    response
+}
+
+/// 将请求转发到已匹配路由的上游：
+/// - 构造上游 URI
+/// - 代理头处理
+/// - HTTP 或 WebSocket 转发
+async fn forward_request(
+    mut request: Request<Incoming>,
+    peer: std::net::SocketAddr,
+    route: &ProxyRoute,
+    context: &Arc<ProxyContext>,
+    trace: &mut RequestTrace,
+) -> ProxyResponse {
+    let websocket_upgrade = is_websocket_upgrade(&request);
+    let client_authority = request
+        .headers()
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .unwrap_or(context.listen_authority.as_str())
+        .to_string();
+    let original_uri = match prepare_upstream_head(
+        &mut request,
+        route,
+        peer,
+        websocket_upgrade,
+        context.forwarded_headers,
+        context.keep_alive,
+    ) {
+        Ok(original_uri) => original_uri,
+        Err(err) => return plain_response(StatusCode::BAD_REQUEST, &err),
+    };
+    trace.upstream_uri = Some(request.uri().to_string());
+
+    let client = select_upstream_client(route, &context.clients);
+
+    if let Some(latency) = route.throttle.latency {
+        sleep(latency).await;
+    }
+
+    if websocket_upgrade {
+        return forward_websocket(request, client, context.clone(), trace).await;
+    }
+
+    let replayable =
+        (route.retries > 0 && is_idempotent_method(request.method())) || route.fallback.is_some();
+    let upstream_request = match UpstreamRequest::new(
+        request,
+        replayable,
+        trace.request_capture.clone(),
+        route.throttle.upload_bytes_per_sec,
+        &context.traffic.bytes_in,
+    )
+    .await
+    {
+        Ok(upstream_request) => upstream_request,
+        Err(err) => {
+            return plain_response(StatusCode::BAD_REQUEST, &format!("读取请求体失败: {}", err));
+        }
+    };
+
+    // 连接失败说明请求未送达主上游，因此任意方法的小请求体都可以转给备用目标。
+    let fallback_request = match (&route.fallback, &upstream_request.body) {
+        (Some(fallback), ReplayableBody::Buffered(body)) => {
+            match fallback_upstream_request(&original_uri, &upstream_request.head, body, fallback) {
+                Ok(request) => Some((fallback.as_ref(), request)),
+                Err(err) => return plain_response(StatusCode::BAD_REQUEST, &err),
+            }
+        }
+        _ => None,
+    };
+
+    // 缓冲的请求体一次性发出，按上行速率等待相应时长来模拟慢速上传。
+    if let (Some(rate), ReplayableBody::Buffered(body)) =
+        (route.throttle.upload_bytes_per_sec, &upstream_request.body)
+    {
+        sleep(Duration::from_secs_f64(body.len() as f64 / rate as f64)).await;
+    }
+
+    let upstream_started = Instant::now();
+    let mut result = send_upstream(&client, upstream_request, route)
+        .await
+        .map(|response| response.map(|body| Either::Left(PrefixedBody::new(body))));
+
+    if let Some((fallback, request)) = fallback_request {
+        if matches!(&result, Err(err) if err.is_connect()) {
+            trace.upstream_uri = Some(request.uri().to_string());
+            let fallback_client = select_upstream_client(fallback, &context.clients);
+            result = fallback_client.request(request).await.map(|mut response| {
+                route.stats.fallbacks.fetch_add(1, Ordering::Relaxed);
+                response.headers_mut().insert(
+                    HeaderName::from_static("x-krate-fallback"),
+                    HeaderValue::from_static("1"),
+                );
+                response.map(|body| Either::Left(PrefixedBody::new(body)))
+            });
+        }
+    }
+    trace.upstream_latency_ms = Some(upstream_started.elapsed().as_millis() as u64);
+    if let Ok(response) = &result {
+        trace.upstream_connect_ms = fresh_connect_ms(response, upstream_started);
+    }
+
+    match result {
+        Ok(mut response) => {
+            context.total_requests.fetch_add(1, Ordering::Relaxed);
+            // 上游的连接管理头只对代理与上游之间有效，客户端侧长连接交给 hyper。
+            sanitize_hop_headers(response.headers_mut(), false);
+            if route.rewrite_cookie_domain || route.strip_cookie_secure {
+                rewrite_set_cookies(
+                    response.headers_mut(),
+                    route.rewrite_cookie_domain,
+                    route.strip_cookie_secure,
+                );
+            }
+            if route.rewrite_redirects && response.status().is_redirection() {
+                trace.location_rewrite =
+                    rewrite_redirect_location(response.headers_mut(), route, &client_authority);
+            }
+            if let Some(max_bytes) = route.html_rewrite_max_bytes {
+                response =
+                    rewrite_html_response(response, route, &client_authority, max_bytes).await;
+            }
+            response
+        }
+        Err(err) => {
+            let kind = classify_upstream_error(&err);
+            context.outcomes.record_upstream_error(kind);
+            let message = format!("转发请求失败: {}", err);
+            trace.error = Some(message.clone());
+            set_runtime_error(&context.snapshot, message);
+            let (status, message) = if kind == UpstreamErrorKind::Timeout {
+                (
+                    StatusCode::GATEWAY_TIMEOUT,
+                    format!("上游服务响应超时: {}", err),
+                )
+            } else {
+                (StatusCode::BAD_GATEWAY, format!("上游服务不可用: {}", err))
+            };
+            error_response(
+                &context.error_pages,
+                status,
+                &message,
+                trace.route_id.as_deref(),
+            )
+        }
+    }
+}
+
+/// 将客户端请求改写为发往上游的请求头部：替换 URI 并写入代理头，返回原始 URI。
+///
+/// 实际转发与 `proxy_test_route` 共用此函数，保证试匹配结果与真实流量一致。
+fn prepare_upstream_head<B>(
+    request: &mut Request<B>,
+    route: &ProxyRoute,
+    peer: std::net::SocketAddr,
+    websocket_upgrade: bool,
+    forwarded: ForwardedHeaders,
+    keep_alive: bool,
+) -> Result<Uri, String> {
+    let upstream_uri = build_upstream_uri(request.uri(), route)?;
+    let original_host = request
+        .headers()
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("")
+        .to_string();
+
+    let original_uri = std::mem::replace(request.uri_mut(), upstream_uri);
+    apply_proxy_headers(
+        request.headers_mut(),
+        route,
+        peer,
+        &original_host,
+        websocket_upgrade,
+        forwarded,
+    )?;
+    if !keep_alive && !websocket_upgrade {
+        request
+            .headers_mut()
+            .insert(header::CONNECTION, HeaderValue::from_static("close"));
+    }
+    Ok(original_uri)
+}
+
+/// 校验请求携带的 Basic 认证凭据（常量时间比较）。
+fn basic_auth_matches(headers: &HeaderMap<HeaderValue>, expected: &str) -> bool {
+    let Some(value) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return false;
+    };
+
+    let Some((scheme, encoded)) = value.trim().split_once(' ') else {
+        return false;
+    };
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return false;
+    }
+
+    match base64::engine::general_purpose::STANDARD.decode(encoded.trim()) {
+        Ok(decoded) => constant_time_eq(&decoded, expected.as_bytes()),
+        Err(_) => false,
+    }
+}
+
+/// 常量时间比较，避免通过响应耗时推测凭据内容。
+fn constant_time_eq(left: &[u8], right: &[u8]) -> bool {
+    if left.len() != right.len() {
+        return false;
+    }
+    left.iter()
+        .zip(right.iter())
+        .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
+/// 上游请求体：原始流，或为了失败重放而缓冲的小请求体。
+enum ReplayableBody {
+    Streaming(CaptureBody<Incoming>),
+    Buffered(Bytes),
+}
+
+/// 已完成头部改写、等待发送到上游的请求。
+struct UpstreamRequest {
+    head: http::request::Parts,
+    body: ReplayableBody,
+}
+
+impl UpstreamRequest {
+    /// 拆分请求；需要重放且请求体大小已知不超过上限时缓冲请求体。
+    ///
+    /// 开启截取时，缓冲的请求体直接写入截取缓冲，流式请求体边转发边截取；
+    /// `upload_limit` 只作用于流式请求体，缓冲请求体由调用方等待；
+    /// 请求体字节数累加到 `counter`。
+    async fn new(
+        request: Request<Incoming>,
+        replayable: bool,
+        capture: Option<Arc<BodyCapture>>,
+        upload_limit: Option<u64>,
+        counter: &Arc<AtomicU64>,
+    ) -> Result<Self, hyper::Error> {
+        let (head, body) = request.into_parts();
+        let fits_buffer = body
+            .size_hint()
+            .upper()
+            .is_some_and(|upper| upper <= REPLAY_BODY_LIMIT);
+
+        let body = if replayable && fits_buffer {
+            let bytes = body.collect().await?.to_bytes();
+            if let Some(capture) = &capture {
+                capture.record(&bytes);
+            }
+            counter.fetch_add(bytes.len() as u64, Ordering::Relaxed);
+            ReplayableBody::Buffered(bytes)
+        } else {
+            ReplayableBody::Streaming(
+                CaptureBody::new(body, capture)
+                    .throttled(upload_limit)
+                    .counted(counter),
+            )
+        };
+
+        Ok(Self { head, body })
+    }
+}
+
+/// 判断请求方法是否幂等且可安全重试。
+fn is_idempotent_method(method: &http::Method) -> bool {
+    *method == http::Method::GET
+        || *method == http::Method::HEAD
+        || *method == http::Method::OPTIONS
+}
+
+/// 由缓冲请求体重新组装一次上游请求（扩展字段不会被复制）。
+fn buffered_request(head: &http::request::Parts, body: &Bytes) -> Request<UpstreamRequestBody> {
+    let mut request = Request::new(Either::Right(Full::new(body.clone())));
+    *request.method_mut() = head.method.clone();
+    *request.uri_mut() = head.uri.clone();
+    *request.version_mut() = head.version;
+    *request.headers_mut() = head.headers.clone();
+    request
+}
+
+/// 基于已改写的主上游请求构造发往备用目标的请求：重新计算 URI，
+/// 并在未要求保留原始 Host 时把 `Host` 换成备用目标地址。
+fn fallback_upstream_request(
+    original_uri: &Uri,
+    head: &http::request::Parts,
+    body: &Bytes,
+    fallback: &ProxyRoute,
+) -> Result<Request<UpstreamRequestBody>, String> {
+    let mut request = buffered_request(head, body);
+    *request.uri_mut() = build_upstream_uri(original_uri, fallback)?;
+    if !fallback.preserve_host_header {
+        request.headers_mut().insert(
+            header::HOST,
+            HeaderValue::from_str(&fallback.target_authority())
+                .map_err(|_| "备用目标主机格式非法，无法写入 Host 头".to_string())?,
+        );
+    }
+    Ok(request)
+}
+
+/// 发送上游请求。
+///
+/// 仅当请求体已缓冲且方法幂等时，遇到连接级错误（而非错误状态码）
+/// 才会按路由配置退避重试。
+async fn send_upstream(
+    client: &UpstreamClient,
+    request: UpstreamRequest,
+    route: &ProxyRoute,
+) -> Result<Response<Incoming>, UpstreamError> {
+    let UpstreamRequest { head, body } = request;
+    let body = match body {
+        ReplayableBody::Buffered(bytes)
+            if route.retries > 0 && is_idempotent_method(&head.method) =>
+        {
+            bytes
+        }
+        ReplayableBody::Buffered(bytes) => {
+            return client
+                .request(Request::from_parts(head, Either::Right(Full::new(bytes))))
+                .await;
+        }
+        ReplayableBody::Streaming(body) => {
+            return client
+                .request(Request::from_parts(head, Either::Left(body)))
+                .await;
+        }
+    };
+
+    let mut attempt = 0u8;
+    loop {
+        match client.request(buffered_request(&head, &body)).await {
+            Err(err) if err.is_connect() && attempt < route.retries => {
+                attempt += 1;
+                route.stats.retries.fetch_add(1, Ordering::Relaxed);
+                sleep(Duration::from_millis(RETRY_BACKOFF_MS * attempt as u64)).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// 按路由选择上游客户端：
+/// - 本地套接字目标使用按套接字路径共享的客户端；
+/// - HTTPS/WSS + TLS 定制参数（自定义 CA / 客户端证书）使用对应客户端（优先级最高）；
+/// - HTTPS/WSS + `allow_insecure_tls=true` 使用不安全客户端；
+/// - 其余情况使用默认安全客户端。
+fn select_upstream_client(route: &ProxyRoute, clients: &ProxyClients) -> UpstreamClient {
+    if let Some(path) = &route.target_socket {
+        let client = clients
+            .local_sockets
+            .get(path)
+            .cloned()
+            .unwrap_or_else(|| local_socket_client(path));
+        return UpstreamClient::LocalSocket(client);
+    }
+    if route.target_scheme == TargetScheme::Https {
+        if let Some(client) = route
+            .tls_profile
+            .as_ref()
+            .and_then(|profile| clients.profiles.get(profile))
+        {
+            return UpstreamClient::Https(client.clone());
+        }
+        if route.allow_insecure_tls {
+            return UpstreamClient::Https(clients.insecure.clone());
+        }
+    }
+    UpstreamClient::Https(clients.secure.clone())
+}
+
+/// 处理 WebSocket 握手与双向流量透传。
+async fn forward_websocket(
+    mut request: Request<Incoming>,
+    client: UpstreamClient,
+    context: Arc<ProxyContext>,
+    trace: &mut RequestTrace,
+) -> ProxyResponse {
+    let on_client_upgrade = hyper::upgrade::on(&mut request);
+
+    let upstream_started = Instant::now();
+    let result = client
+        .request(request.map(|body| Either::Left(CaptureBody::new(body, None))))
+        .await;
+    trace.upstream_latency_ms = Some(upstream_started.elapsed().as_millis() as u64);
+
+    match result {
+        Ok(mut response) => {
+            let on_upstream_upgrade = if response.status() == StatusCode::SWITCHING_PROTOCOLS {
+                Some(hyper::upgrade::on(&mut response))
+            } else {
+                None
+            };
+
+            let response_to_client = response.map(|body| Either::Left(PrefixedBody::new(body)));
+            context.total_requests.fetch_add(1, Ordering::Relaxed);
+
+            if let Some(on_upstream_upgrade) = on_upstream_upgrade {
+                let route_id = trace.route_id.clone();
+                let throttle = trace.throttle;
+                // 隧道脱离了原连接的服务任务，单独计入在途连接，停止代理时超过排空时间即断开。
+                let guard = context.connections.enter();
+                let mut signal = context.connection_signal.clone();
+                tauri::async_runtime::spawn(async move {
+                    let _guard = guard;
+                    let upgraded = tokio::select! {
+                        upgraded = async { tokio::try_join!(on_client_upgrade, on_upstream_upgrade) } => upgraded,
+                        _ = wait_for_abort(&mut signal) => return,
+                    };
+                    match upgraded {
+                        Ok((client_upgraded, upstream_upgraded)) => {
+                            let stats = context.websockets.clone();
+                            let mut client_io = CountingIo {
+                                inner: TokioIo::new(client_upgraded),
+                                stats: stats.clone(),
+                                inbound: true,
+                                traffic: context.traffic.bytes_in.clone(),
+                                transferred: 0,
+                                throttle: throttle.upload_bytes_per_sec.map(Throttle::new),
+                            };
+                            let mut upstream_io = CountingIo {
+                                inner: TokioIo::new(upstream_upgraded),
+                                stats: stats.clone(),
+                                inbound: false,
+                                traffic: context.traffic.bytes_out.clone(),
+                                transferred: 0,
+                                throttle: throttle.download_bytes_per_sec.map(Throttle::new),
+                            };
+
+                            let opened = Instant::now();
+                            stats.active.fetch_add(1, Ordering::Relaxed);
+                            emit_websocket_event(&context.app, "open", &route_id, opened, 0, 0);
+
+                            tokio::select! {
+                                _ = copy_bidirectional(&mut client_io, &mut upstream_io) => {}
+                                _ = wait_for_abort(&mut signal) => {}
+                            }
+
+                            stats.active.fetch_sub(1, Ordering::Relaxed);
+                            emit_websocket_event(
+                                &context.app,
+                                "close",
+                                &route_id,
+                                opened,
+                                client_io.transferred,
+                                upstream_io.transferred,
+                            );
+                        }
+                        Err(err) => {
+                            set_runtime_error(
+                                &context.snapshot,
+                                format!("WebSocket 升级失败: {}", err),
+                            );
+                        }
+                    }
+                });
+            }
+
+            response_to_client
+        }
+        Err(err) => {
+            context
+                .outcomes
+                .record_upstream_error(classify_upstream_error(&err));
+            let message = format!("WebSocket 握手转发失败: {}", err);
+            trace.error = Some(message.clone());
+            set_runtime_error(&context.snapshot, message);
+            error_response(
+                &context.error_pages,
+                StatusCode::BAD_GATEWAY,
+                &format!("WebSocket 上游连接失败: {}", err),
+                trace.route_id.as_deref(),
+            )
+        }
+    }
+}
+
+/// 等待停止代理时的强制断开信号；信号源已释放也视为断开。
+async fn wait_for_abort(signal: &mut watch::Receiver<ConnectionSignal>) {
+    loop {
+        if *signal.borrow_and_update() == ConnectionSignal::Abort {
+            return;
+        }
+        if signal.changed().await.is_err() {
+            return;
+        }
+    }
+}
+
+/// 推送 WebSocket 隧道事件。
+fn emit_websocket_event(
+    app: &AppHandle,
+    event: &'static str,
+    route_id: &Option<String>,
+    opened: Instant,
+    bytes_in: u64,
+    bytes_out: u64,
+) {
+    let _ = app.emit(
+        PROXY_WS_EVENT,
+        WebSocketEventPayload {
+            event,
+            route_id: route_id.clone(),
+            duration_ms: opened.elapsed().as_millis() as u64,
+            bytes_in,
+            bytes_out,
+        },
+    );
+}
+
+/// 创建上游客户端集合。
+fn create_https_clients(routes: &[ProxyRoute]) -> Result<ProxyClients, String> {
+    let secure = create_secure_https_client()?;
+    let insecure = create_insecure_https_client()?;
+
+    let mut profiles = HashMap::new();
+    for profile in routes
+        .iter()
+        .filter(|route| route.target_scheme == TargetScheme::Https)
+        .filter_map(|route| route.tls_profile.as_ref())
+    {
+        if !profiles.contains_key(profile) {
+            profiles.insert(profile.clone(), create_profile_https_client(profile)?);
+        }
+    }
+
+    let mut local_sockets = HashMap::new();
+    for path in routes
+        .iter()
+        .flat_map(ProxyRoute::upstream_routes)
+        .filter_map(|route| route.target_socket.as_ref())
+    {
+        local_sockets
+            .entry(path.clone())
+            .or_insert_with(|| local_socket_client(path));
+    }
+
+    Ok(ProxyClients {
+        secure,
+        insecure,
+        profiles,
+        local_sockets,
+    })
+}
+
+/// 从 PEM 文件加载 CA 证书，构建只信任这些证书的根证书库。
+fn load_ca_root_store(path: &str) -> Result<RootCertStore, String> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .map_err(|err| format!("读取 CA 证书失败 {}: {}", path, err))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("CA 证书格式非法 {}: {}", path, err))?;
+    if certs.is_empty() {
+        return Err(format!("CA 证书文件中没有可用证书: {}", path));
+    }
+
+    let mut roots = RootCertStore::empty();
+    for cert in certs {
+        roots
+            .add(cert)
+            .map_err(|err| format!("CA 证书无效 {}: {}", path, err))?;
+    }
+    Ok(roots)
+}
+
+/// 加载系统信任根证书。
+fn load_native_root_store() -> Result<RootCertStore, String> {
+    let mut roots = RootCertStore::empty();
+    roots.add_parsable_certificates(rustls_native_certs::load_native_certs().certs);
+    if roots.is_empty() {
+        return Err("加载系统证书失败：未找到可用的根证书".to_string());
+    }
+    Ok(roots)
+}
+
+/// 从 PEM 文件加载 mTLS 客户端证书链与私钥。
+fn load_client_identity(
+    cert_path: &str,
+    key_path: &str,
+) -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>), String> {
+    let chain = CertificateDer::pem_file_iter(cert_path)
+        .map_err(|err| format!("读取客户端证书失败 {}: {}", cert_path, err))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("客户端证书格式非法 {}: {}", cert_path, err))?;
+    if chain.is_empty() {
+        return Err(format!("客户端证书文件中没有可用证书: {}", cert_path));
+    }
+
+    let key = PrivateKeyDer::from_pem_file(key_path).map_err(|err| {
+        format!(
+            "读取客户端私钥失败 {}: {}（暂不支持带密码的私钥）",
+            key_path, err
+        )
+    })?;
+
+    Ok((chain, key))
+}
+
+/// 按 TLS 定制参数创建客户端：
+/// - 设置了 CA 时只信任该 CA（而不是关闭校验）；
+/// - 否则按 `insecure` 选择跳过校验或使用系统根证书；
+/// - 配置了客户端证书时在握手中出示证书（可与跳过校验组合）；
+/// - 配置了 SNI 覆盖时握手与证书校验使用该主机名（跳过校验时同样发送）。
+fn create_profile_https_client(profile: &TlsProfile) -> Result<HttpsClient, String> {
+    let builder = match (&profile.ca_cert_path, profile.insecure) {
+        (Some(path), _) => {
+            ClientConfig::builder().with_root_certificates(load_ca_root_store(path)?)
+        }
+        (None, true) => ClientConfig::builder()
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(InsecureTlsVerifier {
+                provider: default_crypto_provider()?,
+            })),
+        (None, false) => ClientConfig::builder().with_root_certificates(load_native_root_store()?),
+    };
+
+    let tls_config = match &profile.client_cert {
+        Some((cert_path, key_path)) => {
+            let (chain, key) = load_client_identity(cert_path, key_path)?;
+            builder
+                .with_client_auth_cert(chain, key)
+                .map_err(|err| format!("客户端证书或私钥无效 {}: {}", key_path, err))?
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let server_name = profile
+        .server_name
+        .as_ref()
+        .map(|name| {
+            ServerName::try_from(name.clone()).map_err(|_| format!("SNI 主机名非法: {}", name))
+        })
+        .transpose()?;
+
+    Ok(https_client_with_tls_config(tls_config, server_name))
+}
+
+/// 基于给定 TLS 配置构建上游客户端；`server_name` 用于固定握手的 SNI。
+fn https_client_with_tls_config(
+    tls_config: ClientConfig,
+    server_name: Option<ServerName<'static>>,
+) -> HttpsClient {
+    let builder = HttpsConnectorBuilder::new()
+        .with_tls_config(tls_config)
+        .https_or_http();
+    let builder = match server_name {
+        Some(name) => builder.with_server_name_resolver(FixedServerNameResolver::new(name)),
+        None => builder,
+    };
+    let https_connector = builder.enable_http1().build();
+
+    Client::builder(TokioExecutor::new()).build(TimedConnector {
+        inner: https_connector,
+    })
+}
+
+/// 创建默认安全客户端（使用系统信任根证书）。
+fn create_secure_https_client() -> Result<HttpsClient, String> {
+    let https_connector = HttpsConnectorBuilder::new()
+        .with_native_roots()
+        .map_err(|err| format!("加载系统证书失败: {}", err))?
+        .https_or_http()
+        .enable_http1()
+        .build();
+
+    Ok(Client::builder(TokioExecutor::new()).build(TimedConnector {
+        inner: https_connector,
+    }))
+}
+
+/// 获取 rustls 默认 crypto provider。
+///
+/// 说明：这里先调用一次 `ClientConfig::builder()`，用于确保 rustls 的默认
+/// crypto provider 已初始化，然后再读取 provider 构建自定义 verifier。
+fn default_crypto_provider() -> Result<Arc<CryptoProvider>, String> {
+    let _ = ClientConfig::builder();
+    CryptoProvider::get_default()
+        .cloned()
+        .ok_or_else(|| "TLS 加密提供方初始化失败".to_string())
+}
+
+/// 创建“不安全 TLS”客户端。
+fn create_insecure_https_client() -> Result<HttpsClient, String> {
+    let tls_config = ClientConfig::builder()
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(InsecureTlsVerifier {
+            provider: default_crypto_provider()?,
+        }))
+        .with_no_client_auth();
+
+    Ok(https_client_with_tls_config(tls_config, None))
+}
+
+/// 写入代理转发相关请求头。
+///
+/// 关键行为：
+/// - 清理 hop-by-hop 头，并移除当前 `forwarded` 策略下代理不负责写入的转发类请求头；
+/// - 重写 `Host` 为目标上游（或路由配置的覆盖值），路由要求保留时不改动；
+/// - 按 `forwarded` 策略追加 `X-Forwarded-For`，写入 `X-Forwarded-Host` 与
+///   `X-Forwarded-Proto`，以及覆盖式的 `X-Real-IP` 和追加式的 `Forwarded`；
+/// - 最后应用路由自定义的请求头移除与注入规则。
+fn apply_proxy_headers(
+    headers: &mut HeaderMap<HeaderValue>,
+    route: &ProxyRoute,
+    peer: std::net::SocketAddr,
+    original_host: &str,
+    keep_upgrade: bool,
+    forwarded: ForwardedHeaders,
+) -> Result<(), String> {
+    sanitize_hop_headers(headers, keep_upgrade);
+    for name in FORWARDED_REQUEST_HEADERS {
+        if !forwarded.writes(name) {
+            headers.remove(name);
+        }
+    }
+
+    if !route.preserve_host_header {
+        let host_value = match &route.host_header {
+            Some(value) => value.clone(),
+            None => HeaderValue::from_str(&route.target_authority())
+                .map_err(|_| "目标主机格式非法，无法写入 Host 头".to_string())?,
+        };
+        headers.insert(header::HOST, host_value);
+    }
+
+    if forwarded != ForwardedHeaders::None {
+        append_x_forwarded_for(headers, peer)?;
+
+        let x_forwarded_host = HeaderName::from_static("x-forwarded-host");
+        if route.preserve_host_header {
+            // Host 已是原始值，不再重复写入（同时丢弃客户端伪造的值）。
+            headers.remove(&x_forwarded_host);
+        } else if !original_host.is_empty() {
+            headers.insert(
+                x_forwarded_host,
+                HeaderValue::from_str(original_host)
+                    .map_err(|_| "原始 Host 非法，无法写入 X-Forwarded-Host".to_string())?,
+            );
+        }
+
+        headers.insert(
+            HeaderName::from_static("x-forwarded-proto"),
+            HeaderValue::from_static("http"),
+        );
+    }
+
+    if forwarded == ForwardedHeaders::All {
+        headers.insert(
+            HeaderName::from_static("x-real-ip"),
+            HeaderValue::from_str(&peer.ip().to_string())
+                .map_err(|_| "X-Real-IP 构建失败，IP 字段非法".to_string())?,
+        );
+        append_forwarded(headers, peer, original_host)?;
+    }
+
+    if route.html_rewrite_max_bytes.is_some() {
+        // 需要改写 HTML 时请求上游返回未压缩内容。
+        headers.insert(
+            header::ACCEPT_ENCODING,
+            HeaderValue::from_static("identity"),
+        );
+    }
+
+    for name in &route.remove_request_headers {
+        headers.remove(name);
+    }
+    for (name, value) in &route.set_request_headers {
+        headers.insert(name.clone(), value.clone());
+    }
+
+    Ok(())
+}
+
+/// 生成 CORS 响应头所需的请求信息（在请求被转发前提取）。
+#[derive(Default)]
+struct CorsRequest {
+    origin: Option<HeaderValue>,
+    request_headers: Option<HeaderValue>,
+    request_method: Option<HeaderValue>,
+}
+
+impl CorsRequest {
+    fn from_headers(headers: &HeaderMap<HeaderValue>) -> Self {
+        Self {
+            origin: headers.get(header::ORIGIN).cloned(),
+            request_headers: headers.get(header::ACCESS_CONTROL_REQUEST_HEADERS).cloned(),
+            request_method: headers.get(header::ACCESS_CONTROL_REQUEST_METHOD).cloned(),
+        }
+    }
+}
+
+/// 判断请求是否为 CORS 预检请求。
+fn is_cors_preflight(request: &Request<Incoming>) -> bool {
+    request.method() == http::Method::OPTIONS
+        && request
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD)
+}
+
+/// 改写返回给客户端的响应头：
+/// - 先移除、再写入路由自定义的响应头；
+/// - 开启 CORS 时注入跨域相关头（回显请求 Origin）。
+fn apply_response_headers(
+    headers: &mut HeaderMap<HeaderValue>,
+    route: &ProxyRoute,
+    cors_request: &CorsRequest,
+    preflight: bool,
+) {
+    for name in &route.remove_response_headers {
+        headers.remove(name);
+    }
+    for (name, value) in &route.set_response_headers {
+        headers.insert(name.clone(), value.clone());
+    }
+
+    if !route.cors {
+        return;
+    }
+
+    match &cors_request.origin {
+        Some(origin) => {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin.clone());
+            if route.cors_allow_credentials {
+                headers.insert(
+                    header::ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                    HeaderValue::from_static("true"),
+                );
+            }
+            headers.append(header::VARY, HeaderValue::from_static("Origin"));
+        }
+        None => {
+            headers.insert(
+                header::ACCESS_CONTROL_ALLOW_ORIGIN,
+                HeaderValue::from_static("*"),
+            );
+        }
+    }
+
+    headers.insert(
+        header::ACCESS_CONTROL_ALLOW_HEADERS,
+        cors_request
+            .request_headers
+            .clone()
+            .unwrap_or_else(|| HeaderValue::from_static("*")),
+    );
+
+    if preflight {
+        headers.insert(
+            header::ACCESS_CONTROL_ALLOW_METHODS,
+            cors_request.request_method.clone().unwrap_or_else(|| {
+                HeaderValue::from_static("GET, POST, PUT, PATCH, DELETE, OPTIONS")
+            }),
+        );
+        headers.insert(
+            header::ACCESS_CONTROL_MAX_AGE,
+            HeaderValue::from_static("86400"),
+        );
+    }
+}
+
+/// 清理 hop-by-hop 头，避免这些头被错误地转发到上游。
+fn sanitize_hop_headers(headers: &mut HeaderMap<HeaderValue>, keep_upgrade: bool) {
+    let connection_tokens = headers
+        .get(header::CONNECTION)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string())
+        .unwrap_or_default();
+
+    for token in connection_tokens.split(',') {
+        let item = token.trim().to_ascii_lowercase();
+        if item.is_empty() || (keep_upgrade && item == "upgrade") {
+            continue;
+        }
+        if let Ok(name) = HeaderName::from_bytes(item.as_bytes()) {
+            headers.remove(name);
+        }
+    }
+
+    for name in [
+        header::CONNECTION,
+        header::PROXY_AUTHENTICATE,
+        header::PROXY_AUTHORIZATION,
+        header::TE,
+        header::TRAILER,
+        header::TRANSFER_ENCODING,
+    ] {
+        headers.remove(name);
+    }
+
+    headers.remove(HeaderName::from_static("proxy-connection"));
+    headers.remove(HeaderName::from_static("keep-alive"));
+
+    if keep_upgrade {
+        headers.insert(header::CONNECTION, HeaderValue::from_static("upgrade"));
+    } else {
+        headers.remove(header::UPGRADE);
+    }
+}
+
+/// 代理自身的访问地址；监听在通配地址时退化为本机回环地址。
+fn proxy_authority(listen_host: &str, port: u16) -> String {
+    let host = match listen_host {
+        "0.0.0.0" => "127.0.0.1",
+        "::" | "[::]" => "[::1]",
+        host => host,
+    };
+    if host.contains(':') && !host.starts_with('[') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+/// 改写指向路由目标的 `Location` 头，返回（改写前、改写后）用于访问日志。
+fn rewrite_redirect_location(
+    headers: &mut HeaderMap<HeaderValue>,
+    route: &ProxyRoute,
+    proxy_authority: &str,
+) -> Option<(String, String)> {
+    let original = headers.get(header::LOCATION)?.to_str().ok()?.to_string();
+    let rewritten = rewrite_location(&original, route, proxy_authority)?;
+    headers.insert(header::LOCATION, HeaderValue::from_str(&rewritten).ok()?);
+    Some((original, rewritten))
+}
+
+/// 把指向路由目标的绝对或协议相对地址改写为代理地址，并还原被剥离的路由前缀。
+///
+/// 相对地址、其他主机以及目标路径前缀之外的地址返回 `None`（保持不变）。
+fn rewrite_location(location: &str, route: &ProxyRoute, proxy_authority: &str) -> Option<String> {
+    let (scheme, rest) = if let Some(rest) = location.strip_prefix("//") {
+        (None, rest)
+    } else {
+        let (scheme, rest) = location.split_once("://")?;
+        let scheme = if scheme.eq_ignore_ascii_case("http") {
+            TargetScheme::Http
+        } else if scheme.eq_ignore_ascii_case("https") {
+            TargetScheme::Https
+        } else {
+            return None;
+        };
+        (Some(scheme), rest)
+    };
+
+    let authority_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let (authority, tail) = rest.split_at(authority_end);
+    let authority = authority.rsplit('@').next().unwrap_or(authority);
+    let (host, port) = match authority.strip_prefix('[') {
+        Some(bracketed) => {
+            let (host, after) = bracketed.split_once(']')?;
+            (host, after.strip_prefix(':'))
+        }
+        None => match authority.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        },
+    };
+    let port = match port {
+        Some(port) => port.parse::<u16>().ok()?,
+        None => scheme.unwrap_or(route.target_scheme).default_port(),
+    };
+    let target_host = route
+        .target_host
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    if !host.eq_ignore_ascii_case(target_host) || port != route.target_port {
+        return None;
+    }
+
+    let path_end = tail.find(['?', '#']).unwrap_or(tail.len());
+    let (path, suffix) = tail.split_at(path_end);
+    let base = route.target_base_path.as_str();
+    let upstream_path = if base.is_empty() {
+        path
+    } else if path == base {
+        ""
+    } else {
+        path.strip_prefix(base)
+            .filter(|rest| rest.starts_with('/'))?
+    };
+
+    let mut external = if route.strip_prefix && route.path_prefix != "/" {
+        format!("{}{}", route.path_prefix, upstream_path)
+    } else {
+        upstream_path.to_string()
+    };
+    if external.is_empty() {
+        external.push('/');
+    }
+
+    let scheme_prefix = if scheme.is_some() { "http://" } else { "//" };
+    Some(format!(
+        "{}{}{}{}",
+        scheme_prefix, proxy_authority, external, suffix
+    ))
+}
+
+/// 改写 HTML 响应中的上游地址。
+///
+/// 仅处理不超过上限的 `text/html` 响应（未编码或 gzip）；分块传输的响应边读边检查上限，
+/// 超限或其他编码的响应原样透传。
+async fn rewrite_html_response(
+    response: ProxyResponse,
+    route: &ProxyRoute,
+    proxy_authority: &str,
+    max_bytes: usize,
+) -> ProxyResponse {
+    let headers = response.headers();
+    let is_html = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.trim().to_ascii_lowercase().starts_with("text/html"));
+    let encoding = headers
+        .get(header::CONTENT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().to_ascii_lowercase());
+    let gzipped = match encoding.as_deref() {
+        None | Some("") | Some("identity") => false,
+        Some("gzip") => true,
+        Some(_) => return response,
+    };
+    let upper = response.body().size_hint().upper();
+    if !is_html || upper.is_some_and(|upper| upper > max_bytes as u64) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let collected = match body {
+        Either::Left(mut stream) if upper.is_none() => {
+            match buffer_limited(&mut stream, max_bytes).await {
+                Ok(Some(raw)) => Ok(raw),
+                Ok(None) => return Response::from_parts(parts, Either::Left(stream)),
+                Err(err) => Err(err.to_string()),
+            }
+        }
+        body => body
+            .collect()
+            .await
+            .map(|collected| collected.to_bytes())
+            .map_err(|err| err.to_string()),
+    };
+    let raw = match collected {
+        Ok(raw) => raw,
+        Err(err) => {
+            return plain_response(
+                StatusCode::BAD_GATEWAY,
+                &format!("读取上游响应失败: {}", err),
+            )
+        }
+    };
+
+    let decoded = if gzipped {
+        use std::io::Read;
+
+        let mut decoded = Vec::new();
+        let mut limited = flate2::read::GzDecoder::new(raw.as_ref()).take(max_bytes as u64 + 1);
+        match limited.read_to_end(&mut decoded) {
+            Ok(_) if decoded.len() <= max_bytes => decoded,
+            // 解压失败或解压后超限时保持原样。
+            _ => return Response::from_parts(parts, Either::Right(Full::new(raw))),
+        }
+    } else {
+        raw.to_vec()
+    };
+
+    let body = match String::from_utf8(decoded) {
+        Ok(html) => Bytes::from(rewrite_html_urls(&html, route, proxy_authority)),
+        Err(err) if gzipped => Bytes::from(err.into_bytes()),
+        Err(_) => raw,
+    };
+    parts.headers.remove(header::CONTENT_ENCODING);
+    parts
+        .headers
+        .insert(header::CONTENT_LENGTH, HeaderValue::from(body.len()));
+    Response::from_parts(parts, Either::Right(Full::new(body)))
+}
+
+/// 把 HTML 中指向路由目标的绝对地址（含协议相对形式）替换为代理地址，
+/// 并按路由前缀映射还原路径；目标路径前缀之外的地址保持不变。
+fn rewrite_html_urls(html: &str, route: &ProxyRoute, proxy_authority: &str) -> String {
+    fn is_name_byte(byte: u8) -> bool {
+        byte.is_ascii_alphanumeric() || matches!(byte, b'.' | b'-' | b'_')
+    }
+
+    let lower = html.to_ascii_lowercase();
+    let needle = format!("//{}", route.target_host.to_ascii_lowercase());
+    // 与 `lower` 比较，基础路径同样转成小写。
+    let base = route.target_base_path.to_ascii_lowercase();
+    let external_prefix = if route.strip_prefix && route.path_prefix != "/" {
+        route.path_prefix.as_str()
+    } else {
+        ""
+    };
+
+    let mut output = String::with_capacity(html.len());
+    let mut copied = 0;
+    let mut search = 0;
+    while let Some(found) = lower[search..].find(&needle) {
+        let start = search + found;
+        let mut end = start + needle.len();
+        search = start + 2;
+
+        let bytes = lower.as_bytes();
+        let port = if bytes.get(end) == Some(&b':') {
+            let digits = lower[end + 1..]
+                .bytes()
+                .take_while(u8::is_ascii_digit)
+                .count();
+            let port = lower[end + 1..end + 1 + digits].parse::<u16>().ok();
+            end += 1 + digits;
+            match port {
+                Some(port) => port,
+                None => continue,
+            }
+        } else {
+            route.target_scheme.default_port()
+        };
+        if port != route.target_port || bytes.get(end).copied().is_some_and(is_name_byte) {
+            continue;
+        }
+
+        if !base.is_empty() {
+            let after_base = end + base.len();
+            if !lower[end..].starts_with(&base)
+                || bytes.get(after_base).copied().is_some_and(is_name_byte)
+            {
+                continue;
+            }
+            end = after_base;
+        }
+
+        let scheme_start = if lower[..start].ends_with("https:") {
+            start - 6
+        } else if lower[..start].ends_with("http:") {
+            start - 5
+        } else {
+            start
+        };
+        output.push_str(&html[copied..scheme_start]);
+        if scheme_start < start {
+            output.push_str("http:");
+        }
+        output.push_str("//");
+        output.push_str(proxy_authority);
+        output.push_str(external_prefix);
+        copied = end;
+        search = end;
+    }
+    output.push_str(&html[copied..]);
+    output
+}
+
+/// 改写全部 `Set-Cookie` 头：按需移除 Domain 属性与 Secure 标记，其余属性保持原样。
+///
+/// 不规范的上游可能把多个 Cookie 折叠进同一个头（以逗号分隔），这里会先拆开再逐个改写。
+fn rewrite_set_cookies(
+    headers: &mut HeaderMap<HeaderValue>,
+    remove_domain: bool,
+    remove_secure: bool,
+) {
+    let cookies: Vec<HeaderValue> = match headers.entry(header::SET_COOKIE) {
+        http::header::Entry::Occupied(entry) => entry.remove_entry_mult().1.collect(),
+        http::header::Entry::Vacant(_) => return,
+    };
+
+    for value in cookies {
+        let Ok(text) = value.to_str() else {
+            // 非 ASCII 的 Cookie 原样保留。
+            headers.append(header::SET_COOKIE, value);
+            continue;
+        };
+        for cookie in split_folded_set_cookie(text) {
+            let rewritten = rewrite_set_cookie(&cookie, remove_domain, remove_secure);
+            match HeaderValue::from_str(&rewritten) {
+                Ok(rewritten) => headers.append(header::SET_COOKIE, rewritten),
+                Err(_) => headers.append(header::SET_COOKIE, value.clone()),
+            };
+        }
+    }
+}
+
+/// 拆分折叠的 `Set-Cookie` 值；`Expires` 日期中的逗号不作为分隔符。
+fn split_folded_set_cookie(value: &str) -> Vec<String> {
+    let mut cookies: Vec<String> = Vec::new();
+    for part in value.split(',') {
+        let continues_expires = cookies.last().is_some_and(|current| {
+            let last_attribute = current.rsplit(';').next().unwrap_or("").trim();
+            last_attribute.len() >= 8
+                && last_attribute[..8].eq_ignore_ascii_case("expires=")
+                && !last_attribute.contains(',')
+        });
+        match cookies.last_mut() {
+            Some(current) if continues_expires => {
+                current.push(',');
+                current.push_str(part);
+            }
+            _ => cookies.push(part.to_string()),
+        }
+    }
+    cookies
+        .into_iter()
+        .map(|cookie| cookie.trim().to_string())
+        .filter(|cookie| !cookie.is_empty())
+        .collect()
+}
+
+/// 改写单个 Cookie：第一段 `name=value` 不动，按属性名（大小写不敏感）过滤。
+fn rewrite_set_cookie(cookie: &str, remove_domain: bool, remove_secure: bool) -> String {
+    let mut parts = cookie.split(';');
+    let mut rewritten = vec![parts.next().unwrap_or("").trim().to_string()];
+    for attribute in parts {
+        let attribute = attribute.trim();
+        if attribute.is_empty() {
+            continue;
+        }
+        let name = attribute.split('=').next().unwrap_or("").trim();
+        if (remove_domain && name.eq_ignore_ascii_case("domain"))
+            || (remove_secure && name.eq_ignore_ascii_case("secure"))
+        {
+            continue;
+        }
+        rewritten.push(attribute.to_string());
+    }
+    rewritten.join("; ")
+}
+
+/// 追加 `X-Forwarded-For` 链路信息。
+fn append_x_forwarded_for(
+    headers: &mut HeaderMap<HeaderValue>,
+    peer: std::net::SocketAddr,
+) -> Result<(), String> {
+    let xff_name = HeaderName::from_static("x-forwarded-for");
+    let current = headers
+        .get(&xff_name)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("")
+        .trim();
+
+    let next = if current.is_empty() {
+        peer.ip().to_string()
+    } else {
+        format!("{}, {}", current, peer.ip())
+    };
+
+    headers.insert(
+        xff_name,
+        HeaderValue::from_str(&next)
+            .map_err(|_| "X-Forwarded-For 构建失败，IP 字段非法".to_string())?,
+    );
+
+    Ok(())
+}
+
+/// 追加 RFC 7239 `Forwarded` 条目（`for=...;host=...;proto=http`）。
+fn append_forwarded(
+    headers: &mut HeaderMap<HeaderValue>,
+    peer: std::net::SocketAddr,
+    original_host: &str,
+) -> Result<(), String> {
+    let node = match peer.ip() {
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => format!("\"[{}]\"", ip),
+    };
+    let mut element = format!("for={}", node);
+    if !original_host.is_empty() {
+        element.push_str(";host=");
+        element.push_str(&forwarded_param_value(original_host));
+    }
+    element.push_str(";proto=http");
+
+    let current = headers
+        .get(header::FORWARDED)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("")
+        .trim();
+    let next = if current.is_empty() {
+        element
+    } else {
+        format!("{}, {}", current, element)
+    };
+
+    headers.insert(
+        header::FORWARDED,
+        HeaderValue::from_str(&next).map_err(|_| "Forwarded 头构建失败".to_string())?,
+    );
+
+    Ok(())
+}
+
+/// `Forwarded` 参数值：合法 token 原样输出，否则按 quoted-string 转义。
+fn forwarded_param_value(value: &str) -> String {
+    let is_token = !value.is_empty()
+        && value
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte));
+    if is_token {
+        return value.to_string();
+    }
+
+    let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("\"{}\"", escaped)
+}
+
+/// 判断请求是否为 WebSocket Upgrade。
+fn is_websocket_upgrade(request: &Request<Incoming>) -> bool {
+    let upgrade = request
+        .headers()
+        .get(header::UPGRADE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_ascii_lowercase())
+        .unwrap_or_default();
+
+    let connection = request
+        .headers()
+        .get(header::CONNECTION)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_ascii_lowercase())
+        .unwrap_or_default();
+
+    upgrade == "websocket" && connection.contains("upgrade")
+}
+
+/// 提取请求 Host（优先 `Host` 头，其次 URI authority）。
+fn extract_request_host<B>(request: &Request<B>) -> Option<String> {
+    if let Some(host) = request
+        .headers()
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok())
+    {
+        return normalize_host_value(host);
+    }
+
+    request
+        .uri()
+        .authority()
+        .and_then(|authority| normalize_host_value(authority.as_str()))
+}
+
+/// 构建转发后的上游 URI（包含路径和 query）；authority 为实际连接地址。
+fn build_upstream_uri(original_uri: &Uri, route: &ProxyRoute) -> Result<Uri, String> {
+    let path_and_query = rewrite_path_and_query(original_uri, route);
+    let uri_text = format!(
+        "{}://{}{}",
+        route.target_scheme.as_str(),
+        route.connect_authority(),
+        path_and_query
+    );
+
+    uri_text
+        .parse::<Uri>()
+        .map_err(|err| format!("构建上游地址失败: {}", err))
+}
+
+/// 基于路由策略重写 path 和 query。
+///
+/// 先按需剥离匹配前缀，再拼接目标路径前缀；剥离后的路径总以 `/` 开头，
+/// 而目标路径前缀不带尾部 `/`，因此拼接时不会出现 `//`。
+fn rewrite_path_and_query(uri: &Uri, route: &ProxyRoute) -> String {
+    let path = uri.path();
+    let mut rewritten_path = path.to_string();
+
+    if route.strip_prefix && route.path_prefix != "/" {
+        if let Some(rest) = path.strip_prefix(&route.path_prefix) {
+            rewritten_path = if rest.is_empty() {
+                "/".to_string()
+            } else if rest.starts_with('/') {
+                rest.to_string()
+            } else {
+                format!("/{}", rest)
+            };
+        }
+    }
+
+    if !route.target_base_path.is_empty() {
+        rewritten_path = if rewritten_path == "/" && !path.ends_with('/') {
+            route.target_base_path.clone()
+        } else {
+            format!("{}{}", route.target_base_path, rewritten_path)
+        };
+    }
+
+    if let Some(query) = uri.query() {
+        if !query.is_empty() {
+            return format!("{}?{}", rewritten_path, query);
+        }
+    }
+
+    rewritten_path
+}
+
+/// 构建并排序路由（停用的路由同样保留，匹配时跳过）。
+///
+/// 排序策略：
+/// 1. 路径前缀长度降序（最长前缀优先）；
+/// 2. 前缀相同则 Host 精确匹配优先于通配。
+fn build_routes(inputs: &[ProxyRouteInput]) -> Result<Vec<ProxyRoute>, String> {
+    let (routes, rejected) = build_route_table(inputs)?;
+    match rejected.into_iter().next() {
+        Some(route) => Err(route.error),
+        None => Ok(routes),
+    }
+}
+
+/// 启动时未能加载的停用路由及其校验错误。
+struct RejectedRoute {
+    id: String,
+    error: String,
+}
+
+/// 构建代理启动使用的路由表。
+///
+/// 启用路由配置非法时直接返回错误；停用路由配置非法时不阻止启动，
+/// 而是记入 `RejectedRoute`，等到有人启用该路由时再报告。
+fn build_route_table(
+    inputs: &[ProxyRouteInput],
+) -> Result<(Vec<ProxyRoute>, Vec<RejectedRoute>), String> {
+    let mut routes = Vec::new();
+    let mut rejected = Vec::new();
+
+    for (index, item) in inputs.iter().enumerate() {
+        match build_route(index, item) {
+            Ok(route) => routes.push(route),
+            Err(error) if !item.enabled => rejected.push(RejectedRoute {
+                id: route_input_id(index, item),
+                error,
+            }),
+            Err(error) => return Err(error),
+        }
+    }
+
+    routes.sort_by(|left, right| {
+        right
+            .path_prefix
+            .len()
+            .cmp(&left.path_prefix.len())
+            .then_with(|| {
+                (right.match_mode == MatchMode::Exact).cmp(&(left.match_mode == MatchMode::Exact))
+            })
+            .then_with(|| right.host.is_some().cmp(&left.host.is_some()))
+            .then_with(|| right.query_match.len().cmp(&left.query_match.len()))
+    });
+    Ok((routes, rejected))
+}
+
+/// 路由 ID：未填写时按配置顺序生成 `route-N`。
+fn route_input_id(index: usize, item: &ProxyRouteInput) -> String {
+    match item.id.trim() {
+        "" => format!("route-{}", index + 1),
+        value => value.to_string(),
+    }
+}
+
+/// 校验并构建单条路由。
+fn build_route(index: usize, item: &ProxyRouteInput) -> Result<ProxyRoute, String> {
+    let path_prefix = normalize_path_prefix(&item.path_prefix);
+    let host = normalize_host_value(&item.host);
+    let mock = match item.route_type {
+        RouteType::Proxy => None,
+        RouteType::Mock => Some(Arc::new(parse_mock_response(item)?)),
+    };
+    let target = match mock {
+        // 模拟路由不访问上游，目标地址仅作占位。
+        Some(_) => ParsedTarget {
+            scheme: TargetScheme::Http,
+            host: String::new(),
+            port: TargetScheme::Http.default_port(),
+            base_path: String::new(),
+            socket_path: None,
+        },
+        // 配置了分流目标时，以第一个目标作为路由的主目标（健康检查、试匹配展示等）。
+        None => match item.targets.first() {
+            Some(first) => parse_target(&first.target)?,
+            None => parse_target(&item.target)?,
+        },
+    };
+    let set_request_headers = parse_set_request_headers(&item.set_request_headers)?;
+    let remove_request_headers = parse_remove_request_headers(&item.remove_request_headers)?;
+    let set_response_headers = parse_set_response_headers(&item.set_response_headers)?;
+    let remove_response_headers = parse_header_names(&item.remove_response_headers)?;
+    let basic_auth = parse_basic_auth(
+        item.basic_auth_user.as_deref(),
+        item.basic_auth_password.as_deref(),
+    )?;
+    let resolve_to = parse_resolve_to(item.resolve_to.as_deref())?;
+    let host_header = parse_host_header(item.host_header.as_deref())?;
+    let (tls_profile, fallback_target, health_check) = if mock.is_some() {
+        (None, None, None)
+    } else {
+        (
+            parse_tls_profile(item, &target, resolve_to.is_some())?,
+            non_empty_trimmed(item.fallback_target.as_deref())
+                .map(|raw| parse_target(&raw))
+                .transpose()
+                .map_err(|err| format!("备用目标地址非法: {}", err))?,
+            parse_health_check(
+                item.health_check_path.as_deref(),
+                item.health_check_interval_secs,
+            )?,
+        )
+    };
+    if item.preserve_host_header && host_header.is_some() {
+        return Err("保留原始 Host 与 Host 头覆盖不能同时设置".to_string());
+    }
+
+    let mut route = ProxyRoute {
+        id: route_input_id(index, item),
+        name: item.name.trim().to_string(),
+        enabled: Arc::new(AtomicBool::new(item.enabled)),
+        host,
+        path_prefix,
+        match_mode: item.match_mode,
+        query_match: parse_query_match(item.query_match.as_deref())?,
+        target_scheme: target.scheme,
+        target_host: target.host,
+        target_port: target.port,
+        target_base_path: target.base_path,
+        target_socket: target.socket_path,
+        strip_prefix: item.strip_prefix,
+        allow_insecure_tls: item.allow_insecure_tls,
+        set_request_headers,
+        remove_request_headers,
+        set_response_headers,
+        remove_response_headers,
+        cors: item.cors,
+        cors_allow_credentials: item.cors_allow_credentials,
+        retries: parse_retries(item.retries)?,
+        basic_auth,
+        tls_profile,
+        host_header,
+        preserve_host_header: item.preserve_host_header,
+        resolve_to,
+        health_check,
+        fallback: None,
+        mock,
+        cache: parse_cache_policy(item)?,
+        capture_max_bytes: parse_capture_limit(item)?,
+        request_id_header: parse_request_id_header(item.request_id_header.as_deref())?,
+        rewrite_cookie_domain: item.rewrite_cookie_domain,
+        strip_cookie_secure: item.strip_cookie_secure,
+        rewrite_redirects: item.rewrite_redirects,
+        html_rewrite_max_bytes: parse_html_rewrite_limit(item)?,
+        throttle: parse_route_throttle(item)?,
+        targets: Arc::default(),
+        sticky_sessions: item.sticky_sessions,
+        sticky_header: non_empty_trimmed(item.sticky_header.as_deref())
+            .map(|name| {
+                HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|_| format!("粘性分流请求头名称非法: {}", name))
+            })
+            .transpose()?,
+        stats: Arc::new(RouteStats::default()),
+    };
+    route.fallback = fallback_target.map(|target| Box::new(route.with_target(target)));
+    if route.mock.is_none() && !item.targets.is_empty() {
+        route.targets = Arc::new(parse_weighted_targets(&route, &item.targets)?);
+    }
+    Ok(route)
+}
+
+/// 解析分流目标，至少需要一个权重大于 0 的目标。
+fn parse_weighted_targets(
+    route: &ProxyRoute,
+    inputs: &[WeightedTargetInput],
+) -> Result<Vec<WeightedTarget>, String> {
+    let targets = inputs
+        .iter()
+        .map(|input| {
+            let target = parse_target(&input.target)
+                .map_err(|err| format!("分流目标地址非法 {}: {}", input.target.trim(), err))?;
+            Ok(WeightedTarget {
+                label: input.target.trim().to_string(),
+                sticky_key: sticky_target_key(input.target.trim()),
+                weight: input.weight,
+                route: route.with_weighted_target(target),
+                stats: Arc::new(TargetStats::default()),
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    if targets.iter().all(|target| target.weight == 0) {
+        return Err("分流目标至少需要一个权重大于 0".to_string());
+    }
+    Ok(targets)
+}
+
+/// 按累计权重选出 `point`（`0..总权重`）落入的目标，权重为 0 的目标永远不会被选中。
+fn pick_weighted(targets: &[WeightedTarget], point: u64) -> Option<(usize, &WeightedTarget)> {
+    let mut cumulative = 0;
+    targets.iter().enumerate().find(|(_, target)| {
+        cumulative += target.weight as u64;
+        point < cumulative
+    })
+}
+
+/// 分流目标的粘性标识：目标地址 SHA-256 的前 8 字节（十六进制）。
+fn sticky_target_key(target: &str) -> String {
+    Sha256::digest(target.as_bytes())[..8]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// 取出并移除请求中的粘性会话 Cookie，返回其中记录的目标标识。
+///
+/// 无论标识是否有效都会移除该 Cookie，避免泄露给上游。
+fn take_sticky_cookie(headers: &mut HeaderMap<HeaderValue>) -> Option<String> {
+    let mut key = None;
+    let mut kept = Vec::new();
+    for value in headers.get_all(header::COOKIE) {
+        let Ok(text) = value.to_str() else {
+            kept.push(value.clone());
+            continue;
+        };
+        let mut found = false;
+        let mut remaining = Vec::new();
+        for pair in text
+            .split(';')
+            .map(str::trim)
+            .filter(|pair| !pair.is_empty())
+        {
+            match pair.split_once('=') {
+                Some((name, value)) if name.trim() == STICKY_COOKIE_NAME => {
+                    found = true;
+                    key = Some(value.trim().to_string()).filter(|value| !value.is_empty());
+                }
+                _ => remaining.push(pair),
+            }
+        }
+        if !found {
+            kept.push(value.clone());
+        } else if !remaining.is_empty() {
+            if let Ok(value) = HeaderValue::from_str(&remaining.join("; ")) {
+                kept.push(value);
+            }
+        }
+    }
+    headers.remove(header::COOKIE);
+    for value in kept {
+        headers.append(header::COOKIE, value);
+    }
+    key
+}
+
+/// 粘性会话 Cookie，作用域限定在路由路径前缀。
+fn sticky_cookie_value(key: &str, path_prefix: &str) -> Option<HeaderValue> {
+    HeaderValue::from_str(&format!(
+        "{}={}; Path={}; HttpOnly; SameSite=Lax",
+        STICKY_COOKIE_NAME, key, path_prefix
+    ))
+    .ok()
+}
+
+/// 校验查询参数条件：参数名去除首尾空白后不能为空。
+fn parse_query_match(
+    raw: Option<&[(String, Option<String>)]>,
+) -> Result<Vec<(String, Option<String>)>, String> {
+    raw.unwrap_or_default()
+        .iter()
+        .map(|(name, value)| {
+            let name = name.trim();
+            if name.is_empty() {
+                return Err("查询参数条件的参数名不能为空".to_string());
+            }
+            Ok((name.to_string(), value.clone()))
+        })
+        .collect()
+}
+
+/// 由代理自身维护的 hop-by-hop 头，不允许通过路由规则注入。
+fn is_reserved_hop_header(name: &HeaderName) -> bool {
+    *name == header::CONNECTION
+        || *name == header::UPGRADE
+        || *name == header::TE
+        || *name == header::TRAILER
+        || *name == header::TRANSFER_ENCODING
+        || name.as_str() == "keep-alive"
+        || name.as_str() == "proxy-connection"
+}
+
+/// 校验并解析路由的请求头注入规则。
+fn parse_set_request_headers(
+    raw: &[(String, String)],
+) -> Result<Vec<(HeaderName, HeaderValue)>, String> {
+    raw.iter()
+        .map(|(name, value)| {
+            let header_name = HeaderName::from_bytes(name.trim().as_bytes())
+                .map_err(|_| format!("请求头名称非法: {}", name))?;
+            if is_reserved_hop_header(&header_name) {
+                return Err(format!("不允许注入连接管理请求头: {}", name));
+            }
+            let header_value = HeaderValue::from_str(value.trim())
+                .map_err(|_| format!("请求头 {} 的值非法", name))?;
+            Ok((header_name, header_value))
+        })
+        .collect()
+}
+
+/// 校验并解析路由的请求头移除规则（`Host` 由代理维护，不允许移除）。
+fn parse_remove_request_headers(raw: &[String]) -> Result<Vec<HeaderName>, String> {
+    let names = parse_header_names(raw)?;
+    if names.contains(&header::HOST) {
+        return Err("不允许移除 Host 请求头".to_string());
+    }
+    Ok(names)
+}
+
+/// 校验并解析路由的响应头注入规则。
+fn parse_set_response_headers(
+    raw: &[(String, String)],
+) -> Result<Vec<(HeaderName, HeaderValue)>, String> {
+    raw.iter()
+        .map(|(name, value)| {
+            let header_name = HeaderName::from_bytes(name.trim().as_bytes())
+                .map_err(|_| format!("响应头名称非法: {}", name))?;
+            if is_reserved_hop_header(&header_name) {
+                return Err(format!("不允许注入连接管理响应头: {}", name));
+            }
+            let header_value = HeaderValue::from_str(value.trim())
+                .map_err(|_| format!("响应头 {} 的值非法", name))?;
+            Ok((header_name, header_value))
+        })
+        .collect()
+}
+
+/// 校验并解析一组头名称。
+fn parse_header_names(raw: &[String]) -> Result<Vec<HeaderName>, String> {
+    raw.iter()
+        .map(|name| {
+            HeaderName::from_bytes(name.trim().as_bytes())
+                .map_err(|_| format!("头名称非法: {}", name))
+        })
+        .collect()
+}
+
+/// 单个 IP 或 CIDR 网段。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct IpNetwork {
+    address: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpNetwork {
+    /// 解析 `192.168.1.10`、`192.168.1.0/24`、`fd00::/8` 等形式。
+    fn parse(raw: &str) -> Result<Self, String> {
+        let value = raw.trim();
+        let (address_text, prefix_text) = match value.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (value, None),
+        };
+
+        let address = address_text
+            .trim()
+            .parse::<IpAddr>()
+            .map_err(|_| format!("IP 地址格式非法: {}", raw))?;
+        let max_prefix = if address.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_text {
+            Some(prefix) => prefix
+                .trim()
+                .parse::<u8>()
+                .ok()
+                .filter(|prefix| *prefix <= max_prefix)
+                .ok_or_else(|| format!("CIDR 前缀长度非法: {}", raw))?,
+            None => max_prefix,
+        };
+
+        Ok(Self {
+            address,
+            prefix_len,
+        })
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        match (self.address, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// 客户端 IP 访问控制：拒绝列表优先，允许列表为空时放行所有地址。
+#[derive(Default)]
+struct IpAccessControl {
+    allow: Vec<IpNetwork>,
+    deny: Vec<IpNetwork>,
+}
+
+impl IpAccessControl {
+    fn new(allow: &[String], deny: &[String]) -> Result<Self, String> {
+        let parse_all = |items: &[String]| {
+            items
+                .iter()
+                .filter(|item| !item.trim().is_empty())
+                .map(|item| IpNetwork::parse(item))
+                .collect::<Result<Vec<_>, String>>()
+        };
+
+        Ok(Self {
+            allow: parse_all(allow)?,
+            deny: parse_all(deny)?,
+        })
+    }
+
+    fn permits(&self, ip: IpAddr) -> bool {
+        // 监听 `::` 时 IPv4 客户端会以 IPv4-mapped 形式出现，先还原再比较。
+        let ip = ip.to_canonical();
+        if self.deny.iter().any(|network| network.contains(ip)) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|network| network.contains(ip))
+    }
+}
+
+/// 去除首尾空白，空串视为未设置。
+fn non_empty_trimmed(value: Option<&str>) -> Option<String> {
+    value
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+/// 解析路由的 TLS 定制参数；未设置 CA、客户端证书与 SNI 覆盖时返回 `None`。
+///
+/// 设置了 `resolve_to` 的 HTTPS 路由连接的是 IP，此时默认以原目标主机名作为
+/// SNI，保证证书仍按原主机名校验。
+fn parse_tls_profile(
+    item: &ProxyRouteInput,
+    target: &ParsedTarget,
+    resolved: bool,
+) -> Result<Option<TlsProfile>, String> {
+    let ca_cert_path = non_empty_trimmed(item.ca_cert_path.as_deref());
+    let server_name = non_empty_trimmed(item.sni_hostname.as_deref()).or_else(|| {
+        (resolved && target.scheme == TargetScheme::Https).then(|| target.host.clone())
+    });
+    if let Some(name) = &server_name {
+        ServerName::try_from(name.as_str()).map_err(|_| format!("SNI 主机名非法: {}", name))?;
+    }
+    let client_cert = match (
+        non_empty_trimmed(item.client_cert_path.as_deref()),
+        non_empty_trimmed(item.client_key_path.as_deref()),
+    ) {
+        (Some(cert_path), Some(key_path)) => Some((cert_path, key_path)),
+        (None, None) => None,
+        _ => return Err("客户端证书和私钥需要同时配置".to_string()),
+    };
+
+    if ca_cert_path.is_none() && client_cert.is_none() && server_name.is_none() {
+        return Ok(None);
+    }
+
+    Ok(Some(TlsProfile {
+        ca_cert_path,
+        client_cert,
+        insecure: item.allow_insecure_tls,
+        server_name,
+    }))
+}
+
+/// 解析固定解析地址，必须是合法的 IPv4/IPv6 地址。
+fn parse_resolve_to(value: Option<&str>) -> Result<Option<IpAddr>, String> {
+    non_empty_trimmed(value)
+        .map(|text| {
+            text.trim_start_matches('[')
+                .trim_end_matches(']')
+                .parse::<IpAddr>()
+                .map_err(|_| format!("固定解析地址必须是 IPv4/IPv6 地址: {}", text))
+        })
+        .transpose()
+}
+
+/// 解析模拟响应配置，并读取响应体文件。
+fn parse_mock_response(item: &ProxyRouteInput) -> Result<MockResponse, String> {
+    let status = StatusCode::from_u16(item.status.unwrap_or(200))
+        .map_err(|_| format!("模拟响应状态码非法: {}", item.status.unwrap_or_default()))?;
+    let content_type = non_empty_trimmed(item.content_type.as_deref())
+        .unwrap_or_else(|| "application/json; charset=utf-8".to_string());
+    let content_type = HeaderValue::from_str(&content_type)
+        .map_err(|_| format!("模拟响应 Content-Type 非法: {}", content_type))?;
+    let headers = parse_set_response_headers(&item.headers)?;
+
+    let body = match (
+        item.body_text.as_deref(),
+        non_empty_trimmed(item.body_file_path.as_deref()),
+    ) {
+        (Some(_), Some(_)) => {
+            return Err("模拟响应体文本与文件路径只能设置一个".to_string());
+        }
+        (_, Some(path)) => Bytes::from(
+            std::fs::read(&path)
+                .map_err(|err| format!("读取模拟响应文件失败 {}: {}", path, err))?,
+        ),
+        (Some(text), None) => Bytes::from(text.to_string()),
+        (None, None) => Bytes::new(),
+    };
+
+    Ok(MockResponse {
+        status,
+        content_type,
+        headers,
+        body,
+        delay: item
+            .delay_ms
+            .filter(|delay| *delay > 0)
+            .map(Duration::from_millis),
+    })
+}
+
+/// 解析缓存策略；有效期与大小上限必须大于 0。
+fn parse_cache_policy(item: &ProxyRouteInput) -> Result<Option<CachePolicy>, String> {
+    if !item.cache {
+        return Ok(None);
+    }
+    let ttl_secs = item.cache_ttl_secs.unwrap_or(DEFAULT_CACHE_TTL_SECS);
+    if ttl_secs == 0 {
+        return Err("缓存有效期必须大于 0 秒".to_string());
+    }
+    let max_bytes = item.cache_max_bytes.unwrap_or(DEFAULT_CACHE_MAX_BYTES);
+    if max_bytes == 0 {
+        return Err("可缓存响应大小上限必须大于 0".to_string());
+    }
+    Ok(Some(CachePolicy {
+        ttl: Duration::from_secs(ttl_secs),
+        max_bytes,
+    }))
+}
+
+/// 解析请求 ID 头名称；未设置时使用 `X-Request-Id`。
+fn parse_request_id_header(raw: Option<&str>) -> Result<HeaderName, String> {
+    let Some(raw) = non_empty_trimmed(raw) else {
+        return Ok(HeaderName::from_static(DEFAULT_REQUEST_ID_HEADER));
+    };
+    let name = HeaderName::from_bytes(raw.as_bytes())
+        .map_err(|_| format!("请求 ID 头名称非法: {}", raw))?;
+    if is_reserved_hop_header(&name) || name == header::HOST {
+        return Err(format!("请求 ID 头不能使用保留请求头: {}", raw));
+    }
+    Ok(name)
+}
+
+/// 解析 HTML 地址改写上限；未开启改写时返回 `None`。
+fn parse_html_rewrite_limit(item: &ProxyRouteInput) -> Result<Option<usize>, String> {
+    if !item.rewrite_html_urls {
+        return Ok(None);
+    }
+    match item
+        .html_rewrite_max_bytes
+        .unwrap_or(DEFAULT_HTML_REWRITE_MAX_BYTES)
+    {
+        0 => Err("HTML 改写大小上限必须大于 0".to_string()),
+        limit => Ok(Some(limit)),
+    }
+}
+
+/// 解析路由限速（KiB/s → 字节/秒）与延迟注入配置，速率为 0 视为非法。
+fn parse_route_throttle(item: &ProxyRouteInput) -> Result<RouteThrottle, String> {
+    let rate = |value: Option<u64>, label: &str| match value {
+        Some(0) => Err(format!("{}限速必须大于 0", label)),
+        Some(kbps) => Ok(Some(kbps.saturating_mul(1024))),
+        None => Ok(None),
+    };
+    Ok(RouteThrottle {
+        download_bytes_per_sec: rate(item.download_limit_kbps, "下行")?,
+        upload_bytes_per_sec: rate(item.upload_limit_kbps, "上行")?,
+        latency: item
+            .latency_ms
+            .filter(|ms| *ms > 0)
+            .map(Duration::from_millis),
+    })
+}
+
+/// 校验重试次数不超过 `MAX_ROUTE_RETRIES`。
+fn parse_retries(retries: u8) -> Result<u8, String> {
+    if retries > MAX_ROUTE_RETRIES {
+        return Err(format!("重试次数不能超过 {} 次", MAX_ROUTE_RETRIES));
+    }
+    Ok(retries)
+}
+
+/// 解析请求体截取上限；未开启截取时返回 `None`。
+fn parse_capture_limit(item: &ProxyRouteInput) -> Result<Option<usize>, String> {
+    if !item.capture_bodies {
+        return Ok(None);
+    }
+    match item.capture_max_bytes.unwrap_or(DEFAULT_CAPTURE_MAX_BYTES) {
+        0 => Err("请求体截取上限必须大于 0".to_string()),
+        limit => Ok(Some(limit)),
+    }
+}
+
+/// 解析健康检查配置：未设置路径时不启用；间隔必须大于 0。
+fn parse_health_check(
+    path: Option<&str>,
+    interval_secs: Option<u64>,
+) -> Result<Option<HealthCheck>, String> {
+    let Some(path) = non_empty_trimmed(path) else {
+        return Ok(None);
+    };
+    let path = if path.starts_with('/') {
+        path
+    } else {
+        format!("/{}", path)
+    };
+    if path.parse::<http::uri::PathAndQuery>().is_err() {
+        return Err(format!("健康检查路径非法: {}", path));
+    }
+
+    let interval_secs = interval_secs.unwrap_or(DEFAULT_HEALTH_CHECK_INTERVAL_SECS);
+    if interval_secs == 0 {
+        return Err("健康检查间隔必须大于 0 秒".to_string());
+    }
+
+    Ok(Some(HealthCheck {
+        path,
+        interval: Duration::from_secs(interval_secs),
+    }))
+}
+
+/// 解析 `Host` 头覆盖值；空串视为未设置。
+fn parse_host_header(value: Option<&str>) -> Result<Option<HeaderValue>, String> {
+    non_empty_trimmed(value)
+        .map(|host| {
+            HeaderValue::from_str(&host).map_err(|_| format!("Host 头覆盖值非法: {}", host))
+        })
+        .transpose()
+}
+
+/// 校验 Basic 认证配置：用户名与密码需同时提供，且用户名不能包含 `:`。
+fn parse_basic_auth(user: Option<&str>, password: Option<&str>) -> Result<Option<String>, String> {
+    let user = user.unwrap_or("");
+    let password = password.unwrap_or("");
+    match (user.is_empty(), password.is_empty()) {
+        (true, true) => Ok(None),
+        (false, false) => {
+            if user.contains(':') {
+                return Err("Basic 认证用户名不能包含冒号".to_string());
+            }
+            Ok(Some(format!("{}:{}", user, password)))
+        }
+        _ => Err("Basic 认证需要同时填写用户名和密码".to_string()),
+    }
+}
+
+/// 解析目标地址（支持 `http://`、`https://`、`ws://`、`wss://`，以及本地套接字
+/// `unix:///path/to.sock`、`npipe://./pipe/name`）。
+///
+/// 目标地址可以携带路径前缀（例如 `https://example.com/service/v2`），
+/// 其中 ws/wss 会映射为 http/https 传输语义。
+fn parse_target(raw: &str) -> Result<ParsedTarget, String> {
+    let normalized = raw.trim().trim_end_matches('/').to_string();
+    if normalized.is_empty() {
+        return Err("目标地址不能为空".to_string());
+    }
+
+    let normalized_lower = normalized.to_ascii_lowercase();
+    if normalized_lower.starts_with("unix://") || normalized_lower.starts_with("npipe://") {
+        return parse_local_socket_target(&normalized);
+    }
+    let (scheme, rest) = if normalized_lower.starts_with("http://") {
+        (TargetScheme::Http, &normalized[7..])
+    } else if normalized_lower.starts_with("https://") {
+        (TargetScheme::Https, &normalized[8..])
+    } else if normalized_lower.starts_with("ws://") {
+        (TargetScheme::Http, &normalized[5..])
+    } else if normalized_lower.starts_with("wss://") {
+        (TargetScheme::Https, &normalized[6..])
+    } else {
+        return Err("目标地址必须以 http://、https://、ws:// 或 wss:// 开头".to_string());
+    };
+
+    if rest.is_empty() {
+        return Err("目标地址不能为空".to_string());
+    }
+
+    if rest.contains('?') || rest.contains('#') {
+        return Err("目标地址不支持查询参数或锚点".to_string());
+    }
+
+    let (rest, base_path) = match rest.find('/') {
+        Some(index) => (&rest[..index], normalize_path_prefix(&rest[index..])),
+        None => (rest, "/".to_string()),
+    };
+    let base_path = if base_path == "/" {
+        String::new()
+    } else {
+        base_path
+    };
+
+    if rest.is_empty() {
+        return Err("目标主机不能为空".to_string());
+    }
+
+    if rest.matches(':').count() > 1 {
+        return Err("当前版本暂不支持 IPv6 地址".to_string());
+    }
+
+    let default_port = scheme.default_port();
+
+    if let Some((host, port_text)) = rest.rsplit_once(':') {
+        let host = host.trim();
+        if host.is_empty() {
+            return Err("目标主机不能为空".to_string());
+        }
+
+        let port = port_text
+            .trim()
+            .parse::<u16>()
+            .map_err(|_| "目标端口非法".to_string())?;
+
+        return Ok(ParsedTarget {
+            scheme,
+            host: host.to_string(),
+            port,
+            base_path,
+            socket_path: None,
+        });
+    }
+
+    Ok(ParsedTarget {
+        scheme,
+        host: rest.to_string(),
+        port: default_port,
+        base_path,
+        socket_path: None,
+    })
+}
+
+/// 解析本地套接字目标；仅在当前平台支持对应类型时接受。
+///
+/// 转发时 `Host` 头默认为合成主机 `localhost`，可通过路由的 `hostHeader` 覆盖。
+fn parse_local_socket_target(raw: &str) -> Result<ParsedTarget, String> {
+    let (scheme, rest) = raw.split_once("://").unwrap_or((raw, ""));
+    let socket_path = if scheme.eq_ignore_ascii_case("unix") {
+        if !cfg!(unix) {
+            return Err("当前系统不支持 Unix 套接字目标（unix://）".to_string());
+        }
+        if !rest.starts_with('/') {
+            return Err("Unix 套接字路径必须是绝对路径，例如 unix:///var/run/app.sock".to_string());
+        }
+        rest.to_string()
+    } else {
+        if !cfg!(windows) {
+            return Err("当前系统不支持命名管道目标（npipe://）".to_string());
+        }
+        let pipe = rest.trim_start_matches(['/', '\\']).replace('/', "\\");
+        if pipe.is_empty() {
+            return Err("命名管道名称不能为空，例如 npipe://./pipe/name".to_string());
+        }
+        format!("\\\\{}", pipe)
+    };
+
+    Ok(ParsedTarget {
+        scheme: TargetScheme::Unix,
+        host: "localhost".to_string(),
+        port: TargetScheme::Unix.default_port(),
+        base_path: String::new(),
+        socket_path: Some(socket_path),
+    })
+}
+
+/// 归一化路径前缀，确保以 `/` 开头并去除尾部多余 `/`。
+fn normalize_path_prefix(raw: &str) -> String {
+    let trimmed = raw.trim();
+    if trimmed.is_empty() {
+        return "/".to_string();
+    }
+
+    let mut prefix = if trimmed.starts_with('/') {
+        trimmed.to_string()
+    } else {
+        format!("/{}", trimmed)
+    };
+
+    if prefix.len() > 1 {
+        prefix = prefix.trim_end_matches('/').to_string();
+    }
+
+    prefix
+}
+
+/// 归一化 Host；空字符串或 `*` 视为通配（返回 `None`）。
+fn normalize_host_value(raw: &str) -> Option<String> {
+    let value = raw.trim().to_ascii_lowercase();
+    if value.is_empty() || value == "*" {
+        return None;
+    }
+
+    let host = value.split(':').next().unwrap_or("").trim().to_string();
+    if host.is_empty() {
+        None
+    } else {
+        Some(host)
+    }
+}
+
+/// 从路由列表中选出第一条匹配规则。
+///
+/// 注意：路由在进入该函数前已经按“优先级”排序。
+fn select_route<'a>(
+    routes: &'a [ProxyRoute],
+    request_host: Option<&str>,
+    request_path: &str,
+    request_query: &[(String, String)],
+) -> Option<&'a ProxyRoute> {
+    routes
+        .iter()
+        .filter(|route| route.is_enabled())
+        .find(|route| {
+            let host_match = match (&route.host, request_host) {
+                (None, _) => true,
+                (Some(_), None) => false,
+                (Some(expect), Some(actual)) => expect == actual,
+            };
+
+            host_match
+                && path_match(&route.path_prefix, request_path, route.match_mode)
+                && query_match(&route.query_match, request_query)
+        })
+}
+
+/// 判断请求查询参数是否满足全部条件；同名参数出现多次时任意一个满足即可。
+fn query_match(conditions: &[(String, Option<String>)], query: &[(String, String)]) -> bool {
+    conditions.iter().all(|(name, expected)| {
+        query.iter().any(|(key, value)| {
+            key == name && expected.as_ref().is_none_or(|expected| expected == value)
+        })
+    })
+}
+
+/// 把查询串拆分为解码后的键值对（`+` 视为空格），保留重复键与出现顺序。
+fn parse_query_pairs(query: Option<&str>) -> Vec<(String, String)> {
+    query
+        .unwrap_or("")
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect()
+}
+
+/// 百分号解码；非法转义原样保留，非 UTF-8 字节按替换字符处理。
+fn percent_decode(raw: &str) -> String {
+    let bytes = raw.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let hex = bytes
+                    .get(index + 1..index + 3)
+                    .and_then(|hex| std::str::from_utf8(hex).ok())
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                match hex {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        index += 2;
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        index += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// 判断路径是否命中路由路径：前缀模式按路径段边界匹配，精确模式忽略尾部 `/` 后比较。
+fn path_match(prefix: &str, path: &str, mode: MatchMode) -> bool {
+    if mode == MatchMode::Exact {
+        let trimmed = path.trim_end_matches('/');
+        return if prefix == "/" {
+            trimmed.is_empty()
+        } else {
+            trimmed == prefix
+        };
+    }
+
+    if prefix == "/" {
+        return true;
+    }
+
+    if path == prefix {
+        return true;
+    }
+
+    match path.strip_prefix(prefix) {
+        Some(rest) => rest.starts_with('/'),
+        None => false,
+    }
+}
+
+/// 构建空响应体的响应。
+fn empty_response(status: StatusCode) -> ProxyResponse {
+    let mut response = Response::new(Either::Right(Full::new(Bytes::new())));
+    *response.status_mut() = status;
+    response
+}
+
+/// 构建要求 Basic 认证的 401 响应。
+fn unauthorized_response() -> ProxyResponse {
+    let mut response = plain_response(StatusCode::UNAUTHORIZED, "需要身份验证");
+    response.headers_mut().insert(
+        header::WWW_AUTHENTICATE,
+        HeaderValue::from_static("Basic realm=\"Krate\""),
+    );
+    response
+}
+
+/// 构建纯文本响应。
+fn plain_response(status: StatusCode, message: &str) -> ProxyResponse {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+        .body(Either::Right(Full::new(Bytes::from(message.to_string()))))
+        .unwrap_or_else(|_| {
+            Response::new(Either::Right(Full::new(Bytes::from_static(
+                b"internal response build error",
+            ))))
+        })
+}
+
+/// 构建 HTML 响应，替换模板中的 `{{error}}` 与 `{{route}}` 占位符（已转义）。
+fn html_response(status: StatusCode, template: &str, error: &str, route: &str) -> ProxyResponse {
+    let body = template
+        .replace("{{error}}", &escape_html(error))
+        .replace("{{route}}", &escape_html(route));
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+        .body(Either::Right(Full::new(Bytes::from(body))))
+        .unwrap_or_else(|_| plain_response(status, error))
+}
+
+/// 代理自身产生的错误响应：配置了对应状态码的错误页面时返回 HTML，否则返回纯文本。
+fn error_response(
+    pages: &ErrorPages,
+    status: StatusCode,
+    message: &str,
+    route: Option<&str>,
+) -> ProxyResponse {
+    let page = match status {
+        StatusCode::NOT_FOUND => pages.not_found.as_deref(),
+        StatusCode::BAD_GATEWAY => pages.bad_gateway.as_deref(),
+        StatusCode::GATEWAY_TIMEOUT => pages.timeout.as_deref(),
+        _ => None,
+    };
+    match page {
+        Some(template) => html_response(status, template, message, route.unwrap_or("")),
+        None => plain_response(status, message),
+    }
+}
+
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// 读取自定义错误页面；任一文件读取失败都会使启动失败。
+fn load_error_pages(input: &ErrorPagesInput) -> Result<ErrorPages, String> {
+    let load = |path: &Option<String>| -> Result<Option<String>, String> {
+        match non_empty_trimmed(path.as_deref()) {
+            Some(path) => std::fs::read_to_string(&path)
+                .map(Some)
+                .map_err(|err| format!("读取错误页面失败 {}: {}", path, err)),
+            None => Ok(None),
+        }
+    };
+    Ok(ErrorPages {
+        not_found: load(&input.not_found)?,
+        bad_gateway: load(&input.bad_gateway)?,
+        timeout: load(&input.timeout)?,
+    })
+}
+
+/// 生成 16 位十六进制请求 ID；系统随机源不可用时退化为时间戳与计数器。
+fn generate_request_id() -> String {
+    format!("{:016x}", random_u64())
+}
+
+/// 取一个随机数；系统随机源不可用时退化为时间戳与计数器混合。
+fn random_u64() -> u64 {
+    let mut bytes = [0u8; 8];
+    if getrandom::fill(&mut bytes).is_err() {
+        static FALLBACK_COUNTER: AtomicU64 = AtomicU64::new(0);
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_nanos() as u64)
+            .unwrap_or(0);
+        let counter = FALLBACK_COUNTER.fetch_add(1, Ordering::Relaxed);
+        return nanos ^ counter.rotate_left(48);
+    }
+    u64::from_be_bytes(bytes)
+}
+
+/// 确定请求 ID 并写入转发请求头：客户端已携带合法 ID 时沿用，否则写入生成的 ID。
+fn assign_request_id(
+    headers: &mut HeaderMap<HeaderValue>,
+    name: &HeaderName,
+    generated: String,
+) -> String {
+    let existing = headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|value| !value.is_empty() && value.len() <= REQUEST_ID_MAX_LEN)
+        .map(str::to_string);
+    if let Some(existing) = existing {
+        return existing;
+    }
+
+    if let Ok(value) = HeaderValue::from_str(&generated) {
+        headers.insert(name.clone(), value);
+    }
+    generated
+}
+
+/// 把请求 ID 回显到返回给客户端的响应头中。
+fn echo_request_id(headers: &mut HeaderMap<HeaderValue>, trace: &RequestTrace) {
+    let name = trace
+        .request_id_header
+        .clone()
+        .unwrap_or_else(|| HeaderName::from_static(DEFAULT_REQUEST_ID_HEADER));
+    if let Ok(value) = HeaderValue::from_str(&trace.request_id) {
+        headers.insert(name, value);
+    }
+}
+
+/// 推送访问日志事件。
+fn emit_proxy_log(app: &AppHandle, payload: ProxyLogPayload) {
+    let _ = app.emit(PROXY_LOG_EVENT, payload);
+}
+
+/// 写入最近请求环形缓冲区，超出容量时淘汰最旧记录。
+fn record_recent_request(
+    recent_requests: &Mutex<VecDeque<RecentProxyRequest>>,
+    entry: RecentProxyRequest,
+) {
+    if let Ok(mut recent) = recent_requests.lock() {
+        if recent.len() >= RECENT_REQUESTS_CAPACITY {
+            recent.pop_front();
+        }
+        recent.push_back(entry);
+    }
+}
+
+fn record_slow_request(slow_requests: &Mutex<VecDeque<SlowProxyRequest>>, entry: SlowProxyRequest) {
+    if let Ok(mut slow) = slow_requests.lock() {
+        if slow.len() >= SLOW_REQUESTS_CAPACITY {
+            slow.pop_front();
+        }
+        slow.push_back(entry);
+    }
+}
+
+/// 更新运行时错误快照（用于前端展示最近错误），并保留最近 10 条历史。
+fn set_runtime_error(snapshot: &Arc<Mutex<ProxySnapshot>>, message: String) {
+    if let Ok(mut snap) = snapshot.lock() {
+        if snap.recent_errors.len() >= RECENT_ERRORS_CAPACITY {
+            snap.recent_errors.pop_front();
+        }
+        snap.recent_errors.push_back(ProxyErrorEntry {
+            message: message.clone(),
+            timestamp: current_timestamp(),
+        });
+        snap.last_error = Some(message);
+    }
+}
+
+/// 获取当前 UNIX 秒级时间戳。
+fn current_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|value| value.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled_route(host: &str, path_prefix: &str, target: &str) -> ProxyRouteInput {
+        ProxyRouteInput {
+            enabled: true,
+            host: host.to_string(),
+            path_prefix: path_prefix.to_string(),
+            target: target.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parse_target_supports_http_https_ws_wss_and_case_insensitive_scheme() {
+        let http = parse_target("HTTP://example.com").unwrap();
+        assert_eq!(http.scheme, TargetScheme::Http);
+        assert_eq!(http.host, "example.com");
+        assert_eq!(http.port, 80);
+        assert_eq!(http.base_path, "");
+
+        let https = parse_target("Https://api.example.com:8443").unwrap();
+        assert_eq!(https.scheme, TargetScheme::Https);
+        assert_eq!(https.host, "api.example.com");
+        assert_eq!(https.port, 8443);
+
+        let ws = parse_target("ws://socket.local").unwrap();
+        assert_eq!(ws.scheme, TargetScheme::Http);
+        assert_eq!(ws.host, "socket.local");
+        assert_eq!(ws.port, 80);
+
+        let wss = parse_target("WSS://socket.secure.local").unwrap();
+        assert_eq!(wss.scheme, TargetScheme::Https);
+        assert_eq!(wss.host, "socket.secure.local");
+        assert_eq!(wss.port, 443);
+    }
+
+    #[test]
+    fn parse_target_accepts_local_socket_for_current_platform() {
+        let unix = parse_target("unix:///var/run/app.sock");
+        let pipe = parse_target("npipe://./pipe/app");
+        if cfg!(unix) {
+            let target = unix.unwrap();
+            assert_eq!(target.scheme, TargetScheme::Unix);
+            assert_eq!(target.host, "localhost");
+            assert_eq!(target.socket_path.as_deref(), Some("/var/run/app.sock"));
+            assert!(parse_target("unix://relative.sock").is_err());
+            assert!(pipe.err().unwrap().contains("npipe://"));
+        } else {
+            assert!(unix.err().unwrap().contains("unix://"));
+            assert_eq!(pipe.unwrap().socket_path.as_deref(), Some(r"\\.\pipe\app"));
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn local_socket_client_forwards_over_unix_socket() {
+        let dir = std::env::temp_dir().join(format!("krate-uds-{}", generate_request_id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("upstream.sock");
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let service = service_fn(|request: Request<Incoming>| async move {
+                let host = request.headers().get(header::HOST).cloned();
+                Ok::<_, Infallible>(Response::new(Full::new(Bytes::from(format!(
+                    "{} {:?}",
+                    request.uri().path(),
+                    host
+                )))))
+            });
+            let _ = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await;
+        });
+
+        let client = UpstreamClient::LocalSocket(local_socket_client(path.to_str().unwrap()));
+        let request = Request::get("http://localhost/ping")
+            .header(header::HOST, "localhost")
+            .body(Either::Right(Full::new(Bytes::new())))
+            .unwrap();
+        let response = client.request(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body.as_ref(), b"/ping Some(\"localhost\")");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn parse_target_accepts_base_path() {
+        let target = parse_target("https://example.com/service/v2/").unwrap();
+        assert_eq!(target.host, "example.com");
+        assert_eq!(target.port, 443);
+        assert_eq!(target.base_path, "/service/v2");
+
+        let target = parse_target("http://127.0.0.1:3000/").unwrap();
+        assert_eq!(target.port, 3000);
+        assert_eq!(target.base_path, "");
+
+        assert!(parse_target("https://example.com/api?x=1").is_err());
+        assert!(parse_target("https:///api").is_err());
+    }
+
+    #[test]
+    fn rewrite_path_and_query_respects_strip_prefix() {
+        let mut input = enabled_route("", "/api", "http://127.0.0.1:3000");
+        input.strip_prefix = true;
+        let route = build_routes(&[input]).unwrap().remove(0);
+
+        let uri: Uri = "/api/user/list?page=1".parse().unwrap();
+        assert_eq!(rewrite_path_and_query(&uri, &route), "/user/list?page=1");
+    }
+
+    #[test]
+    fn rewrite_path_and_query_prepends_target_base_path() {
+        let mut input = enabled_route("", "/api", "https://example.com/service/v2");
+        input.strip_prefix = true;
+        let stripped = build_routes(&[input]).unwrap().remove(0);
+
+        let uri: Uri = "/api/users?page=2".parse().unwrap();
+        assert_eq!(
+            rewrite_path_and_query(&uri, &stripped),
+            "/service/v2/users?page=2"
+        );
+        let uri: Uri = "/api".parse().unwrap();
+        assert_eq!(rewrite_path_and_query(&uri, &stripped), "/service/v2");
+        let uri: Uri = "/api/".parse().unwrap();
+        assert_eq!(rewrite_path_and_query(&uri, &stripped), "/service/v2/");
+
+        let kept = build_routes(&[enabled_route("", "/api", "https://example.com/service/v2")])
+            .unwrap()
+            .remove(0);
+        let uri: Uri = "/api/users".parse().unwrap();
+        assert_eq!(rewrite_path_and_query(&uri, &kept), "/service/v2/api/users");
+        assert_eq!(
+            build_upstream_uri(&uri, &kept).unwrap().to_string(),
+            "https://example.com/service/v2/api/users"
+        );
+    }
+
+    #[test]
+    fn build_routes_prefers_more_specific_host_when_prefix_equal() {
+        let routes = build_routes(&[
+            enabled_route("", "/api", "http://127.0.0.1:3001"),
+            enabled_route("api.example.com", "/api", "http://127.0.0.1:3002"),
+        ])
+        .unwrap();
+
+        let selected = select_route(&routes, Some("api.example.com"), "/api/users", &[]).unwrap();
+        assert_eq!(selected.host.as_deref(), Some("api.example.com"));
+        assert_eq!(selected.target_port, 3002);
+    }
+
+    #[test]
+    fn disabled_routes_are_kept_and_toggled_at_runtime() {
+        let mut disabled = enabled_route("", "/api", "http://127.0.0.1:3001");
+        disabled.id = "api".to_string();
+        disabled.enabled = false;
+        let mut fallback = enabled_route("", "/", "http://127.0.0.1:3002");
+        fallback.id = "root".to_string();
+        let routes = build_routes(&[disabled, fallback]).unwrap();
+        assert_eq!(routes.len(), 2);
+
+        let selected = select_route(&routes, None, "/api/users", &[]).unwrap();
+        assert_eq!(selected.id, "root");
+
+        set_route_enabled(&routes, &[], "api", true).unwrap();
+        let selected = select_route(&routes, None, "/api/users", &[]).unwrap();
+        assert_eq!(selected.id, "api");
+        assert!(routes[0].stats_status().enabled);
+
+        set_route_enabled(&routes, &[], "root", false).unwrap();
+        assert!(select_route(&routes, None, "/other", &[]).is_none());
+
+        let err = set_route_enabled(&routes, &[], "missing", true).unwrap_err();
+        assert!(err.contains("missing"));
+        assert!(err.contains("api, root"));
+    }
+
+    #[test]
+    fn test_route_reports_match_upstream_uri_and_headers() {
+        let mut api = enabled_route("api.example.com", "/api", "http://127.0.0.1:3001/v2");
+        api.id = "api".to_string();
+        api.strip_prefix = true;
+        api.set_request_headers = vec![("X-Env".to_string(), "dev".to_string())];
+        let routes = build_routes(&[api]).unwrap();
+
+        let result = test_route(
+            &routes,
+            Some("API.example.com:8080"),
+            "/api/users?page=2",
+            Some("post"),
+            ForwardedHeaders::All,
+            true,
+        )
+        .unwrap();
+        assert!(result.matched);
+        assert_eq!(result.route_id.as_deref(), Some("api"));
+        assert_eq!(result.method, "POST");
+        assert_eq!(
+            result.upstream_uri.as_deref(),
+            Some("http://127.0.0.1:3001/v2/users?page=2")
+        );
+        let header = |name: &str| {
+            result
+                .request_headers
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
+        assert_eq!(header("host"), Some("127.0.0.1:3001"));
+        assert_eq!(header("x-env"), Some("dev"));
+        assert_eq!(header("x-forwarded-host"), Some("API.example.com:8080"));
+
+        let missed = test_route(
+            &routes,
+            Some("other.example.com"),
+            "/api",
+            None,
+            ForwardedHeaders::All,
+            true,
+        )
+        .unwrap();
+        assert!(!missed.matched);
+        assert!(missed.upstream_uri.is_none());
+    }
+
+    #[test]
+    fn build_routes_prefers_longest_path_prefix() {
+        let routes = build_routes(&[
+            enabled_route("", "/api", "http://127.0.0.1:3001"),
+            enabled_route("", "/api/admin", "http://127.0.0.1:3002"),
+        ])
+        .unwrap();
+
+        let selected = select_route(&routes, None, "/api/admin/users", &[]).unwrap();
+        assert_eq!(selected.path_prefix, "/api/admin");
+        assert_eq!(selected.target_port, 3002);
+    }
+
+    #[test]
+    fn build_routes_keeps_ids_and_starts_with_empty_stats() {
+        let mut named = enabled_route("", "/api", "http://127.0.0.1:3001");
+        named.id = "api".to_string();
+        named.name = "API".to_string();
+        let routes =
+            build_routes(&[named, enabled_route("", "/", "http://127.0.0.1:3002")]).unwrap();
+
+        assert_eq!(routes[0].id, "api");
+        assert_eq!(routes[1].id, "route-2");
+
+        routes[0].clone().stats.record_hit();
+        let stats = routes[0].stats_status();
+        assert_eq!(stats.name, "API");
+        assert_eq!(stats.requests, 1);
+        assert!(stats.last_hit_at.is_some());
+        assert_eq!(routes[1].stats_status().requests, 0);
+        assert!(routes[1].stats_status().last_hit_at.is_none());
+    }
+
+    fn peer() -> std::net::SocketAddr {
+        "192.168.1.20:50000".parse().unwrap()
+    }
+
+    #[test]
+    fn custom_request_headers_override_and_remove_case_insensitively() {
+        let mut input = enabled_route("", "/", "https://api.example.com");
+        input.set_request_headers = vec![
+            ("Authorization".to_string(), "Bearer token".to_string()),
+            ("X-Env".to_string(), "staging".to_string()),
+        ];
+        input.remove_request_headers = vec!["COOKIE".to_string()];
+        let route = build_routes(&[input]).unwrap().remove(0);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("Basic abc"));
+        headers.insert(header::COOKIE, HeaderValue::from_static("sid=1"));
+        apply_proxy_headers(
+            &mut headers,
+            &route,
+            peer(),
+            "localhost:8080",
+            false,
+            ForwardedHeaders::All,
+        )
+        .unwrap();
+
+        assert_eq!(headers.get(header::AUTHORIZATION).unwrap(), "Bearer token");
+        assert_eq!(headers.get_all(header::AUTHORIZATION).iter().count(), 1);
+        assert_eq!(headers.get("x-env").unwrap(), "staging");
+        assert!(headers.get(header::COOKIE).is_none());
+        assert_eq!(headers.get(header::HOST).unwrap(), "api.example.com");
+    }
+
+    #[test]
+    fn forwarded_headers_follow_configured_mode() {
+        let route = build_routes(&[enabled_route("", "/", "http://127.0.0.1:3000")])
+            .unwrap()
+            .remove(0);
+        let v6_peer: std::net::SocketAddr = "[2001:db8::1]:50000".parse().unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-real-ip", HeaderValue::from_static("1.2.3.4"));
+        headers.insert(header::FORWARDED, HeaderValue::from_static("for=10.0.0.1"));
+        apply_proxy_headers(
+            &mut headers,
+            &route,
+            v6_peer,
+            "localhost:8080",
+            false,
+            ForwardedHeaders::All,
+        )
+        .unwrap();
+        assert_eq!(headers.get("x-real-ip").unwrap(), "2001:db8::1");
+        assert_eq!(
+            headers.get(header::FORWARDED).unwrap(),
+            "for=10.0.0.1, for=\"[2001:db8::1]\";host=\"localhost:8080\";proto=http"
+        );
+
+        let mut headers = HeaderMap::new();
+        apply_proxy_headers(
+            &mut headers,
+            &route,
+            peer(),
+            "app.local",
+            false,
+            ForwardedHeaders::Xff,
+        )
+        .unwrap();
+        assert_eq!(headers.get("x-forwarded-for").unwrap(), "192.168.1.20");
+        assert!(headers.get("x-real-ip").is_none());
+        assert!(headers.get(header::FORWARDED).is_none());
+
+        let mut headers = HeaderMap::new();
+        apply_proxy_headers(
+            &mut headers,
+            &route,
+            peer(),
+            "app.local",
+            false,
+            ForwardedHeaders::None,
+        )
+        .unwrap();
+        assert!(headers.get("x-forwarded-for").is_none());
+        assert!(headers.get("x-forwarded-host").is_none());
+        assert!(headers.get(header::HOST).is_some());
+    }
+
+    #[test]
+    fn custom_request_headers_keep_websocket_upgrade_intact() {
+        let mut input = enabled_route("", "/", "ws://127.0.0.1:3000");
+        input.set_request_headers = vec![("X-Env".to_string(), "staging".to_string())];
+        input.remove_request_headers = vec!["origin".to_string()];
+        let route = build_routes(&[input]).unwrap().remove(0);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONNECTION, HeaderValue::from_static("Upgrade"));
+        headers.insert(header::UPGRADE, HeaderValue::from_static("websocket"));
+        headers.insert(header::ORIGIN, HeaderValue::from_static("http://localhost"));
+        apply_proxy_headers(
+            &mut headers,
+            &route,
+            peer(),
+            "localhost",
+            true,
+            ForwardedHeaders::All,
+        )
+        .unwrap();
+
+        assert_eq!(headers.get(header::CONNECTION).unwrap(), "upgrade");
+        assert_eq!(headers.get(header::UPGRADE).unwrap(), "websocket");
+        assert_eq!(headers.get("x-env").unwrap(), "staging");
+        assert!(headers.get(header::ORIGIN).is_none());
+    }
+
+    #[test]
+    fn build_routes_rejects_invalid_custom_request_headers() {
+        let mut remove_host = enabled_route("", "/", "http://127.0.0.1:3000");
+        remove_host.remove_request_headers = vec!["Host".to_string()];
+        assert!(build_routes(&[remove_host]).err().unwrap().contains("Host"));
+
+        let mut bad_name = enabled_route("", "/", "http://127.0.0.1:3000");
+        bad_name.set_request_headers = vec![("Bad Header".to_string(), "x".to_string())];
+        assert!(build_routes(&[bad_name]).is_err());
+
+        let mut bad_value = enabled_route("", "/", "http://127.0.0.1:3000");
+        bad_value.set_request_headers = vec![("X-Env".to_string(), "a\nb".to_string())];
+        assert!(build_routes(&[bad_value]).is_err());
+
+        let mut hop = enabled_route("", "/", "http://127.0.0.1:3000");
+        hop.set_request_headers = vec![("Upgrade".to_string(), "h2c".to_string())];
+        assert!(build_routes(&[hop]).is_err());
+    }
+
+    #[test]
+    fn response_headers_are_rewritten_with_cors() {
+        let mut input = enabled_route("", "/", "https://api.example.com");
+        input.cors = true;
+        input.set_response_headers = vec![("X-Frame-Options".to_string(), "DENY".to_string())];
+        input.remove_response_headers = vec!["server".to_string()];
+        let route = build_routes(&[input]).unwrap().remove(0);
+
+        let mut request_headers = HeaderMap::new();
+        request_headers.insert(
+            header::ORIGIN,
+            HeaderValue::from_static("http://localhost:5173"),
+        );
+        request_headers.insert(
+            header::ACCESS_CONTROL_REQUEST_HEADERS,
+            HeaderValue::from_static("content-type, x-token"),
+        );
+        let cors_request = CorsRequest::from_headers(&request_headers);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::SERVER, HeaderValue::from_static("nginx"));
+        apply_response_headers(&mut headers, &route, &cors_request, true);
+
+        assert!(headers.get(header::SERVER).is_none());
+        assert_eq!(headers.get("x-frame-options").unwrap(), "DENY");
+        assert_eq!(
+            headers.get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+            "http://localhost:5173"
+        );
+        assert_eq!(
+            headers.get(header::ACCESS_CONTROL_ALLOW_HEADERS).unwrap(),
+            "content-type, x-token"
+        );
+        assert!(headers.contains_key(header::ACCESS_CONTROL_ALLOW_METHODS));
+        assert!(!headers.contains_key(header::ACCESS_CONTROL_ALLOW_CREDENTIALS));
+
+        let mut input = enabled_route("", "/", "https://api.example.com");
+        input.cors = true;
+        input.cors_allow_credentials = true;
+        let route = build_routes(&[input]).unwrap().remove(0);
+        let mut headers = HeaderMap::new();
+        apply_response_headers(&mut headers, &route, &cors_request, false);
+        assert_eq!(
+            headers
+                .get(header::ACCESS_CONTROL_ALLOW_CREDENTIALS)
+                .unwrap(),
+            "true"
+        );
+    }
+
+    #[test]
+    fn response_headers_skip_cors_when_disabled() {
+        let route = build_routes(&[enabled_route("", "/", "http://127.0.0.1:3000")])
+            .unwrap()
+            .remove(0);
+        let mut headers = HeaderMap::new();
+        apply_response_headers(&mut headers, &route, &CorsRequest::default(), false);
+        assert!(headers.is_empty());
+    }
+
+    #[test]
+    fn basic_auth_accepts_only_matching_credentials() {
+        let expected = parse_basic_auth(Some("admin"), Some("s3cret"))
+            .unwrap()
+            .unwrap();
+        let encode = |raw: &str| {
+            HeaderValue::from_str(&format!(
+                "Basic {}",
+                base64::engine::general_purpose::STANDARD.encode(raw)
+            ))
+            .unwrap()
+        };
+
+        let mut headers = HeaderMap::new();
+        assert!(!basic_auth_matches(&headers, &expected));
+
+        headers.insert(header::AUTHORIZATION, encode("admin:s3cret"));
+        assert!(basic_auth_matches(&headers, &expected));
+
+        headers.insert(header::AUTHORIZATION, encode("admin:wrong"));
+        assert!(!basic_auth_matches(&headers, &expected));
+
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer abc"),
+        );
+        assert!(!basic_auth_matches(&headers, &expected));
+    }
+
+    #[test]
+    fn basic_auth_config_requires_both_fields() {
+        assert_eq!(parse_basic_auth(None, None).unwrap(), None);
+        assert!(parse_basic_auth(Some("admin"), None).is_err());
+        assert!(parse_basic_auth(Some("a:b"), Some("pw")).is_err());
+    }
+
+    #[test]
+    fn ip_access_control_supports_cidr_and_deny_precedence() {
+        let control = IpAccessControl::new(
+            &["192.168.1.0/24".to_string(), "10.0.0.5".to_string()],
+            &["192.168.1.13".to_string()],
+        )
+        .unwrap();
+
+        assert!(control.permits("192.168.1.20".parse().unwrap()));
+        assert!(control.permits("10.0.0.5".parse().unwrap()));
+        assert!(control.permits("::ffff:192.168.1.21".parse().unwrap()));
+        assert!(!control.permits("192.168.1.13".parse().unwrap()));
+        assert!(!control.permits("192.168.2.1".parse().unwrap()));
+        assert!(!control.permits("::1".parse().unwrap()));
+
+        let open = IpAccessControl::new(&[], &["::1".to_string()]).unwrap();
+        assert!(open.permits("203.0.113.7".parse().unwrap()));
+        assert!(!open.permits("::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn ip_network_rejects_invalid_input() {
+        assert!(IpNetwork::parse("192.168.1.0/33").is_err());
+        assert!(IpNetwork::parse("not-an-ip").is_err());
+        assert!(IpNetwork::parse("fd00::/8")
+            .unwrap()
+            .contains("fd12::1".parse().unwrap()));
+        assert!(IpNetwork::parse("0.0.0.0/0")
+            .unwrap()
+            .contains("8.8.8.8".parse().unwrap()));
+    }
+
+    #[test]
+    fn invalid_ca_certificate_reports_file_path() {
+        let path = std::env::temp_dir().join(format!("krate-proxy-ca-{}.pem", std::process::id()));
+        std::fs::write(&path, "not a certificate").unwrap();
+        let path_text = path.to_string_lossy().to_string();
+
+        let err = load_ca_root_store(&path_text).unwrap_err();
+        assert!(err.contains(&path_text));
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn tls_profile_combines_client_cert_with_insecure_tls() {
+        let mut input = enabled_route("", "/", "https://mtls.internal");
+        input.client_cert_path = Some("/certs/client.pem".to_string());
+        input.client_key_path = Some("/certs/client.key".to_string());
+        input.allow_insecure_tls = true;
+        let route = build_routes(&[input]).unwrap().remove(0);
+
+        let profile = route.tls_profile.unwrap();
+        assert_eq!(
+            profile.client_cert,
+            Some((
+                "/certs/client.pem".to_string(),
+                "/certs/client.key".to_string()
+            ))
+        );
+        assert!(profile.insecure);
+        assert!(profile.ca_cert_path.is_none());
+
+        let plain = build_routes(&[enabled_route("", "/", "https://example.com")])
+            .unwrap()
+            .remove(0);
+        assert!(plain.tls_profile.is_none());
+
+        let mut half = enabled_route("", "/", "https://mtls.internal");
+        half.client_cert_path = Some("/certs/client.pem".to_string());
+        assert!(build_routes(&[half]).is_err());
+    }
+
+    #[test]
+    fn sni_override_keeps_connect_target_and_host_header() {
+        let mut input = enabled_route("", "/", "https://10.0.0.5:8443");
+        input.sni_hostname = Some("api.example.com".to_string());
+        input.allow_insecure_tls = true;
+        let route = build_routes(&[input]).unwrap().remove(0);
+
+        let profile = route.tls_profile.as_ref().unwrap();
+        assert_eq!(profile.server_name.as_deref(), Some("api.example.com"));
+        assert!(profile.insecure);
+        assert_eq!(route.target_authority(), "10.0.0.5:8443");
+
+        let mut headers = HeaderMap::new();
+        apply_proxy_headers(
+            &mut headers,
+            &route,
+            peer(),
+            "localhost:8080",
+            false,
+            ForwardedHeaders::All,
+        )
+        .unwrap();
+        assert_eq!(headers.get(header::HOST).unwrap(), "10.0.0.5:8443");
+
+        let mut input = enabled_route("", "/", "https://10.0.0.5:8443");
+        input.sni_hostname = Some("api.example.com".to_string());
+        input.host_header = Some("api.example.com".to_string());
+        let route = build_routes(&[input]).unwrap().remove(0);
+        let mut headers = HeaderMap::new();
+        apply_proxy_headers(
+            &mut headers,
+            &route,
+            peer(),
+            "localhost:8080",
+            false,
+            ForwardedHeaders::All,
+        )
+        .unwrap();
+        assert_eq!(headers.get(header::HOST).unwrap(), "api.example.com");
+
+        let mut invalid = enabled_route("", "/", "https://10.0.0.5");
+        invalid.sni_hostname = Some("bad name".to_string());
+        assert!(build_routes(&[invalid]).is_err());
+    }
+
+    #[test]
+    fn preserved_host_header_does_not_affect_https_sni() {
+        let mut input = enabled_route("", "/", "https://api.example.com:8443");
+        input.preserve_host_header = true;
+        let route = build_routes(&[input]).unwrap().remove(0);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, HeaderValue::from_static("app.local:8080"));
+        headers.insert("x-forwarded-host", HeaderValue::from_static("spoofed"));
+        apply_proxy_headers(
+            &mut headers,
+            &route,
+            peer(),
+            "app.local:8080",
+            false,
+            ForwardedHeaders::Xff,
+        )
+        .unwrap();
+        assert_eq!(headers.get(header::HOST).unwrap(), "app.local:8080");
+        assert!(headers.get("x-forwarded-host").is_none());
+
+        // SNI 由上游 URI 的主机决定，而不是保留下来的 Host。
+        let uri = build_upstream_uri(&"/".parse().unwrap(), &route).unwrap();
+        assert_eq!(uri.host(), Some("api.example.com"));
+        assert!(route.tls_profile.is_none());
+
+        let mut input = enabled_route("", "/", "https://api.example.com");
+        input.preserve_host_header = true;
+        input.resolve_to = Some("10.0.0.5".to_string());
+        let route = build_routes(&[input]).unwrap().remove(0);
+        assert_eq!(
+            route.tls_profile.unwrap().server_name.as_deref(),
+            Some("api.example.com")
+        );
+    }
+
+    #[test]
+    fn resolve_to_connects_by_ip_but_keeps_hostname() {
+        let mut input = enabled_route("", "/", "https://api.example.com");
+        input.resolve_to = Some("10.0.0.5".to_string());
+        let route = build_routes(&[input]).unwrap().remove(0);
+
+        let uri = build_upstream_uri(&"/v1?a=1".parse().unwrap(), &route).unwrap();
+        assert_eq!(uri.to_string(), "https://10.0.0.5/v1?a=1");
+        assert_eq!(
+            route.tls_profile.as_ref().unwrap().server_name.as_deref(),
+            Some("api.example.com")
+        );
+
+        let mut headers = HeaderMap::new();
+        apply_proxy_headers(
+            &mut headers,
+            &route,
+            peer(),
+            "localhost:8080",
+            false,
+            ForwardedHeaders::All,
+        )
+        .unwrap();
+        assert_eq!(headers.get(header::HOST).unwrap(), "api.example.com");
+
+        let mut input = enabled_route("", "/", "http://api.example.com:8080");
+        input.resolve_to = Some("::1".to_string());
+        let route = build_routes(&[input]).unwrap().remove(0);
+        assert_eq!(route.connect_authority(), "[::1]:8080");
+        assert!(route.tls_profile.is_none());
+
+        let mut invalid = enabled_route("", "/", "https://api.example.com");
+        invalid.resolve_to = Some("example.internal".to_string());
+        assert!(build_routes(&[invalid]).is_err());
+    }
+
+    // 自签名测试证书（CN=krate-test-client），不对应任何可用私钥。
+    const CLIENT_CERT_PEM: &str = "-----BEGIN CERTIFICATE-----
+MIIBjzCCATWgAwIBAgIULRv/6jrVibjdc+knsEuw2vxWTzUwCgYIKoZIzj0EAwIw
+HDEaMBgGA1UEAwwRa3JhdGUtdGVzdC1jbGllbnQwIBcNMjYxMDE2MDMwOTM5WhgP
+MjEyNjA5MjIwMzA5MzlaMBwxGjAYBgNVBAMMEWtyYXRlLXRlc3QtY2xpZW50MFkw
+EwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEcrFdyzaFChnWWL5Nh/lI0ONNwOP2jLan
+P1oKl8YbUNxjzFdexWOr/UdwZ7QZxs/2DrVSa4yThs1aTJyl+XuUrKNTMFEwHQYD
+VR0OBBYEFLItNL9wyRYkr7iJmNUVV0J+9s4DMB8GA1UdIwQYMBaAFLItNL9wyRYk
+r7iJmNUVV0J+9s4DMA8GA1UdEwEB/wQFMAMBAf8wCgYIKoZIzj0EAwIDSAAwRQIg
+EubmrjgHf6lQoTiZXNohXvJZL2zsxRiMLzQGdHYMz9ECIQCsDn+KD2nwzroGrftZ
+nRUjupPFkNmUBOTxn9Uy5NqiWw==
+-----END CERTIFICATE-----
+";
+
+    #[test]
+    fn missing_client_key_reports_file_path() {
+        let dir = std::env::temp_dir();
+        let cert_path = dir.join(format!("krate-proxy-client-{}.pem", std::process::id()));
+        std::fs::write(&cert_path, CLIENT_CERT_PEM).unwrap();
+        let cert_text = cert_path.to_string_lossy().to_string();
+        let key_path = dir.join(format!("krate-proxy-missing-{}.key", std::process::id()));
+        let key_text = key_path.to_string_lossy().to_string();
+
+        // 证书合法，错误只能来自私钥文件。
+        let err = load_client_identity(&cert_text, &key_text).unwrap_err();
+        assert!(err.contains("读取客户端私钥失败"), "{err}");
+        assert!(err.contains(&key_text), "{err}");
+
+        let _ = std::fs::remove_file(cert_path);
+    }
+
+    #[test]
+    fn instances_are_isolated_and_port_conflicts_name_the_owner() {
+        let state = ProxyState::new();
+        assert_eq!(state.all_instances().unwrap().len(), 1);
+        assert!(state.instance("project-b").is_err());
+
+        let project_a = state.instance_or_create("project-a").unwrap();
+        {
+            let mut snapshot = project_a.snapshot.lock().unwrap();
+            snapshot.running = true;
+            snapshot.listen_port = Some(8080);
+        }
+        project_a.total_requests.store(3, Ordering::Relaxed);
+
+        assert_eq!(
+            state.port_owner(8080, "project-b").unwrap().as_deref(),
+            Some("project-a")
+        );
+        assert!(state.port_owner(8080, "project-a").unwrap().is_none());
+        assert!(state.port_owner(9090, "project-b").unwrap().is_none());
+
+        let default = state.instance(DEFAULT_INSTANCE_ID).unwrap();
+        assert_eq!(default.status().total_requests, 0);
+        let ids: Vec<String> = state
+            .all_instances()
+            .unwrap()
+            .iter()
+            .map(|instance| instance.id.clone())
+            .collect();
+        assert_eq!(ids, vec!["default", "project-a"]);
+    }
+
+    #[tokio::test]
+    async fn listen_port_zero_reports_assigned_port() {
+        let (listener, port) = bind_listener("127.0.0.1", 0).await.unwrap();
+        assert_ne!(port, 0);
+        assert_eq!(listener.local_addr().unwrap().port(), port);
+    }
+
+    #[test]
+    fn listen_hosts_merge_single_field_and_list() {
+        assert_eq!(
+            resolve_listen_hosts(
+                " 127.0.0.1 ",
+                &["100.64.0.1".to_string(), "127.0.0.1".to_string()]
+            )
+            .unwrap(),
+            vec!["127.0.0.1", "100.64.0.1"]
+        );
+        assert_eq!(
+            resolve_listen_hosts("", &["127.0.0.1".to_string()]).unwrap(),
+            vec!["127.0.0.1"]
+        );
+        assert!(resolve_listen_hosts(" ", &[]).is_err());
+    }
+
+    #[tokio::test]
+    async fn bind_listeners_share_port_and_accept_on_each() {
+        let hosts = vec!["127.0.0.1".to_string(), "127.0.0.2".to_string()];
+        let (listeners, port) = match bind_listeners(&hosts, 0).await {
+            Ok(bound) => bound,
+            // 部分系统未配置 127.0.0.2 回环别名。
+            Err(_) => return,
+        };
+        assert_eq!(listeners.len(), 2);
+        let mut next = 0;
+        for host in &hosts {
+            let client = tokio::net::TcpStream::connect((host.as_str(), port))
+                .await
+                .unwrap();
+            let (_, peer) = accept_any(&listeners, &mut next).await.unwrap();
+            assert_eq!(peer, client.local_addr().unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn bind_listeners_roll_back_when_any_address_fails() {
+        let hosts = vec!["127.0.0.1".to_string(), "203.0.113.1".to_string()];
+        let taken = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let probe_port = taken.local_addr().unwrap().port();
+        drop(taken);
+
+        let err = bind_listeners(&hosts, probe_port).await.err().unwrap();
+        assert!(err.contains("203.0.113.1"));
+        // 首个地址已随失败一起释放，可以再次绑定。
+        tokio::net::TcpListener::bind(("127.0.0.1", probe_port))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn connection_tracker_waits_until_all_guards_drop() {
+        let tracker = Arc::new(ConnectionTracker::default());
+        tracker.wait_idle().await;
+
+        let first = tracker.enter();
+        let second = tracker.enter();
+        assert_eq!(tracker.active(), 2);
+        assert!(timeout(Duration::from_millis(20), tracker.wait_idle())
+            .await
+            .is_err());
+
+        drop(first);
+        let (_, waited) = tokio::join!(
+            async move {
+                sleep(Duration::from_millis(10)).await;
+                drop(second);
+            },
+            timeout(Duration::from_secs(1), tracker.wait_idle())
+        );
+        assert!(waited.is_ok());
+        assert_eq!(tracker.active(), 0);
+    }
+
+    #[test]
+    fn latency_histogram_reports_avg_p95_and_max() {
+        let histogram = LatencyHistogram::default();
+        assert!(histogram.summary().is_none());
+
+        for _ in 0..95 {
+            histogram.record(8);
+        }
+        for _ in 0..5 {
+            histogram.record(1_500);
+        }
+
+        let summary = histogram.summary().unwrap();
+        assert_eq!(summary.avg_ms, (95 * 8 + 5 * 1_500) / 100);
+        assert_eq!(summary.p95_ms, 10);
+        assert_eq!(summary.max_ms, 1_500);
+
+        histogram.record(60_000);
+        let summary = histogram.summary().unwrap();
+        assert_eq!(summary.max_ms, 60_000);
+        assert!(summary.p95_ms <= summary.max_ms);
+    }
+
+    #[test]
+    fn health_check_config_is_optional_and_validated() {
+        let route = build_routes(&[enabled_route("", "/", "http://127.0.0.1:3000")])
+            .unwrap()
+            .remove(0);
+        assert!(route.health_check.is_none());
+
+        let mut input = enabled_route("", "/", "http://127.0.0.1:3000");
+        input.health_check_path = Some("healthz".to_string());
+        let route = build_routes(&[input]).unwrap().remove(0);
+        assert_eq!(
+            route.health_check,
+            Some(HealthCheck {
+                path: "/healthz".to_string(),
+                interval: Duration::from_secs(DEFAULT_HEALTH_CHECK_INTERVAL_SECS),
+            })
+        );
+
+        let mut input = enabled_route("", "/", "http://127.0.0.1:3000");
+        input.health_check_path = Some("/healthz".to_string());
+        input.health_check_interval_secs = Some(0);
+        assert!(build_routes(&[input]).is_err());
+    }
+
+    #[test]
+    fn fallback_request_targets_fallback_host_with_same_path() {
+        let mut input = enabled_route("", "/api", "http://localhost:3000");
+        input.strip_prefix = true;
+        input.fallback_target = Some("https://staging.example.com/v2".to_string());
+        let route = build_routes(&[input]).unwrap().remove(0);
+        let fallback = route.fallback.as_deref().unwrap();
+        assert_eq!(fallback.target_authority(), "staging.example.com");
+        assert!(fallback.fallback.is_none());
+        assert!(Arc::ptr_eq(&fallback.stats, &route.stats));
+
+        let original_uri: Uri = "/api/users?page=2".parse().unwrap();
+        let (head, _) = Request::builder()
+            .method(http::Method::POST)
+            .uri(build_upstream_uri(&original_uri, &route).unwrap())
+            .header(header::HOST, "localhost:3000")
+            .header("x-env", "dev")
+            .body(())
+            .unwrap()
+            .into_parts();
+        let request =
+            fallback_upstream_request(&original_uri, &head, &Bytes::from_static(b"{}"), fallback)
+                .unwrap();
+        assert_eq!(
+            request.uri().to_string(),
+            "https://staging.example.com/v2/users?page=2"
+        );
+        assert_eq!(*request.method(), http::Method::POST);
+        assert_eq!(
+            request.headers().get(header::HOST).unwrap(),
+            "staging.example.com"
+        );
+        assert_eq!(request.headers().get("x-env").unwrap(), "dev");
+
+        let mut invalid = enabled_route("", "/", "http://localhost:3000");
+        invalid.fallback_target = Some("ftp://staging".to_string());
+        assert!(build_routes(&[invalid]).is_err());
+    }
+
+    #[tokio::test]
+    async fn mock_route_answers_without_target() {
+        let input = ProxyRouteInput {
+            enabled: true,
+            path_prefix: "/api/todo".to_string(),
+            route_type: RouteType::Mock,
+            status: Some(201),
+            headers: vec![("X-Mock".to_string(), "1".to_string())],
+            body_text: Some("{\"ok\":true}".to_string()),
+            ..Default::default()
+        };
+        let route = build_routes(&[input]).unwrap().remove(0);
+        let mock = route.mock.as_deref().unwrap();
+
+        let response = mock_response(mock).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(
+            response.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/json; charset=utf-8"
+        );
+        assert_eq!(response.headers().get("x-mock").unwrap(), "1");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, Bytes::from_static(b"{\"ok\":true}"));
+
+        let missing = ProxyRouteInput {
+            enabled: true,
+            route_type: RouteType::Mock,
+            body_file_path: Some("/definitely/missing/mock.json".to_string()),
+            ..Default::default()
+        };
+        let err = build_routes(&[missing]).err().unwrap();
+        assert!(err.contains("/definitely/missing/mock.json"));
+    }
+
+    fn cached(body: &'static [u8]) -> CachedResponse {
+        CachedResponse {
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+            body: Bytes::from_static(body),
+            expires_at: Instant::now() + Duration::from_secs(60),
+        }
+    }
+
+    #[test]
+    fn response_cache_tracks_hits_and_expiry() {
+        let cache = ResponseCache::default();
+        assert!(cache.get("GET app.local/a.js").is_none());
+
+        cache.insert("GET app.local/a.js".to_string(), cached(b"console.log(1)"));
+        assert!(cache.get("GET app.local/a.js").is_some());
+
+        let mut expired = cached(b"old");
+        expired.expires_at = Instant::now();
+        cache.insert("GET app.local/b.js".to_string(), expired);
+        assert!(cache.get("GET app.local/b.js").is_none());
+
+        let status = cache.status();
+        assert_eq!(status.entries, 1);
+        assert_eq!(status.hits, 1);
+        assert_eq!(status.misses, 2);
+
+        cache.clear();
+        assert_eq!(cache.status().entries, 0);
+        assert_eq!(cache.status().hit_ratio, 0.0);
+    }
+
+    #[test]
+    fn response_cache_evicts_least_recently_used() {
+        let cache = ResponseCache::default();
+        for index in 0..CACHE_MAX_ENTRIES {
+            cache.insert(format!("GET /{}", index), cached(b"x"));
+        }
+        // 访问最早的条目，使其变为最近使用。
+        assert!(cache.get("GET /0").is_some());
+        cache.insert("GET /new".to_string(), cached(b"x"));
+
+        assert_eq!(cache.status().entries, CACHE_MAX_ENTRIES);
+        assert!(cache.get("GET /0").is_some());
+        assert!(cache.get("GET /1").is_none());
+    }
+
+    #[test]
+    fn responses_with_cookies_or_no_store_are_not_cached() {
+        let response = |header: Option<(HeaderName, &'static str)>| {
+            let mut response: ProxyResponse =
+                Response::new(Either::Right(Full::new(Bytes::from_static(b"body"))));
+            if let Some((name, value)) = header {
+                response
+                    .headers_mut()
+                    .insert(name, HeaderValue::from_static(value));
+            }
+            response
+        };
+
+        assert!(is_cacheable_response(&response(None), 1024));
+        assert!(!is_cacheable_response(&response(None), 2));
+        assert!(!is_cacheable_response(
+            &response(Some((header::SET_COOKIE, "sid=1"))),
+            1024
+        ));
+        assert!(!is_cacheable_response(
+            &response(Some((header::CACHE_CONTROL, "private, No-Store"))),
+            1024
+        ));
+    }
+
+    #[test]
+    fn gzip_is_negotiated_and_limited_to_text_responses() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::ACCEPT_ENCODING,
+            HeaderValue::from_static("br, gzip;q=0.8"),
+        );
+        assert!(accepts_gzip(&headers));
+        headers.insert(
+            header::ACCEPT_ENCODING,
+            HeaderValue::from_static("gzip;q=0"),
+        );
+        assert!(!accepts_gzip(&headers));
+
+        let response = |content_type: &'static str, body: Vec<u8>| {
+            let mut response: ProxyResponse =
+                Response::new(Either::Right(Full::new(Bytes::from(body))));
+            response
+                .headers_mut()
+                .insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+            response
+        };
+        assert!(should_gzip_response(&response(
+            "text/html; charset=utf-8",
+            vec![b'a'; 2048]
+        )));
+        assert!(!should_gzip_response(&response(
+            "text/html",
+            vec![b'a'; 16]
+        )));
+        assert!(!should_gzip_response(&response("image/png", vec![0; 2048])));
+
+        let mut encoded = response("application/json", vec![b'a'; 2048]);
+        encoded
+            .headers_mut()
+            .insert(header::CONTENT_ENCODING, HeaderValue::from_static("br"));
+        assert!(!should_gzip_response(&encoded));
+    }
+
+    #[tokio::test]
+    async fn gzip_body_round_trips() {
+        let original = "hello krate ".repeat(200);
+        let mut upstream = response_with_text(&original);
+        upstream
+            .headers_mut()
+            .insert(header::CONTENT_LENGTH, HeaderValue::from(original.len()));
+
+        let response = gzip_response(upstream.map(|body| CaptureBody::new(body, None)));
+        assert_eq!(
+            response.headers().get(header::CONTENT_ENCODING).unwrap(),
+            "gzip"
+        );
+        assert!(response.headers().get(header::CONTENT_LENGTH).is_none());
+
+        let compressed = response.into_body().collect().await.unwrap().to_bytes();
+        assert!(compressed.len() < original.len());
+        let mut decoded = String::new();
+        std::io::Read::read_to_string(
+            &mut flate2::read::GzDecoder::new(compressed.as_ref()),
+            &mut decoded,
+        )
+        .unwrap();
+        assert_eq!(decoded, original);
+    }
+
+    fn response_with_text(text: &str) -> ProxyResponse {
+        let mut response: ProxyResponse =
+            Response::new(Either::Right(Full::new(Bytes::from(text.to_string()))));
+        response
+            .headers_mut()
+            .insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+        response
+    }
+
+    #[tokio::test]
+    async fn counting_io_tracks_bytes_per_direction() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let stats = Arc::new(WebSocketStats::default());
+        let traffic = TrafficStats::default();
+        let (client, mut client_peer) = tokio::io::duplex(64);
+        let (upstream, mut upstream_peer) = tokio::io::duplex(64);
+        let mut client_io = CountingIo {
+            inner: client,
+            stats: stats.clone(),
+            inbound: true,
+            traffic: traffic.bytes_in.clone(),
+            transferred: 0,
+            throttle: None,
+        };
+        let mut upstream_io = CountingIo {
+            inner: upstream,
+            stats: stats.clone(),
+            inbound: false,
+            traffic: traffic.bytes_out.clone(),
+            transferred: 0,
+            throttle: None,
+        };
+
+        let peers = async {
+            client_peer.write_all(b"ping!").await.unwrap();
+            let mut received = [0u8; 5];
+            upstream_peer.read_exact(&mut received).await.unwrap();
+            upstream_peer.write_all(b"pong").await.unwrap();
+            let mut received = [0u8; 4];
+            client_peer.read_exact(&mut received).await.unwrap();
+            drop(client_peer);
+            drop(upstream_peer);
+        };
+        let (copied, _) = tokio::join!(copy_bidirectional(&mut client_io, &mut upstream_io), peers);
+
+        assert_eq!(copied.unwrap(), (5, 4));
+        assert_eq!(stats.bytes_in.load(Ordering::Relaxed), 5);
+        assert_eq!(stats.bytes_out.load(Ordering::Relaxed), 4);
+        assert_eq!(traffic.bytes_in.load(Ordering::Relaxed), 5);
+        assert_eq!(traffic.bytes_out.load(Ordering::Relaxed), 4);
+        assert_eq!((client_io.transferred, upstream_io.transferred), (5, 4));
+    }
+
+    #[tokio::test]
+    async fn overloaded_connection_receives_503_and_close() {
+        use tokio::io::AsyncReadExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (accepted, connected) =
+            tokio::join!(listener.accept(), tokio::net::TcpStream::connect(addr));
+        let (server, _) = accepted.unwrap();
+        let mut client = connected.unwrap();
+
+        let mut response = String::new();
+        let (_, read) = tokio::join!(
+            reject_overloaded_connection(server),
+            client.read_to_string(&mut response)
+        );
+        read.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+        assert!(response.contains("Connection: close\r\n"));
+    }
+
+    #[test]
+    fn hop_headers_are_stripped_without_forcing_close() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONNECTION,
+            HeaderValue::from_static("keep-alive, x-hop"),
+        );
+        headers.insert("keep-alive", HeaderValue::from_static("timeout=5"));
+        headers.insert("x-hop", HeaderValue::from_static("1"));
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+
+        sanitize_hop_headers(&mut headers, false);
+
+        assert!(headers.get(header::CONNECTION).is_none());
+        assert!(headers.get("keep-alive").is_none());
+        assert!(headers.get("x-hop").is_none());
+        assert_eq!(headers.get(header::CONTENT_TYPE).unwrap(), "text/plain");
+    }
+
+    async fn count_connections_for_sequential_requests(keep_alive: bool) -> usize {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let accepted = Arc::new(AtomicUsize::new(0));
+
+        let server_accepted = accepted.clone();
+        let server = tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                server_accepted.fetch_add(1, Ordering::Relaxed);
+                tokio::spawn(proxy_http1_builder(keep_alive).serve_connection(
+                    TokioIo::new(stream),
+                    service_fn(|_| async {
+                        Ok::<_, Infallible>(Response::new(Full::new(Bytes::from_static(b"ok"))))
+                    }),
+                ));
+            }
+        });
+
+        let client: Client<HttpConnector, Full<Bytes>> =
+            Client::builder(TokioExecutor::new()).build_http();
+        let uri: Uri = format!("http://{}/asset", addr).parse().unwrap();
+        for _ in 0..50 {
+            let response = client.get(uri.clone()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            response.into_body().collect().await.unwrap();
+        }
+
+        server.abort();
+        accepted.load(Ordering::Relaxed)
+    }
+
+    #[tokio::test]
+    async fn keep_alive_reuses_client_connections() {
+        assert_eq!(count_connections_for_sequential_requests(true).await, 1);
+        assert_eq!(count_connections_for_sequential_requests(false).await, 50);
+    }
+
+    #[test]
+    fn connection_limit_mode_defaults_to_backpressure() {
+        assert_eq!(
+            ConnectionLimitMode::default(),
+            ConnectionLimitMode::Backpressure
+        );
+        let mode: ConnectionLimitMode = serde_json::from_str("\"reject\"").unwrap();
+        assert_eq!(mode, ConnectionLimitMode::Reject);
+    }
+
+    #[test]
+    fn only_safe_methods_are_retried() {
+        assert!(is_idempotent_method(&http::Method::GET));
+        assert!(is_idempotent_method(&http::Method::HEAD));
+        assert!(is_idempotent_method(&http::Method::OPTIONS));
+        assert!(!is_idempotent_method(&http::Method::POST));
+        assert!(!is_idempotent_method(&http::Method::PUT));
+    }
+
+    #[test]
+    fn buffered_request_can_be_rebuilt_for_each_attempt() {
+        let (head, _) = Request::builder()
+            .method(http::Method::GET)
+            .uri("http://127.0.0.1:3000/users?page=1")
+            .header("x-env", "staging")
+            .body(())
+            .unwrap()
+            .into_parts();
+        let body = Bytes::from_static(b"payload");
+
+        let first = buffered_request(&head, &body);
+        let second = buffered_request(&head, &body);
+        assert_eq!(first.uri(), second.uri());
+        assert_eq!(second.method(), http::Method::GET);
+        assert_eq!(second.headers().get("x-env").unwrap(), "staging");
+        assert_eq!(second.body().size_hint().exact(), Some(7));
+    }
+
+    #[test]
+    fn body_capture_keeps_prefix_and_flags_binary() {
+        let capture = BodyCapture::new(8);
+        capture.record(b"hello ");
+        capture.record(b"world");
+        let view = capture.view();
+        assert!(!view.binary);
+        assert_eq!(view.content, "hello wo");
+        assert_eq!(view.captured_bytes, 8);
+        assert_eq!(view.total_bytes, 11);
+        assert!(view.truncated);
+
+        let binary = BodyCapture::new(16);
+        binary.record(&[0x89, b'P', b'N', b'G', 0, 1]);
+        let view = binary.view();
+        assert!(view.binary);
+        assert_eq!(view.content, "iVBORwAB");
+
+        // 截断在多字节字符中间时仍按文本处理。
+        let text = BodyCapture::new(4);
+        text.record("你好".as_bytes());
+        assert!(!text.view().binary);
+    }
+
+    #[tokio::test]
+    async fn capture_body_tees_frames_without_changing_them() {
+        let capture = Arc::new(BodyCapture::new(4));
+        let body = CaptureBody::new(
+            Full::new(Bytes::from_static(b"payload")),
+            Some(capture.clone()),
+        );
+        assert_eq!(body.size_hint().exact(), Some(7));
+
+        let forwarded = body.collect().await.unwrap().to_bytes();
+        assert_eq!(forwarded.as_ref(), b"payload");
+        assert_eq!(capture.view().content, "payl");
+        assert_eq!(capture.view().total_bytes, 7);
+    }
+
+    #[test]
+    fn saved_config_round_trips_and_tolerates_bad_files() {
+        let dir = std::env::temp_dir().join(format!("krate-proxy-config-{}", std::process::id()));
+        let path = dir.join(SAVED_CONFIG_FILE);
+        assert!(read_saved_config(&path).is_none());
+
+        let config: ProxyStartRequest = serde_json::from_value(serde_json::json!({
+            "listenHost": "127.0.0.1",
+            "listenPort": 8080,
+            "routes": [{ "enabled": true, "pathPrefix": "/api", "target": "http://127.0.0.1:3000" }],
+        }))
+        .unwrap();
+        write_saved_config(
+            &path,
+            &SavedProxyConfig {
+                auto_start: true,
+                config,
+                missing_passwords: Vec::new(),
+            },
+        )
+        .unwrap();
+
+        let loaded = read_saved_config(&path).unwrap();
+        assert!(loaded.auto_start);
+        assert_eq!(loaded.config.listen_port, 8080);
+        assert!(loaded.config.keep_alive);
+        assert_eq!(loaded.config.routes[0].path_prefix, "/api");
+
+        std::fs::write(&path, "{ not json").unwrap();
+        assert!(read_saved_config(&path).is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn exported_routes_import_without_secrets() {
+        let mut config: ProxyStartRequest = serde_json::from_value(serde_json::json!({
+            "listenHost": "127.0.0.1",
+            "listenPort": 8080,
+            "routes": [{
+                "enabled": true,
+                "host": " API.example.com ",
+                "pathPrefix": "api/",
+                "target": "http://127.0.0.1:3000",
+                "basicAuthUser": "admin",
+                "basicAuthPassword": "secret",
+            }],
+        }))
+        .unwrap();
+        strip_route_secrets(&mut config);
+        let json = export_routes_json(&config).unwrap();
+        assert!(json.contains("\"schemaVersion\": 1"));
+        assert!(!json.contains("secret"));
+
+        let imported = import_routes_json(&json).unwrap();
+        let route = &imported.config.routes[0];
+        assert_eq!(route.id, "route-1");
+        assert_eq!(route.host, "api.example.com");
+        assert_eq!(route.path_prefix, "/api");
+        assert_eq!(route.basic_auth_user.as_deref(), Some("admin"));
+        assert_eq!(imported.warnings.len(), 1);
+        assert!(imported.warnings[0].contains("routes[0]"));
+    }
+
+    #[test]
+    fn import_reports_route_index_and_field() {
+        let bad_field = serde_json::json!({
+            "schemaVersion": 1,
+            "config": {
+                "listenHost": "127.0.0.1",
+                "listenPort": 8080,
+                "routes": [
+                    { "enabled": true, "target": "http://127.0.0.1:3000" },
+                    { "enabled": true, "target": "http://127.0.0.1:3001", "retries": "three" },
+                ],
+            },
+        });
+        let err = import_routes_json(&bad_field.to_string()).err().unwrap();
+        assert!(err.starts_with("routes[1].retries"), "{}", err);
+
+        let bad_target = serde_json::json!({
+            "schemaVersion": 1,
+            "config": {
+                "listenHost": "127.0.0.1",
+                "listenPort": 8080,
+                "routes": [{ "enabled": true, "target": "ftp://example.com" }],
+            },
+        });
+        let err = import_routes_json(&bad_target.to_string()).err().unwrap();
+        assert!(err.starts_with("routes[0] 校验失败"), "{}", err);
+
+        assert!(import_routes_json("{\"config\": {}}")
+            .err()
+            .unwrap()
+            .contains("schemaVersion"));
+    }
+
+    #[test]
+    fn outcome_stats_count_status_classes_and_reset() {
+        let stats = OutcomeStats::default();
+        for status in [200, 204, 301, 404, 502, 503, 101] {
+            stats.record_status(status);
         }
+        stats.record_upstream_error(UpstreamErrorKind::Tls);
+        stats.record_upstream_error(UpstreamErrorKind::Other);
+
+        let status = stats.status();
+        assert_eq!(status.status_2xx, 2);
+        assert_eq!(status.status_3xx, 1);
+        assert_eq!(status.status_4xx, 1);
+        assert_eq!(status.status_5xx, 2);
+        assert_eq!(status.tls_error, 1);
+        assert_eq!(status.upstream_connect_failed, 0);
+
+        stats.reset();
+        assert_eq!(stats.status(), OutcomeStatus::default());
     }
 
-    if let Some(query) = uri.query() {
-        if !query.is_empty() {
-            return format!("{}?{}", rewritten_path, query);
+    #[tokio::test]
+    async fn refused_upstream_is_classified_as_connect_failure() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let client: Client<HttpConnector, Full<Bytes>> =
+            Client::builder(TokioExecutor::new()).build_http();
+        let uri: Uri = format!("http://{}/", addr).parse().unwrap();
+        let err = client.get(uri).await.unwrap_err();
+        assert_eq!(classify_upstream_error(&err), UpstreamErrorKind::Connect);
+    }
+
+    #[test]
+    fn runtime_errors_keep_last_ten() {
+        let instance = ProxyInstance::new("errors");
+        for index in 0..RECENT_ERRORS_CAPACITY + 2 {
+            set_runtime_error(&instance.snapshot, format!("error {}", index));
         }
+
+        let status = instance.status();
+        assert_eq!(status.recent_errors.len(), RECENT_ERRORS_CAPACITY);
+        assert_eq!(status.recent_errors[0].message, "error 11");
+        assert_eq!(status.recent_errors[9].message, "error 2");
+        assert_eq!(status.last_error.as_deref(), Some("error 11"));
     }
 
-    rewritten_path
-}
+    #[test]
+    fn request_id_is_forwarded_upstream_and_echoed_to_client() {
+        let mut input = enabled_route("", "/", "http://127.0.0.1:3000");
+        input.request_id_header = Some("X-Correlation-Id".to_string());
+        let route = build_routes(&[input]).unwrap().remove(0);
+
+        let generated = generate_request_id();
+        assert_eq!(generated.len(), 16);
+        assert!(generated.chars().all(|c| c.is_ascii_hexdigit()));
+
+        let mut upstream = HeaderMap::new();
+        let request_id = assign_request_id(&mut upstream, &route.request_id_header, generated);
+        apply_proxy_headers(
+            &mut upstream,
+            &route,
+            peer(),
+            "localhost",
+            false,
+            ForwardedHeaders::All,
+        )
+        .unwrap();
+        let trace = RequestTrace {
+            request_id,
+            request_id_header: Some(route.request_id_header.clone()),
+            ..Default::default()
+        };
+        let mut downstream = HeaderMap::new();
+        echo_request_id(&mut downstream, &trace);
 
-/// 构建并排序启用路由。
-///
-/// 排序策略：
-/// 1. 路径前缀长度降序（最长前缀优先）；
-/// 2. 前缀相同则 Host 精确匹配优先于通配。
-fn build_routes(inputs: &[ProxyRouteInput]) -> Result<Vec<ProxyRoute>, String> {
-    let mut routes = Vec::new();
+        assert_eq!(
+            upstream.get("x-correlation-id"),
+            downstream.get("x-correlation-id")
+        );
+        assert_eq!(
+            upstream.get("x-correlation-id").unwrap(),
+            trace.request_id.as_str()
+        );
 
-    for item in inputs.iter().filter(|route| route.enabled) {
-        let path_prefix = normalize_path_prefix(&item.path_prefix);
-        let host = normalize_host_value(&item.host);
-        let (scheme, target_host, target_port) = parse_target(&item.target)?;
+        let mut client = HeaderMap::new();
+        client.insert("x-request-id", HeaderValue::from_static("client-id-1"));
+        let kept = assign_request_id(
+            &mut client,
+            &HeaderName::from_static(DEFAULT_REQUEST_ID_HEADER),
+            generate_request_id(),
+        );
+        assert_eq!(kept, "client-id-1");
+        assert!(parse_request_id_header(Some("connection")).is_err());
+    }
 
-        let _ = (&item.id, &item.name);
+    #[test]
+    fn set_cookie_rewriting_removes_domain_and_secure_only() {
+        let mut headers = HeaderMap::new();
+        headers.append(
+            header::SET_COOKIE,
+            HeaderValue::from_static(
+                "session=abc123; Domain=.example.com; Path=/; Secure; HttpOnly; SameSite=Lax",
+            ),
+        );
+        headers.append(
+            header::SET_COOKIE,
+            HeaderValue::from_static(
+                "theme=dark; Expires=Wed, 21 Oct 2026 07:28:00 GMT; domain=example.com; secure",
+            ),
+        );
+        headers.append(
+            header::SET_COOKIE,
+            HeaderValue::from_static("csrf=xyz; Path=/app;SameSite=Strict"),
+        );
 
-        routes.push(ProxyRoute {
-            host,
-            path_prefix,
-            target_scheme: scheme,
-            target_host,
-            target_port,
-            strip_prefix: item.strip_prefix,
-            allow_insecure_tls: item.allow_insecure_tls,
-        });
+        rewrite_set_cookies(&mut headers, true, true);
+
+        let cookies: Vec<_> = headers
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .map(|value| value.to_str().unwrap())
+            .collect();
+        assert_eq!(
+            cookies,
+            [
+                "session=abc123; Path=/; HttpOnly; SameSite=Lax",
+                "theme=dark; Expires=Wed, 21 Oct 2026 07:28:00 GMT",
+                "csrf=xyz; Path=/app; SameSite=Strict",
+            ]
+        );
     }
 
-    routes.sort_by(|left, right| {
-        right
-            .path_prefix
-            .len()
-            .cmp(&left.path_prefix.len())
-            .then_with(|| right.host.is_some().cmp(&left.host.is_some()))
-    });
-    Ok(routes)
-}
+    #[test]
+    fn folded_set_cookie_headers_are_split() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::SET_COOKIE,
+            HeaderValue::from_static(
+                "a=1; Expires=Thu, 01 Jan 2026 00:00:00 GMT; Domain=example.com, b=2; Path=/; Secure",
+            ),
+        );
 
-/// 解析目标地址（支持 `http://`、`https://`、`ws://`、`wss://`）。
-///
-/// 返回 `(scheme, host, port)`，其中 ws/wss 会映射为 http/https 传输语义。
-fn parse_target(raw: &str) -> Result<(TargetScheme, String, u16), String> {
-    let normalized = raw.trim().trim_end_matches('/').to_string();
-    if normalized.is_empty() {
-        return Err("目标地址不能为空".to_string());
+        rewrite_set_cookies(&mut headers, true, false);
+
+        let cookies: Vec<_> = headers
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .map(|value| value.to_str().unwrap())
+            .collect();
+        assert_eq!(
+            cookies,
+            [
+                "a=1; Expires=Thu, 01 Jan 2026 00:00:00 GMT",
+                "b=2; Path=/; Secure",
+            ]
+        );
     }
 
-    let normalized_lower = normalized.to_ascii_lowercase();
-    let (scheme, rest) = if normalized_lower.starts_with("http://") {
-        (TargetScheme::Http, &normalized[7..])
-    } else if normalized_lower.starts_with("https://") {
-        (TargetScheme::Https, &normalized[8..])
-    } else if normalized_lower.starts_with("ws://") {
-        (TargetScheme::Http, &normalized[5..])
-    } else if normalized_lower.starts_with("wss://") {
-        (TargetScheme::Https, &normalized[6..])
-    } else {
-        return Err("目标地址必须以 http://、https://、ws:// 或 wss:// 开头".to_string());
-    };
+    #[test]
+    fn redirect_locations_pointing_at_target_are_rewritten() {
+        let mut input = enabled_route("", "/api", "https://api.internal:8443/v1");
+        input.strip_prefix = true;
+        input.rewrite_redirects = true;
+        let route = build_routes(&[input]).unwrap().remove(0);
+        let proxy = "localhost:8080";
+
+        assert_eq!(
+            rewrite_location("https://api.internal:8443/v1/login?next=%2F", &route, proxy)
+                .as_deref(),
+            Some("http://localhost:8080/api/login?next=%2F")
+        );
+        assert_eq!(
+            rewrite_location("//API.internal:8443/v1", &route, proxy).as_deref(),
+            Some("//localhost:8080/api")
+        );
+        assert_eq!(rewrite_location("/v1/login", &route, proxy), None);
+        assert_eq!(
+            rewrite_location("https://api.internal/v1/login", &route, proxy),
+            None
+        );
+        assert_eq!(
+            rewrite_location("https://other.example.com:8443/v1", &route, proxy),
+            None
+        );
+        assert_eq!(
+            rewrite_location("https://api.internal:8443/other", &route, proxy),
+            None
+        );
 
-    if rest.is_empty() {
-        return Err("目标地址不能为空".to_string());
+        let kept = build_routes(&[enabled_route("", "/", "http://10.0.0.5:3000")])
+            .unwrap()
+            .remove(0);
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::LOCATION,
+            HeaderValue::from_static("http://10.0.0.5:3000/dashboard#top"),
+        );
+        let logged =
+            rewrite_redirect_location(&mut headers, &kept, &proxy_authority("0.0.0.0", 9000));
+        assert_eq!(
+            headers.get(header::LOCATION).unwrap(),
+            "http://127.0.0.1:9000/dashboard#top"
+        );
+        assert_eq!(logged.unwrap().0, "http://10.0.0.5:3000/dashboard#top");
     }
 
-    if rest.contains('/') {
-        return Err("目标地址暂不支持路径，请只填写主机和端口".to_string());
+    #[test]
+    fn html_urls_pointing_at_target_are_rewritten() {
+        let mut input = enabled_route("", "/app", "http://10.0.0.5:3000/ui");
+        input.strip_prefix = true;
+        input.rewrite_html_urls = true;
+        let route = build_routes(&[input]).unwrap().remove(0);
+
+        let html = concat!(
+            r#"<a href="http://10.0.0.5:3000/ui/users">users</a>"#,
+            r#"<script src="//10.0.0.5:3000/ui/app.js"></script>"#,
+            r#"<a href="HTTP://10.0.0.5:3000/ui">home</a>"#,
+            r#"<a href="http://10.0.0.5:3000/other">outside</a>"#,
+            r#"<a href="http://10.0.0.5:30001/ui">port</a>"#,
+            r#"<a href="http://10.0.0.55:3000/ui">host</a>"#,
+        );
+        assert_eq!(
+            rewrite_html_urls(html, &route, "localhost:8080"),
+            concat!(
+                r#"<a href="http://localhost:8080/app/users">users</a>"#,
+                r#"<script src="//localhost:8080/app/app.js"></script>"#,
+                r#"<a href="http://localhost:8080/app">home</a>"#,
+                r#"<a href="http://10.0.0.5:3000/other">outside</a>"#,
+                r#"<a href="http://10.0.0.5:30001/ui">port</a>"#,
+                r#"<a href="http://10.0.0.55:3000/ui">host</a>"#,
+            )
+        );
+
+        let mut mixed_case = enabled_route("", "/app", "http://10.0.0.5:3000/UI");
+        mixed_case.strip_prefix = true;
+        mixed_case.rewrite_html_urls = true;
+        let mixed_case = build_routes(&[mixed_case]).unwrap().remove(0);
+        assert_eq!(
+            rewrite_html_urls(
+                r#"<a href="http://10.0.0.5:3000/UI/users">users</a>"#,
+                &mixed_case,
+                "localhost:8080"
+            ),
+            r#"<a href="http://localhost:8080/app/users">users</a>"#
+        );
+
+        let mut headers = HeaderMap::new();
+        apply_proxy_headers(
+            &mut headers,
+            &route,
+            peer(),
+            "localhost",
+            false,
+            ForwardedHeaders::None,
+        )
+        .unwrap();
+        assert_eq!(headers.get(header::ACCEPT_ENCODING).unwrap(), "identity");
     }
 
-    if rest.matches(':').count() > 1 {
-        return Err("当前版本暂不支持 IPv6 地址".to_string());
+    #[tokio::test]
+    async fn gzipped_html_response_is_decoded_and_rewritten() {
+        let mut input = enabled_route("", "/", "http://10.0.0.5:3000");
+        input.rewrite_html_urls = true;
+        let route = build_routes(&[input]).unwrap().remove(0);
+
+        let html = r#"<a href="http://10.0.0.5:3000/login">login</a>"#;
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(html.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut upstream = Response::new(Either::Right(Full::new(Bytes::from(compressed))));
+        upstream.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("text/html; charset=utf-8"),
+        );
+        upstream
+            .headers_mut()
+            .insert(header::CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+
+        let response = rewrite_html_response(upstream, &route, "127.0.0.1:8080", 1024).await;
+        assert!(response.headers().get(header::CONTENT_ENCODING).is_none());
+        let expected = r#"<a href="http://127.0.0.1:8080/login">login</a>"#;
+        assert_eq!(
+            response.headers().get(header::CONTENT_LENGTH).unwrap(),
+            &HeaderValue::from(expected.len())
+        );
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body.as_ref(), expected.as_bytes());
     }
 
-    let default_port = scheme.default_port();
+    #[tokio::test]
+    async fn error_pages_render_html_with_placeholders() {
+        let pages = ErrorPages {
+            bad_gateway: Some("<h1>{{route}}</h1><p>{{error}}</p>".to_string()),
+            ..Default::default()
+        };
 
-    if let Some((host, port_text)) = rest.rsplit_once(':') {
-        let host = host.trim();
-        if host.is_empty() {
-            return Err("目标主机不能为空".to_string());
-        }
+        let response = error_response(
+            &pages,
+            StatusCode::BAD_GATEWAY,
+            "connect <refused>",
+            Some("api"),
+        );
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(
+            response.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/html; charset=utf-8"
+        );
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body.as_ref(), b"<h1>api</h1><p>connect &lt;refused&gt;</p>");
+
+        let fallback = error_response(&pages, StatusCode::NOT_FOUND, "no route", None);
+        assert_eq!(fallback.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            fallback.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/plain; charset=utf-8"
+        );
 
-        let port = port_text
-            .trim()
-            .parse::<u16>()
-            .map_err(|_| "目标端口非法".to_string())?;
+        let missing = ErrorPagesInput {
+            timeout: Some("/nonexistent/krate-504.html".to_string()),
+            ..Default::default()
+        };
+        let err = load_error_pages(&missing).err().unwrap();
+        assert!(err.contains("/nonexistent/krate-504.html"));
+    }
 
-        return Ok((scheme, host.to_string(), port));
+    #[tokio::test]
+    async fn throttle_paces_to_configured_rate() {
+        let mut throttle = Throttle::new(20_000);
+        let started = Instant::now();
+        for _ in 0..4 {
+            std::future::poll_fn(|cx| throttle.poll_ready(cx)).await;
+            throttle.record(2_000);
+        }
+        std::future::poll_fn(|cx| throttle.poll_ready(cx)).await;
+        let elapsed = started.elapsed();
+        // 8000 字节 / 20000 字节每秒 = 400ms。
+        assert!(elapsed >= Duration::from_millis(380), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(2), "{:?}", elapsed);
     }
 
-    Ok((scheme, rest.to_string(), default_port))
-}
+    #[test]
+    fn route_throttle_parses_kib_rates_and_rejects_zero() {
+        let mut input = enabled_route("", "/", "http://127.0.0.1:3000");
+        input.download_limit_kbps = Some(200);
+        input.upload_limit_kbps = Some(50);
+        input.latency_ms = Some(300);
+        let routes = build_routes(&[input.clone()]).unwrap();
+        let throttle = routes[0].throttle;
+        assert_eq!(throttle.download_bytes_per_sec, Some(200 * 1024));
+        assert_eq!(throttle.upload_bytes_per_sec, Some(50 * 1024));
+        assert_eq!(throttle.latency, Some(Duration::from_millis(300)));
+        let status = routes[0].stats_status().throttle.unwrap();
+        assert_eq!(status.download_limit_kbps, Some(200));
+        assert_eq!(status.scope, "perConnection");
+
+        input.upload_limit_kbps = Some(0);
+        let err = build_routes(&[input]).err().unwrap();
+        assert!(err.contains("上行限速"));
+    }
 
-/// 归一化路径前缀，确保以 `/` 开头并去除尾部多余 `/`。
-fn normalize_path_prefix(raw: &str) -> String {
-    let trimmed = raw.trim();
-    if trimmed.is_empty() {
-        return "/".to_string();
+    #[test]
+    fn weighted_targets_split_by_weight_and_sticky_header() {
+        let mut input = enabled_route("", "/app", "");
+        input.targets = vec![
+            WeightedTargetInput {
+                target: "http://127.0.0.1:3000".to_string(),
+                weight: 9,
+            },
+            WeightedTargetInput {
+                target: "http://127.0.0.1:3001".to_string(),
+                weight: 1,
+            },
+            WeightedTargetInput {
+                target: "http://127.0.0.1:3002".to_string(),
+                weight: 0,
+            },
+        ];
+        input.sticky_header = Some("x-user-id".to_string());
+        let route = build_routes(&[input.clone()]).unwrap().remove(0);
+        assert_eq!(route.target_port, 3000);
+
+        let ports: Vec<u16> = [0, 8, 9]
+            .into_iter()
+            .map(|point| {
+                pick_weighted(&route.targets, point)
+                    .unwrap()
+                    .1
+                    .route
+                    .target_port
+            })
+            .collect();
+        assert_eq!(ports, vec![3000, 3000, 3001]);
+        assert!(pick_weighted(&route.targets, 10).is_none());
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-user-id", HeaderValue::from_static("alice"));
+        let first = route.pick_target(&headers).unwrap().0;
+        for _ in 0..20 {
+            assert_eq!(route.pick_target(&headers).unwrap().0, first);
+        }
+        for _ in 0..50 {
+            assert_ne!(route.pick_target(&HeaderMap::new()).unwrap().0, 2);
+        }
+        assert_eq!(route.stats_status().targets.len(), 3);
+
+        for target in &mut input.targets {
+            target.weight = 0;
+        }
+        assert!(build_routes(&[input]).is_err());
     }
 
-    let mut prefix = if trimmed.starts_with('/') {
-        trimmed.to_string()
-    } else {
-        format!("/{}", trimmed)
-    };
+    #[test]
+    fn sticky_cookie_is_stripped_and_survives_target_removal() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::COOKIE,
+            HeaderValue::from_static("sid=1; krate_upstream=a1b2; theme=dark"),
+        );
+        assert_eq!(take_sticky_cookie(&mut headers).as_deref(), Some("a1b2"));
+        assert_eq!(headers.get(header::COOKIE).unwrap(), "sid=1; theme=dark");
+
+        headers.insert(header::COOKIE, HeaderValue::from_static("krate_upstream="));
+        assert_eq!(take_sticky_cookie(&mut headers), None);
+        assert!(headers.get(header::COOKIE).is_none());
+
+        // 重新加载路由后指向已删除目标的 Cookie 不再有效，按权重重新分配。
+        let mut input = enabled_route("", "/app", "");
+        input.sticky_sessions = true;
+        input.targets = vec![
+            WeightedTargetInput {
+                target: "http://127.0.0.1:3000".to_string(),
+                weight: 1,
+            },
+            WeightedTargetInput {
+                target: "http://127.0.0.1:3001".to_string(),
+                weight: 1,
+            },
+        ];
+        let route = build_routes(&[input]).unwrap().remove(0);
+        let removed = sticky_target_key("http://127.0.0.1:3002");
+        let (index, target, cookie) = route
+            .select_target(Some(&removed), &HeaderMap::new())
+            .unwrap();
+        assert!(index < 2);
+        assert_eq!(
+            cookie.unwrap(),
+            sticky_cookie_value(&target.sticky_key, &route.path_prefix).unwrap()
+        );
+        let kept = sticky_target_key("http://127.0.0.1:3001");
+        let (index, _, cookie) = route.select_target(Some(&kept), &HeaderMap::new()).unwrap();
+        assert_eq!(index, 1);
+        assert!(cookie.is_none());
+
+        // 粘性 Cookie 不带 Domain / Secure，Cookie 改写不会改变它。
+        let mut response_headers = HeaderMap::new();
+        response_headers.append(
+            header::SET_COOKIE,
+            sticky_cookie_value(&kept, &route.path_prefix).unwrap(),
+        );
+        rewrite_set_cookies(&mut response_headers, true, true);
+        assert_eq!(
+            response_headers.get(header::SET_COOKIE).unwrap(),
+            &format!("krate_upstream={kept}; Path=/app; HttpOnly; SameSite=Lax")
+        );
+    }
 
-    if prefix.len() > 1 {
-        prefix = prefix.trim_end_matches('/').to_string();
+    #[tokio::test]
+    async fn traffic_stats_count_body_bytes_and_derive_throughput() {
+        let traffic = TrafficStats::default();
+        let body = CaptureBody::new(Full::new(Bytes::from_static(b"0123456789")), None)
+            .counted(&traffic.bytes_out);
+        body.collect().await.unwrap();
+        traffic.bytes_in.fetch_add(4, Ordering::Relaxed);
+
+        let status = traffic.status();
+        assert_eq!((status.bytes_in, status.bytes_out), (4, 10));
+        assert_eq!(status.bytes_out_per_sec, 0);
+
+        if let Ok(mut samples) = traffic.samples.lock() {
+            let now = Instant::now();
+            samples.push_back((now - Duration::from_secs(2), 0, 0));
+            samples.push_back((now, 4, 10));
+        }
+        let status = traffic.status();
+        assert_eq!((status.bytes_in_per_sec, status.bytes_out_per_sec), (2, 5));
+
+        traffic.reset();
+        assert_eq!(
+            traffic.status(),
+            TrafficStatus {
+                bytes_in: 0,
+                bytes_out: 0,
+                bytes_in_per_sec: 0,
+                bytes_out_per_sec: 0,
+                window_secs: THROUGHPUT_WINDOW_SECS as u64,
+            }
+        );
     }
 
-    prefix
-}
+    #[tokio::test]
+    async fn timed_connector_reports_only_fresh_connections() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let service = service_fn(|_request: Request<Incoming>| async {
+                Ok::<_, Infallible>(Response::new(Full::new(Bytes::from_static(b"ok"))))
+            });
+            let _ = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await;
+        });
 
-/// 归一化 Host；空字符串或 `*` 视为通配（返回 `None`）。
-fn normalize_host_value(raw: &str) -> Option<String> {
-    let value = raw.trim().to_ascii_lowercase();
-    if value.is_empty() || value == "*" {
-        return None;
+        let client: Client<TimedConnector<HttpConnector>, Full<Bytes>> =
+            Client::builder(TokioExecutor::new()).build(TimedConnector {
+                inner: HttpConnector::new(),
+            });
+        let uri: Uri = format!("http://{}/", addr).parse().unwrap();
+
+        let since = Instant::now();
+        let response = client.get(uri.clone()).await.unwrap();
+        assert!(fresh_connect_ms(&response, since).is_some());
+        response.into_body().collect().await.unwrap();
+
+        let since = Instant::now();
+        let reused = client.get(uri).await.unwrap();
+        assert!(reused.extensions().get::<ConnectTiming>().is_some());
+        assert!(fresh_connect_ms(&reused, since).is_none());
     }
 
-    let host = value.split(':').next().unwrap_or("").trim().to_string();
-    if host.is_empty() {
-        None
-    } else {
-        Some(host)
+    #[test]
+    fn slow_request_list_keeps_latest_entries() {
+        let slow_requests = Mutex::new(VecDeque::new());
+        for index in 0..=SLOW_REQUESTS_CAPACITY {
+            record_slow_request(
+                &slow_requests,
+                SlowProxyRequest {
+                    timestamp: index as u64,
+                    request_id: String::new(),
+                    method: "GET".to_string(),
+                    path: "/slow".to_string(),
+                    route_id: None,
+                    status: 200,
+                    duration_ms: 1500,
+                    connect_ms: None,
+                    response_ms: Some(1500),
+                },
+            );
+        }
+        let slow = slow_requests.lock().unwrap();
+        assert_eq!(slow.len(), SLOW_REQUESTS_CAPACITY);
+        assert_eq!(slow.front().unwrap().timestamp, 1);
     }
-}
 
-/// 从路由列表中选出第一条匹配规则。
-///
-/// 注意：路由在进入该函数前已经按“优先级”排序。
-fn select_route<'a>(
-    routes: &'a [ProxyRoute],
-    request_host: Option<&str>,
-    request_path: &str,
-) -> Option<&'a ProxyRoute> {
-    routes.iter().find(|route| {
-        let host_match = match (&route.host, request_host) {
-            (None, _) => true,
-            (Some(_), None) => false,
-            (Some(expect), Some(actual)) => expect == actual,
-        };
+    #[test]
+    fn recent_requests_buffer_drops_oldest_entries() {
+        let recent = Mutex::new(VecDeque::new());
+        for index in 0..RECENT_REQUESTS_CAPACITY + 3 {
+            record_recent_request(
+                &recent,
+                RecentProxyRequest {
+                    timestamp: 0,
+                    request_id: format!("{:016x}", index),
+                    method: "GET".to_string(),
+                    host: None,
+                    path: format!("/{}", index),
+                    route_id: None,
+                    status: 200,
+                    upstream_latency_ms: None,
+                    error: None,
+                    request_body: None,
+                    response_body: None,
+                },
+            );
+        }
 
-        host_match && path_match(&route.path_prefix, request_path)
-    })
-}
+        let recent = recent.into_inner().unwrap();
+        assert_eq!(recent.len(), RECENT_REQUESTS_CAPACITY);
+        assert_eq!(recent.front().unwrap().path, "/3");
+        assert_eq!(
+            recent.back().unwrap().path,
+            format!("/{}", RECENT_REQUESTS_CAPACITY + 2)
+        );
+    }
 
-/// 判断路径是否命中前缀。
-fn path_match(prefix: &str, path: &str) -> bool {
-    if prefix == "/" {
-        return true;
+    #[test]
+    fn path_match_handles_boundary_correctly() {
+        assert!(path_match("/", "/anything", MatchMode::Prefix));
+        assert!(path_match("/api", "/api", MatchMode::Prefix));
+        assert!(path_match("/api", "/api/user", MatchMode::Prefix));
+        assert!(!path_match("/api", "/apix", MatchMode::Prefix));
+
+        assert!(path_match("/api", "/api", MatchMode::Exact));
+        assert!(path_match("/api", "/api/", MatchMode::Exact));
+        assert!(!path_match("/api", "/api/user", MatchMode::Exact));
+        assert!(!path_match("/api", "/apix", MatchMode::Exact));
+        assert!(path_match("/", "/", MatchMode::Exact));
+        assert!(!path_match("/", "/anything", MatchMode::Exact));
     }
 
-    if path == prefix {
-        return true;
+    #[test]
+    fn exact_routes_outrank_prefix_routes_and_strip_to_root() {
+        let prefix = enabled_route("", "/health", "http://127.0.0.1:3000");
+        let mut exact = enabled_route("", "/health/", "http://127.0.0.1:3001");
+        exact.match_mode = MatchMode::Exact;
+        exact.strip_prefix = true;
+        let routes = build_routes(&[prefix, exact]).unwrap();
+        assert_eq!(routes[0].match_mode, MatchMode::Exact);
+
+        let selected = select_route(&routes, None, "/health/", &[]).unwrap();
+        assert_eq!(selected.target_port, 3001);
+        let uri: Uri = "/health/?verbose=1".parse().unwrap();
+        assert_eq!(rewrite_path_and_query(&uri, selected), "/?verbose=1");
+
+        let selected = select_route(&routes, None, "/health/live", &[]).unwrap();
+        assert_eq!(selected.target_port, 3000);
     }
 
-    match path.strip_prefix(prefix) {
-        Some(rest) => rest.starts_with('/'),
-        None => false,
+    #[test]
+    fn query_conditions_match_decoded_values_and_outrank_plain_routes() {
+        let plain = enabled_route("", "/app", "http://127.0.0.1:3000");
+        let mut canary = enabled_route("", "/app", "http://127.0.0.1:3001");
+        canary.query_match = Some(vec![("env".to_string(), Some("canary build".to_string()))]);
+        let mut debug = enabled_route("", "/app", "http://127.0.0.1:3002");
+        debug.query_match = Some(vec![("debug".to_string(), None)]);
+        let routes = build_routes(&[plain, canary, debug]).unwrap();
+
+        let select = |query: &str| {
+            select_route(&routes, None, "/app", &parse_query_pairs(Some(query)))
+                .unwrap()
+                .target_port
+        };
+        assert_eq!(select(""), 3000);
+        assert_eq!(select("env=canary%20build"), 3001);
+        assert_eq!(select("env=canary+build"), 3001);
+        assert_eq!(select("env=stable&env=canary%20build"), 3001);
+        assert_eq!(select("env=stable"), 3000);
+        assert_eq!(select("debug"), 3002);
+
+        let mut invalid = enabled_route("", "/app", "http://127.0.0.1:3000");
+        invalid.query_match = Some(vec![(" ".to_string(), None)]);
+        assert!(build_routes(&[invalid]).is_err());
     }
-}
 
-/// 构建纯文本响应。
-fn plain_response(status: StatusCode, message: &str) -> ProxyResponse {
-    Response::builder()
-        .status(status)
-        .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
-        .body(Either::Right(Full::new(Bytes::from(message.to_string()))))
-        .unwrap_or_else(|_| {
-            Response::new(Either::Right(Full::new(Bytes::from_static(
-                b"internal response build error",
-            ))))
-        })
-}
+    #[test]
+    fn percent_decode_keeps_invalid_escapes() {
+        assert_eq!(percent_decode("a%2Fb%zz%"), "a/b%zz%");
+        assert_eq!(percent_decode("%E4%BD%A0"), "你");
+    }
 
-/// 更新运行时错误快照（用于前端展示最近错误）。
-fn set_runtime_error(snapshot: &Arc<Mutex<ProxySnapshot>>, message: String) {
-    if let Ok(mut snap) = snapshot.lock() {
-        snap.last_error = Some(message);
+    #[test]
+    fn weighted_targets_keep_host_override_and_fallback() {
+        let mut input = enabled_route("", "/app", "");
+        input.host_header = Some("app.internal".to_string());
+        input.fallback_target = Some("http://127.0.0.1:4000".to_string());
+        input.targets = vec![WeightedTargetInput {
+            target: "http://127.0.0.1:3000".to_string(),
+            weight: 1,
+        }];
+        let route = build_routes(&[input]).unwrap().remove(0);
+
+        let target = &route.targets[0].route;
+        assert_eq!(target.target_port, 3000);
+        assert_eq!(target.host_header.as_ref().unwrap(), "app.internal");
+        assert_eq!(target.fallback.as_deref().unwrap().target_port, 4000);
     }
-}
 
-/// 获取当前 UNIX 秒级时间戳。
-fn current_timestamp() -> u64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map(|value| value.as_secs())
-        .unwrap_or(0)
-}
+    #[test]
+    fn invalid_disabled_routes_do_not_block_startup() {
+        let mut broken = enabled_route("", "/api", "not a url");
+        broken.id = "api".to_string();
+        broken.enabled = false;
+        let root = enabled_route("", "/", "http://127.0.0.1:3000");
+        let inputs = [broken, root];
+
+        assert!(build_routes(&inputs).is_err());
+        let (routes, rejected) = build_route_table(&inputs).unwrap();
+        assert_eq!(routes.len(), 1);
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].id, "api");
+
+        set_route_enabled(&routes, &rejected, "api", false).unwrap();
+        let err = set_route_enabled(&routes, &rejected, "api", true).unwrap_err();
+        assert!(err.contains("无法启用 api"));
+
+        let mut enabled_broken = inputs[0].clone();
+        enabled_broken.enabled = true;
+        assert!(build_route_table(&[enabled_broken]).is_err());
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[tokio::test]
+    async fn websocket_tunnels_close_only_on_abort() {
+        let (sender, mut receiver) = watch::channel(ConnectionSignal::Running);
+        sender.send(ConnectionSignal::Drain).unwrap();
+        assert!(
+            timeout(Duration::from_millis(20), wait_for_abort(&mut receiver))
+                .await
+                .is_err()
+        );
 
-    fn enabled_route(host: &str, path_prefix: &str, target: &str) -> ProxyRouteInput {
-        ProxyRouteInput {
-            id: String::new(),
-            name: String::new(),
-            enabled: true,
-            host: host.to_string(),
-            path_prefix: path_prefix.to_string(),
-            target: target.to_string(),
-            strip_prefix: false,
-            allow_insecure_tls: false,
-        }
+        sender.send(ConnectionSignal::Abort).unwrap();
+        timeout(Duration::from_secs(1), wait_for_abort(&mut receiver))
+            .await
+            .unwrap();
+
+        let (sender, mut receiver) = watch::channel(ConnectionSignal::Running);
+        drop(sender);
+        timeout(Duration::from_secs(1), wait_for_abort(&mut receiver))
+            .await
+            .unwrap();
     }
 
     #[test]
-    fn parse_target_supports_http_https_ws_wss_and_case_insensitive_scheme() {
-        let (scheme_http, host_http, port_http) = parse_target("HTTP://example.com").unwrap();
-        assert_eq!(scheme_http, TargetScheme::Http);
-        assert_eq!(host_http, "example.com");
-        assert_eq!(port_http, 80);
+    fn client_forwarded_headers_are_dropped_unless_the_proxy_writes_them() {
+        let route = build_routes(&[enabled_route("", "/", "http://127.0.0.1:3000")])
+            .unwrap()
+            .remove(0);
+        let client_headers = || {
+            let mut headers = HeaderMap::new();
+            for name in FORWARDED_REQUEST_HEADERS {
+                headers.insert(name, HeaderValue::from_static("spoofed"));
+            }
+            headers
+        };
+        let apply = |forwarded| {
+            let mut headers = client_headers();
+            apply_proxy_headers(&mut headers, &route, peer(), "app.local", false, forwarded)
+                .unwrap();
+            headers
+        };
 
-        let (scheme_https, host_https, port_https) =
-            parse_target("Https://api.example.com:8443").unwrap();
-        assert_eq!(scheme_https, TargetScheme::Https);
-        assert_eq!(host_https, "api.example.com");
-        assert_eq!(port_https, 8443);
+        let headers = apply(ForwardedHeaders::None);
+        for name in FORWARDED_REQUEST_HEADERS {
+            assert!(headers.get(name).is_none(), "{name}");
+        }
 
-        let (scheme_ws, host_ws, port_ws) = parse_target("ws://socket.local").unwrap();
-        assert_eq!(scheme_ws, TargetScheme::Http);
-        assert_eq!(host_ws, "socket.local");
-        assert_eq!(port_ws, 80);
+        let headers = apply(ForwardedHeaders::Xff);
+        assert!(headers.get("x-real-ip").is_none());
+        assert!(headers.get(header::FORWARDED).is_none());
+        assert!(headers.get("x-forwarded-port").is_none());
+        assert_eq!(headers.get("x-forwarded-host").unwrap(), "app.local");
+        assert_eq!(headers.get("x-forwarded-proto").unwrap(), "http");
 
-        let (scheme_wss, host_wss, port_wss) = parse_target("WSS://socket.secure.local").unwrap();
-        assert_eq!(scheme_wss, TargetScheme::Https);
-        assert_eq!(host_wss, "socket.secure.local");
-        assert_eq!(port_wss, 443);
+        let headers = apply(ForwardedHeaders::All);
+        assert_eq!(headers.get("x-real-ip").unwrap(), "192.168.1.20");
+        assert!(headers.get("x-forwarded-prefix").is_none());
     }
 
     #[test]
-    fn parse_target_rejects_path() {
-        let err = parse_target("https://example.com/api").unwrap_err();
-        assert!(err.contains("暂不支持路径"));
+    fn sticky_cookie_follows_target_when_targets_are_reordered() {
+        let target = |url: &str| WeightedTargetInput {
+            target: url.to_string(),
+            weight: 1,
+        };
+        let build = |urls: &[&str]| {
+            let mut input = enabled_route("", "/", "");
+            input.sticky_sessions = true;
+            input.targets = urls.iter().map(|url| target(url)).collect();
+            build_routes(&[input]).unwrap().remove(0)
+        };
+
+        let route = build(&["http://127.0.0.1:3000", "http://127.0.0.1:3001"]);
+        let key = route.targets[1].sticky_key.clone();
+        assert_eq!(key, sticky_target_key("http://127.0.0.1:3001"));
+
+        // 在前面插入新目标后，同一 Cookie 仍落到原来的上游。
+        let route = build(&[
+            "http://127.0.0.1:3002",
+            "http://127.0.0.1:3000",
+            "http://127.0.0.1:3001",
+        ]);
+        let (index, selected, cookie) = route.select_target(Some(&key), &HeaderMap::new()).unwrap();
+        assert_eq!(index, 2);
+        assert_eq!(selected.label, "http://127.0.0.1:3001");
+        assert!(cookie.is_none());
     }
 
     #[test]
-    fn rewrite_path_and_query_respects_strip_prefix() {
-        let route = ProxyRoute {
-            host: None,
-            path_prefix: "/api".to_string(),
-            target_scheme: TargetScheme::Http,
-            target_host: "127.0.0.1".to_string(),
-            target_port: 3000,
-            strip_prefix: true,
-            allow_insecure_tls: false,
+    fn test_route_picks_weighted_target_and_injects_request_id() {
+        let mut input = enabled_route("", "/", "");
+        input.request_id_header = Some("X-Correlation-Id".to_string());
+        input.targets = vec![
+            WeightedTargetInput {
+                target: "http://127.0.0.1:3000".to_string(),
+                weight: 0,
+            },
+            WeightedTargetInput {
+                target: "http://127.0.0.1:3001".to_string(),
+                weight: 1,
+            },
+        ];
+        let routes = build_routes(&[input]).unwrap();
+
+        let result =
+            test_route(&routes, None, "/users", None, ForwardedHeaders::All, true).unwrap();
+        assert_eq!(
+            result.upstream_uri.as_deref(),
+            Some("http://127.0.0.1:3001/users")
+        );
+        let request_id = result
+            .request_headers
+            .iter()
+            .find(|(name, _)| name == "x-correlation-id")
+            .map(|(_, value)| value.as_str());
+        assert_eq!(request_id.map(str::len), Some(generate_request_id().len()));
+    }
+
+    #[test]
+    fn cache_key_varies_by_accept_encoding_and_other_vary_skips_cache() {
+        let request = |accept_encoding: Option<&'static str>| {
+            let mut builder = Request::builder().uri("/app.js?v=1");
+            if let Some(value) = accept_encoding {
+                builder = builder.header(header::ACCEPT_ENCODING, value);
+            }
+            builder.body(()).unwrap()
         };
+        let gzip_key = response_cache_key(&request(Some("gzip")), Some("app.local"));
+        assert_ne!(
+            gzip_key,
+            response_cache_key(&request(None), Some("app.local"))
+        );
+        assert_eq!(
+            gzip_key,
+            response_cache_key(&request(Some(" gzip ")), Some("app.local"))
+        );
 
-        let uri: Uri = "/api/user/list?page=1".parse().unwrap();
-        assert_eq!(rewrite_path_and_query(&uri, &route), "/user/list?page=1");
+        let response = |vary: &'static str| {
+            let mut response: ProxyResponse =
+                Response::new(Either::Right(Full::new(Bytes::from_static(b"body"))));
+            response
+                .headers_mut()
+                .insert(header::VARY, HeaderValue::from_static(vary));
+            response
+        };
+        assert!(is_cacheable_response(&response("Accept-Encoding"), 1024));
+        assert!(!is_cacheable_response(
+            &response("Accept-Encoding, Cookie"),
+            1024
+        ));
+        assert!(!is_cacheable_response(&response("*"), 1024));
     }
 
     #[test]
-    fn build_routes_prefers_more_specific_host_when_prefix_equal() {
-        let routes = build_routes(&[
-            enabled_route("", "/api", "http://127.0.0.1:3001"),
-            enabled_route("api.example.com", "/api", "http://127.0.0.1:3002"),
-        ])
-        .unwrap();
+    fn route_retries_are_capped() {
+        let mut input = enabled_route("", "/", "http://127.0.0.1:3000");
+        input.retries = MAX_ROUTE_RETRIES;
+        assert_eq!(
+            build_routes(&[input.clone()]).unwrap()[0].retries,
+            MAX_ROUTE_RETRIES
+        );
 
-        let selected = select_route(&routes, Some("api.example.com"), "/api/users").unwrap();
-        assert_eq!(selected.host.as_deref(), Some("api.example.com"));
-        assert_eq!(selected.target_port, 3002);
+        input.retries = MAX_ROUTE_RETRIES + 1;
+        assert!(build_routes(&[input]).err().unwrap().contains("重试次数"));
     }
 
     #[test]
-    fn build_routes_prefers_longest_path_prefix() {
-        let routes = build_routes(&[
-            enabled_route("", "/api", "http://127.0.0.1:3001"),
-            enabled_route("", "/api/admin", "http://127.0.0.1:3002"),
-        ])
+    fn saved_config_omits_basic_auth_passwords() {
+        let dir = std::env::temp_dir().join(format!("krate-proxy-secrets-{}", std::process::id()));
+        let path = dir.join(SAVED_CONFIG_FILE);
+        let config: ProxyStartRequest = serde_json::from_value(serde_json::json!({
+            "listenHost": "127.0.0.1",
+            "listenPort": 8080,
+            "routes": [
+                {
+                    "id": "admin",
+                    "enabled": true,
+                    "pathPrefix": "/admin",
+                    "target": "http://127.0.0.1:3000",
+                    "basicAuthUser": "admin",
+                    "basicAuthPassword": "s3cret",
+                },
+                { "enabled": true, "pathPrefix": "/", "target": "http://127.0.0.1:3001" },
+            ],
+        }))
+        .unwrap();
+        write_saved_config(
+            &path,
+            &SavedProxyConfig {
+                auto_start: true,
+                config,
+                missing_passwords: Vec::new(),
+            },
+        )
         .unwrap();
 
-        let selected = select_route(&routes, None, "/api/admin/users").unwrap();
-        assert_eq!(selected.path_prefix, "/api/admin");
-        assert_eq!(selected.target_port, 3002);
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(!content.contains("s3cret"));
+        assert!(!content.contains("missingPasswords"));
+        let loaded = read_saved_config(&path).unwrap();
+        assert_eq!(
+            loaded.config.routes[0].basic_auth_user.as_deref(),
+            Some("admin")
+        );
+        assert_eq!(loaded.missing_passwords, vec!["admin".to_string()]);
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 
-    #[test]
-    fn path_match_handles_boundary_correctly() {
-        assert!(path_match("/", "/anything"));
-        assert!(path_match("/api", "/api"));
-        assert!(path_match("/api", "/api/user"));
-        assert!(!path_match("/api", "/apix"));
+    #[tokio::test]
+    async fn chunked_bodies_are_buffered_up_to_the_limit() {
+        let mut body = PrefixedBody::new(Full::new(Bytes::from_static(b"<p>small</p>")));
+        assert_eq!(
+            buffer_limited(&mut body, 64)
+                .await
+                .unwrap()
+                .unwrap()
+                .as_ref(),
+            b"<p>small</p>"
+        );
+
+        // 超出上限时已读取的数据补回开头，透传的内容与上游一致。
+        let mut body = PrefixedBody::new(Full::new(Bytes::from_static(b"0123456789")));
+        assert!(buffer_limited(&mut body, 4).await.unwrap().is_none());
+        assert_eq!(body.size_hint().exact(), Some(10));
+        assert_eq!(
+            body.collect().await.unwrap().to_bytes().as_ref(),
+            b"0123456789"
+        );
     }
 }
@@ -1,8 +1,23 @@
-use crate::commands::archive::{create_archive, extract_archive, open_output_dir};
+use crate::commands::archive::{
+    append_to_archive, benchmark_compression, cancel_archive, change_archive_password,
+    convert_archive, create_archive, create_archive_from_entries, create_archive_incremental,
+    estimate_archive, extract_archive, extract_archive_chain, get_archive_info,
+    list_archive_operations, open_output_dir, pause_archive, quick_check_archive,
+    read_archive_entry, resume_archive, search_archive, search_archives, validate_archive_password,
+    verify_archive, ArchiveState,
+};
 use crate::commands::image::{get_image_info, resize_image};
-use crate::commands::network::{kill_process, scan_ports};
+use crate::commands::network::{
+    cancel_port_scan, check_port, get_process_details, kill_process, kill_process_on_port,
+    scan_ports, scan_remote_ports, PortScanState,
+};
 use crate::commands::pdf::{decrypt_pdf, encrypt_pdf};
-use crate::commands::proxy::{proxy_get_status, proxy_start, proxy_stop, ProxyState};
+use crate::commands::proxy::{
+    autostart_saved_proxy, proxy_clear_cache, proxy_export_routes, proxy_get_recent_requests,
+    proxy_get_slow_requests, proxy_get_status, proxy_import_routes, proxy_load_config,
+    proxy_save_config, proxy_set_route_enabled, proxy_start, proxy_stop, proxy_test_route,
+    ProxyState,
+};
 use crate::commands::system::{get_system_info, SystemState};
 use tauri::menu::{Menu, MenuItem};
 use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
@@ -54,6 +69,8 @@ pub fn run() {
                     }
                 })
                 .build(app)?;
+            // === 3. 按已保存配置自动启动代理 ===
+            autostart_saved_proxy(app.handle());
 
             Ok(())
         })
@@ -76,20 +93,55 @@ pub fn run() {
         .plugin(tauri_plugin_autostart::Builder::new().build())
         .manage(SystemState::new()) // 系统信息
         .manage(ProxyState::new())
+        .manage(ArchiveState::new())
+        .manage(PortScanState::new())
         .invoke_handler(tauri::generate_handler![
             resize_image,
             get_image_info,
             scan_ports,
+            get_process_details,
             kill_process,
+            kill_process_on_port,
+            scan_remote_ports,
+            cancel_port_scan,
+            check_port,
             create_archive,
+            create_archive_incremental,
+            create_archive_from_entries,
             extract_archive,
+            extract_archive_chain,
+            cancel_archive,
+            pause_archive,
+            resume_archive,
+            search_archive,
+            search_archives,
+            append_to_archive,
+            benchmark_compression,
+            get_archive_info,
+            list_archive_operations,
+            validate_archive_password,
+            verify_archive,
+            change_archive_password,
+            convert_archive,
+            estimate_archive,
+            quick_check_archive,
+            read_archive_entry,
             open_output_dir,
             encrypt_pdf,
             decrypt_pdf,
             get_system_info,
             proxy_start,
             proxy_stop,
-            proxy_get_status
+            proxy_get_status,
+            proxy_get_recent_requests,
+            proxy_get_slow_requests,
+            proxy_clear_cache,
+            proxy_set_route_enabled,
+            proxy_test_route,
+            proxy_save_config,
+            proxy_load_config,
+            proxy_export_routes,
+            proxy_import_routes
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");